@@ -8,8 +8,9 @@ use crate::value::*;
 use crate::value::any::Any;
 use crate::value::array::ArrayBuilder;
 use crate::value::symbol::Symbol;
-use crate::value::list::List;
+use crate::value::list::{List, ListBuilder};
 use crate::value::syntax::Syntax;
+use crate::value::syntax::r#macro::Macro;
 use crate::value::iform::*;
 use crate::value::func::*;
 use crate::value::app::{Parameter, ParamKind, Param};
@@ -19,25 +20,29 @@ struct LocalVar {
     pub init_form: Option<Cap<iform::IForm>>,
 }
 
+//マクロ展開をネストできる最大の深さ
+const MAX_MACRO_EXPAND_DEPTH: usize = 256;
+
 ///
 /// Compile Context
 pub struct CCtx<'a> {
     frames: &'a mut Vec<Vec<LocalVar>>,
     toplevel: bool,
     tail: bool,
+    macro_depth: usize,
 }
 
 #[derive(Debug)]
 pub enum SyntaxException {
-    OutOfMemory,
+    OutOfMemory(err::OutOfMemory),
     TypeMismatch(err::TypeMismatch),
     MalformedFormat(err::MalformedFormat),
     DisallowContext,
 }
 
 impl From<err::OutOfMemory> for SyntaxException {
-    fn from(_: err::OutOfMemory) -> Self {
-        SyntaxException::OutOfMemory
+    fn from(this: err::OutOfMemory) -> Self {
+        SyntaxException::OutOfMemory(this)
     }
 }
 
@@ -62,7 +67,7 @@ impl From<err::DisallowContext> for SyntaxException {
 impl From<SyntaxException> for Exception {
     fn from(this: SyntaxException) -> Self {
         match this {
-            SyntaxException::OutOfMemory => Exception::OutOfMemory,
+            SyntaxException::OutOfMemory(inner) => Exception::from(inner),
             SyntaxException::TypeMismatch(inner) => Exception::TypeMismatch(inner),
             SyntaxException::MalformedFormat(inner) => Exception::MalformedFormat(inner),
             SyntaxException::DisallowContext => Exception::DisallowContext,
@@ -76,6 +81,7 @@ pub fn compile_transform(sexp: &Reachable<Any>, obj: &mut Object) -> NResult<ifo
         frames: &mut frames,
         toplevel: true,
         tail: false,
+        macro_depth: 0,
     };
 
     pass_transform(sexp, &mut ctx, obj)
@@ -92,7 +98,7 @@ pub fn compile(sexp: &Reachable<Any>, obj: &mut Object) -> NResult<compiled::Cod
 fn alloc_into_iform<T: AsIForm>(result: Result<Ref<T>, OutOfMemory>) -> NResult<IForm, SyntaxException> {
     match result {
         Ok(v) => Ok(v.into_iform()),
-        Err(_) => Err(SyntaxException::OutOfMemory),
+        Err(err) => Err(SyntaxException::OutOfMemory(err)),
     }
 }
 
@@ -101,7 +107,7 @@ fn alloc_into_iform<T: AsIForm>(result: Result<Ref<T>, OutOfMemory>) -> NResult<
 // Covnerts S expression into intermediates form (IForm).
 fn pass_transform(sexp: &Reachable<Any>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     if let Some(list) = sexp.try_cast::<List>() {
-        if list.as_ref().is_nil() {
+        if list.is_nil() {
             alloc_into_iform(IFormConst::alloc(sexp, obj))
 
         } else {
@@ -117,6 +123,9 @@ fn pass_transform(sexp: &Reachable<Any>, ctx: &mut CCtx, obj: &mut Object) -> NR
     } else if let Some(array) = sexp.try_cast::<array::Array<Any>>() {
         transform_array(array, ctx, obj)
 
+    } else if let Some(map) = sexp.try_cast::<hashmap::HashMap>() {
+        transform_hash_map(map, ctx, obj)
+
     } else {
         alloc_into_iform(IFormConst::alloc(sexp, obj))
     }
@@ -219,11 +228,23 @@ fn transform_apply(list: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
         if let Some(val) = get_binding_variable(symbol.as_ref(), ctx, obj) {
             if let Some(syntax) = val.try_cast::<Syntax>() {
                 return transform_syntax(&syntax.clone().reach(obj), list, ctx, obj);
+            } else if let Some(mac) = val.try_cast::<Macro>() {
+                return transform_macro(&mac.clone().reach(obj), list, ctx, obj);
             }
         }
 
     } else if let Some(syntax) = app.try_cast::<Syntax>() {
         return transform_syntax(&syntax.clone().reach(obj), list, ctx, obj);
+
+    } else if let Some(symbol) = global_ref_symbol(&app) {
+        //マクロの展開で(global-ref if)のように置き換えられたSyntaxやマクロ
+        if let Some(val) = obj.find_global_value(symbol.as_ref()) {
+            if let Some(syntax) = val.try_cast::<Syntax>() {
+                return transform_syntax(&syntax.clone().reach(obj), list, ctx, obj);
+            } else if let Some(mac) = val.try_cast::<Macro>() {
+                return transform_macro(&mac.clone().reach(obj), list, ctx, obj);
+            }
+        }
     }
 
     //Syntax以外の場合は関数呼び出しとして変換する
@@ -231,13 +252,14 @@ fn transform_apply(list: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
         frames: ctx.frames,
         toplevel: false,
         tail: false,
+        macro_depth: ctx.macro_depth,
     };
 
     //適用される値を変換
     let app =  pass_transform(&app.reach(obj), &mut ctx, obj)?.reach(obj);
 
     //引数部分の値を変換
-    let count = list.as_ref().tail().as_ref().count();
+    let count = list.as_ref().tail().count();
     let mut builder_args = ArrayBuilder::<IForm>::new(count, obj)?;
 
     for v in list.as_ref().tail().reach(obj).iter(obj) {
@@ -246,8 +268,14 @@ fn transform_apply(list: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
     }
     let args = builder_args.get().reach(obj);
 
+    //読み込み時に記録された呼び出し式の位置
+    let pos = obj.source_position(list.as_ref());
+
     //IFormCallを作成して戻り値にする
-    alloc_into_iform(IFormCall::alloc(&app, &args, is_tail, obj))
+    alloc_into_iform(IFormCall::alloc(&app, &args, is_tail, obj).map(|mut call| {
+        call.as_mut().set_source_pos(pos);
+        call
+    }))
 }
 
 fn transform_tuple(tuple: &Reachable<tuple::Tuple>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
@@ -256,6 +284,7 @@ fn transform_tuple(tuple: &Reachable<tuple::Tuple>, ctx: &mut CCtx, obj: &mut Ob
         frames: ctx.frames,
         toplevel: false,
         tail: false,
+        macro_depth: ctx.macro_depth,
     };
 
     let app = pass_transform(&tuple::literal::tuple().into_value(), &mut ctx, obj)?.reach(obj);
@@ -279,6 +308,7 @@ fn transform_array(array: &Reachable<array::Array<Any>>, ctx: &mut CCtx, obj: &m
         frames: ctx.frames,
         toplevel: false,
         tail: false,
+        macro_depth: ctx.macro_depth,
     };
 
     let app = pass_transform(&array::literal::array().into_value(), &mut ctx, obj)?.reach(obj);
@@ -296,6 +326,38 @@ fn transform_array(array: &Reachable<array::Array<Any>>, ctx: &mut CCtx, obj: &m
     alloc_into_iform(IFormCall::alloc(&app, &args, is_tail, obj))
 }
 
+fn transform_hash_map(map: &Reachable<hashmap::HashMap>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    let is_tail = ctx.tail;
+    let mut ctx = CCtx {
+        frames: ctx.frames,
+        toplevel: false,
+        tail: false,
+        macro_depth: ctx.macro_depth,
+    };
+
+    let app = pass_transform(&hashmap::literal::hash_map().into_value(), &mut ctx, obj)?.reach(obj);
+
+    //キーと値を交互に並べた引数で(hash-map k v ...)を呼び出す
+    let keys: Vec<Reachable<Any>> = map.as_ref().keys().into_iter()
+        .map(|key| key.reach(obj))
+        .collect();
+    let values: Vec<Reachable<Any>> = map.as_ref().values().into_iter()
+        .map(|value| value.reach(obj))
+        .collect();
+    let mut builder_args = ArrayBuilder::<IForm>::new(keys.len() * 2, obj)?;
+
+    for (key, value) in keys.iter().zip(values.iter()) {
+        let iform = pass_transform(key, &mut ctx, obj)?;
+        unsafe { builder_args.push_uncheck(&iform, obj) };
+        let iform = pass_transform(value, &mut ctx, obj)?;
+        unsafe { builder_args.push_uncheck(&iform, obj) };
+    }
+    let args = builder_args.get().reach(obj);
+
+    //IFormCallを作成して戻り値にする
+    alloc_into_iform(IFormCall::alloc(&app, &args, is_tail, obj))
+}
+
 fn transform_syntax(syntax: &Reachable<Syntax>, list: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     let syntax = syntax.as_ref();
     let args = list.as_ref().tail().reach(obj);
@@ -311,11 +373,70 @@ fn transform_syntax(syntax: &Reachable<Syntax>, list: &Reachable<List>, ctx: &mu
     }
 }
 
+fn transform_macro(mac: &Reachable<Macro>, list: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //展開が終わらないマクロでスタックを使い果たさないように、展開の深さを制限する
+    if MAX_MACRO_EXPAND_DEPTH <= ctx.macro_depth {
+        return Err(err::MalformedFormat::new(Some(list.make().into_value()), "macro expansion is too deep").into());
+    }
+
+    //テンプレート内の自由なシンボルが呼び出し側のローカル変数に隠されないように、見えているローカル変数の名前を渡す
+    let locals: Vec<String> = ctx.frames.iter()
+        .flat_map(|frame| frame.iter())
+        .map(|lvar| lvar.name.as_ref().as_ref().to_string())
+        .collect();
+    let expanded = Macro::expand(mac, list, &locals, obj)?.reach(obj);
+
+    //展開後の式でも、バックトレースにマクロ呼び出し式の位置が表示されるようにする
+    if let Some(pos) = obj.source_position(list.as_ref()) {
+        if let Some(expanded) = expanded.try_cast::<List>() {
+            if !expanded.is_nil() {
+                obj.add_source_position(&expanded.make(), pos);
+            }
+        }
+    }
+
+    //展開結果にマクロ呼び出しが含まれていても、再帰的に変換される
+    let mut ctx = CCtx {
+        frames: ctx.frames,
+        toplevel: ctx.toplevel,
+        tail: ctx.tail,
+        macro_depth: ctx.macro_depth + 1,
+    };
+    pass_transform(&expanded, &mut ctx, obj)
+}
+
+fn syntax_global_ref(args: &Reachable<List>, _ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //global-refはマクロの展開でだけ使用される特殊な構文
+    //(global-ref symbol)
+    //ローカル変数を無視して、グローバル変数を直接参照する
+    let symbol = args.as_ref().head().reach(obj);
+    if let Some(symbol) = symbol.try_cast::<Symbol>() {
+        alloc_into_iform(IFormGRef::alloc(symbol, obj))
+    } else {
+        Err(err::TypeMismatch::new(symbol.make(), symbol::Symbol::typeinfo()).into())
+    }
+}
+
+//(global-ref symbol)の形式であれば、参照しているシンボルを返す
+fn global_ref_symbol(v: &Ref<Any>) -> Option<Ref<Symbol>> {
+    let list = v.try_cast::<List>()?;
+    if list.is_nil() || !list.len_exactly(2) {
+        return None;
+    }
+
+    let head = list.as_ref().head();
+    if !std::ptr::eq(head.as_ref(), literal::global_ref().cast_value().as_ref()) {
+        return None;
+    }
+    list.as_ref().tail().as_ref().head().try_cast::<Symbol>().cloned()
+}
+
 fn syntax_if(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     let mut test_ctx = CCtx {
         frames: ctx.frames,
         toplevel: false,
         tail: false,
+        macro_depth: ctx.macro_depth,
     };
     let pred = pass_transform(&args.as_ref().head().reach(obj), &mut test_ctx, obj)?.reach(obj);
 
@@ -326,10 +447,11 @@ fn syntax_if(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResul
         frames: ctx.frames,
         toplevel: false,
         tail: ctx.tail,
+        macro_depth: ctx.macro_depth,
     };
 
     let args = args.as_ref().tail();
-    let false_ = if args.as_ref().is_nil() {
+    let false_ = if args.is_nil() {
         alloc_into_iform(IFormConst::alloc(&bool::Bool::false_().into_value(), obj))
     } else {
         let false_ = args.as_ref().head().reach(obj);
@@ -344,7 +466,7 @@ fn syntax_if(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResul
 
 fn syntax_cond(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     fn cond_inner(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
-        let is_last = args.as_ref().tail().as_ref().is_nil();
+        let is_last = args.as_ref().tail().is_nil();
 
         let clause = args.as_ref().head();
         if let Some(clause) = clause.try_cast::<List>() {
@@ -364,6 +486,7 @@ fn syntax_cond(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRes
                 frames: ctx.frames,
                 toplevel: false,
                 tail: false,
+                macro_depth: ctx.macro_depth,
             };
             //TEST部分を変換
             let test_iform = pass_transform(&test, &mut test_ctx, obj)?.reach(obj);
@@ -386,7 +509,7 @@ fn syntax_cond(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRes
     }
 
     //(cond)のようにテスト部分が空のcondであれば
-    if args.as_ref().is_nil() {
+    if args.is_nil() {
         //無条件でfalseを返す
         alloc_into_iform(IFormConst::alloc(&bool::Bool::false_().into_value(), obj))
     } else {
@@ -394,6 +517,7 @@ fn syntax_cond(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRes
             frames: ctx.frames,
             toplevel: false,
             tail: ctx.tail,
+            macro_depth: ctx.macro_depth,
         };
 
         cond_inner(args, &mut ctx, obj)
@@ -403,7 +527,7 @@ fn syntax_cond(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRes
 fn transform_begin(body: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     //Beginは現在のコンテキスト(トップレベルや末尾文脈)をそのまま引き継いで各式を評価します
 
-    let size = body.as_ref().count();
+    let size = body.count();
     let mut builder = array::ArrayBuilder::new(size, obj)?;
 
     for (index, sexp) in body.iter(obj).enumerate() {
@@ -414,6 +538,7 @@ fn transform_begin(body: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
                 frames: ctx.frames,
                 toplevel: ctx.toplevel,
                 tail: ctx.tail,
+                macro_depth: ctx.macro_depth,
             }
         } else {
             //途中の式はすべてtail文脈ではない
@@ -421,6 +546,7 @@ fn transform_begin(body: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
                 frames: ctx.frames,
                 toplevel: ctx.toplevel,
                 tail: false,
+                macro_depth: ctx.macro_depth,
             }
         };
 
@@ -447,10 +573,30 @@ fn syntax_def_recv(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) ->
     }
 }
 
+fn syntax_def_syntax(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //def-syntaxはトップレベルのコンテキストで使用可能(letやfunで作成されたローカルフレーム内では使用不可能)
+    if !ctx.frames.is_empty() {
+        return Err(SyntaxException::DisallowContext);
+    }
+
+    let name = args.as_ref().head().reach(obj);
+    if let Some(name) = name.try_cast::<Symbol>() {
+        let spec = args.as_ref().tail().as_ref().head().reach(obj);
+        let mac = Macro::from_syntax_rules(name, &spec, obj)?.reach(obj);
+
+        //続く式の変換でマクロを使用できるように、実行を待たずに変換の時点でグローバルに定義する
+        obj.define_global_value(name.as_ref(), &mac.make());
+
+        alloc_into_iform(IFormConst::alloc(mac.cast_value(), obj))
+    } else {
+        Err(err::TypeMismatch::new(name.make(), symbol::Symbol::typeinfo()).into())
+    }
+}
+
 fn syntax_fun(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     let params = args.as_ref().head().reach(obj);
     if let Some(params) = params.try_cast::<List>() {
-        let mut builder_params = ArrayBuilder::<Symbol>::new(params.as_ref().count(), obj)?;
+        let mut builder_params = ArrayBuilder::<Symbol>::new(params.count(), obj)?;
         let mut local_frame: Vec<LocalVar> = Vec::new();
 
         //TODO keywordやrest引数の処理
@@ -477,6 +623,7 @@ fn syntax_fun(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
             frames: ctx.frames,
             toplevel: true,
             tail: true,
+            macro_depth: ctx.macro_depth,
         };
 
         //ローカルフレーム内でBody部分を変換
@@ -503,6 +650,7 @@ fn syntax_local(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRe
         frames: ctx.frames,
         toplevel: true,
         tail: ctx.tail,
+        macro_depth: ctx.macro_depth,
     };
 
     //ローカルフレームが積まれた状態でBody部分を変換
@@ -524,10 +672,12 @@ fn syntax_let(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
             frames: ctx.frames,
             toplevel: false,
             tail: false,
+            macro_depth: ctx.macro_depth,
         };
 
         let value = args.as_ref().tail().as_ref().head().reach(obj);
         let iform = pass_transform(&value, &mut ctx, obj)?.reach(obj);
+        set_closure_name(&iform, symbol, obj);
 
         //現在のローカルフレームに新しく定義した変数を追加
         if let Some(cur_frame) = ctx.frames.last_mut() {
@@ -543,6 +693,14 @@ fn syntax_let(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
     }
 }
 
+fn set_closure_name(iform: &Reachable<IForm>, symbol: &Reachable<Symbol>, obj: &mut Object) {
+    //(let f (fun ...))のように束縛されたクロージャは、バックトレースなどで表示するために変数名を名前として持つ
+    if let Some(fun) = iform.cast_value().try_cast::<IFormFun>() {
+        fun.make().as_mut().set_name(symbol);
+        obj.write_barrier(fun.as_ref(), symbol.as_ref());
+    }
+}
+
 fn syntax_let_global(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     let symbol = args.as_ref().head().reach(obj);
     if let Some(symbol) = symbol.try_cast::<Symbol>() {
@@ -550,10 +708,12 @@ fn syntax_let_global(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -
             frames: ctx.frames,
             toplevel: false,
             tail: false,
+            macro_depth: ctx.macro_depth,
         };
 
         let value = args.as_ref().tail().as_ref().head().reach(obj);
         let iform = pass_transform(&value, &mut ctx, obj)?.reach(obj);
+        set_closure_name(&iform, symbol, obj);
 
         alloc_into_iform(IFormLet::alloc(&symbol, &iform, true, obj))
     } else {
@@ -567,9 +727,178 @@ fn syntax_quote(args: &Reachable<List>, _ctx: &mut CCtx, obj: &mut Object) -> NR
 }
 
 
-#[allow(unused_variables)]
-fn syntax_unquote(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
-    unimplemented!()
+fn syntax_unquote(args: &Reachable<List>, _ctx: &mut CCtx, _obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //quasiquoteの展開とmatchのパターン以外でunquoteが現れることはない
+    Err(err::MalformedFormat::new(Some(args.as_ref().head()), "unquote appeared outside of quasiquote.").into())
+}
+
+fn syntax_unquote_splicing(args: &Reachable<List>, _ctx: &mut CCtx, _obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    Err(err::MalformedFormat::new(Some(args.as_ref().head()), "unquote-splicing appeared outside of quasiquote.").into())
+}
+
+fn syntax_quasiquote(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //テンプレートを値を組み立てる式に展開してから、通常の式として変換する
+    let template = args.as_ref().head().reach(obj);
+    let expr = quasiquote_expand(&template, 1, obj)?.reach(obj);
+
+    pass_transform(&expr, ctx, obj)
+}
+
+//(syntax x)の形をしているか
+fn is_modifier_form(v: &Ref<Any>, syntax: &Reachable<Syntax>) -> bool {
+    match v.try_cast::<List>() {
+        Some(list) => list.len_exactly(2)
+            && list.as_ref().head().as_ref().eq(syntax.cast_value().as_ref()),
+        None => false,
+    }
+}
+
+//現在のネストの深さで評価されるunquoteがテンプレート内に含まれているか
+fn quasiquote_has_unquote(v: &Ref<Any>, depth: usize) -> bool {
+    if is_modifier_form(v, &literal::unquote()) || is_modifier_form(v, &literal::unquote_splicing()) {
+        depth == 1 || quasiquote_has_unquote(&unsafe { v.cast_unchecked::<List>() }.as_ref().tail().as_ref().head(), depth - 1)
+
+    } else if is_modifier_form(v, &literal::quasiquote()) {
+        quasiquote_has_unquote(&unsafe { v.cast_unchecked::<List>() }.as_ref().tail().as_ref().head(), depth + 1)
+
+    } else if let Some(list) = v.try_cast::<List>() {
+        unsafe { list.as_ref().iter_gcunsafe() }.any(|v| quasiquote_has_unquote(&v, depth))
+
+    } else if let Some(tuple) = v.try_cast::<tuple::Tuple>() {
+        (0 .. tuple.as_ref().len()).any(|index| quasiquote_has_unquote(&tuple.as_ref().get(index), depth))
+
+    } else if let Some(array) = v.try_cast::<array::Array<Any>>() {
+        (0 .. array.as_ref().len()).any(|index| quasiquote_has_unquote(&array.as_ref().get(index), depth))
+
+    } else {
+        false
+    }
+}
+
+fn quasiquote_expand(template: &Reachable<Any>, depth: usize, obj: &mut Object) -> NResult<Any, SyntaxException> {
+    //評価される部分を含まないテンプレートはそのままquoteする
+    if !quasiquote_has_unquote(&template.make(), depth) {
+        let mut builder = ListBuilder::new(obj);
+        builder.push(literal::quote().cast_value(), obj)?;
+        builder.push(template, obj)?;
+        return Ok(builder.get().into_value());
+    }
+
+    let modifier = |syntax: Reachable<Syntax>| if is_modifier_form(&template.make(), &syntax) { Some(syntax) } else { None };
+    let nested = modifier(literal::unquote())
+        .map(|syntax| (syntax, depth - 1))
+        .or_else(|| modifier(literal::unquote_splicing()).map(|syntax| (syntax, depth - 1)))
+        .or_else(|| modifier(literal::quasiquote()).map(|syntax| (syntax, depth + 1)));
+
+    if let Some((syntax, inner_depth)) = nested {
+        let inner = unsafe { template.cast_unchecked::<List>() }.as_ref().tail().as_ref().head().reach(obj);
+
+        if inner_depth == 0 {
+            if syntax.as_ref().eq(literal::unquote().as_ref()) {
+                //,xはxを評価した値になる
+                return Ok(inner.make());
+            } else {
+                return Err(err::MalformedFormat::new(Some(template.make()), ",@ must appear inside a list, array or tuple.").into());
+            }
+        }
+
+        //ネストしたquasiquoteの内側は、深さを変えて展開した結果を(list syntax 内側)で組み立てる
+        let inner = quasiquote_expand(&inner, inner_depth, obj)?.reach(obj);
+        let mut builder = ListBuilder::new(obj);
+        builder.push(list::literal::list().cast_value(), obj)?;
+        builder.push(syntax.cast_value(), obj)?;
+        builder.push(&inner, obj)?;
+        return Ok(builder.get().into_value());
+    }
+
+    //シーケンスの要素をリストとして取り出す
+    let mut builder = ListBuilder::new(obj);
+    let constructor = if let Some(tuple) = template.try_cast::<tuple::Tuple>() {
+        for index in 0 .. tuple.as_ref().len() {
+            builder.push(&tuple.as_ref().get(index).reach(obj), obj)?;
+        }
+        Some(tuple::literal::tuple())
+    } else if let Some(array) = template.try_cast::<array::Array<Any>>() {
+        for index in 0 .. array.as_ref().len() {
+            builder.push(&array.as_ref().get(index).reach(obj), obj)?;
+        }
+        Some(array::literal::array())
+    } else {
+        None
+    };
+    let elements = match template.try_cast::<List>() {
+        Some(list) => list.clone(obj),
+        None => builder.get().reach(obj),
+    };
+
+    //,@で展開される要素の前後で区切り、区切りごとに値を組み立てる式を作る
+    let mut segments = ListBuilder::new(obj);
+    let mut group: Option<ListBuilder> = None;
+    let mut spliced = false;
+    for v in elements.iter(obj) {
+        if depth == 1 && is_modifier_form(&v, &literal::unquote_splicing()) {
+            if let Some(group) = group.take() {
+                segments.push(&group.get().reach(obj).into_value(), obj)?;
+            }
+
+            let inner = unsafe { v.cast_unchecked::<List>() }.as_ref().tail().as_ref().head().reach(obj);
+            segments.push(&inner, obj)?;
+            spliced = true;
+
+        } else {
+            let v = quasiquote_expand(&v.reach(obj), depth, obj)?.reach(obj);
+            if group.is_none() {
+                let mut builder = ListBuilder::new(obj);
+                builder.push(list::literal::list().cast_value(), obj)?;
+                group = Some(builder);
+            }
+            group.as_mut().unwrap().push(&v, obj)?;
+        }
+    }
+    if let Some(group) = group.take() {
+        segments.push(&group.get().reach(obj).into_value(), obj)?;
+    }
+    let segments = segments.get().reach(obj);
+
+    let mut builder = ListBuilder::new(obj);
+    if spliced {
+        //(append seg1 seg2 ...)でリストを組み立て、配列かタプルなら(apply array リスト)で変換する
+        let mut append = ListBuilder::new(obj);
+        append.push(list::literal::append().cast_value(), obj)?;
+        for seg in segments.iter(obj) {
+            append.push(&seg.reach(obj), obj)?;
+        }
+        let append = append.get().reach(obj);
+
+        match constructor {
+            Some(constructor) => {
+                builder.push(crate::eval::literal::apply().cast_value(), obj)?;
+                builder.push(constructor.cast_value(), obj)?;
+                builder.push(append.cast_value(), obj)?;
+            }
+            None => {
+                return Ok(append.make().into_value());
+            }
+        }
+
+    } else {
+        //,@を含まなければ一つの(list ...)の引数をそのまま使う
+        let args = unsafe { segments.as_ref().head().cast_unchecked::<List>() }.as_ref().tail().reach(obj);
+        match constructor {
+            Some(constructor) => builder.push(constructor.cast_value(), obj)?,
+            None => builder.push(list::literal::list().cast_value(), obj)?,
+        }
+        for arg in args.iter(obj) {
+            builder.push(&arg.reach(obj), obj)?;
+        }
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn syntax_error_pattern(args: &Reachable<List>, _ctx: &mut CCtx, _obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //matchのパターン以外でerror-patternが現れることはない
+    Err(err::MalformedFormat::new(Some(args.as_ref().head()), "error-pattern appeared outside of match pattern.").into())
 }
 
 #[allow(unused_variables)]
@@ -579,7 +908,7 @@ fn syntax_bind(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRes
 
 fn syntax_match(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     //パターン部が一つもなければUnitを返す
-    if args.as_ref().is_nil() {
+    if args.is_nil() {
         alloc_into_iform(IFormConst::alloc(&tuple::Tuple::unit().into_value(), obj))
     } else {
         let match_expr = crate::value::syntax::r#match::translate(args, obj)?.into_value().reach(obj);
@@ -587,13 +916,18 @@ fn syntax_match(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NRe
     }
 }
 
+fn syntax_receive(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    let receive_expr = crate::value::syntax::receive::translate(args, obj)?.reach(obj);
+    pass_transform(&receive_expr, ctx, obj)
+}
+
 fn syntax_fail_catch(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     //fail-catchはmatch式の中でだけ使用される特殊な構文
     //引数の式を評価し、値がFAILでなければその値を返す。
     //引数全てFAILならFAILを返す。
     //特殊なor構文のような動作。
 
-    let size = args.as_ref().count();
+    let size = args.count();
     debug_assert!(size != 0);
 
     let mut builder = array::ArrayBuilder::new(size, obj)?;
@@ -606,6 +940,7 @@ fn syntax_fail_catch(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -
                 frames: ctx.frames,
                 toplevel: false,
                 tail: ctx.tail,
+                macro_depth: ctx.macro_depth,
             }
         } else {
             //途中の式はすべてtail文脈ではない
@@ -613,6 +948,7 @@ fn syntax_fail_catch(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -
                 frames: ctx.frames,
                 toplevel: false,
                 tail: false,
+                macro_depth: ctx.macro_depth,
             }
         };
 
@@ -624,7 +960,7 @@ fn syntax_fail_catch(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -
 }
 
 fn syntax_and(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
-    let size = args.as_ref().count();
+    let size = args.count();
     //(and)のように引数が一つもなければ
     if size == 0 {
         alloc_into_iform(IFormConst::alloc(&bool::Bool::true_().into_value(), obj))
@@ -640,6 +976,7 @@ fn syntax_and(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
                     frames: ctx.frames,
                     toplevel: false,
                     tail: ctx.tail,
+                    macro_depth: ctx.macro_depth,
                 }
             } else {
                 //途中の式はすべてtail文脈ではない
@@ -647,6 +984,7 @@ fn syntax_and(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
                     frames: ctx.frames,
                     toplevel: false,
                     tail: false,
+                    macro_depth: ctx.macro_depth,
                 }
             };
             let iform = pass_transform(&sexp.reach(obj), &mut ctx, obj)?;
@@ -658,7 +996,7 @@ fn syntax_and(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResu
 }
 
 fn syntax_or(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
-    let size = args.as_ref().count();
+    let size = args.count();
     //(or)のように引数が一つもなければ
     if size == 0 {
         alloc_into_iform(IFormConst::alloc(&bool::Bool::false_().into_value(), obj))
@@ -674,6 +1012,7 @@ fn syntax_or(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResul
                     frames: ctx.frames,
                     toplevel: false,
                     tail: ctx.tail,
+                    macro_depth: ctx.macro_depth,
                 }
             } else {
                 //途中の式はすべてtail文脈ではない
@@ -681,6 +1020,7 @@ fn syntax_or(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResul
                     frames: ctx.frames,
                     toplevel: false,
                     tail: false,
+                    macro_depth: ctx.macro_depth,
                 }
             };
             let iform = pass_transform(&sexp.reach(obj), &mut ctx, obj)?;
@@ -691,6 +1031,211 @@ fn syntax_or(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResul
     }
 }
 
+fn syntax_try(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //(try body ... (catch pattern expr ...) ... (finally expr ...))
+    //本体の式、catch節、finally節に分解する
+    let mut builder_body = ListBuilder::new(obj);
+    let mut builder_clauses = ListBuilder::new(obj);
+    let mut builder_without_finally = ListBuilder::new(obj);
+    let mut has_clause = false;
+    let mut finally: Option<Reachable<List>> = None;
+
+    for sexp in args.iter(obj) {
+        let sexp = sexp.reach(obj);
+        if finally.is_some() {
+            return Err(err::MalformedFormat::new(Some(sexp.make()), "finally clause must be the last clause of try.").into());
+        }
+
+        if let Some(clause) = try_clause(&sexp, literal::catch_symbol().as_ref()) {
+            //(catch pattern expr ...)の(pattern expr ...)部分をmatchの節としてそのまま使用する
+            builder_clauses.push(&clause.into_value().reach(obj), obj)?;
+            has_clause = true;
+
+        } else if let Some(clause) = try_clause(&sexp, literal::finally_symbol().as_ref()) {
+            finally = Some(clause.reach(obj));
+            continue;
+
+        } else if has_clause {
+            return Err(err::MalformedFormat::new(Some(sexp.make()), "try body must precede catch clauses.").into());
+
+        } else {
+            builder_body.push(&sexp, obj)?;
+        }
+
+        builder_without_finally.push(&sexp, obj)?;
+    }
+
+    let body = builder_body.get().reach(obj);
+    let clauses = builder_clauses.get().reach(obj);
+
+    if let Some(finally) = finally {
+        //finally節を持つtryは以下の形に変換する
+        //(local
+        //  (let r (try-finally (try body ... (catch ...) ...) finally-expr ...))
+        //  finally-expr ...
+        //  r)
+        let result_symbol = symbol::Symbol::gensym("r", obj)?.into_value().reach(obj);
+
+        //(try body ... (catch ...) ...)
+        let inner_try = List::alloc(literal::try_().cast_value(), &builder_without_finally.get().reach(obj), obj)?.into_value().reach(obj);
+
+        //(try-finally (try ...) finally-expr ...)
+        let try_finally = {
+            let mut builder = ListBuilder::new(obj);
+            builder.push(literal::try_finally().cast_value(), obj)?;
+            builder.push(&inner_try, obj)?;
+            for sexp in finally.iter(obj) {
+                builder.push(&sexp.reach(obj), obj)?;
+            }
+            builder.get().into_value().reach(obj)
+        };
+
+        //(let r (try-finally ...))
+        let let_ = {
+            let mut builder = ListBuilder::new(obj);
+            builder.push(literal::let_().cast_value(), obj)?;
+            builder.push(&result_symbol, obj)?;
+            builder.push(&try_finally, obj)?;
+            builder.get().into_value().reach(obj)
+        };
+
+        let mut builder_local = ListBuilder::new(obj);
+        builder_local.push(literal::local().cast_value(), obj)?;
+        builder_local.push(&let_, obj)?;
+        for sexp in finally.iter(obj) {
+            builder_local.push(&sexp.reach(obj), obj)?;
+        }
+        builder_local.push(&result_symbol, obj)?;
+
+        let local = builder_local.get().into_value().reach(obj);
+        pass_transform(&local, ctx, obj)
+
+    } else {
+        if clauses.is_nil() {
+            //catch節がなければハンドラは不要
+            let mut body_ctx = CCtx {
+                frames: ctx.frames,
+                toplevel: false,
+                tail: false,
+                macro_depth: ctx.macro_depth,
+            };
+            return transform_begin(&body, &mut body_ctx, obj);
+        }
+
+        //捕捉した例外を束縛する変数
+        let var = symbol::Symbol::gensym("e", obj)?.reach(obj);
+
+        //(match (caught-value e) clause ... (@_ (raise e)))
+        //catch節にはraiseで投げられた値そのものを渡す。
+        //どの節にもマッチしなかった例外は、元のエラーとバックトレースのまま外側へ投げなおす
+        let handler = {
+            let mut builder_caught = ListBuilder::new(obj);
+            builder_caught.push(exception::literal::caught_value().cast_value(), obj)?;
+            builder_caught.push(var.cast_value(), obj)?;
+
+            let mut builder_match = ListBuilder::new(obj);
+            builder_match.push(literal::match_().cast_value(), obj)?;
+            builder_match.push(&builder_caught.get().into_value().reach(obj), obj)?;
+            for clause in clauses.iter(obj) {
+                builder_match.push(&clause.reach(obj), obj)?;
+            }
+
+            let mut builder_raise = ListBuilder::new(obj);
+            builder_raise.push(exception::literal::raise().cast_value(), obj)?;
+            builder_raise.push(var.cast_value(), obj)?;
+
+            //(@_ (raise e))
+            let ignore = symbol::Symbol::alloc("_", obj)?.into_value().reach(obj);
+            let mut builder_bind = ListBuilder::new(obj);
+            builder_bind.push(literal::bind().cast_value(), obj)?;
+            builder_bind.push(&ignore, obj)?;
+
+            let mut builder_fallback = ListBuilder::new(obj);
+            builder_fallback.push(&builder_bind.get().into_value().reach(obj), obj)?;
+            builder_fallback.push(&builder_raise.get().into_value().reach(obj), obj)?;
+            builder_match.push(&builder_fallback.get().into_value().reach(obj), obj)?;
+
+            builder_match.get().into_value().reach(obj)
+        };
+
+        transform_try(&body, &var, &handler, ctx, obj)
+    }
+}
+
+fn syntax_try_finally(args: &Reachable<List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //try-finallyはfinally節を持つtryの変換でだけ使用される特殊な構文
+    //(try-finally body finally-expr ...)
+    //bodyで発生した例外を捕捉してfinally-exprを評価した後、元のエラーとバックトレースのまま投げなおす
+    let mut builder_body = ListBuilder::new(obj);
+    builder_body.push(&args.as_ref().head().reach(obj), obj)?;
+    let body = builder_body.get().reach(obj);
+
+    let var = symbol::Symbol::gensym("e", obj)?.reach(obj);
+
+    //(begin finally-expr ... (raise e))
+    let handler = {
+        let mut builder = ListBuilder::new(obj);
+        builder.push(literal::begin().cast_value(), obj)?;
+        for sexp in args.as_ref().tail().reach(obj).iter(obj) {
+            builder.push(&sexp.reach(obj), obj)?;
+        }
+
+        let mut builder_raise = ListBuilder::new(obj);
+        builder_raise.push(exception::literal::raise().cast_value(), obj)?;
+        builder_raise.push(var.cast_value(), obj)?;
+        builder.push(&builder_raise.get().into_value().reach(obj), obj)?;
+
+        builder.get().into_value().reach(obj)
+    };
+
+    transform_try(&body, &var, &handler, ctx, obj)
+}
+
+fn transform_try(body: &Reachable<List>, var: &Reachable<Symbol>, handler: &Reachable<Any>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
+    //tryの本体はハンドラを設置した状態で評価するため、末尾文脈にはならない
+    let mut body_ctx = CCtx {
+        frames: ctx.frames,
+        toplevel: false,
+        tail: false,
+        macro_depth: ctx.macro_depth,
+    };
+    let body = transform_begin(body, &mut body_ctx, obj)?.reach(obj);
+
+    //例外を束縛したローカルフレーム内でハンドラを変換
+    let frame = vec![LocalVar {
+        name: var.make().capture(obj),
+        init_form: None,
+    }];
+    ctx.frames.push(frame);
+
+    let mut handler_ctx = CCtx {
+        frames: ctx.frames,
+        toplevel: false,
+        tail: false,
+        macro_depth: ctx.macro_depth,
+    };
+    let handler = pass_transform(handler, &mut handler_ctx, obj)?.reach(obj);
+
+    ctx.frames.pop();
+
+    alloc_into_iform(IFormTry::alloc(&body, var, &handler, obj))
+}
+
+fn try_clause(sexp: &Reachable<Any>, keyword: &Symbol) -> Option<Ref<List>> {
+    //(catch ...)や(finally ...)のように、先頭が指定のシンボルのリストであればその後ろ部分を返す
+    if let Some(list) = sexp.try_cast::<List>() {
+        if list.is_nil() == false {
+            if let Some(head) = list.as_ref().head().try_cast::<Symbol>() {
+                if head.as_ref() == keyword {
+                    return Some(list.as_ref().tail());
+                }
+            }
+        }
+    }
+
+    None
+}
+
 fn syntax_object_switch(args: &Reachable<list::List>, ctx: &mut CCtx, obj: &mut Object) -> NResult<IForm, SyntaxException> {
     //TODO グローバル環境のbegin内にある場合、続きの式があるので動作がおかしくなる。
     //TODO 末尾文脈でのみ許可するようにしたい
@@ -746,9 +1291,36 @@ mod codegen {
         pub buf: Vec<u8>,
         pub constants: &'a mut Vec<Cap<Any>>,
         pub frames: &'a mut Vec<LocalFrame>,
+        pub source_map: compiled::SourceMap,
+    }
+
+    //
+    // 別バッファに生成したコードと、そのコードのソース位置の対応表
+    struct CGBlock {
+        buf: Vec<u8>,
+        source_map: compiled::SourceMap,
+    }
+
+    impl CGBlock {
+        pub fn len(&self) -> usize {
+            self.buf.len()
+        }
     }
 
     impl <'a> CGCtx<'a> {
+        pub fn into_block(self) -> CGBlock {
+            CGBlock {
+                buf: self.buf,
+                source_map: self.source_map,
+            }
+        }
+
+        pub fn extend_block(&mut self, block: CGBlock) {
+            //ブロックを書き込む位置に合わせて対応表の位置をずらす
+            self.source_map.append(self.buf.len(), block.source_map);
+            self.buf.extend(block.buf);
+        }
+
         pub fn add_constant(&mut self, v: Ref<Any>, obj: &mut Object) -> usize {
             //同じ値が既に存在しているなら
             if let Some((index, _)) = self.constants.iter().enumerate()
@@ -779,11 +1351,13 @@ mod codegen {
             buf: Vec::new(),
             constants: &mut constants,
             frames: &mut frames,
+            source_map: compiled::SourceMap::new(),
         };
 
         pass_codegen(iform, &mut ctx, obj);
 
-        compiled::Code::alloc(ctx.buf, constants, obj)
+        let CGCtx { buf, source_map, .. } = ctx;
+        compiled::Code::alloc(buf, constants, source_map, obj)
     }
 
     fn pass_codegen(iform: &Reachable<IForm>, ctx: &mut CGCtx, obj: &mut Object) {
@@ -824,6 +1398,9 @@ mod codegen {
             IFormKind::ObjectSwitch => {
                 codegen_object_switch(unsafe { iform.cast_unchecked::<IFormObjectSwitch>() }, ctx, obj)
             },
+            IFormKind::Try => {
+                codegen_try(unsafe { iform.cast_unchecked::<IFormTry>() }, ctx, obj)
+            },
         }
     }
 
@@ -866,9 +1443,10 @@ mod codegen {
                 buf: Vec::new(),
                 constants: ctx.constants,
                 frames: ctx.frames,
+                source_map: compiled::SourceMap::new(),
             };
             pass_codegen(&iform.as_ref().then().reach(obj), &mut ctx_then, obj);
-            ctx_then.buf
+            ctx_then.into_block()
         };
 
         let buf_else = {
@@ -876,10 +1454,11 @@ mod codegen {
                 buf: Vec::new(),
                 constants: ctx.constants,
                 frames: ctx.frames,
+                source_map: compiled::SourceMap::new(),
             };
             pass_codegen(&iform.as_ref().else_().reach(obj), &mut ctx_else, obj);
 
-            ctx_else.buf
+            ctx_else.into_block()
         };
 
         //タグ
@@ -893,7 +1472,7 @@ mod codegen {
         write_u16(jump_offset as u16, &mut ctx.buf);
 
         //THEN式を書き込む
-        ctx.buf.extend(buf_then);
+        ctx.extend_block(buf_then);
 
         //ELSE式をスキップするためのジャンプを書き込む
         write_u8(vm::tag::JUMP_OFFSET, &mut ctx.buf);
@@ -901,7 +1480,7 @@ mod codegen {
         write_u16(buf_else.len() as u16, &mut ctx.buf);
 
         //ELSE式を書き込む
-        ctx.buf.extend(buf_else);
+        ctx.extend_block(buf_else);
     }
 
     fn codegen_local(iform: &Reachable<IFormLocal>, ctx: &mut CGCtx, obj: &mut Object) {
@@ -977,6 +1556,7 @@ mod codegen {
                 buf: Vec::new(),
                 constants: &mut constants,
                 frames: ctx.frames,
+                source_map: compiled::SourceMap::new(),
             };
             //クロージャの本体を変換
             pass_codegen(&iform.as_ref().body().reach(obj), &mut ctx_body, obj);
@@ -984,7 +1564,7 @@ mod codegen {
             //リターンタグ
             write_u8(vm::tag::RETURN, &mut ctx_body.buf);
 
-            ctx_body.buf
+            ctx_body.into_block()
         };
         let free_vars = ctx.frames.pop().unwrap().free_vars.unwrap();
 
@@ -1009,8 +1589,13 @@ mod codegen {
         debug_assert!(num_free_vars < u16::MAX as usize);
         write_u16(num_free_vars as u16, &mut ctx.buf);
 
+        //クロージャ本体の範囲と名前を記録する
+        let name = iform.as_ref().name().map(|name| name.as_ref().as_ref().to_string());
+        let body_start = ctx.buf.len();
+        ctx.source_map.add_fun(body_start, body_start + size, name);
+
         //本体を書き込む
-        ctx.buf.extend(buf_body);
+        ctx.extend_block(buf_body);
 
         //Closureに自由変数を取り込むための命令を書き込む
         for (index, (_, refer)) in free_vars.into_iter().enumerate() {
@@ -1045,6 +1630,8 @@ mod codegen {
     }
 
     fn codegen_call(iform: &Reachable<IFormCall>, ctx: &mut CGCtx, obj: &mut Object) {
+        let start = ctx.buf.len();
+
         if iform.as_ref().is_tail() {
             write_u8(vm::tag::CALL_TAIL_PREPARE, &mut ctx.buf);
         } else {
@@ -1069,6 +1656,11 @@ mod codegen {
         } else {
             write_u8(vm::tag::CALL, &mut ctx.buf);
         }
+
+        //呼び出し式全体の命令列とソース上の位置を対応付ける
+        if let Some(pos) = iform.as_ref().source_pos() {
+            ctx.source_map.add_call(start, ctx.buf.len(), pos);
+        }
     }
 
     fn codegen_const(iform: &Reachable<IFormConst>, ctx: &mut CGCtx, obj: &mut Object) {
@@ -1100,7 +1692,7 @@ mod codegen {
 
     fn codegen_andor(iform: &Reachable<IFormAndOr>, ctx: &mut CGCtx, obj: &mut Object) {
         let num_exprs = iform.as_ref().len_exprs();
-        let mut expr_buf_vec: Vec<Vec<u8>> = Vec::new();
+        let mut expr_buf_vec: Vec<CGBlock> = Vec::new();
 
         //次の処理の簡単にするために、後ろの式から順に変換する
         //expr_buf_vecの一番後ろには、and/or式の第一引数が来る。
@@ -1110,10 +1702,11 @@ mod codegen {
                     buf: Vec::new(),
                     constants: ctx.constants,
                     frames: ctx.frames,
+                    source_map: compiled::SourceMap::new(),
                 };
                 pass_codegen(&iform.as_ref().get_expr(index).reach(obj), &mut ctx_expr, obj);
 
-                ctx_expr.buf
+                ctx_expr.into_block()
             };
 
             expr_buf_vec.push(buf_expr);
@@ -1123,7 +1716,7 @@ mod codegen {
         for _ in 0..num_exprs {
             let buf = expr_buf_vec.pop().unwrap();
             //引数式の実行部分を追加
-            ctx.buf.extend(buf);
+            ctx.extend_block(buf);
 
             //結果が確定したときにJUMPする先のオフセットを計算
             //全ての式の一番後ろの位置を計算している(+3は各式を評価した後にand/or判定するためのタグとオフセットの3Byte分。
@@ -1165,6 +1758,67 @@ mod codegen {
         }
     }
 
+    fn codegen_try(iform: &Reachable<IFormTry>, ctx: &mut CGCtx, obj: &mut Object) {
+        //本体とハンドラはJUMPさせるためにバッファの大きさを知る必要がある。
+        //それぞれ別のバッファを作成してそちらに書き込むようにする
+        let buf_body = {
+            let mut ctx_body = CGCtx {
+                buf: Vec::new(),
+                constants: ctx.constants,
+                frames: ctx.frames,
+                source_map: compiled::SourceMap::new(),
+            };
+            pass_codegen(&iform.as_ref().body().reach(obj), &mut ctx_body, obj);
+
+            ctx_body.into_block()
+        };
+
+        let buf_handler = {
+            let mut ctx_handler = CGCtx {
+                buf: Vec::new(),
+                constants: ctx.constants,
+                frames: ctx.frames,
+                source_map: compiled::SourceMap::new(),
+            };
+
+            //ハンドラ開始時点のaccには捕捉した例外が入っているので、新しいフレームに束縛する
+            write_u8(vm::tag::PUSH_EMPTY_ENV, &mut ctx_handler.buf);
+            write_u8(vm::tag::LET_LOCAL, &mut ctx_handler.buf);
+            ctx_handler.frames.push(LocalFrame {
+                frame: vec![iform.as_ref().var().capture(obj)],
+                free_vars: None,
+            });
+
+            pass_codegen(&iform.as_ref().handler().reach(obj), &mut ctx_handler, obj);
+
+            //フレームをpop
+            write_u8(vm::tag::POP_ENV, &mut ctx_handler.buf);
+            ctx_handler.frames.pop();
+
+            ctx_handler.into_block()
+        };
+
+        //タグ
+        write_u8(vm::tag::TRY, &mut ctx.buf);
+
+        //ハンドラまでのオフセットを書き込む
+        //本体の長さ + TRY_END命令3Byte
+        let handler_offset = buf_body.len() + 3;
+        debug_assert!(handler_offset < u16::MAX as usize);
+        write_u16(handler_offset as u16, &mut ctx.buf);
+
+        //本体を書き込む
+        ctx.extend_block(buf_body);
+
+        //正常に終了した場合はハンドラを取り除いて、ハンドラ部分をスキップする
+        write_u8(vm::tag::TRY_END, &mut ctx.buf);
+        debug_assert!(buf_handler.len() < u16::MAX as usize);
+        write_u16(buf_handler.len() as u16, &mut ctx.buf);
+
+        //ハンドラを書き込む
+        ctx.extend_block(buf_handler);
+    }
+
     enum LocalRefer {
         Normal(usize, usize),
         FreeVar(usize, usize),
@@ -1233,6 +1887,33 @@ fn func_compile_transform(_num_rest: usize, obj: &mut Object) -> NResult<Any, Ex
     Ok(compiled.into_value())
 }
 
+fn func_macroexpand(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut v = crate::vm::refer_arg::<Any>(0, obj).reach(obj);
+
+    //式の先頭がマクロである間、展開を繰り返す
+    for _ in 0 .. MAX_MACRO_EXPAND_DEPTH {
+        let mac = v.try_cast::<List>()
+            .filter(|list| !list.is_nil())
+            .and_then(|list| list.as_ref().head().try_cast::<Symbol>().cloned())
+            .and_then(|symbol| obj.find_global_value(symbol.as_ref()))
+            .and_then(|val| val.try_cast::<Macro>().cloned());
+
+        match mac {
+            Some(mac) => {
+                let mac = mac.reach(obj);
+                let list = unsafe { v.cast_unchecked::<List>() }.make().reach(obj);
+                //macroexpandはトップレベルの式として展開するため、隠されるローカル変数はない
+                v = Macro::expand(&mac, &list, &[], obj)?.reach(obj);
+            }
+            None => {
+                return Ok(v.make());
+            }
+        }
+    }
+
+    Err(Exception::MalformedFormat(err::MalformedFormat::new(Some(v.make()), "macro expansion is too deep")))
+}
+
 static SYMBOL_APP: Lazy<GCAllocationStruct<symbol::StaticSymbol>> = Lazy::new(|| {
     symbol::gensym_static("app")
 });
@@ -1253,6 +1934,10 @@ static SYNTAX_DEF_RECV: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("def-recv", 1, 0, true, syntax_def_recv))
 });
 
+static SYNTAX_DEF_SYNTAX: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("def-syntax", 2, 0, false, syntax_def_syntax))
+});
+
 static SYNTAX_FUN: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("fun", 1, 0, true, syntax_fun))
 });
@@ -1277,14 +1962,30 @@ static SYNTAX_UNQUOTE: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("unquote", 1, 0, false, syntax_unquote))
 });
 
+static SYNTAX_QUASIQUOTE: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("quasiquote", 1, 0, false, syntax_quasiquote))
+});
+
+static SYNTAX_UNQUOTE_SPLICING: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("unquote-splicing", 1, 0, false, syntax_unquote_splicing))
+});
+
 static SYNTAX_BIND: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("bind", 1, 0, false, syntax_bind))
 });
 
+static SYNTAX_ERROR_PATTERN: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("error-pattern", 1, 0, false, syntax_error_pattern))
+});
+
 static SYNTAX_MATCH: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("match", 1, 0, true, syntax_match))
 });
 
+static SYNTAX_RECEIVE: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("receive", 0, 0, true, syntax_receive))
+});
+
 static SYNTAX_AND: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(Syntax::new("and", 0, 0, true, syntax_and))
 });
@@ -1305,6 +2006,26 @@ static SYNTAX_FAIL_CATCH: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
     GCAllocationStruct::new(syntax::Syntax::new("fail-catch", 0, 0, true, syntax_fail_catch))
 });
 
+static SYNTAX_GLOBAL_REF: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("global-ref", 1, 0, false, syntax_global_ref))
+});
+
+static SYNTAX_TRY_FINALLY: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("try-finally", 1, 0, true, syntax_try_finally))
+});
+
+static SYNTAX_TRY: Lazy<GCAllocationStruct<Syntax>> = Lazy::new(|| {
+    GCAllocationStruct::new(Syntax::new("try", 0, 0, true, syntax_try))
+});
+
+static SYMBOL_CATCH: Lazy<GCAllocationStruct<symbol::StaticSymbol>> = Lazy::new(|| {
+    symbol::symbol_static("catch")
+});
+
+static SYMBOL_FINALLY: Lazy<GCAllocationStruct<symbol::StaticSymbol>> = Lazy::new(|| {
+    symbol::symbol_static("finally")
+});
+
 static FUNC_COMPILE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     GCAllocationStruct::new(
         Func::new("compile", func_compile,
@@ -1325,25 +2046,42 @@ static FUNC_COMPILE_TRANSFORM: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     )
 });
 
+static FUNC_MACROEXPAND: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("macroexpand", func_macroexpand,
+        Parameter::new(&[
+            Param::new("v", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("if", &Ref::new(&SYNTAX_IF.value));
     obj.define_global_value("begin", &Ref::new(&SYNTAX_BEGIN.value));
     obj.define_global_value("cond", &Ref::new(&SYNTAX_COND.value));
     obj.define_global_value("def-recv", &Ref::new(&SYNTAX_DEF_RECV.value));
+    obj.define_global_value("def-syntax", &Ref::new(&SYNTAX_DEF_SYNTAX.value));
     obj.define_global_value("fun", &Ref::new(&SYNTAX_FUN.value));
     obj.define_global_value("local", &Ref::new(&SYNTAX_LOCAL.value));
     obj.define_global_value("let", &Ref::new(&SYNTAX_LET.value));
     obj.define_global_value("let-global", &Ref::new(&SYNTAX_LET_GLOBAL.value));
     obj.define_global_value("quote", &Ref::new(&SYNTAX_QUOTE.value));
+    obj.define_global_value("quasiquote", &Ref::new(&SYNTAX_QUASIQUOTE.value));
     obj.define_global_value("unquote", &Ref::new(&SYNTAX_UNQUOTE.value));
+    obj.define_global_value("unquote-splicing", &Ref::new(&SYNTAX_UNQUOTE_SPLICING.value));
     obj.define_global_value("bind", &Ref::new(&SYNTAX_BIND.value));
+    obj.define_global_value("error-pattern", &Ref::new(&SYNTAX_ERROR_PATTERN.value));
     obj.define_global_value("match", &Ref::new(&SYNTAX_MATCH.value));
+    obj.define_global_value("receive", &Ref::new(&SYNTAX_RECEIVE.value));
     obj.define_global_value("and", &Ref::new(&SYNTAX_AND.value));
     obj.define_global_value("or", &Ref::new(&SYNTAX_OR.value));
     obj.define_global_value("object-switch", &Ref::new(&SYNTAX_OBJECT_SWITCH.value));
     obj.define_global_value("return-object-switch", &Ref::new(&SYNTAX_RETURN_OBJECT_SWITCH.value));
+    obj.define_global_value("try", &Ref::new(&SYNTAX_TRY.value));
     obj.define_global_value("compile", &Ref::new(&FUNC_COMPILE.value));
     obj.define_global_value("compile-transform", &Ref::new(&FUNC_COMPILE_TRANSFORM.value));
+    obj.define_global_value("macroexpand", &Ref::new(&FUNC_MACROEXPAND.value));
 }
 
 pub mod literal {
@@ -1354,14 +2092,26 @@ pub mod literal {
         Reachable::new_static(&SYNTAX_QUOTE.value)
     }
 
+    pub fn quasiquote() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_QUASIQUOTE.value)
+    }
+
     pub fn unquote() -> Reachable<Syntax> {
         Reachable::new_static(&SYNTAX_UNQUOTE.value)
     }
 
+    pub fn unquote_splicing() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_UNQUOTE_SPLICING.value)
+    }
+
     pub fn bind() -> Reachable<Syntax> {
         Reachable::new_static(&SYNTAX_BIND.value)
     }
 
+    pub fn error_pattern() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_ERROR_PATTERN.value)
+    }
+
     pub fn fun() -> Reachable<Syntax> {
         Reachable::new_static(&SYNTAX_FUN.value)
     }
@@ -1378,6 +2128,10 @@ pub mod literal {
         Reachable::new_static(&SYNTAX_MATCH.value)
     }
 
+    pub fn and() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_AND.value)
+    }
+
     pub fn if_() -> Reachable<Syntax> {
         Reachable::new_static(&SYNTAX_IF.value)
     }
@@ -1394,6 +2148,26 @@ pub mod literal {
         Reachable::new_static(&SYNTAX_FAIL_CATCH.value)
     }
 
+    pub fn try_() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_TRY.value)
+    }
+
+    pub fn global_ref() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_GLOBAL_REF.value)
+    }
+
+    pub fn try_finally() -> Reachable<Syntax> {
+        Reachable::new_static(&SYNTAX_TRY_FINALLY.value)
+    }
+
+    pub fn catch_symbol() -> Reachable<symbol::Symbol> {
+        Reachable::new_static(SYMBOL_CATCH.value.as_ref())
+    }
+
+    pub fn finally_symbol() -> Reachable<symbol::Symbol> {
+        Reachable::new_static(SYMBOL_FINALLY.value.as_ref())
+    }
+
     pub fn app_symbol() -> Reachable<symbol::Symbol> {
         Reachable::new_static(SYMBOL_APP.value.as_ref())
     }
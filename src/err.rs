@@ -1,5 +1,6 @@
 use crate::object::AnyAllocator;
 use crate::ptr::*;
+use crate::read::SourcePos;
 use crate::value::{*, self};
 use crate::value::any::Any;
 
@@ -156,8 +157,11 @@ impl ArgTypeMismatch {
 pub struct DisallowContext {}
 
 
-#[derive(Clone, Debug)]
-pub struct OutOfMemory {}
+#[derive(Clone, Debug, Default)]
+pub struct OutOfMemory {
+    //オブジェクトごとに設定したメモリ上限を超えたことによる失敗か
+    pub quota_exceeded: bool,
+}
 
 #[derive(Clone, Debug)]
 pub struct MySelfObjectDeleted {}
@@ -190,6 +194,81 @@ impl UnboundVariable {
 
 }
 
+#[derive(Clone, Debug)]
+pub struct Raise {
+    //raiseで投げられた任意の値
+    pub value: Ref<Any>,
+}
+
+impl Raise {
+    pub fn new(value: Ref<Any>) -> Self {
+        Raise {
+            value
+        }
+    }
+
+    unsafe fn value_clone_gcunsafe(&self, allocator: &mut AnyAllocator) -> Result<Self, OutOfMemory> {
+        let value = self.value.clone().into_reachable();
+        let value_cloned = value::value_clone(&value, allocator)?;
+        Ok(Self::new(value_cloned))
+    }
+
+    fn for_each_alived_value(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(&mut self.value, arg);
+    }
+
+    fn display(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "uncaught exception:{}", self.value.as_ref())
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct UserError {
+    pub kind: Ref<keyword::Keyword>,
+    pub message: Ref<string::NString>,
+    pub payload: Ref<Any>,
+}
+
+impl UserError {
+    pub fn new(kind: Ref<keyword::Keyword>, message: Ref<string::NString>, payload: Ref<Any>) -> Self {
+        UserError {
+            kind,
+            message,
+            payload,
+        }
+    }
+
+    unsafe fn value_clone_gcunsafe(&self, allocator: &mut AnyAllocator) -> Result<Self, OutOfMemory> {
+        let kind = self.kind.clone().into_reachable();
+        let kind_cloned = value::value_clone(&kind, allocator)?;
+        let message = self.message.clone().into_reachable();
+        let message_cloned = value::value_clone(&message, allocator)?;
+        let payload = self.payload.clone().into_reachable();
+        let payload_cloned = value::value_clone(&payload, allocator)?;
+
+        Ok(Self::new(kind_cloned, message_cloned, payload_cloned))
+    }
+
+    fn for_each_alived_value(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(self.kind.cast_mut_value(), arg);
+        callback(self.message.cast_mut_value(), arg);
+        callback(&mut self.payload, arg);
+    }
+
+    fn display(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind.as_ref(), self.message.as_ref())?;
+        //ペイロードが省略されている場合は表示しない
+        let is_unit = self.payload.try_cast::<tuple::Tuple>().is_some_and(|t| t.as_ref().is_unit());
+        if !is_unit {
+            write!(f, "\n  payload:{}", self.payload.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+}
+
 #[derive(Debug, Clone)]
 pub enum Exception {
     OutOfBounds(OutOfBounds),
@@ -197,8 +276,13 @@ pub enum Exception {
     MalformedFormat(MalformedFormat),
     UnboundVariable(UnboundVariable),
     ArgTypeMismatch(ArgTypeMismatch),
+    Raise(Raise),
+    UserError(UserError),
     DisallowContext,
+    DivisionByZero,
     OutOfMemory,
+    MemoryQuotaExceeded,
+    ReplyTimeout,
     TimeLimit,
     WaitReply,
     MySelfObjectDeleted,
@@ -228,10 +312,21 @@ impl Exception {
            Self::ArgTypeMismatch(inner) => {
                let inner = inner.value_clone_gcunsafe(allocator)?;
                Ok(Exception::ArgTypeMismatch(inner))
+           }
+           Self::Raise(inner) => {
+               let inner = inner.value_clone_gcunsafe(allocator)?;
+               Ok(Exception::Raise(inner))
+           }
+           Self::UserError(inner) => {
+               let inner = inner.value_clone_gcunsafe(allocator)?;
+               Ok(Exception::UserError(inner))
            }
             //enum項目追加の時にmatch節の追加忘れを防ぐためにワイルドカードで書かない
             Exception::DisallowContext => { Ok(Self::DisallowContext) }
+            Exception::DivisionByZero => { Ok(Self::DivisionByZero) }
             Exception::OutOfMemory => { Ok(Self::OutOfMemory) }
+            Exception::MemoryQuotaExceeded => { Ok(Self::MemoryQuotaExceeded) }
+            Exception::ReplyTimeout => { Ok(Self::ReplyTimeout) }
             Exception::MySelfObjectDeleted => { Ok(Self::MySelfObjectDeleted) }
             Exception::TimeLimit => { Ok(Self::TimeLimit) }
             Exception::WaitReply => { Ok(Self::WaitReply) }
@@ -257,9 +352,18 @@ impl Exception {
             Exception::ArgTypeMismatch(inner) => {
                 inner.for_each_alived_value(arg, callback);
             }
+            Exception::Raise(inner) => {
+                inner.for_each_alived_value(arg, callback);
+            }
+            Exception::UserError(inner) => {
+                inner.for_each_alived_value(arg, callback);
+            }
             //enum項目追加の時にmatch節の追加忘れを防ぐためにワイルドカードで書かない
             Exception::DisallowContext => { }
+            Exception::DivisionByZero => { }
             Exception::OutOfMemory => { }
+            Exception::MemoryQuotaExceeded => { }
+            Exception::ReplyTimeout => { }
             Exception::MySelfObjectDeleted => { }
             Exception::TimeLimit => { }
             Exception::WaitReply => { }
@@ -268,12 +372,56 @@ impl Exception {
         }
     }
 
-}
+    ///
+    /// try式のハンドラで捕捉できる例外か。
+    /// VMの中断や終了を表す例外は捕捉せずにそのまま呼び出し元へ返す。
+    pub fn is_catchable(&self) -> bool {
+        !matches!(self, Exception::TimeLimit
+            | Exception::WaitReply
+            | Exception::MySelfObjectDeleted
+            | Exception::Exit)
+    }
 
-impl std::fmt::Display for Exception {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "*** Error:")?;
+    ///
+    /// 例外の種類を表す名前。
+    /// errorで作成された例外は作成時に指定されたキーワードが種類になるため、ここではNoneを返す。
+    pub fn kind_name(&self) -> Option<&'static str> {
+        match self {
+            Exception::OutOfBounds(_) => Some("out-of-bounds"),
+            Exception::TypeMismatch(_) => Some("type-mismatch"),
+            Exception::MalformedFormat(_) => Some("malformed-format"),
+            Exception::UnboundVariable(_) => Some("unbound-variable"),
+            Exception::ArgTypeMismatch(_) => Some("arg-type-mismatch"),
+            Exception::Raise(_) => Some("raise"),
+            Exception::UserError(_) => None,
+            Exception::DisallowContext => Some("disallow-context"),
+            Exception::DivisionByZero => Some("division-by-zero"),
+            Exception::OutOfMemory => Some("out-of-memory"),
+            Exception::MemoryQuotaExceeded => Some("memory-quota-exceeded"),
+            Exception::ReplyTimeout => Some("timeout"),
+            Exception::TimeLimit => Some("time-limit"),
+            Exception::WaitReply => Some("wait-reply"),
+            Exception::MySelfObjectDeleted => Some("myself-object-deleted"),
+            Exception::Exit => Some("exit"),
+            Exception::Other(_) => Some("other"),
+        }
+    }
+
+    ///
+    /// "*** Error:"の接頭辞を含まない例外のメッセージ
+    pub fn message(&self) -> String {
+        struct Message<'a>(&'a Exception);
 
+        impl std::fmt::Display for Message<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.display_message(f)
+            }
+        }
+
+        Message(self).to_string()
+    }
+
+    fn display_message(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Exception::OutOfBounds(inner) => {
                 inner.display(f)
@@ -290,13 +438,28 @@ impl std::fmt::Display for Exception {
             Exception::ArgTypeMismatch(inner) => {
                 inner.display(f)
             }
+            Exception::Raise(inner) => {
+                inner.display(f)
+            }
+            Exception::UserError(inner) => {
+                inner.display(f)
+            }
             //enum項目追加の時にmatch節の追加忘れを防ぐためにワイルドカードで書かない
             Exception::DisallowContext => {
                 write!(f, "Disallow context")
             }
+            Exception::DivisionByZero => {
+                write!(f, "Division by zero")
+            }
             Exception::OutOfMemory => {
                 write!(f, "Out of memory")
             }
+            Exception::MemoryQuotaExceeded => {
+                write!(f, "Memory quota exceeded")
+            }
+            Exception::ReplyTimeout => {
+                write!(f, "Reply timed out")
+            }
             Exception::MySelfObjectDeleted => {
                 //MySelfObjectDeletedがDisplayの対象になること自体が不具合
                 unreachable!()
@@ -318,6 +481,59 @@ impl std::fmt::Display for Exception {
             }
         }
     }
+
+}
+
+impl std::fmt::Display for Exception {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "*** Error:")?;
+        self.display_message(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktraceFrame {
+    //フレームで実行していた関数の名前
+    pub name: String,
+    //フレーム内で実行中だった呼び出し式のソース上の位置。ネイティブ関数ではNone
+    pub pos: Option<SourcePos>,
+}
+
+impl BacktraceFrame {
+    pub fn new(name: String, pos: Option<SourcePos>) -> Self {
+        BacktraceFrame {
+            name,
+            pos,
+        }
+    }
+}
+
+///
+/// 例外が発生した位置から呼び出し元へ向かう順にフレームを並べたバックトレース
+#[derive(Debug, Clone)]
+pub struct Backtrace {
+    pub frames: Vec<BacktraceFrame>,
+}
+
+impl Backtrace {
+    pub fn new(frames: Vec<BacktraceFrame>) -> Self {
+        Backtrace {
+            frames,
+        }
+    }
+}
+
+impl std::fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "backtrace:")?;
+        for frame in self.frames.iter() {
+            match frame.pos {
+                Some(pos) => write!(f, "\n  {} at {}", frame.name, pos)?,
+                None => write!(f, "\n  {}", frame.name)?,
+            }
+        }
+        Ok(())
+    }
 }
 
 impl From<OutOfBounds> for Exception {
@@ -327,8 +543,12 @@ impl From<OutOfBounds> for Exception {
 }
 
 impl From<OutOfMemory> for Exception {
-    fn from(_: OutOfMemory) -> Self {
-        Exception::OutOfMemory
+    fn from(this: OutOfMemory) -> Self {
+        if this.quota_exceeded {
+            Exception::MemoryQuotaExceeded
+        } else {
+            Exception::OutOfMemory
+        }
     }
 }
 
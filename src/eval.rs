@@ -70,7 +70,7 @@ fn func_apply(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
 
     match last_arg.try_cast::<list::List>() {
         Some(last_arg) => {
-            let args = builder.append_get(&last_arg.make());
+            let args = builder.append_get(&last_arg.make(), obj);
             let iter = args.reach(obj).iter(obj);
             func_apply_result(vm::app_call(&app, iter, vm::WorkTimeLimit::TakeOver, obj), obj)
         }
@@ -125,6 +125,15 @@ pub fn register_global(obj: &mut Object) {
     obj.define_global_value("apply", &Ref::new(&FUNC_APPLY.value));
 }
 
+pub mod literal {
+    use super::*;
+
+    pub fn apply() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_APPLY.value)
+    }
+
+}
+
 #[cfg(test)]
 pub fn exec<T: NaviType>(program: &str, obj: &mut Object) -> Ref<T> {
     let mut reader = crate::read::Reader::new(program.chars().peekable());
@@ -141,13 +150,21 @@ pub fn exec<T: NaviType>(program: &str, obj: &mut Object) -> Ref<T> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::eval::exec;
     use crate::object::Object;
     use crate::value::*;
     use crate::value::any::Any;
     use crate::ptr::*;
 
+    ///
+    /// programの評価結果が、ansを評価した値と等しいことを確認する。
+    /// ansはans_objの中で評価するため、programを評価するobjの状態には影響しない。
+    pub(crate) fn check(program: &str, ans: &str, obj: &mut Object, ans_obj: &mut Object) {
+        let result = exec::<Any>(program, obj).capture(obj);
+        let ans = exec::<Any>(ans, ans_obj);
+        assert_eq!(result.as_ref(), ans.as_ref(), "{}", program);
+    }
 
     #[test]
     fn func_test() {
@@ -248,6 +265,25 @@ mod tests {
     }
 
 
+    #[test]
+    fn immidiate_value_optimized() {
+        //Nilやunitのようなimmidiate valueをたどる処理が、リリースビルドの最適化で壊れないことの確認。
+        //cargo test --releaseでも実行すること。
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(cond (true 1))", "1", obj, ans_obj);
+        check("(cond (false 1) (else 2))", "2", obj, ans_obj);
+        check("(match 1 (1 2))", "2", obj, ans_obj);
+        check("(match '() (() 1) (_ 2))", "1", obj, ans_obj);
+        check("(match {} ({} 1) (_ 2))", "1", obj, ans_obj);
+        check("(list-len '())", "0", obj, ans_obj);
+        check("(= '() '())", "true", obj, ans_obj);
+        check("(= {} {})", "true", obj, ans_obj);
+    }
+
     #[test]
     fn syntax_cond_test() {
         let mut obj = Object::new_for_test();
@@ -470,6 +506,30 @@ mod tests {
             let ans = number::make_integer(5, ans_obj).unwrap().into_value();
             assert_eq!(result.as_ref(), ans.as_ref());
         }
+
+        {
+            //ハッシュマップのパターンは指定したキーだけを取り出してマッチする
+            let program = "(match #{:a 1 :b {2 3} :c 4} (#{:a 2} 1) (#{:a 1 :b {@x @y}} (+ x y)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(5, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //キーの組み合わせが異なるパターンは、前のパターンが失敗したら順番に試される
+            let program = "(match #{:a 1 :b 2} (#{:a @x :b 3} x) (#{:b @y} (+ y 10)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(12, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(match #{:a 1} (#{:b @x} x) ([@x] x))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = bool::Bool::false_().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(match '(1) (#{} 1) ((@x) 2))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
     }
 
     #[test]
@@ -498,4 +558,379 @@ mod tests {
         assert_eq!(result.as_ref(), ans.as_ref());
     }
 
+    #[test]
+    fn syntax_try() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        {
+            let program = "(try 1 2)";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (raise 1) (catch 2 20) (catch 1 10))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(10, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (+ 1 (raise 5)) (catch @e (+ e 1)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(6, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(+ (try 1 (catch @e 0)) (try (raise 2) (catch @e e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //マッチしなかった値は外側のtryへ投げ直される
+            let program = "(try (try (raise 1) (catch 2 20)) (catch 1 10))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(10, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //ハンドラ内で投げられた値は外側のtryで捕捉される
+            let program = "(try (try (raise 1) (catch @e (raise (+ e 1)))) (catch @e e))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //関数呼び出しやapplyをまたいで捕捉できる
+            let program = "(local (let f (fun (n) (raise n))) (try (f 3) (catch @e e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (apply (fun (n) (raise n)) '(4)) (catch @e e))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(4, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(apply (fun (n) (try (raise n) (catch @e (+ e 1)))) '(4))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(5, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //組み込みのエラーもExceptionとして捕捉できる
+            let program = "(try (undefined-variable) (catch @e 1))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(1, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //finallyは正常終了時もエラー時も実行される
+            let program = "(try (let-global try-a 1) (finally (let-global try-a 2)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(1, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "try-a";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (try (raise 1) (catch 2 20) (finally (let-global try-b 3))) (catch 1 try-b))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_error() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        {
+            let program = "(try (error :timeout \"no reply\" {1 2}) (catch @e (error-kind e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = keyword::Keyword::alloc("timeout", ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (error :timeout \"no reply\" {1 2}) (catch @e (error-message e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = string::NString::alloc(&"no reply".to_string(), ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (error :timeout \"no reply\" {1 2}) (catch @e (tuple-ref (error-payload e) 1)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //組み込みの例外も種類をキーワードで取得できる
+            let program = "(try (undefined-variable) (catch @e (error-kind e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = keyword::Keyword::alloc("unbound-variable", ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //matchのパターンで種類やペイロードを取り出せる
+            let program = "(try (error :timeout \"no reply\" {1 2}) (catch !(:not-found @m) 1) (catch !(:timeout @m {@a @b}) (+ a b)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (error :not-found \"missing\") (catch !(:timeout) 1) (catch !(:not-found \"missing\") 2))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //どのパターンにもマッチしなければ外側に投げなおされる
+            let program = "(try (try (error :timeout \"no reply\") (catch !(:not-found) 1)) (catch !(@k) (error? k)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = bool::Bool::false_().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(match 1 (!(@k) k) (@x x))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(1, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //(error ...)は例外パターンではなく、通常のリストパターンとして扱われる
+            let program = "(match (list error 1 \"x\") (!(@k) :error) ((error @code @msg) code))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(1, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+    }
+
+
+    fn exec_error(program: &str, obj: &mut Object) -> crate::err::Backtrace {
+        let mut reader = crate::read::Reader::new(program.chars().peekable());
+        loop {
+            let sexp = crate::read::read(&mut reader, obj).unwrap().reach(obj);
+            if crate::eval::eval(&sexp, obj).is_err() {
+                return crate::vm::take_backtrace(obj).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_backtrace() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        {
+            let program = "(let f (fun (x)\n  (+ x undefined-var)))\n(let g (fun (y) (f y) y))\n(g 1)";
+            let backtrace = exec_error(program, obj);
+            let frames: Vec<String> = backtrace.frames.iter()
+                .map(|frame| format!("{} {}", frame.name, frame.pos.map(|pos| pos.to_string()).unwrap_or_default()))
+                .collect();
+            assert_eq!(frames, vec!["f 2:3", "g 3:17", "<toplevel> 4:1"]);
+        }
+
+        {
+            //ネイティブ関数内で発生した例外は関数名が一番内側のフレームになる
+            let program = "(let h (fun () (error :oops \"m\") 1))\n(h)";
+            let backtrace = exec_error(program, obj);
+            let frames: Vec<String> = backtrace.frames.iter()
+                .map(|frame| format!("{} {}", frame.name, frame.pos.map(|pos| pos.to_string()).unwrap_or_default()))
+                .collect();
+            assert_eq!(frames, vec!["error ", "h 1:16", "<toplevel> 2:1"]);
+        }
+
+        {
+            //捕捉された例外はバックトレースを残さない
+            let program = "(try (undefined-variable) (catch @e 1))";
+            let result = exec::<Any>(program, obj);
+            assert!(result.as_ref().is::<number::Integer>());
+            assert!(crate::vm::take_backtrace(obj).is_none());
+        }
+
+        {
+            //finallyやマッチしなかったcatch節を通り抜けた例外は、元の位置のバックトレースを保持する
+            let program = "(let f (fun (x)\n  (+ x undefined-var)))\n(let g (fun (y) (f y) y))\n(try (g 1) (finally 1))";
+            let backtrace = exec_error(program, obj);
+            let frames: Vec<String> = backtrace.frames.iter()
+                .map(|frame| format!("{} {}", frame.name, frame.pos.map(|pos| pos.to_string()).unwrap_or_default()))
+                .collect();
+            assert_eq!(frames, vec!["f 2:3", "g 3:17", "<toplevel> 4:6"]);
+
+            let program = "(let h (fun (n) (raise n) n))\n(try (h 1) (catch 2 20) (finally 1))";
+            let backtrace = exec_error(program, obj);
+            let frames: Vec<String> = backtrace.frames.iter()
+                .map(|frame| format!("{} {}", frame.name, frame.pos.map(|pos| pos.to_string()).unwrap_or_default()))
+                .collect();
+            assert_eq!(frames, vec!["raise ", "h 1:17", "<toplevel> 2:6"]);
+        }
+
+        {
+            //finallyを通り抜けた例外は元の種類と値のまま捕捉できる
+            let mut ans_obj = Object::new_for_test();
+            let ans_obj = &mut ans_obj;
+
+            let program = "(try (try (undefined-variable) (finally 1)) (catch @e (error-kind e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = keyword::Keyword::alloc("unbound-variable", ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(try (try (raise 3) (finally 1)) (catch @e e))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+    }
+
+
+    #[test]
+    fn test_macro() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        {
+            let program = "(def-syntax my-or (syntax-rules () ((_) false) ((_ e) e) ((_ e r ...) (local (let t e) (if t t (my-or r ...))))))";
+            exec::<Any>(program, obj);
+
+            let program = "(my-or false 2)";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //マクロ内で導入した変数tは、呼び出し側の変数tを隠さない
+            let program = "(local (let t 5) (my-or false t))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(5, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(let f (fun (x) (my-or false false x)))";
+            exec::<Any>(program, obj);
+            let program = "(f 3)";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(3, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //テンプレート内の自由なシンボルは、呼び出し側のローカル変数ではなくグローバルの値を参照する
+            let program = "(def-syntax my-list (syntax-rules () ((_ a) (list a a))))";
+            exec::<Any>(program, obj);
+
+            let program = "(local (let list (fun (x y) :shadowed)) (my-list 1))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = crate::eval::exec::<Any>("'(1 1)", ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(local (let if 3) (my-or false 2))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //quoteされたシンボルはそのまま残る
+            let program = "(def-syntax quoted-list (syntax-rules () ((_ a) '(list a))))";
+            exec::<Any>(program, obj);
+
+            let program = "(local (let list 1) (quoted-list 5))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = crate::eval::exec::<Any>("'(list 5)", ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //ネストした繰り返しとタプルのテンプレート
+            let program = "(def-syntax pairs (syntax-rules () ((_ (k v ...) ...) (list {k (list v ...)} ...))))";
+            exec::<Any>(program, obj);
+
+            let program = "(pairs (1 2 3) (4))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = crate::eval::exec::<Any>("'({1 (2 3)} {4 ()})", ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //リテラルは同じ名前のシンボルにだけマッチする
+            let program = "(def-syntax when-else (syntax-rules (else) ((_ c e1 else e2) (if c e1 e2)) ((_ c e1) (if c e1 false))))";
+            exec::<Any>(program, obj);
+
+            let program = "(when-else false 1 else 2)";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(2, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(when-else true 1)";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = number::make_integer(1, ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //先頭のマクロだけを展開する
+            let program = "(macroexpand '(when-else c (when-else d 1)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = crate::eval::exec::<Any>("'(if c (when-else d 1) false)", ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let program = "(macroexpand '(+ 1 2))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = crate::eval::exec::<Any>("'(+ 1 2)", ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            //どのパターンにもマッチしなければ例外になる
+            let program = "(try (macroexpand (quote (when-else 1 2 3))) (catch @e (error-kind e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = keyword::Keyword::alloc("malformed-format", ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //展開が終わらないマクロは深さの上限でエラーになる
+            let program = "(def-syntax m (syntax-rules () ((_) (m))))";
+            exec::<Any>(program, obj);
+
+            let mut reader = crate::read::Reader::new("(m)".chars().peekable());
+            let sexp = crate::read::read(&mut reader, obj).unwrap().reach(obj);
+            assert!(crate::compile::compile(&sexp, obj).is_err());
+
+            let program = "(try (macroexpand '(m)) (catch @e (error-kind e)))";
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = keyword::Keyword::alloc("malformed-format", ans_obj).unwrap().into_value();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_quasiquote() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        exec::<Any>("(let x 1)", obj);
+        exec::<Any>("(let ys '(2 3))", obj);
+
+        let cases = [
+            ("`(a ,x b)", "'(a 1 b)"),
+            ("`(a ,@ys c)", "'(a 2 3 c)"),
+            ("`(,@ys ,@ys)", "'(2 3 2 3)"),
+            ("`(1 ,@'() 2)", "'(1 2)"),
+            ("`[,x ,@ys 4]", "[1 2 3 4]"),
+            ("`{:msg ,x (y ,@ys)}", "{:msg 1 '(y 2 3)}"),
+            ("`{a b}", "'{a b}"),
+            //ネストしたquasiquoteの内側のunquoteは評価されない
+            ("`(1 `(2 ,(3 ,x)))", "'(1 `(2 ,(3 1)))"),
+        ];
+        for (program, ans) in cases {
+            let result = exec::<Any>(program, obj).capture(obj);
+            let ans = exec::<Any>(ans, ans_obj).capture(ans_obj);
+            assert_eq!(result.as_ref(), ans.as_ref(), "{}", program);
+        }
+
+        //quasiquoteの外でのunquoteはエラー
+        let mut reader = crate::read::Reader::new(",x".chars().peekable());
+        let sexp = crate::read::read(&mut reader, obj).unwrap().reach(obj);
+        assert!(crate::compile::compile(&sexp, obj).is_err());
+    }
+
 }
\ No newline at end of file
@@ -32,6 +32,9 @@ fn main() {
                             }
                             err => {
                                 println!("{}", err);
+                                if let Some(backtrace) = navi::vm::take_backtrace(standalone.mut_object()) {
+                                    println!("{}", backtrace);
+                                }
                             }
                         }
                     }
@@ -41,7 +44,8 @@ fn main() {
                 break;
             }
             Err(navi::read::ReadException::OutOfMemory) => {
-                panic!("OOM");
+                //読み込み途中の値は破棄して、次の入力を受け付ける
+                println!("{}", Exception::OutOfMemory);
             }
             Err(navi::read::ReadException::MalformedFormat(err)) => {
                 println!("{}", Exception::MalformedFormat(err));
@@ -1,11 +1,12 @@
 mod fixed_size_alloc;
 mod world;
 pub mod mm;
-mod balance;
+pub mod balance;
 mod schedule;
 pub mod mailbox;
 
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::mem::MaybeUninit;
 use std::sync::atomic::AtomicUsize;
@@ -18,6 +19,7 @@ use crate::value::*;
 use crate::ptr::*;
 use crate::err::*;
 use crate::compile;
+use crate::read::SourcePos;
 
 use crate::value::any::Any;
 use crate::value::func::Func;
@@ -39,11 +41,45 @@ pub trait Allocator {
     fn heap_used(&self) -> usize;
 }
 
+//スケジューラが一度にオブジェクトを実行するreduction数の初期値
+pub const DEFAULT_REDUCTION_BUDGET: usize = 1000;
+
+///
+/// スケジューラが次に実行するオブジェクトを選ぶときの優先度
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Priority {
+    Low = 0,
+    Normal = 1,
+    High = 2,
+}
+
+impl Priority {
+    pub fn from_name(name: &str) -> Option<Priority> {
+        match name {
+            "low" => Some(Priority::Low),
+            "normal" => Some(Priority::Normal),
+            "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
 enum SuspendState {
     Sleep,
-    VMSuspend(Arc<Mutex<MailBox>>, ReplyToken),
-    WaitReply(Ref<reply::Reply>, Arc<Mutex<MailBox>>, ReplyToken),
-    DuplicateWaitReply(Arc<Mutex<MailBox>>, ReplyToken),
+    VMSuspend(ReplyTo),
+    //VM内で返信を待っている状態。返信が届くまでスケジューラに実行されることはない。
+    VMWaitReply(ReplyTo),
+    //レシーバーの結果に含まれる返信を待っている状態。
+    WaitReply(Ref<Any>, ReplyTo),
+    DuplicateWaitReply(ReplyTo),
 }
 
 impl SuspendState {
@@ -61,16 +97,25 @@ struct ObjectGCRootValues {
     world: world::World,
 
     vm_state: VMState,
+    //Funcの中から別のVMStateでクロージャを実行している間、退避させている実行中のVMState
+    saved_vm_states: Vec<VMState>,
 
     captures: FixedSizeAllocator<Ref<Any>>,
 
     receiver_vec: Vec<(Ref<Any>, Ref<list::List>)>,
     receiver_closure: Option<Ref<compiled::Closure>>,
+
+    //直前に読み込んだ式に含まれるリストと、そのソース上の位置
+    //リストのアドレスをキーにして保持する
+    source_positions: HashMap<usize, (Ref<list::List>, SourcePos)>,
 }
 
 impl mm::GCRootValueHolder for ObjectGCRootValues {
     fn for_each_alived_value(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
         self.vm_state.for_each_all_alived_value(arg, callback);
+        self.saved_vm_states.iter_mut().for_each(|vm_state| {
+            vm_state.for_each_all_alived_value(arg, callback);
+        });
 
         if let Some(prev_object) = self.prev_object.as_mut() {
             callback(prev_object.cast_mut_value(), arg);
@@ -98,6 +143,14 @@ impl mm::GCRootValueHolder for ObjectGCRootValues {
         if let Some(closure) = self.receiver_closure.as_mut() {
             callback(closure.cast_mut_value(), arg);
         }
+
+        //ソース上の位置を記録しているリスト
+        //GCでリストが移動するため、移動後のアドレスをキーにして登録しなおす
+        let source_positions = std::mem::take(&mut self.source_positions);
+        for (_, (mut list, pos)) in source_positions.into_iter() {
+            callback(list.cast_mut_value(), arg);
+            self.source_positions.insert(list.raw_ptr() as usize, (list, pos));
+        }
     }
 }
 
@@ -111,6 +164,25 @@ pub struct Object {
 
     heap: Heap,
 
+    //sleep関数で実行を停止している場合の再開時刻
+    sleep_until: Option<std::time::Instant>,
+
+    //返信を待つ間に実行していた処理が、返信とは関係なく実行時間の制限で中断している場合はtrue。
+    //返信の到着を待たずに、次の実行で続きを再開する
+    resume_immediately: bool,
+
+    //現在処理中のメッセージの返信先。
+    //処理中にOOMが発生した場合に、送信元へエラーを返信するために保持する。
+    processing: ReplyTo,
+
+    //receiveで受け取ったメッセージの返信先。
+    //節の本体を実行し終わるまで保持し、本体の結果を返信する。
+    receiving: Vec<ReplyTo>,
+
+    //スケジューラが一度に実行するreduction数
+    reduction_budget: usize,
+    priority: Priority,
+
     values: UnsafeCell<ObjectGCRootValues>,
 }
 
@@ -123,6 +195,15 @@ impl Object {
 
             heap: Heap::new(mm::StartHeapSize::Default),
 
+            sleep_until: None,
+            resume_immediately: false,
+
+            processing: None,
+            receiving: Vec::new(),
+
+            reduction_budget: DEFAULT_REDUCTION_BUDGET,
+            priority: Priority::Normal,
+
             values: UnsafeCell::new(ObjectGCRootValues {
                 suspend_state: SuspendState::Sleep,
 
@@ -131,11 +212,14 @@ impl Object {
                 world: world::World::new(),
 
                 vm_state: VMState::new(),
+                saved_vm_states: Vec::new(),
 
                 captures: FixedSizeAllocator::new(),
 
                 receiver_vec: Vec::new(),
                 receiver_closure: None,
+
+                source_positions: HashMap::new(),
             })
         };
         obj.register_core_global();
@@ -150,7 +234,14 @@ impl Object {
             mailbox,
 
             //複製元のヒープ内オブジェクトがすべて収まる範囲の新しいヒープを作成
-            heap: Heap::new_capacity(object.heap.used()),
+            heap: Heap::new_capacity(object.heap.used(), object.heap.large_used()),
+            sleep_until: None,
+            resume_immediately: false,
+            processing: None,
+            receiving: Vec::new(),
+            //スケジューリングとGCの設定は複製元から引き継ぐ
+            reduction_budget: object.reduction_budget,
+            priority: object.priority,
             //valuesは新しいヒープにコピーしないといけないので、現時点ではダミーの値を入れておく
             values: unsafe { MaybeUninit::uninit().assume_init() }
         };
@@ -162,10 +253,14 @@ impl Object {
                     prev_object: None,
                     world: unsafe { &*object.values.get() }.world.clone(),
                     vm_state: VMState::new(),
+                    saved_vm_states: Vec::new(),
                     captures: FixedSizeAllocator::new(),
 
                     receiver_vec: unsafe { &*object.values.get() }.receiver_vec.clone(),
                     receiver_closure: unsafe { &*object.values.get() }.receiver_closure.clone(),
+
+                    //位置情報は読み込み直後のコンパイルでのみ使用するため引き継がない
+                    source_positions: HashMap::new(),
                 };
 
             let mut allocator = AnyAllocator::Object(&mut obj_cloned);
@@ -197,6 +292,8 @@ impl Object {
         unsafe {
             std::ptr::write(&mut obj_cloned.values, UnsafeCell::new(values));
         }
+        obj_cloned.heap.set_grow_threshold(object.heap.grow_threshold());
+        obj_cloned.heap.set_quota(object.heap.quota());
 
         obj_cloned
     }
@@ -226,11 +323,126 @@ impl Object {
         self.values.get_mut().prev_object.take()
     }
 
+    #[inline]
+    pub fn reduction_budget(&self) -> usize {
+        self.reduction_budget
+    }
+
+    pub fn set_reduction_budget(&mut self, budget: usize) {
+        //少なくとも一つの命令は実行できるようにする
+        self.reduction_budget = budget.max(1);
+    }
+
+    #[inline]
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    ///
+    /// VMを一時停止させる関数から呼び出され、スケジューラに指定時刻まで実行を止めてもらう。
+    /// 指定時刻より前でも、メッセージや返信が届けば実行は再開される。
+    pub(crate) fn set_sleep_until(&mut self, time: std::time::Instant) {
+        self.sleep_until = Some(time);
+    }
+
+    ///
+    /// 返信の確認中に実行した処理が実行時間の制限で中断したときに呼び出され、
+    /// 返信待ちの状態でもスケジューラにすぐ実行を再開してもらう。
+    pub(crate) fn set_resume_immediately(&mut self) {
+        self.resume_immediately = true;
+    }
+
+    ///
+    /// 自分自身のメールボックスを取得する。
+    /// オブジェクトが削除されようとしている場合はNoneを返す。
+    pub(crate) fn mailbox(&self) -> Option<Arc<Mutex<MailBox>>> {
+        self.mailbox.upgrade()
+    }
+
+    ///
+    /// receiveで受け取ったメッセージの返信先を保持して、取り出すためのキーを返す。
+    pub(crate) fn hold_receiving(&mut self, reply_to: (Arc<Mutex<MailBox>>, ReplyToken)) -> usize {
+        //空いている場所があれば再利用する
+        match self.receiving.iter().position(|slot| slot.is_none()) {
+            Some(key) => {
+                self.receiving[key] = Some(reply_to);
+                key
+            }
+            None => {
+                self.receiving.push(Some(reply_to));
+                self.receiving.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn take_receiving(&mut self, key: usize) -> ReplyTo {
+        self.receiving.get_mut(key).and_then(|slot| slot.take())
+    }
+
+    fn abort_receiving(&mut self, err: &Exception) {
+        //返信できないまま処理が中断されたreceiveの送信元にも、同じエラーを返信する
+        for (reply_to_mailbox, reply_token) in self.receiving.drain(..).flatten() {
+            let mut reply_to_mailbox = reply_to_mailbox.lock().unwrap();
+            if reply_to_mailbox.recv_reply(Err(err.clone()), reply_token).is_err() {
+                let _ = reply_to_mailbox.recv_reply(Err(Exception::OutOfMemory), reply_token);
+            }
+        }
+    }
+
+    pub fn heap_stats(&self) -> mm::HeapStats {
+        self.heap.stats()
+    }
+
+    ///
+    /// ヒープを少なくともsizeバイトまで拡張する
+    pub fn reserve_heap(&mut self, size: usize) -> Result<(), OutOfMemory> {
+        self.heap.reserve(size, self.values.get_mut())
+    }
+
+    pub fn set_gc_grow_threshold(&mut self, threshold: usize) {
+        self.heap.set_grow_threshold(threshold)
+    }
+
+    #[inline]
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.heap.quota()
+    }
+
+    ///
+    /// ヒープで使用できるメモリの上限を設定する。Noneの場合は上限を解除する。
+    /// 上限を超えるアロケーションはException::MemoryQuotaExceededになる。
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.heap.set_quota(limit)
+    }
+
     #[inline(always)]
     pub fn vm_state(&mut self) -> &mut VMState {
         &mut self.values.get_mut().vm_state
     }
 
+    ///
+    /// 実行中のVMStateを退避させ、指定したVMStateに切り替える。
+    /// Funcの中からクロージャを呼び出す場合に、呼び出し元のスタックやコードを壊さないようにするために使用する。
+    /// 退避したVMStateはpop_vm_stateで必ず元に戻すこと。
+    ///
+    pub(crate) fn push_vm_state(&mut self, state: VMState) {
+        let saved = std::mem::replace(&mut self.values.get_mut().vm_state, state);
+        self.values.get_mut().saved_vm_states.push(saved);
+    }
+
+    ///
+    /// push_vm_stateで退避したVMStateに戻し、それまで実行していたVMStateを返す。
+    /// 返されたVMStateを再びpush_vm_stateすることで、途中で中断した処理を再開できる。
+    ///
+    pub(crate) fn pop_vm_state(&mut self) -> VMState {
+        let saved = self.values.get_mut().saved_vm_states.pop().unwrap();
+        std::mem::replace(&mut self.values.get_mut().vm_state, saved)
+    }
+
     pub fn find_global_value(&self, symbol: &symbol::Symbol) -> Option<Ref<Any>> {
         //ローカルフレーム上になければ、グローバルスペースから探す
         if let Some(v) = unsafe { &*self.values.get() }.world.get(symbol) {
@@ -254,7 +466,12 @@ impl Object {
         tuple::register_global(self);
         array::register_global(self);
         list::register_global(self);
+        string::register_global(self);
+        char::register_global(self);
+        r#box::register_global(self);
+        hashmap::register_global(self);
         reply::register_global(self);
+        exception::register_global(self);
     }
 
     pub fn capture<T: NaviType>(&mut self, v: Ref<T>) -> Cap<T> {
@@ -289,12 +506,14 @@ impl Object {
         let mailbox = standalone.mailbox;
         {
             let mut mailbox = mailbox.lock().unwrap();
-            mailbox.give_object_ownership(Arc::clone(&obj));
-        }
 
-        //objをバランサーに渡して、スケジューラに割り当ててもらう。
-        //スケジューラは渡したオブジェクトの弱参照を内部で保持する。
-        crate::object::balance::add_object(obj);
+            //objをバランサーに渡して、スケジューラに割り当ててもらう。
+            //スケジューラは渡したオブジェクトの弱参照を内部で保持する。
+            //※MailBoxにTaskを設定し終わるまでにメッセージが届いて起床を取りこぼさないように、ロックを保持したまま行う
+            let task = crate::object::balance::add_object(&obj);
+
+            mailbox.give_object_ownership(obj, task);
+        }
 
         mailbox
     }
@@ -338,9 +557,11 @@ impl Object {
             //MailBoxを保持しているObjectRef値がなくなってしまうと、メッセージを送信した先のオブジェクトが削除される可能性がある。
             //そうなると一生Replyを受け取ることができなくなるため、ArcをReply内にも保持させる。
             let dest_mailbox = target_obj.as_ref().mailbox();
+            //Replyが受け取られずに削除されたときに、返信を破棄させるための集合
+            let discarded = mailbox.lock().unwrap().discarded_replies();
 
             //返信を受け取るための特別な値を生成して返す
-            let reply = crate::value::reply::Reply::alloc(reply_token, mailbox, dest_mailbox, self)?;
+            let reply = crate::value::reply::Reply::alloc(reply_token, mailbox, dest_mailbox, discarded, self)?;
             Ok(reply.into_value())
 
         } else {
@@ -350,6 +571,21 @@ impl Object {
         }
     }
 
+    pub fn add_source_position(&mut self, list: &Ref<list::List>, pos: SourcePos) {
+        //既に位置が記録されているリストは、最初に記録した位置を優先する
+        self.values.get_mut().source_positions.entry(list.raw_ptr() as usize)
+            .or_insert_with(|| (list.clone(), pos));
+    }
+
+    pub fn source_position(&self, list: &list::List) -> Option<SourcePos> {
+        unsafe { &*self.values.get() }.source_positions.get(&(list as *const list::List as usize))
+            .map(|(_, pos)| *pos)
+    }
+
+    pub fn clear_source_positions(&mut self) {
+        self.values.get_mut().source_positions.clear();
+    }
+
     pub fn add_receiver(&mut self, pattern: &Reachable<Any>, body: &Reachable<list::List>) {
         //コンテキストが持つレシーバーリストに追加する
         self.values.get_mut().receiver_vec.push((pattern.make(), body.make()));
@@ -359,18 +595,54 @@ impl Object {
         self.values.get_mut().receiver_closure = None;
     }
 
+    ///
+    /// スケジューラから呼び出され、reduction_countを上限としてdo_workを実行する。
+    /// 実行中にOOMが発生した場合は、処理中のメッセージを破棄してオブジェクトを待機状態に戻す。
+    /// # Returns
+    /// 実際に消費したreduction数
+    pub fn do_work_slice(&mut self, reduction_count: usize) -> usize {
+        //前回の実行で残っていたreduction数が消費量の計算に混ざらないようにリセットしておく
+        self.vm_state().set_remain_reductions(reduction_count);
+
+        if let Err(err) = self.do_work(reduction_count) {
+            self.recover_from_oom(err);
+        }
+
+        reduction_count.saturating_sub(self.vm_state().remain_reductions())
+    }
+
+    fn recover_from_oom(&mut self, err: OutOfMemory) {
+        //途中まで実行していた処理を破棄して、次のメッセージを受信できる状態に戻す。
+        //グローバル変数やレシーバーはそのまま残す。
+        self.values.get_mut().suspend_state = SuspendState::Sleep;
+        self.sleep_until = None;
+        self.resume_immediately = false;
+        self.vm_state().reset();
+
+        //処理中だったメッセージの送信元にはOOM(もしくはメモリ上限超過)を返信する
+        self.abort_receiving(&Exception::from(err.clone()));
+        if let Some((reply_to_mailbox, reply_token)) = self.processing.take() {
+            //エラーの返信は返信先のヒープを消費しないため、失敗しない
+            let _ = reply_to_mailbox.lock().unwrap().recv_reply(Err(Exception::from(err)), reply_token);
+        }
+    }
+
     pub fn do_work(&mut self, reduction_count: usize) -> Result<(), OutOfMemory> {
 
         match self.values.get_mut().suspend_state.take() {
-            SuspendState::VMSuspend(reply_to_mailbox, reply_token) => {
+            SuspendState::VMSuspend(reply_to)
+            | SuspendState::VMWaitReply(reply_to) => {
+                self.processing = reply_to.clone();
                 let result = vm::resume(vm::WorkTimeLimit::Reductions(reduction_count), self);
-                self.apply_message_finish(result, reply_to_mailbox, reply_token)
+                self.apply_message_finish(result, reply_to)
             }
-            SuspendState::WaitReply(reply, reply_to_mailbox, reply_token) => {
-                self.wait_reply(reply, reply_to_mailbox, reply_token)
+            SuspendState::WaitReply(reply, reply_to) => {
+                self.processing = reply_to.clone();
+                self.wait_reply(reply, reply_to)
             }
-            SuspendState::DuplicateWaitReply(reply_to_mailbox, reply_token) => {
-                self.do_duplicate(reply_to_mailbox, reply_token)
+            SuspendState::DuplicateWaitReply(reply_to) => {
+                self.processing = reply_to.clone();
+                self.do_duplicate(reply_to)
             }
             SuspendState::Sleep => {
                 if let Some(mailbox) = self.mailbox.upgrade() {
@@ -388,7 +660,7 @@ impl Object {
                                     Ok(data)
                                 }
                                 Err(oom) => {
-                                    Err((data.reply_to_mailbox, data.reply_token, oom))
+                                    Err((data.reply_to, oom))
                                 }
                             }
                         })
@@ -398,21 +670,23 @@ impl Object {
                     if let Some(data) = data {
                         match data {
                             Ok(data) => {
+                                self.processing = data.reply_to.clone();
                                 match data.kind {
-                                    MessageKind::Message(msg) => {
+                                    MessageKind::Message(msg)
+                                    | MessageKind::Cast(msg) => {
                                         //受信処理を実行
-                                        self.apply_message(msg, data.reply_to_mailbox, data.reply_token, reduction_count)
+                                        self.apply_message(msg, data.reply_to, reduction_count)
                                     }
                                     MessageKind::Duplicate => {
                                         //複製処理を実行
-                                        self.do_duplicate(data.reply_to_mailbox, data.reply_token)
+                                        self.do_duplicate(data.reply_to)
                                     }
                                 }
 
                             }
-                            Err((reply_to_mailbox, reply_token, e)) => {
+                            Err((reply_to, e)) => {
                                 //自分自身のオブジェクト内でOOMが発生したため、エラーとして返信する
-                                self.apply_message_finish(Err(ExecException::from(e)), reply_to_mailbox, reply_token)
+                                self.apply_message_finish(Err(ExecException::from(e)), reply_to)
                             }
                         }
                     } else {
@@ -428,8 +702,47 @@ impl Object {
         }
     }
 
+    ///
+    /// do_work実行後に、次にスケジューラがこのオブジェクトを実行する必要があるかを判定する。
+    pub(crate) fn next_run(&mut self) -> schedule::NextRun {
+        let sleep_until = self.sleep_until.take();
+        let resume_immediately = std::mem::take(&mut self.resume_immediately);
+
+        match &self.values.get_mut().suspend_state {
+            SuspendState::VMSuspend(_) => {
+                match sleep_until {
+                    //sleep中なら指定時刻まで実行しない
+                    Some(time) if std::time::Instant::now() < time => schedule::NextRun::At(time),
+                    _ => schedule::NextRun::Immediately,
+                }
+            }
+            //返信待ちの場合は、返信が届いたときにMailBoxから起こされる
+            //ただし返信の確認中の処理が中断しているだけなら、すぐに続きを実行する
+            SuspendState::VMWaitReply(_)
+            | SuspendState::WaitReply(_, _)
+            | SuspendState::DuplicateWaitReply(_) => {
+                if resume_immediately {
+                    schedule::NextRun::Immediately
+                } else {
+                    schedule::NextRun::Wait
+                }
+            }
+            SuspendState::Sleep => {
+                let has_message = self.mailbox.upgrade()
+                    .map(|mailbox| mailbox.lock().unwrap().count_inbox() != 0)
+                    .unwrap_or(false);
+
+                if has_message {
+                    schedule::NextRun::Immediately
+                } else {
+                    schedule::NextRun::Wait
+                }
+            }
+        }
+    }
+
     fn apply_message(&mut self
-        , msg: Ref<Any>, reply_to_mailbox: Arc<Mutex<MailBox>>, reply_token: ReplyToken
+        , msg: Ref<Any>, reply_to: ReplyTo
         , mut reduction_count: usize) -> Result<(), OutOfMemory> {
         let obj = self;
         let message = msg.reach(obj);
@@ -470,7 +783,17 @@ impl Object {
             let receiver = builder_fun.get().into_value().reach(obj);
 
             //クロージャを生成するコードを実行
-            let message_receiver = crate::eval::eval(&receiver, obj).unwrap();
+            let message_receiver = match crate::eval::eval(&receiver, obj) {
+                Ok(v) => v,
+                Err(crate::eval::EvalError::Exception(e)) => {
+                    //レシーバーの構築に失敗した場合(OOMを含む)は、そのエラーを返信する
+                    return obj.apply_message_finish(Err(ExecException::from(e)), reply_to);
+                }
+                Err(crate::eval::EvalError::ObjectSwitch(_)) => {
+                    //レシーバーの構築中にObjectの切り替えが発生することはない
+                    unreachable!()
+                }
+            };
             //実行結果は必ずコンパイル済みクロージャなのでuncheckedでキャスト
             let message_receiver = unsafe { message_receiver.cast_unchecked::<compiled::Closure>() }.clone();
 
@@ -489,19 +812,19 @@ impl Object {
 
         //メッセージをクロージャに適用してパターンマッチを実行する
         let result = vm::app_call(closure.cast_app(), args_iter, limit, obj);
-        obj.apply_message_finish(result, reply_to_mailbox, reply_token)
+        obj.apply_message_finish(result, reply_to)
     }
 
     fn apply_message_finish(&mut self, result: Result<Ref<Any>, vm::ExecException>
-        , reply_to_mailbox: Arc<Mutex<MailBox>>, reply_token: ReplyToken) -> Result<(), OutOfMemory> {
+        , reply_to: ReplyTo) -> Result<(), OutOfMemory> {
 
         match result {
             Ok(result) => {
-                if let Some(reply) = result.try_cast::<reply::Reply>() {
-                    self.wait_reply(reply.clone(), reply_to_mailbox, reply_token)
-
+                if reply_to.is_some() && result.has_replytype() {
+                    //返信先がある場合は、結果に含まれるReplyの返信を待ってからその値を返信する
+                    self.wait_reply(result, reply_to)
                 } else {
-                    self.send_reply(Ok(result), reply_to_mailbox, reply_token)?;
+                    self.send_reply(Ok(result), reply_to)?;
 
                     //残ったreductions分もう一度do_workを実行する
                     let remain = self.vm_state().remain_reductions();
@@ -516,12 +839,12 @@ impl Object {
                 match e {
                     Exception::TimeLimit => {
                         //VMの状態をsuspendにして、次回のdo_work時に処理を継続する
-                        self.values.get_mut().suspend_state = SuspendState::VMSuspend(reply_to_mailbox, reply_token);
+                        self.values.get_mut().suspend_state = SuspendState::VMSuspend(reply_to);
                         Ok(())
                     }
                     Exception::WaitReply => {
-                        //VMの状態をsuspendにして、次回のdo_work時に処理を継続する
-                        self.values.get_mut().suspend_state = SuspendState::VMSuspend(reply_to_mailbox, reply_token);
+                        //VMの状態をsuspendにして、返信が届いた後のdo_work時に処理を継続する
+                        self.values.get_mut().suspend_state = SuspendState::VMWaitReply(reply_to);
                         Ok(())
                     }
                     Exception::MySelfObjectDeleted => {
@@ -534,7 +857,7 @@ impl Object {
                         Ok(())
                     }
                     other => {
-                        self.send_reply(Err(other), reply_to_mailbox, reply_token)?;
+                        self.send_reply(Err(other), reply_to)?;
 
                         //残ったreductions分もう一度do_workを実行する
                         let remain = self.vm_state().remain_reductions();
@@ -545,27 +868,37 @@ impl Object {
         }
     }
 
-    fn wait_reply(&mut self, reply: Ref<reply::Reply>, reply_to_mailbox: Arc<Mutex<MailBox>>, reply_token: ReplyToken) -> Result<(), OutOfMemory> {
-        let mut cap = reply.capture(self);
-        match reply::Reply::try_get_reply_value(&mut cap, self) {
-            ResultNone::Ok(result) => {
-                self.send_reply(result, reply_to_mailbox, reply_token)?;
-
-                //残ったreductions分もう一度do_workを実行する
-                let remain = self.vm_state().remain_reductions();
-                self.do_work(remain)
-            }
-            ResultNone::Err(oom) => {
-                Err(oom)
+    fn wait_reply(&mut self, value: Ref<Any>, reply_to: ReplyTo) -> Result<(), OutOfMemory> {
+        let result = if let Some(reply) = value.try_cast::<reply::Reply>() {
+            //Reply自体が結果の場合は、エラーの返信もそのままエラーとして返信する
+            let mut cap = reply.clone().capture(self);
+            match reply::Reply::try_get_reply_value(&mut cap, self) {
+                ResultNone::Ok(result) => result,
+                ResultNone::Err(oom) => return Err(oom),
+                ResultNone::None => {
+                    self.values.get_mut().suspend_state = SuspendState::WaitReply(cap.take().into_value(), reply_to);
+                    return Ok(());
+                }
             }
-            ResultNone::None => {
-                self.values.get_mut().suspend_state = SuspendState::WaitReply(cap.take(), reply_to_mailbox, reply_token);
-                Ok(())
+        } else {
+            //リストやreply-allなど、内部にReplyを含む値はすべての返信がそろうまで待つ
+            let mut cap = value.capture(self);
+            if crate::value::check_reply(&mut cap, self)? {
+                Ok(cap.take())
+            } else {
+                self.values.get_mut().suspend_state = SuspendState::WaitReply(cap.take(), reply_to);
+                return Ok(());
             }
-        }
+        };
+
+        self.send_reply(result, reply_to)?;
+
+        //残ったreductions分もう一度do_workを実行する
+        let remain = self.vm_state().remain_reductions();
+        self.do_work(remain)
     }
 
-    fn do_duplicate(&mut self, reply_to_mailbox: Arc<Mutex<MailBox>>, reply_token: ReplyToken) -> Result<(), OutOfMemory> {
+    fn do_duplicate(&mut self, reply_to: ReplyTo) -> Result<(), OutOfMemory> {
         let mut has_reply = false;
         let mut oom: Option<OutOfMemory> = None;
 
@@ -597,7 +930,7 @@ impl Object {
         }
 
         if has_reply {
-            self.values.get_mut().suspend_state = SuspendState::DuplicateWaitReply(reply_to_mailbox, reply_token);
+            self.values.get_mut().suspend_state = SuspendState::DuplicateWaitReply(reply_to);
             Ok(())
 
         } else {
@@ -612,11 +945,23 @@ impl Object {
             let objectref = ObjectRef::alloc(id, mailbox,self)?;
 
             //作成したObjectRefを返信する
-            self.apply_message_finish(Ok(objectref.into_value()), reply_to_mailbox, reply_token)
+            self.apply_message_finish(Ok(objectref.into_value()), reply_to)
         }
     }
 
-    fn send_reply(&mut self, result: NResult<Any, Exception>, reply_to_mailbox: Arc<Mutex<MailBox>>, reply_token: ReplyToken) -> Result<(), OutOfMemory> {
+    fn send_reply(&mut self, result: NResult<Any, Exception>, reply_to: ReplyTo) -> Result<(), OutOfMemory> {
+        //返信を送信した時点でメッセージの処理は完了している
+        self.processing = None;
+        if let Err(err) = result.as_ref() {
+            self.abort_receiving(err);
+        }
+
+        //castで受信したメッセージには返信先がないため、結果は捨てる
+        let (reply_to_mailbox, reply_token) = match reply_to {
+            Some(reply_to) => reply_to,
+            None => return Ok(()),
+        };
+
         match result {
             Ok(v) => {
                 //結果を送信元のオブジェクト(MailBox)に返す
@@ -628,8 +973,11 @@ impl Object {
                     //返信先メールボックスのロックを取得
                     let mut reply_to_mailbox = reply_to_mailbox.lock().unwrap();
                     //返信を送信
-                    //TODO 相手先メールボックスがOOMの可能性がある。どうする？
-                    reply_to_mailbox.recv_reply(Ok(&result), reply_token)?;
+                    if reply_to_mailbox.recv_reply(Ok(&result), reply_token).is_err() {
+                        //相手先メールボックスに値をコピーできなかった場合は、代わりにOOMを返信する。
+                        //エラーの返信は返信先のヒープを消費しないため、失敗しない
+                        let _ = reply_to_mailbox.recv_reply(Err(Exception::OutOfMemory), reply_token);
+                    }
                 }
             }
             Err(err) => {
@@ -640,14 +988,34 @@ impl Object {
                 //返信先メールボックスのロックを取得
                 let mut reply_to_mailbox = reply_to_mailbox.lock().unwrap();
                 //返信を送信
-                //TODO 相手先メールボックスがOOMの可能性がある。どうする？
-                reply_to_mailbox.recv_reply(Err(err), reply_token)?;
+                if reply_to_mailbox.recv_reply(Err(err), reply_token).is_err() {
+                    //エラーの追加情報をコピーできなかった場合は、代わりにOOMを返信する
+                    let _ = reply_to_mailbox.recv_reply(Err(Exception::OutOfMemory), reply_token);
+                }
             }
         }
 
         Ok(())
     }
 
+    ///
+    /// ヒープ内の値parentの内部に、childへの参照を書き込んだ後に呼び出す。
+    /// 世代別GCで、古い世代の値から新しい値への参照を見失わないようにするための書き込みバリア。
+    #[inline]
+    pub(crate) fn write_barrier<P: NaviType, C: NaviType>(&mut self, parent: &P, child: &C) {
+        let parent: &Any = unsafe { std::mem::transmute(parent) };
+        let child: &Any = unsafe { std::mem::transmute(child) };
+        self.heap.write_barrier(parent, child)
+    }
+
+    ///
+    /// 書き込んだ子の値を特定できない場合に、parentを記録する書き込みバリア。
+    #[inline]
+    pub(crate) fn remember<P: NaviType>(&mut self, parent: &P) {
+        let parent: &Any = unsafe { std::mem::transmute(parent) };
+        self.heap.remember(parent)
+    }
+
 }
 
 impl Eq for Object {}
@@ -727,12 +1095,145 @@ fn func_sleep_result(sleep_in_milliseconds: i64, sleep_start: std::time::Instant
         obj.vm_state().stack().push(sleep_in_milliseconds);
         obj.vm_state().stack().push(sleep_start);
         vm::save_func_suspend_info(func_sleep_resume, obj);
+        //スケジューラに指定時刻まで実行を止めてもらう
+        obj.sleep_until = Some(sleep_start + std::time::Duration::from_millis(sleep_in_milliseconds as u64));
 
         //厳密にはタイムリミットではないので専用の例外を作るべきか？
         Err(crate::err::Exception::TimeLimit)
     }
 }
 
+pub(crate) fn keyword_to_priority(keyword: &keyword::Keyword) -> Result<Priority, Exception> {
+    Priority::from_name(keyword.as_ref())
+        .ok_or_else(|| Exception::Other(format!("unknown priority :{}. expected :low, :normal or :high", keyword.as_ref())))
+}
+
+pub(crate) fn integer_to_reduction_budget(budget: &number::Integer) -> Result<usize, Exception> {
+    let budget = budget.get();
+    if budget <= 0 {
+        Err(Exception::Other(format!("reduction budget must be positive: {}", budget)))
+    } else {
+        Ok(budget as usize)
+    }
+}
+
+pub(crate) fn integer_to_heap_size(size: &number::Integer) -> Result<usize, Exception> {
+    let size = size.get();
+    if size <= 0 || (mm::MAX_HEAP_SIZE as i64) < size {
+        Err(Exception::Other(format!("heap size must be between 1 and {}: {}", mm::MAX_HEAP_SIZE, size)))
+    } else {
+        Ok(size as usize)
+    }
+}
+
+pub(crate) fn integer_to_grow_threshold(threshold: &number::Integer) -> Result<usize, Exception> {
+    let threshold = threshold.get();
+    if !(1..=100).contains(&threshold) {
+        Err(Exception::Other(format!("gc grow threshold must be between 1 and 100: {}", threshold)))
+    } else {
+        Ok(threshold as usize)
+    }
+}
+
+pub(crate) fn integer_to_memory_limit(limit: &number::Integer) -> Result<usize, Exception> {
+    let limit = limit.get();
+    if limit <= 0 || (mm::MAX_HEAP_SIZE as i64) < limit {
+        Err(Exception::Other(format!("memory limit must be between 1 and {}: {}", mm::MAX_HEAP_SIZE, limit)))
+    } else {
+        Ok(limit as usize)
+    }
+}
+
+fn func_gc(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    obj.do_gc();
+    Ok(tuple::Tuple::unit().make().into_value())
+}
+
+fn func_gc_stats(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let stats = obj.heap_stats();
+    let items = [
+        ("gc-count", stats.gc_count as i64),
+        ("minor-gc-count", stats.minor_gc_count as i64),
+        ("total-pause-us", stats.total_pause.as_micros() as i64),
+        ("last-pause-us", stats.last_pause.as_micros() as i64),
+        ("reclaimed-bytes", stats.reclaimed_bytes as i64),
+        ("grow-count", stats.grow_count as i64),
+        ("shrink-count", stats.shrink_count as i64),
+        ("heap-size", stats.heap_size as i64),
+        ("nursery-size", stats.nursery_size as i64),
+        ("used", stats.used as i64),
+        ("large-used", stats.large_used as i64),
+    ];
+
+    //(:gc-count 1 :minor-gc-count 2 ...)のようなキーワードと値が交互に並んだリストを返す
+    let mut builder = ListBuilder::new(obj);
+    for (name, num) in items {
+        let key = keyword::Keyword::alloc(name, obj)?.into_value().reach(obj);
+        builder.push(&key, obj)?;
+        let num = number::make_integer(num, obj)?.reach(obj);
+        builder.push(&num, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_object_memory_limit(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    match obj.memory_limit() {
+        Some(limit) => {
+            let num = number::make_integer(limit as i64, obj)?;
+            Ok(num)
+        }
+        None => {
+            Ok(bool::Bool::false_().into_ref().into_value())
+        }
+    }
+}
+
+fn func_set_object_memory_limit(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+
+    //falseを指定すると上限を解除する
+    if let Some(limit) = v.try_cast::<number::Integer>() {
+        let limit = integer_to_memory_limit(limit.as_ref())?;
+        obj.set_memory_limit(Some(limit));
+    } else if v.try_cast::<bool::Bool>().is_some_and(|b| b.as_ref().is_false()) {
+        obj.set_memory_limit(None);
+    } else {
+        return Err(Exception::TypeMismatch(TypeMismatch::new(v, number::Integer::typeinfo())));
+    }
+
+    Ok(v)
+}
+
+fn func_object_priority(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let priority = obj.priority();
+    let keyword = keyword::Keyword::alloc(priority.name(), obj)?;
+    Ok(keyword.into_value())
+}
+
+fn func_set_object_priority(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let keyword = vm::refer_arg::<keyword::Keyword>(0, obj);
+    let priority = keyword_to_priority(keyword.as_ref())?;
+
+    //次回スケジューラが実行キューに入れるときから反映される
+    obj.set_priority(priority);
+    Ok(keyword.into_value())
+}
+
+fn func_object_reduction_budget(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let budget = obj.reduction_budget();
+    let num = number::make_integer(budget as i64, obj)?;
+    Ok(num)
+}
+
+fn func_set_object_reduction_budget(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<number::Integer>(0, obj);
+    let budget = integer_to_reduction_budget(v.as_ref())?;
+
+    obj.set_reduction_budget(budget);
+    Ok(v.into_value())
+}
+
 static FUNC_EXIT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     GCAllocationStruct::new(
         Func::new("exit", func_exit,
@@ -752,9 +1253,92 @@ static FUNC_SLEEP: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     )
 });
 
+static FUNC_OBJECT_MEMORY_LIMIT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("object-memory-limit", func_object_memory_limit,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_SET_OBJECT_MEMORY_LIMIT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("set-object-memory-limit", func_set_object_memory_limit,
+        Parameter::new(&[
+            Param::new("limit", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_GC: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("gc", func_gc,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_GC_STATS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("gc-stats", func_gc_stats,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_OBJECT_PRIORITY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("object-priority", func_object_priority,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_SET_OBJECT_PRIORITY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("set-object-priority", func_set_object_priority,
+        Parameter::new(&[
+            Param::new("priority", ParamKind::Require, keyword::Keyword::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_OBJECT_REDUCTION_BUDGET: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("object-reduction-budget", func_object_reduction_budget,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_SET_OBJECT_REDUCTION_BUDGET: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("set-object-reduction-budget", func_set_object_reduction_budget,
+        Parameter::new(&[
+            Param::new("budget", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("exit", &Ref::new(&FUNC_EXIT.value));
     obj.define_global_value("sleep", &Ref::new(&FUNC_SLEEP.value));
+    obj.define_global_value("object-memory-limit", &Ref::new(&FUNC_OBJECT_MEMORY_LIMIT.value));
+    obj.define_global_value("set-object-memory-limit", &Ref::new(&FUNC_SET_OBJECT_MEMORY_LIMIT.value));
+    obj.define_global_value("gc", &Ref::new(&FUNC_GC.value));
+    obj.define_global_value("gc-stats", &Ref::new(&FUNC_GC_STATS.value));
+    obj.define_global_value("object-priority", &Ref::new(&FUNC_OBJECT_PRIORITY.value));
+    obj.define_global_value("set-object-priority", &Ref::new(&FUNC_SET_OBJECT_PRIORITY.value));
+    obj.define_global_value("object-reduction-budget", &Ref::new(&FUNC_OBJECT_REDUCTION_BUDGET.value));
+    obj.define_global_value("set-object-reduction-budget", &Ref::new(&FUNC_SET_OBJECT_REDUCTION_BUDGET.value));
 }
 
 mod literal {
@@ -1,26 +1,161 @@
 use std::cell::RefCell;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use once_cell::sync::Lazy;
 
-use super::schedule::Scheduler;
+use super::schedule::{Scheduler, Task};
 use super::{Object};
 
+//起動するスケジューラ(スレッド)の数。0の場合はCPU数に合わせる。
+static NUM_SCHEDULERS: AtomicUsize = AtomicUsize::new(0);
+
+//スケジューラ間の負荷を確認する間隔
+const BALANCE_INTERVAL_MILLIS: u64 = 100;
+//1回の確認間隔中に、これ以上のreductionを消費しているスケジューラを高負荷とみなす
+const HOT_REDUCTIONS: usize = 10_000;
+
 static BALANCER: Lazy<Mutex<Balancer>> = Lazy::new(|| {
+    let num = num_schedulers();
     let balancer = Balancer {
-        scheduler: Scheduler::new(),
+        schedulers: (0..num).map(Scheduler::new).collect(),
     };
 
+    //スケジューラが複数ある場合のみ、スケジューラ間でオブジェクトを移動させるスレッドを起動する
+    if num > 1 {
+        std::thread::Builder::new()
+            .name("navi-balancer".to_string())
+            .spawn(balancer_main)
+            .unwrap();
+    }
+
     Mutex::new(balancer)
 });
 
 
 struct Balancer {
-    scheduler: Scheduler,
+    schedulers: Vec<Scheduler>,
+}
+
+impl Balancer {
+    fn least_loaded(&self) -> &Scheduler {
+        let index = least_loaded_index(self.schedulers.iter().map(Scheduler::load));
+        &self.schedulers[index]
+    }
+
+    fn rebalance(&self) {
+        let stats: Vec<(usize, usize)> = self.schedulers.iter()
+            .map(|s| (s.load(), s.take_reductions()))
+            .collect();
+
+        if let Some((from, to, num)) = plan_migration(&stats) {
+            //高負荷のスケジューラから待機中のオブジェクトを取り出し、低負荷のスケジューラに移動させる
+            let objects = self.schedulers[from].steal(num);
+            self.schedulers[to].push_objects(objects.into_iter());
+        }
+    }
+}
+
+fn balancer_main() {
+    loop {
+        std::thread::sleep(std::time::Duration::from_millis(BALANCE_INTERVAL_MILLIS));
+
+        let balancer = BALANCER.lock().unwrap();
+        balancer.rebalance();
+    }
+}
+
+fn least_loaded_index<I: Iterator<Item=usize>>(loads: I) -> usize {
+    //負荷が同じ場合は先頭に近いスケジューラを優先する
+    loads.enumerate()
+        .min_by_key(|(_, load)| *load)
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+///
+/// 各スケジューラの(オブジェクト数, 消費reduction数)から、オブジェクトの移動計画を立てる。
+/// # Returns
+/// (移動元スケジューラ, 移動先スケジューラ, 移動するオブジェクト数)
+fn plan_migration(stats: &[(usize, usize)]) -> Option<(usize, usize, usize)> {
+    if stats.len() < 2 {
+        return None;
+    }
+
+    //まずはオブジェクト数の偏りを解消する
+    let hot = (0..stats.len()).max_by_key(|i| stats[*i].0).unwrap();
+    let cold = (0..stats.len()).min_by_key(|i| stats[*i].0).unwrap();
+    let diff = stats[hot].0 - stats[cold].0;
+    if diff >= 2 {
+        return Some((hot, cold, diff / 2));
+    }
+
+    //オブジェクト数に偏りがなくても、reductionの消費量に偏りがあれば一つだけ移動させる
+    let hot = (0..stats.len()).max_by_key(|i| stats[*i].1).unwrap();
+    let cold = (0..stats.len()).min_by_key(|i| stats[*i].1).unwrap();
+    let (hot_load, hot_reductions) = stats[hot];
+    let (cold_load, cold_reductions) = stats[cold];
+    //移動後にオブジェクト数の偏りが生まれて、次回に移動が戻されることがないようにする
+    if hot_reductions >= HOT_REDUCTIONS
+        && cold_reductions * 2 < hot_reductions
+        && cold_load < hot_load {
+        return Some((hot, cold, 1));
+    }
+
+    None
 }
 
-pub fn add_object(obj: Arc<RefCell<Object>>) {
+///
+/// スケジューラの数を設定する。
+/// 最初のオブジェクトがスケジューラに登録される前に呼び出す必要がある。
+/// 0を指定した場合はCPU数と同じ数のスケジューラを起動する。
+pub fn set_num_schedulers(num: usize) {
+    NUM_SCHEDULERS.store(num, Ordering::SeqCst);
+}
+
+pub fn num_schedulers() -> usize {
+    match NUM_SCHEDULERS.load(Ordering::SeqCst) {
+        0 => {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        }
+        num => num,
+    }
+}
+
+pub(crate) fn add_object(obj: &Arc<RefCell<Object>>) -> Arc<Task> {
     let balancer = BALANCER.lock().unwrap();
-    balancer.scheduler.add_object(obj);
+    balancer.least_loaded().add_object(obj)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_loaded() {
+        assert_eq!(least_loaded_index([3, 1, 2].iter().cloned()), 1);
+        assert_eq!(least_loaded_index([0, 0, 0].iter().cloned()), 0);
+        assert_eq!(least_loaded_index([2, 2, 1, 1].iter().cloned()), 2);
+        assert_eq!(least_loaded_index(std::iter::empty()), 0);
+    }
+
+    #[test]
+    fn test_plan_migration() {
+        //スケジューラが一つなら移動しない
+        assert_eq!(plan_migration(&[(10, 100_000)]), None);
+
+        //オブジェクト数の偏り
+        assert_eq!(plan_migration(&[(6, 0), (0, 0)]), Some((0, 1, 3)));
+        assert_eq!(plan_migration(&[(1, 0), (2, 0), (5, 0)]), Some((2, 0, 2)));
+        assert_eq!(plan_migration(&[(2, 0), (1, 0)]), None);
+
+        //reduction消費量の偏り
+        assert_eq!(plan_migration(&[(2, 50_000), (1, 0)]), Some((0, 1, 1)));
+        //消費量が少なければ移動しない
+        assert_eq!(plan_migration(&[(2, 500), (1, 0)]), None);
+        //移動するとオブジェクト数が逆転する場合は移動しない
+        assert_eq!(plan_migration(&[(2, 50_000), (2, 0)]), None);
+    }
+}
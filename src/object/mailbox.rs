@@ -1,4 +1,6 @@
+use std::collections::{HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::cell::RefCell;
 
 use crate::err::{OutOfMemory, Exception, NResult};
@@ -9,23 +11,51 @@ use crate::ptr::*;
 
 use super::{Object, Allocator, AnyAllocator};
 use super::mm::{self, Heap};
+use super::schedule::Task;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ReplyToken(usize);
 
+//返信は送信元のメールボックスの中でReplyTokenによって識別される。
+//複数の送信先から返信を受け取ってもトークンが重複しないように、プロセス全体で一意な値を割り当てる。
+static NEXT_REPLY_TOKEN: AtomicUsize = AtomicUsize::new(0);
+
 impl ReplyToken {
-    fn new() -> Self {
-        ReplyToken(0)
+    fn next() -> Self {
+        //オーバーフローを無視してインクリメント
+        ReplyToken(NEXT_REPLY_TOKEN.fetch_add(1, Ordering::Relaxed))
     }
+}
 
-    fn next(&self) -> Self {
-        //オーバーフローを無視してインクリメント
-        ReplyToken(self.0.wrapping_add(1))
+///
+/// 返信を受け取る前に値が削除されたReplyのReplyTokenの集合。
+/// ReplyのfinalizeはObjectのGC中に呼ばれるが、その時点で自分自身のメールボックスのロックを取得していることがある。
+/// そのためMailBox本体とは別のロックで管理し、finalizeからはこちらのロックだけを取得する。
+#[derive(Clone, Default)]
+pub struct DiscardedReplies(Arc<Mutex<HashSet<ReplyToken>>>);
+
+impl DiscardedReplies {
+    fn insert(&self, reply_token: ReplyToken) {
+        self.0.lock().unwrap().insert(reply_token);
+    }
+
+    fn remove(&self, reply_token: ReplyToken) -> bool {
+        self.0.lock().unwrap().remove(&reply_token)
+    }
+
+    fn sweep(&self, result_box: &mut Vec<(ReplyToken, NResult<Any, Exception>)>) {
+        let mut discarded = self.0.lock().unwrap();
+        if !discarded.is_empty() {
+            //既に届いている返信のうち、破棄されたReplyに対応するものを取り除く
+            result_box.retain(|(token, _)| !discarded.remove(token));
+        }
     }
 }
 
 pub enum MessageKind {
     Message(Ref<Any>),
+    //返信を必要としないメッセージ
+    Cast(Ref<Any>),
     Duplicate,
 }
 
@@ -40,6 +70,12 @@ impl MessageKind {
 
                 Ok(MessageKind::Message(msg))
             }
+            MessageKind::Cast(msg) => {
+                let msg = msg.clone().into_reachable();
+                let msg = crate::value::value_clone(&msg, allocator)?;
+
+                Ok(MessageKind::Cast(msg))
+            }
             Self::Duplicate => {
                 Ok(Self::Duplicate)
             }
@@ -47,22 +83,28 @@ impl MessageKind {
     }
 }
 
+///
+/// メッセージの返信先のメールボックスと、返信を識別するためのReplyToken。
+/// castで送信されたメッセージは返信先を持たないためNoneになる。
+pub type ReplyTo = Option<(Arc<Mutex<MailBox>>, ReplyToken)>;
+
 pub struct MessageData {
     pub kind: MessageKind,
-    pub reply_to_mailbox: Arc<Mutex<MailBox>>,
-    pub reply_token: ReplyToken,
+    pub reply_to: ReplyTo,
 }
 
 struct MailBoxGCRootValues {
-    pub inbox: Vec<MessageData>,
+    pub inbox: VecDeque<MessageData>,
     pub result_box: Vec<(ReplyToken, NResult<Any, Exception>)>,
+    pub discarded: DiscardedReplies,
 }
 
 impl mm::GCRootValueHolder for MailBoxGCRootValues {
     fn for_each_alived_value(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
         self.inbox.iter_mut().for_each(|data| {
             match &mut data.kind {
-                MessageKind::Message(msg) => {
+                MessageKind::Message(msg)
+                | MessageKind::Cast(msg) => {
                     callback(msg, arg)
                 }
                 MessageKind::Duplicate => { }
@@ -103,9 +145,10 @@ pub struct MailBox {
     //関連しているObjectがスケジューラに紐づけられている時に値が設定される。
     //Objectの初期化時やスケジューラから切り離されている時はNoneになる。
     obj: Option<Arc<RefCell<Object>>>,
+    //Objectがスケジューラに紐づけられている時に、メッセージや返信の到着をスケジューラへ通知するためのTask
+    task: Option<Arc<Task>>,
     heap: Heap,
 
-    reply_token: ReplyToken,
     values: MailBoxGCRootValues,
 }
 
@@ -113,43 +156,99 @@ impl MailBox {
     pub(super) fn new() -> Self {
         MailBox {
             obj:None,
+            task: None,
             heap: Heap::new(mm::StartHeapSize::Small),
 
-            reply_token: ReplyToken::new(),
             values: MailBoxGCRootValues {
-                inbox: Vec::new(),
+                inbox: VecDeque::new(),
                 result_box: Vec::new(),
+                discarded: DiscardedReplies::default(),
             }
         }
     }
 
     pub fn recv_message(&mut self, msg: MessageKind, reply_to_mailbox: Arc<Mutex<MailBox>>) -> Result<ReplyToken, OutOfMemory> {
+        //コピー中のGCで回収できるように、破棄された返信を先に取り除いておく
+        self.sweep_discarded();
+
         //受け取ったメッセージをすべて自分自身のヒープ内にコピーする
         let mut allocator = AnyAllocator::MailBox(self);
         let msg = (unsafe { msg.value_clone_gcunsafe(&mut allocator) })?;
 
         //返信を送受信するためのtx/rxを作成
-        let reply_token = self.reply_token;
-        self.reply_token = self.reply_token.next();
+        let reply_token = ReplyToken::next();
 
         //受け取ったメッセージを内部バッファに保存する
-        self.values.inbox.push(MessageData {
+        self.values.inbox.push_back(MessageData {
             kind: msg,
-            reply_to_mailbox: reply_to_mailbox,
-            reply_token: reply_token,
+            reply_to: Some((reply_to_mailbox, reply_token)),
         });
 
+        //メッセージを処理してもらうためにオブジェクトを起こす
+        self.wake();
+
         //処理終了後の値を受け取るための受信用トークンを返す
         Ok(reply_token)
     }
 
+    ///
+    /// 返信を必要としないメッセージを受け取る。
+    /// ReplyTokenを割り当てず、処理結果も返信されない。
+    pub fn recv_cast(&mut self, msg: MessageKind) -> Result<(), OutOfMemory> {
+        self.sweep_discarded();
+
+        //受け取ったメッセージをすべて自分自身のヒープ内にコピーする
+        let mut allocator = AnyAllocator::MailBox(self);
+        let msg = (unsafe { msg.value_clone_gcunsafe(&mut allocator) })?;
+
+        self.values.inbox.push_back(MessageData {
+            kind: msg,
+            reply_to: None,
+        });
+
+        //メッセージを処理してもらうためにオブジェクトを起こす
+        self.wake();
+
+        Ok(())
+    }
+
     pub fn pop_inbox(&mut self) -> Option<MessageData> {
-        self.values.inbox.pop()
+        //届いた順番にメッセージを取り出す
+        self.values.inbox.pop_front()
+    }
+
+    ///
+    /// index番目以降で、receiveの対象になる最初のメッセージの位置を返す。
+    /// 複製の依頼はreceiveの対象にならないため飛ばす。
+    pub fn find_inbox_message(&self, index: usize) -> Option<usize> {
+        self.values.inbox.iter().enumerate()
+            .skip(index)
+            .find(|(_, data)| !matches!(data.kind, MessageKind::Duplicate))
+            .map(|(index, _)| index)
+    }
+
+    ///
+    /// index番目のメッセージを取り出さずに参照する。
+    pub fn peek_inbox(&self, index: usize) -> Option<&Ref<Any>> {
+        match &self.values.inbox.get(index)?.kind {
+            MessageKind::Message(msg)
+            | MessageKind::Cast(msg) => Some(msg),
+            MessageKind::Duplicate => None,
+        }
+    }
+
+    ///
+    /// index番目のメッセージを受信箱から取り除く。
+    pub fn take_inbox(&mut self, index: usize) -> Option<MessageData> {
+        self.values.inbox.remove(index)
     }
 
     pub fn recv_reply(&mut self, result: Result<&Reachable<Any>, Exception>, reply_token: ReplyToken) -> Result<(), OutOfMemory> {
-        //返信を受け取るVec内に既にReplyTokenに対応する値が入っている場合、返信不要のマークなので何もしない。
-        if self.try_take_reply(reply_token).is_none() {
+        //既にReplyが削除されている場合は、誰も受け取らないので何もしない。
+        if !self.values.discarded.remove(reply_token) {
+            //破棄された返信が残っていれば先に取り除いておく
+            self.sweep_discarded();
+
             match result {
                 Ok(v) => {
                     //受け取ったメッセージをすべて自分自身のヒープ内にコピーする
@@ -167,6 +266,9 @@ impl MailBox {
                     self.values.result_box.push((reply_token, Err(cloned)));
                 }
             }
+
+            //返信を待っているオブジェクトを起こす
+            self.wake();
         }
 
         Ok(())
@@ -180,22 +282,57 @@ impl MailBox {
             })
     }
 
-    pub fn discard_reply(&mut self, reply_token: ReplyToken) {
-        //まだ返信を受け取っていなければ
-        if self.try_take_reply(reply_token).is_none() {
-            //どんな値でもいいので、事前に返信を受け取るためのVecにreply_tokenを入れておく。
-            self.values.result_box.push((reply_token, Err(Exception::OutOfMemory)));
+    ///
+    /// 受け取る相手がいなくなった返信を取り除き、GCで回収できるようにする。
+    /// GCのルート走査中には状態を変更しないため、ヒープを使用する前に呼び出す。
+    fn sweep_discarded(&mut self) {
+        self.values.discarded.sweep(&mut self.values.result_box);
+    }
+
+    pub fn discarded_replies(&self) -> DiscardedReplies {
+        self.values.discarded.clone()
+    }
+
+    ///
+    /// 受け取る前に削除されたReplyの返信を破棄する。
+    /// MailBoxのロックが取得できれば届いている返信をその場で取り除き、まだ届いていなければ受信時に破棄させる。
+    /// GC中などロックが取得できない場合はトークンを登録するだけにして、次のGCか返信の受信時に取り除かせる。
+    /// ロックの取得を待たないため、ReplyのfinalizeなどGC中でも呼び出せる。
+    pub fn discard_reply(mailbox: &Mutex<MailBox>, discarded: &DiscardedReplies, reply_token: ReplyToken) {
+        match mailbox.try_lock() {
+            Ok(mut mailbox) => {
+                if mailbox.try_take_reply(reply_token).is_none() {
+                    discarded.insert(reply_token);
+                }
+            }
+            Err(_) => {
+                discarded.insert(reply_token);
+            }
         }
     }
 
-    pub(super) fn give_object_ownership(&mut self, obj: Arc<RefCell<Object>>) {
+    fn wake(&self) {
+        if let Some(task) = self.task.as_ref() {
+            task.wake();
+        }
+    }
+
+    pub(super) fn give_object_ownership(&mut self, obj: Arc<RefCell<Object>>, task: Arc<Task>) {
         self.obj = Some(obj);
+        self.task = Some(task);
     }
 
     pub(super) fn take_object_ownership(&mut self) -> Arc<RefCell<Object>> {
+        //スケジューラから切り離されるので、Taskも破棄する
+        self.task = None;
         self.obj.take().unwrap()
     }
 
+    #[allow(dead_code)]
+    pub(crate) fn set_heap_max_size(&mut self, max_size: usize) {
+        self.heap.set_max_size(max_size);
+    }
+
     #[allow(dead_code)]
     pub(crate) fn count_inbox(&self) -> usize {
         self.values.inbox.len()
@@ -235,6 +372,7 @@ impl Allocator for MailBox {
     }
 
     fn do_gc(&mut self) {
+        self.sweep_discarded();
         self.heap.gc(&mut self.values)
     }
 
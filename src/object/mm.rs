@@ -1,5 +1,6 @@
 use std::alloc;
 use std::mem;
+use std::time::{Duration, Instant};
 use crate::err::OutOfMemory;
 use crate::ptr::*;
 use crate::value::{self, TypeInfo, NaviType};
@@ -50,22 +51,57 @@ const SIZE_BIT_SHIFT: usize = 3;
 
 const VALUE_ALIGN:usize = mem::size_of::<usize>();
 
-#[derive(Debug, Copy, Clone)]
-enum HeapSize {
-    _256,
-    _512,
-    _1k,
-    _2k,
-    _8k,
-    _16k,
-    _32k,
-}
+//ヒープの初期サイズ
+const SMALL_HEAP_SIZE: usize = 256;
+const DEFAULT_HEAP_SIZE: usize = 1024 * 2;
+
+//ヒープとラージオブジェクトの合計サイズの上限。これを超えるとOOMになる。
+pub const MAX_HEAP_SIZE: usize = 1024 * 1024 * 256;
+
+//このサイズ以上の値は、ヒープとは別に個別に確保するラージオブジェクトとして扱う。
+//ラージオブジェクトはGCで移動しないため、大きな文字列や配列によってヒープ全体のコンパクションが発生することはない。
+const LARGE_OBJECT_SIZE: usize = 1024 * 8;
+//ラージオブジェクトの合計サイズがこの値を超えるとGCを実行する(初期値)
+const LARGE_OBJECT_GC_THRESHOLD: usize = 1024 * 1024;
+
+//新しく確保した値を置く若い世代の領域(nursery)のサイズ。
+//ラージオブジェクト未満の値は必ず空のnurseryに収まるように、LARGE_OBJECT_SIZEより大きくする。
+const NURSERY_SIZE: usize = 1024 * 16;
+
+//GC後の使用率(%)がこの値を超えている場合は、空きを使い切る前にヒープを拡張する(初期値)
+const DEFAULT_GROW_THRESHOLD: usize = 75;
+
+//GC後の使用率(%)がこの値を下回る状態がSHRINK_GC_COUNT回続いたら、ヒープを縮小する
+const SHRINK_THRESHOLD: usize = 25;
+const SHRINK_GC_COUNT: usize = 4;
 
 pub enum StartHeapSize {
     Default,
     Small,
 }
 
+///
+/// ヒープごとのGCの統計情報
+#[derive(Debug, Clone, Default)]
+pub struct HeapStats {
+    //古い世代の領域を含むGCの実行回数
+    pub gc_count: usize,
+    //nurseryだけを対象にしたGCの実行回数
+    pub minor_gc_count: usize,
+    pub total_pause: Duration,
+    pub last_pause: Duration,
+    //GCで開放した合計バイト数
+    pub reclaimed_bytes: usize,
+    pub grow_count: usize,
+    pub shrink_count: usize,
+
+    //以下はstats()の呼び出し時点の値
+    pub heap_size: usize,
+    pub nursery_size: usize,
+    pub used: usize,
+    pub large_used: usize,
+}
+
 pub trait GCRootValueHolder {
     fn for_each_alived_value(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8));
 }
@@ -74,21 +110,66 @@ pub struct Heap {
     pool_ptr : *mut u8,
     used : usize,
     page_layout : alloc::Layout,
-    heap_size: HeapSize,
+
+    //ヒープとは別に個別に確保したラージオブジェクト。アドレス順にソートして保持する。
+    large_objects: Vec<LargeObject>,
+    large_used: usize,
+    large_gc_threshold: usize,
+
+    //新しく確保した値を置く若い世代の領域(nursery)。
+    //満杯になるとマイナーGCを実行し、生き残った値を古い世代の領域(pool)へ移動する。
+    //メールボックス用の小さなヒープはnurseryを持たない(None)。
+    nursery_ptr: *mut u8,
+    nursery_used: usize,
+    nursery_layout: Option<alloc::Layout>,
+    //nursery内の値への参照を書き込まれた古い世代の値(remembered set)。マイナーGCではルートとして扱う。
+    //※グローバル変数やレシーバーなど、GCRootValueHolderが保持する値はマイナーGCのたびに走査されるため、
+    //LET_GLOBALやdef-recvのような書き込みは記録しなくてよい。
+    remembered: Vec<*mut u8>,
+
+    //force_allocation_spaceで事前に確保した残りサイズ。
+    //確保した領域を使い切るまではGCを実行しない。
+    reserved: usize,
+    //事前に確保した領域が古い世代の領域にあるか(falseならnurseryにある)
+    reserved_in_old: bool,
+    max_size: usize,
+    //オブジェクトごとに設定されたメモリ上限。設定されている場合はmax_sizeと同じ値になる。
+    quota: Option<usize>,
+
+    grow_threshold: usize,
+    //縮小するときの下限サイズ
+    min_size: usize,
+    //使用率が低い状態が続いたGCの回数
+    low_usage_count: usize,
+    stats: HeapStats,
+}
+
+struct LargeObject {
+    ptr: *mut u8,
+    layout: alloc::Layout,
+    marked: bool,
+}
+
+impl LargeObject {
+    fn find(large_objects: &[LargeObject], alloc_ptr: *const u8) -> Option<usize> {
+        large_objects.binary_search_by(|obj| (obj.ptr as *const u8).cmp(&alloc_ptr)).ok()
+    }
 }
 
 struct GCCompactionArg<'a> {
     start_addr: *const u8,
     end_addr: *const u8,
-    flags: &'a mut [u16],
+    flags: &'a mut [u32],
+    large_objects: &'a mut [LargeObject],
 }
 
 impl <'a> GCCompactionArg<'a> {
-    pub fn new(start_addr: *const u8, end_addr: *const u8, flags: &'a mut [u16]) -> Self {
+    pub fn new(start_addr: *const u8, end_addr: *const u8, flags: &'a mut [u32], large_objects: &'a mut [LargeObject]) -> Self {
         GCCompactionArg {
             start_addr: start_addr,
             end_addr: end_addr,
             flags: flags,
+            large_objects,
         }
     }
 
@@ -104,6 +185,9 @@ impl <'a> GCCompactionArg<'a> {
 struct GCCopyingArg {
     start_addr: *const u8,
     end_addr: *const u8,
+    //コピー対象とするもう一つの領域(nursery)
+    nursery_start_addr: *const u8,
+    nursery_end_addr: *const u8,
     free_ptr: *mut u8,
     used: usize,
 }
@@ -113,11 +197,19 @@ impl GCCopyingArg {
         GCCopyingArg {
             start_addr: start_addr,
             end_addr: end_addr,
+            nursery_start_addr: std::ptr::null(),
+            nursery_end_addr: std::ptr::null(),
             free_ptr: free_ptr,
             used: 0,
         }
     }
 
+    pub fn with_nursery(mut self, nursery_start_addr: *const u8, nursery_end_addr: *const u8) -> Self {
+        self.nursery_start_addr = nursery_start_addr;
+        self.nursery_end_addr = nursery_end_addr;
+        self
+    }
+
     pub fn as_ptr(&mut self) -> *mut u8 {
         self as *mut Self as *mut u8
     }
@@ -153,103 +245,113 @@ impl CopiedValue {
 
 impl Heap {
     pub fn new(startsize: StartHeapSize) -> Self {
-        let heapsize = match startsize {
-            StartHeapSize::Default => HeapSize::_2k,
-            StartHeapSize::Small => HeapSize::_256,
+        let (heapsize, nursery_size) = match startsize {
+            StartHeapSize::Default => (DEFAULT_HEAP_SIZE, NURSERY_SIZE),
+            StartHeapSize::Small => (SMALL_HEAP_SIZE, 0),
         };
 
+        Self::with_size(heapsize, nursery_size, 0)
+    }
+
+    ///
+    /// min_capacityのサイズの値を、GCを発生させずに確保できるヒープを作成する。
+    /// min_large_capacityはラージオブジェクトとして確保される値の合計サイズ。
+    pub fn new_capacity(min_capacity: usize, min_large_capacity: usize) -> Self {
+        //確保できるかの判定は使用量がサイズ未満かで行うため、必ず一回り大きなサイズにする
+        let heapsize = (min_capacity + 1).next_power_of_two().max(SMALL_HEAP_SIZE);
+
+        //事前に確保する領域は古い世代の領域に取る
+        Self::with_size(heapsize, NURSERY_SIZE, min_capacity + min_large_capacity)
+    }
+
+    fn with_size(heapsize: usize, nursery_size: usize, reserved: usize) -> Self {
         let layout = Self::get_alloc_layout(heapsize);
         let ptr = unsafe { alloc::alloc(layout) };
 
-        let heap = Heap {
-            pool_ptr: ptr,
-            used: 0,
-            page_layout: layout,
-            heap_size: heapsize,
-        };
-        heap
-    }
-
-    pub fn new_capacity(min_capacity: usize) -> Self {
-        let heapsize = if min_capacity <= 256 {
-            HeapSize::_256
-        } else if min_capacity <= 512 {
-            HeapSize::_512
-        } else if min_capacity <= 1024 {
-            HeapSize::_1k
-        } else if min_capacity <= 1024 * 2 {
-            HeapSize::_2k
-        } else if min_capacity <= 1024 * 8 {
-            HeapSize::_8k
-        } else if min_capacity <= 1024 * 16 {
-            HeapSize::_16k
-        } else if min_capacity <= 1024 * 32 {
-            HeapSize::_32k
+        let (nursery_ptr, nursery_layout) = if nursery_size == 0 {
+            (std::ptr::null_mut(), None)
         } else {
-            unreachable!()
+            let nursery_layout = Self::get_alloc_layout(nursery_size);
+            (unsafe { alloc::alloc(nursery_layout) }, Some(nursery_layout))
         };
 
-        let layout = Self::get_alloc_layout(heapsize);
-        let ptr = unsafe { alloc::alloc(layout) };
-
         Heap {
             pool_ptr: ptr,
             used: 0,
             page_layout: layout,
-            heap_size: heapsize,
+            large_objects: Vec::new(),
+            large_used: 0,
+            large_gc_threshold: LARGE_OBJECT_GC_THRESHOLD,
+            nursery_ptr,
+            nursery_used: 0,
+            nursery_layout,
+            remembered: Vec::new(),
+            reserved,
+            reserved_in_old: true,
+            max_size: MAX_HEAP_SIZE,
+            quota: None,
+            grow_threshold: DEFAULT_GROW_THRESHOLD,
+            min_size: heapsize.min(DEFAULT_HEAP_SIZE),
+            low_usage_count: 0,
+            stats: HeapStats::default(),
         }
     }
 
-    fn get_alloc_layout(heapsize: HeapSize) -> alloc::Layout {
-        let size = match heapsize {
-            HeapSize::_256 => 256usize,
-            HeapSize::_512 => 512usize,
-            HeapSize::_1k => 1024usize * 1,
-            HeapSize::_2k => 1024usize * 2,
-            HeapSize::_8k => 1024usize * 8,
-            HeapSize::_16k => 1024usize * 16,
-            HeapSize::_32k => 1024usize * 32,
-        };
-
-        alloc::Layout::from_size_align(size, VALUE_ALIGN).unwrap()
+    fn get_alloc_layout(heapsize: usize) -> alloc::Layout {
+        alloc::Layout::from_size_align(heapsize, VALUE_ALIGN).unwrap()
     }
 
     pub fn alloc<'a, 'b, T: NaviType, R: GCRootValueHolder>(&'a mut self, root: &'b mut R) -> Result<UIPtr<T>, OutOfMemory> {
         self.alloc_with_additional_size::<T, R>(0, root)
     }
 
-    pub fn alloc_with_additional_size<T: NaviType, R: GCRootValueHolder>(&mut self, additional_size: usize, root: &mut R) -> Result<UIPtr<T>, OutOfMemory> {
-        //GCのバグを発見しやすいように、allocのたびにGCを実行する
-        //self.debug_gc(obj);
-
+    ///
+    /// T型の値を確保するときに、ヒープ内で実際に使用されるバイト数を計算する
+    pub fn calc_alloc_size<T: NaviType>(additional_size: usize) -> usize {
         let gc_header_size = mem::size_of::<GCHeader>();
         let obj_size = std::mem::size_of::<T>();
 
-        //TODO 確保するオブジェクトのサイズが16bit範囲内かをチェック
-        //16bit以上のオブジェクト(64KB)は別に確保する。
-        //別に確保しタオブジェクトはポインタ内のイミディエイト判別フラグで判断する。
         let need_size = gc_header_size + obj_size + additional_size;
 
         //確保するバイト数をアラインメントに沿うように切り上げる
-        let aligned_size = (need_size + (VALUE_ALIGN - 1)) / VALUE_ALIGN * VALUE_ALIGN;
+        (need_size + (VALUE_ALIGN - 1)) / VALUE_ALIGN * VALUE_ALIGN
+    }
 
-        let alloc_size = aligned_size;
+    pub fn alloc_with_additional_size<T: NaviType, R: GCRootValueHolder>(&mut self, additional_size: usize, root: &mut R) -> Result<UIPtr<T>, OutOfMemory> {
+        //GCのバグを発見しやすいように、allocのたびにGCを実行する
+        //self.debug_gc(obj);
 
-        let mut try_count = 0;
-        loop {
-            if self.used + alloc_size < self.page_layout.size() {
-                unsafe {
-                    let gc_header_ptr = self.pool_ptr.add(self.used);
-                    let gc_header = &mut *(gc_header_ptr as *mut GCHeader);
+        let alloc_size = Self::calc_alloc_size::<T>(additional_size);
 
-                    gc_header.typeinfo = T::typeinfo();
+        //大きな値はヒープ外に個別に確保する
+        if LARGE_OBJECT_SIZE <= alloc_size {
+            return self.alloc_large::<T, R>(alloc_size, root);
+        }
 
-                    let obj_ptr = gc_header_ptr.add(gc_header_size) as *mut T;
+        //事前に確保された領域内のアロケーションでは、GCを実行しない
+        if 0 < self.reserved {
+            let in_nursery = !self.reserved_in_old && self.is_nursery_fit(alloc_size);
+            if in_nursery || self.used + alloc_size < self.page_layout.size() {
+                return Ok(self.alloc_at::<T>(in_nursery, alloc_size));
+            }
+            //確保した領域を超えた場合は、通常のアロケーションに切り替える
+            self.reserved = 0;
+        }
 
-                    self.used += alloc_size;
+        //新しい値はnurseryに確保する
+        if self.nursery_layout.is_some() {
+            if !self.is_nursery_fit(alloc_size) {
+                self.minor_gc(root)?;
+            }
+            //ラージオブジェクト未満の値は、空のnurseryに必ず収まる
+            return Ok(self.alloc_at::<T>(true, alloc_size));
+        }
 
-                    return Ok(UIPtr::new(obj_ptr));
-                }
+        //nurseryを持たないヒープは、古い世代の領域に直接確保する
+        let mut try_count = 0;
+        loop {
+            if self.used + alloc_size < self.page_layout.size() {
+                return Ok(self.alloc_at::<T>(false, alloc_size));
             } else if try_count == 0 {
                 self.gc(root);
                 try_count += 1;
@@ -260,6 +362,72 @@ impl Heap {
         }
     }
 
+    fn is_nursery_fit(&self, size: usize) -> bool {
+        match self.nursery_layout {
+            Some(layout) => self.nursery_used + size < layout.size(),
+            None => false,
+        }
+    }
+
+    fn alloc_at<T: NaviType>(&mut self, in_nursery: bool, alloc_size: usize) -> UIPtr<T> {
+        unsafe {
+            let gc_header_ptr = if in_nursery {
+                let ptr = self.nursery_ptr.add(self.nursery_used);
+                self.nursery_used += alloc_size;
+                ptr
+            } else {
+                let ptr = self.pool_ptr.add(self.used);
+                self.used += alloc_size;
+                //古い世代に直接確保した値は、初期化時にnursery内の値を参照する可能性があるため記録しておく
+                if 0 < self.nursery_used {
+                    self.remembered.push(ptr.add(mem::size_of::<GCHeader>()));
+                }
+                ptr
+            };
+            self.reserved = self.reserved.saturating_sub(alloc_size);
+
+            let gc_header = &mut *(gc_header_ptr as *mut GCHeader);
+            gc_header.typeinfo = T::typeinfo();
+
+            let obj_ptr = gc_header_ptr.add(mem::size_of::<GCHeader>()) as *mut T;
+            UIPtr::new(obj_ptr)
+        }
+    }
+
+    fn alloc_large<T: NaviType, R: GCRootValueHolder>(&mut self, alloc_size: usize, root: &mut R) -> Result<UIPtr<T>, OutOfMemory> {
+        //事前に確保された領域内のアロケーションでは、GCを実行しない
+        if self.reserved == 0 && self.large_gc_threshold < self.large_used + alloc_size {
+            self.gc(root);
+            //GC後も生き残っているラージオブジェクトの量に合わせて、次にGCを実行する閾値を決める
+            self.large_gc_threshold = LARGE_OBJECT_GC_THRESHOLD.max(self.large_used * 2);
+        }
+
+        if self.max_size < self.page_layout.size() + self.large_used + alloc_size {
+            return Err(self.out_of_memory());
+        }
+
+        let layout = alloc::Layout::from_size_align(alloc_size, VALUE_ALIGN).unwrap();
+        unsafe {
+            let gc_header_ptr = alloc::alloc(layout);
+            let gc_header = &mut *(gc_header_ptr as *mut GCHeader);
+            gc_header.typeinfo = T::typeinfo();
+
+            //GC時にアドレスから検索できるように、アドレス順を保って追加する
+            let index = self.large_objects.partition_point(|obj| obj.ptr < gc_header_ptr);
+            self.large_objects.insert(index, LargeObject {
+                ptr: gc_header_ptr,
+                layout,
+                marked: false,
+            });
+
+            self.large_used += alloc_size;
+            self.reserved = self.reserved.saturating_sub(alloc_size);
+
+            let obj_ptr = gc_header_ptr.add(mem::size_of::<GCHeader>()) as *mut T;
+            Ok(UIPtr::new(obj_ptr))
+        }
+    }
+
     fn get_gc_header(v: &Any) -> &mut GCHeader {
         let ptr = v as *const Any as *const u8;
         unsafe {
@@ -271,9 +439,11 @@ impl Heap {
     pub fn is_in_heap_object<T: NaviType>(&self, v: &T) -> bool {
         let v: &Any = unsafe { std::mem::transmute(v) };
 
-        //ポインタかつ、自分自身のヒープ内に存在するオブジェクトなら、有効な値。
+        //ポインタかつ、自分自身のヒープ内(nurseryを含む)かラージオブジェクトとして存在するオブジェクトなら、有効な値。
         value::value_is_pointer(v)
-            && Self::is_pointer_within_heap(v, self.pool_ptr, unsafe { self.pool_ptr.add(self.used) })
+            && (Self::is_pointer_within_heap(v, self.pool_ptr, unsafe { self.pool_ptr.add(self.used) })
+                || self.is_in_nursery(v)
+                || LargeObject::find(&self.large_objects, Self::get_gc_header(v) as *const GCHeader as *const u8).is_some())
     }
 
     pub fn calc_total_size(v: &Any) -> usize {
@@ -309,23 +479,132 @@ impl Heap {
         }
     }
 
+    fn is_in_nursery(&self, v: &Any) -> bool {
+        Self::is_pointer_within_heap(v, self.nursery_ptr, unsafe { self.nursery_ptr.add(self.nursery_used) })
+    }
+
+    ///
+    /// ヒープ内の値parentの内部に、childへの参照を書き込んだ後に呼び出す書き込みバリア。
+    /// 古い世代の値がnursery内の値を参照するようになった場合は、マイナーGCで辿れるようにparentを記録する。
+    pub fn write_barrier(&mut self, parent: &Any, child: &Any) {
+        if value::value_is_pointer(child) && self.is_in_nursery(child) {
+            self.remember(parent);
+        }
+    }
+
+    ///
+    /// 子の値を特定できない書き込みを行ったparentを、古い世代の値であれば記録する。
+    pub fn remember(&mut self, parent: &Any) {
+        //ラージオブジェクトはマイナーGCのたびに走査するため記録しない
+        if value::value_is_pointer(parent)
+            && Self::is_pointer_within_heap(parent, self.pool_ptr, unsafe { self.pool_ptr.add(self.used) }) {
+            let ptr = parent as *const Any as *mut u8;
+            //同じ値への連続した書き込みは一度だけ記録する
+            if self.remembered.last() != Some(&ptr) {
+                self.remembered.push(ptr);
+            }
+        }
+    }
+
     pub fn force_allocation_space<R: GCRootValueHolder>(&mut self, require_size: usize, root: &mut R) -> Result<(), OutOfMemory> {
+        //nurseryに収まるサイズであればnurseryに確保する
+        if let Some(layout) = self.nursery_layout {
+            if require_size < layout.size() {
+                if !self.is_nursery_fit(require_size) {
+                    self.minor_gc(root)?;
+                }
+                //確保した領域を使い切るまではGCを発生させない
+                self.reserved = require_size;
+                self.reserved_in_old = false;
+                return Ok(())
+            }
+        }
+
         let mut try_count = 0;
         loop {
             if self.used + require_size < self.page_layout.size() {
+                //確保した領域を使い切るまではGCを発生させない
+                self.reserved = require_size;
+                self.reserved_in_old = true;
                 return Ok(())//OK!!
             } else if try_count == 0 {
                 self.gc(root);
                 try_count += 1;
 
             } else {
-                return self.grow(root);
+                //一度の拡張で足りない場合もあるため、必要なサイズが確保できるまで拡張を繰り返す
+                self.grow(root)?;
             }
         }
     }
 
     pub fn used(&self) -> usize {
-        self.used
+        self.used + self.nursery_used
+    }
+
+    pub fn large_used(&self) -> usize {
+        self.large_used
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
+    ///
+    /// ヒープとラージオブジェクトの合計サイズの上限を設定する。
+    /// 上限を超えるアロケーションは、OutOfMemoryではなくMemoryQuotaExceededとして失敗する。
+    pub fn set_quota(&mut self, quota: Option<usize>) {
+        self.quota = quota;
+        self.max_size = quota.unwrap_or(MAX_HEAP_SIZE);
+    }
+
+    pub fn quota(&self) -> Option<usize> {
+        self.quota
+    }
+
+    fn out_of_memory(&self) -> OutOfMemory {
+        OutOfMemory {
+            quota_exceeded: self.quota.is_some(),
+        }
+    }
+
+    ///
+    /// GC後の使用率(%)がthresholdを超えていたら、ヒープを拡張するようにする。
+    /// 100を指定すると、空きが足りなくなるまで拡張しない。
+    pub fn set_grow_threshold(&mut self, threshold: usize) {
+        self.grow_threshold = threshold.clamp(1, 100);
+    }
+
+    pub fn grow_threshold(&self) -> usize {
+        self.grow_threshold
+    }
+
+    ///
+    /// 古い世代の領域をsizeバイト以上に拡張する。すでに十分な大きさがあれば何もしない。
+    pub fn reserve<R: GCRootValueHolder>(&mut self, size: usize, root: &mut R) -> Result<(), OutOfMemory> {
+        let next_heap_size = size.next_power_of_two();
+        //指定されたサイズより小さくは縮小しない
+        self.min_size = self.min_size.max(next_heap_size);
+        if next_heap_size <= self.page_layout.size() {
+            return Ok(());
+        }
+        if self.max_size < next_heap_size + self.large_used {
+            return Err(self.out_of_memory());
+        }
+
+        self.grow_copying(next_heap_size, root);
+        self.stats.grow_count += 1;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            heap_size: self.page_layout.size(),
+            nursery_size: self.nursery_layout.map_or(0, |layout| layout.size()),
+            used: self.used(),
+            large_used: self.large_used,
+            ..self.stats.clone()
+        }
     }
 
     #[allow(dead_code)]
@@ -378,7 +657,7 @@ impl Heap {
         println!("[dump] **** end ****");
     }
 
-    pub fn dump_gc_heap(&self, flags: &[u16]) {
+    pub fn dump_gc_heap(&self, flags: &[u32]) {
         println!("[dump]------------------------------------");
 
         unsafe {
@@ -409,81 +688,151 @@ impl Heap {
         println!("[dump] **** end ****");
     }
 
+    ///
+    /// ヒープ全体のGCを行う。
+    /// 古い世代の領域をコンパクションした後に、nurseryで生き残っている値を古い世代へ移動する。
     pub(crate) fn gc<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //self.dump_heap();
-
-        match self.heap_size {
-            HeapSize::_256 => self.gc_compaction_256(root),
-            HeapSize::_512 => self.gc_compaction_512(root),
-            HeapSize::_1k => self.gc_compaction_1k(root),
-            HeapSize::_2k => self.gc_compaction_2k(root),
-            HeapSize::_8k => self.gc_compaction_8k(root),
-            HeapSize::_16k => self.gc_compaction_16k(root),
-            HeapSize::_32k => self.gc_compaction_32k(root),
-        };
+        self.major_gc(root);
 
-        //self.dump_heap();
+        if self.used + self.nursery_used < self.page_layout.size() {
+            self.evacuate_nursery(root);
+        }
     }
 
-    fn gc_compaction_256<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 256");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 256 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
-    }
+    ///
+    /// nurseryだけを対象にしたGCを行う。成功した場合、nurseryは空になっている。
+    fn minor_gc<R: GCRootValueHolder>(&mut self, root: &mut R) -> Result<(), OutOfMemory> {
+        //生き残った値をすべて古い世代へ移動できるだけの空きが必要
+        if self.page_layout.size() <= self.used + self.nursery_used {
+            //古い世代のコンパクションで空きを作る
+            self.major_gc(root);
 
-    fn gc_compaction_512<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 512");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 512 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
-    }
+            if self.page_layout.size() <= self.used + self.nursery_used {
+                //それでも足りなければ古い世代の領域を拡張する。(拡張時にnurseryの値も移動される)
+                return self.grow(root);
+            }
+
+            self.evacuate_nursery(root);
+            return Ok(());
+        }
 
-    fn gc_compaction_1k<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 1k");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 1024 * 1 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
+        self.evacuate_nursery(root);
+
+        //古い世代が一杯にならない限りmajor_gcは実行されないため、
+        //拡張後に使用量が減ったヒープもnurseryのGCのたびに縮小の判定を行う
+        self.check_shrink(root);
+        Ok(())
     }
 
-    fn gc_compaction_2k<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 2k");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 1024 * 2 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
+    ///
+    /// nurseryで生き残っている値を、古い世代の領域の末尾にコピーする。
+    /// 古い世代には、nursery内の値をすべて受け入れられるだけの空きがあること。
+    fn evacuate_nursery<R: GCRootValueHolder>(&mut self, root: &mut R) {
+        if self.nursery_used == 0 {
+            return;
+        }
+        let start_time = Instant::now();
+
+        let nursery_start = self.nursery_ptr;
+        let nursery_end = unsafe { self.nursery_ptr.add(self.nursery_used) };
+
+        let scan_addr = unsafe { self.pool_ptr.add(self.used) };
+        let mut arg = GCCopyingArg::new(nursery_start, nursery_end, scan_addr);
+
+        //ルートから参照されているnursery内の値をコピーする
+        root.for_each_alived_value(arg.as_ptr(), Self::copying_reference);
+
+        unsafe {
+            //nurseryへの参照を書き込まれた古い世代の値から参照されている値をコピーする
+            for parent in self.remembered.drain(..) {
+                Self::copying_children(parent, &mut arg);
+            }
+
+            //ラージオブジェクトはすべてルートとして扱う
+            for large in self.large_objects.iter() {
+                Self::copying_children(large.ptr.add(mem::size_of::<GCHeader>()), &mut arg);
+            }
+
+            //コピーした値から辿れる値をコピーする
+            Self::copying_scan(scan_addr, &mut arg);
+
+            //コピーされなかった値を開放する
+            Self::finalize_uncopied(nursery_start, nursery_end);
+        }
+
+        self.used += arg.used;
+        self.stats.reclaimed_bytes += self.nursery_used - arg.used;
+        self.nursery_used = 0;
+
+        self.stats.minor_gc_count += 1;
+        self.record_pause(start_time);
     }
 
-    fn gc_compaction_8k<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 8k");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 1024 * 8 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
+    fn record_pause(&mut self, start_time: Instant) {
+        let pause = start_time.elapsed();
+        self.stats.last_pause = pause;
+        self.stats.total_pause += pause;
     }
 
-    fn gc_compaction_16k<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 16k");
-        //16kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        let mut flags = [0u16; 1024 * 16 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
+    ///
+    /// 古い世代の領域のコンパクションを行う。
+    /// nurseryの値はすべて生きているものとして扱い、nurseryの値から参照されている古い世代の値は解放しない。
+    fn major_gc<R: GCRootValueHolder>(&mut self, root: &mut R) {
+        //self.dump_heap();
+        let start_time = Instant::now();
+        let before_used = self.used + self.large_used;
+
+        //ヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
+        //※シフトは値の最低サイズで割り算することと同じ意味。
+        let mut flags = vec![0u32; (self.used >> SIZE_BIT_SHIFT) + 1];
+        //ラージオブジェクトのリストはGC中にマークを書き換えるため、一時的に取り出しておく
+        let mut large_objects = std::mem::take(&mut self.large_objects);
+        self.gc_compaction_body(&mut flags, &mut large_objects, root);
+        self.large_objects = large_objects;
+
+        self.stats.reclaimed_bytes += before_used - (self.used + self.large_used);
+        self.stats.gc_count += 1;
+        self.record_pause(start_time);
+
+        //GC後も空きが少ない場合は、GCが頻発しないように先にヒープを拡張しておく。
+        //拡張できなくても、空きが残っている間はそのまま動作を続ける。
+        let used = self.used + self.nursery_used;
+        if self.page_layout.size() * self.grow_threshold < used * 100 {
+            self.low_usage_count = 0;
+            let _ = self.grow(root);
+
+        } else {
+            self.check_shrink(root);
+        }
+
+        //self.dump_heap();
     }
 
-    fn gc_compaction_32k<R: GCRootValueHolder>(&mut self, root: &mut R) {
-        //println!("compaction: 32k");
+    ///
+    /// 一時的に使用量が増えただけのヒープをいつまでも大きなサイズのまま保持しないように、
+    /// GC後の使用率が低い状態が続いたら縮小する。
+    fn check_shrink<R: GCRootValueHolder>(&mut self, root: &mut R) {
+        let used = self.used + self.nursery_used;
+        if used * 100 < self.page_layout.size() * SHRINK_THRESHOLD {
+            self.low_usage_count += 1;
+            if SHRINK_GC_COUNT <= self.low_usage_count {
+                self.low_usage_count = 0;
+                self.shrink(root);
+            }
 
-        //32kサイズのヒープ内に存在する可能性がある値すべてのGCフラグを保持できる大きさの配列
-        //※最後のシフトは8,または16で割り算することと同じ意味。
-        //値の最低サイズは32bitOSなら8、64bitOSなら16なのでそれぞれの数字で割る。
-        let mut flags = [0u16; 1024 * 32 >> SIZE_BIT_SHIFT];
-        self.gc_compaction_body(&mut flags, root);
+        } else {
+            self.low_usage_count = 0;
+        }
     }
 
-    fn gc_compaction_body<R: GCRootValueHolder>(&mut self, flags: &mut [u16], root: &mut R) {
-        self.gc_compaction_mark_phase(flags, root);
+    fn gc_compaction_body<R: GCRootValueHolder>(&mut self, flags: &mut [u32], large_objects: &mut Vec<LargeObject>, root: &mut R) {
+        self.gc_compaction_mark_phase(flags, large_objects, root);
         self.gc_compaction_setup_forwad_ptr(flags);
         //self.dump_gc_heap(&flags, obj);
 
-        self.gc_compaction_update_reference(flags, root);
+        self.gc_compaction_update_reference(flags, large_objects, root);
         self.gc_compaction_move_object(flags);
+        self.gc_sweep_large_objects(large_objects);
     }
 
     fn get_allocation_size(v: &Any, typeinfo: &TypeInfo) -> usize {
@@ -503,39 +852,48 @@ impl Heap {
 
     fn is_need_mark(v: &Any, arg: &GCCompactionArg) -> bool {
         //Immidiate Valueの場合があるため正しくポインタであるかを確認
-        //かつ、 funcやsyntaxなど、ヒープ外のstaticな領域に確保された値の可能性があるのでチェック
-        if value::value_is_pointer(v) && Self::is_pointer_within_heap(v, arg.start_addr, arg.end_addr) {
-            //値を指している参照から、GCHeaderを指しているポインタに変換
-            let alloc_ptr = unsafe {
-                let ptr = v as *const Any as *const u8;
-                ptr.sub(mem::size_of::<GCHeader>())
-            };
+        if !value::value_is_pointer(v) {
+            return false;
+        }
+
+        //値を指している参照から、GCHeaderを指しているポインタに変換
+        let alloc_ptr = unsafe {
+            let ptr = v as *const Any as *const u8;
+            ptr.sub(mem::size_of::<GCHeader>())
+        };
+
+        if Self::is_pointer_within_heap(v, arg.start_addr, arg.end_addr) {
             let (alive, _) = Self::get_gc_flag(arg.start_addr, alloc_ptr, arg.flags);
 
             //まだ生存フラグを立てていなければ、マークが必要
             alive == false
         } else {
-            false
+            //funcやsyntaxなど、ヒープ外のstaticな領域に確保された値の可能性があるので、
+            //ラージオブジェクトとして確保された値かを確認する
+            match LargeObject::find(arg.large_objects, alloc_ptr) {
+                Some(index) => !arg.large_objects[index].marked,
+                None => false,
+            }
         }
     }
 
-    fn set_gc_flag(alive: bool, forwarding_index: usize, start_ptr: *const u8, alloc_ptr: *const u8, flags: &mut [u16]) {
+    fn set_gc_flag(alive: bool, forwarding_index: usize, start_ptr: *const u8, alloc_ptr: *const u8, flags: &mut [u32]) {
         //ヒープの開始位置からのオフセットを取得
         let offset = unsafe { alloc_ptr.offset_from(start_ptr) };
         //値は最低でも8、もしくは16Byteある。
         //offsetを値ごとのインデックスにするために8か16で割り算するためにシフトする。
         let offset = offset >> SIZE_BIT_SHIFT;
 
-        //15bit forwarding index
+        //31bit forwarding index
         //1bit  alive?
         //※forwarding indexは必ず8の倍数になっている(32bitOSの値の最低サイズが8)。
         //8の倍数なら下位3bitは0のため、aliveフラグのための最下位1bitを除く2bit分詰めることで容量を稼ぐ。
-        //forwarding inddexとして扱える値の幅は実質17bit分になる。
+        //forwarding inddexとして扱える値の幅は実質33bit分になる。
         let flag = (forwarding_index >> 2) | (alive as usize);
-        flags[offset as usize] = flag as u16;
+        flags[offset as usize] = flag as u32;
     }
 
-    fn get_gc_flag(start_ptr: *const u8, alloc_ptr: *const u8, flags: &[u16]) -> (bool, usize) {
+    fn get_gc_flag(start_ptr: *const u8, alloc_ptr: *const u8, flags: &[u32]) -> (bool, usize) {
         //ヒープの開始位置からのオフセットを取得
         let offset = unsafe { alloc_ptr.offset_from(start_ptr) };
         //値は最低でも8、もしくは16Byteある。
@@ -545,7 +903,7 @@ impl Heap {
         let flag = flags[offset as usize];
         (
             (flag & 1) == 1, //GC到達可能フラグ 1bit
-            ((flag & 0xFFFE) << 2) as usize, // forwarding index 15bit
+            ((flag & !1) as usize) << 2, // forwarding index 31bit
         )
     }
 
@@ -556,7 +914,13 @@ impl Heap {
             ptr.sub(mem::size_of::<GCHeader>())
         };
         //対象オブジェクトに対して生存フラグを立てる
-        Self::set_gc_flag(true, 0, arg.start_addr, alloc_ptr, arg.flags);
+        if Self::is_pointer_within_heap(v, arg.start_addr, arg.end_addr) {
+            Self::set_gc_flag(true, 0, arg.start_addr, alloc_ptr, arg.flags);
+        } else {
+            //is_need_markでラージオブジェクトであることは確認済み
+            let index = LargeObject::find(arg.large_objects, alloc_ptr).unwrap();
+            arg.large_objects[index].marked = true;
+        }
 
         //対象オブジェクトが子オブジェクトを持っているなら、再帰的にマーク処理を行う
         let header = unsafe { & *(alloc_ptr as *const GCHeader as *mut GCHeader) };
@@ -574,23 +938,50 @@ impl Heap {
         }
     }
 
-    fn gc_compaction_mark_phase<R: GCRootValueHolder>(&mut self, flags: &mut [u16], root: &mut R) {
+    fn gc_compaction_mark_phase<R: GCRootValueHolder>(&mut self, flags: &mut [u32], large_objects: &mut [LargeObject], root: &mut R) {
         let mut arg = unsafe {
-            GCCompactionArg::new(self.pool_ptr, self.pool_ptr.add(self.used), flags)
+            GCCompactionArg::new(self.pool_ptr, self.pool_ptr.add(self.used), flags, large_objects)
         };
 
         //Typeinfoの実装の都合上、クロージャを渡すことができないので、無理やりポインタを経由して値を渡す
-        root.for_each_alived_value(arg.as_ptr(), |v, arg_ptr| {
+        fn mark_reference(v: &mut Ref<Any>, arg_ptr: *mut u8) {
             let arg = unsafe { GCCompactionArg::from_ptr(arg_ptr) };
 
             let v = v.as_mut();
-            if Self::is_need_mark(v, arg) {
-                Self::gc_compaction_mark(v, arg);
+            if crate::object::mm::Heap::is_need_mark(v, arg) {
+                crate::object::mm::Heap::gc_compaction_mark(v, arg);
             }
-        });
+        }
+
+        root.for_each_alived_value(arg.as_ptr(), mark_reference);
+
+        //nursery内の値から参照されている値も生きているものとして扱う
+        unsafe {
+            Self::for_each_value(self.nursery_ptr, self.nursery_ptr.add(self.nursery_used), |v, typeinfo| {
+                if let Some(func) = typeinfo.child_traversal_func {
+                    func(v, arg.as_ptr(), mark_reference);
+                }
+            });
+        }
+    }
+
+    ///
+    /// start_addrからend_addrまでの領域に確保されている値を順に処理する
+    unsafe fn for_each_value(start_addr: *mut u8, end_addr: *mut u8, mut f: impl FnMut(&mut Any, &'static TypeInfo)) {
+        let mut ptr = start_addr;
+        while ptr < end_addr {
+            let header = &mut *(ptr as *mut GCHeader);
+            let typeinfo = header.typeinfo;
+            let v = &mut *(ptr.add(std::mem::size_of::<GCHeader>()) as *mut Any);
+            let size = Self::get_allocation_size(v, typeinfo);
+
+            f(v, typeinfo);
+
+            ptr = ptr.add(size);
+        }
     }
 
-    fn gc_compaction_setup_forwad_ptr(&self, flags: &mut [u16]) {
+    fn gc_compaction_setup_forwad_ptr(&self, flags: &mut [u32]) {
         unsafe {
             let mut ptr = self.pool_ptr;
             let end = self.pool_ptr.add(self.used);
@@ -622,7 +1013,7 @@ impl Heap {
         }
     }
 
-    fn gc_compaction_update_reference<R: GCRootValueHolder>(&mut self, flags: &mut [u16], root: &mut R) {
+    fn gc_compaction_update_reference<R: GCRootValueHolder>(&mut self, flags: &mut [u32], large_objects: &mut [LargeObject], root: &mut R) {
         //生きているオブジェクトの内部で保持したままのアドレスを、
         //再配置後のアドレスで上書きする
 
@@ -653,7 +1044,18 @@ impl Heap {
             let mut ptr = self.pool_ptr;
             let end = ptr.add(self.used);
 
-            let mut arg = GCCompactionArg::new(ptr, end, flags);
+            //ラージオブジェクト内のポインタを更新
+            //※ラージオブジェクト自体は移動しないため、ラージオブジェクトへのポインタは更新不要
+            for large in large_objects.iter().filter(|obj| obj.marked) {
+                let header = &mut *(large.ptr as *mut GCHeader);
+                if let Some(func) = header.typeinfo.child_traversal_func {
+                    let v = &mut *(large.ptr.add(std::mem::size_of::<GCHeader>()) as *mut Any);
+                    let mut arg = GCCompactionArg::new(ptr, end, flags, &mut []);
+                    func(v, arg.as_ptr(), update_child_pointer);
+                }
+            }
+
+            let mut arg = GCCompactionArg::new(ptr, end, flags, &mut []);
 
             //ルートオブジェクトとして保持されているオブジェクト内のポインタを更新
             root.for_each_alived_value(arg.as_ptr(), update_child_pointer);
@@ -675,10 +1077,28 @@ impl Heap {
 
                 ptr = ptr.add(size as usize);
             }
+
+            //nursery内のオブジェクト内のポインタを更新
+            Self::for_each_value(self.nursery_ptr, self.nursery_ptr.add(self.nursery_used), |v, typeinfo| {
+                if let Some(func) = typeinfo.child_traversal_func {
+                    func(v, arg.as_ptr(), update_child_pointer);
+                }
+            });
+
+            //remembered setに記録されている値を再配置後のアドレスに置き換える。開放される値は取り除く。
+            let start = arg.start_addr;
+            self.remembered.retain_mut(|parent| {
+                let alloc_ptr = parent.sub(mem::size_of::<GCHeader>());
+                let (alive, forwarding_index) = Self::get_gc_flag(start, alloc_ptr, flags);
+                if alive {
+                    *parent = start.add(forwarding_index + mem::size_of::<GCHeader>()) as *mut u8;
+                }
+                alive
+            });
         }
     }
 
-    fn gc_compaction_move_object(&mut self, flags: &[u16]) {
+    fn gc_compaction_move_object(&mut self, flags: &[u32]) {
         unsafe {
             let mut ptr = self.pool_ptr;
             let start = ptr;
@@ -711,118 +1131,209 @@ impl Heap {
         }
     }
 
+    fn gc_sweep_large_objects(&mut self, large_objects: &mut Vec<LargeObject>) {
+        large_objects.retain_mut(|obj| {
+            if obj.marked {
+                //次回のGCのためにマークを外しておく
+                obj.marked = false;
+                true
+
+            } else {
+                //マークがないオブジェクトは開放する
+                unsafe {
+                    let header = &mut *(obj.ptr as *mut GCHeader);
+                    if let Some(finalize) = header.typeinfo.finalize {
+                        let v = &mut *(obj.ptr.add(std::mem::size_of::<GCHeader>()) as *mut Any);
+                        finalize(v);
+                    }
+                    alloc::dealloc(obj.ptr, obj.layout);
+                }
+                self.large_used -= obj.layout.size();
+                false
+            }
+        });
+    }
+
     fn grow<R: GCRootValueHolder>(&mut self, root: &mut R) -> Result<(), OutOfMemory> {
         //self.dump_heap();
 
-        match self.heap_size {
-            HeapSize::_256 => { self.grow_copying(HeapSize::_512, root); Ok(()) }
-            HeapSize::_512 => { self.grow_copying(HeapSize::_1k, root); Ok(()) }
-            HeapSize::_1k => { self.grow_copying(HeapSize::_2k, root); Ok(()) }
-            HeapSize::_2k => { self.grow_copying(HeapSize::_8k, root); Ok(()) }
-            HeapSize::_8k => { self.grow_copying(HeapSize::_16k, root); Ok(()) }
-            HeapSize::_16k => { self.grow_copying(HeapSize::_32k, root); Ok(()) }
-            HeapSize::_32k => Err(OutOfMemory {}),
+        //ヒープサイズを倍にする。上限を超える場合は上限までの拡張にとどめる。
+        //nursery内の値も拡張後のヒープへ移動するため、その分も収まるサイズにする。
+        let require_size = self.used + self.nursery_used + 1;
+        let limit = self.max_size.saturating_sub(self.large_used) / VALUE_ALIGN * VALUE_ALIGN;
+        let next_heap_size = (self.page_layout.size() * 2).max(require_size.next_power_of_two()).min(limit);
+        if next_heap_size <= self.page_layout.size() || next_heap_size < require_size {
+            return Err(self.out_of_memory());
         }
 
+        self.grow_copying(next_heap_size, root);
+        self.stats.grow_count += 1;
+        Ok(())
+
         //self.dump_heap();
     }
 
 
+    fn shrink<R: GCRootValueHolder>(&mut self, root: &mut R) {
+        //ヒープサイズを半分にする。縮小後も使用率が拡張の閾値を超えないように、使用量の倍は残す。
+        //nurseryの値をすべて古い世代へ移動できないと再び拡張されてしまうため、nurseryの大きさ分の空きも残す。
+        let nursery_size = self.nursery_layout.map_or(0, |layout| layout.size());
+        let require_size = (self.used + self.nursery_used) * 2 + nursery_size + 1;
+        let next_heap_size = (self.page_layout.size() / 2).max(require_size.next_power_of_two()).max(self.min_size);
+        if self.page_layout.size() <= next_heap_size {
+            return;
+        }
+
+        self.grow_copying(next_heap_size, root);
+        self.stats.shrink_count += 1;
+    }
+
     fn is_valid_value(v: &Any, arg: &mut GCCopyingArg) -> bool {
-        //ポインタかつ、自分自身のヒープ内に存在するオブジェクトなら、有効な値。
+        //ポインタかつ、自分自身のヒープ内(nurseryを含む)に存在するオブジェクトなら、有効な値。
         value::value_is_pointer(v)
-            && Self::is_pointer_within_heap(v, arg.start_addr, arg.end_addr)
+            && (Self::is_pointer_within_heap(v, arg.start_addr, arg.end_addr)
+                || Self::is_pointer_within_heap(v, arg.nursery_start_addr, arg.nursery_end_addr))
     }
 
-    fn grow_copying_copy(v: &Any, arg: &mut GCCopyingArg) -> *mut u8 {
-        //値を指している参照から、GCHeaderを指しているポインタに変換
-        let alloc_ptr = unsafe {
-            let ptr = v as *const Any as *const u8;
-            ptr.sub(mem::size_of::<GCHeader>())
-        };
+    fn copying_reference(v: &mut Ref<Any>, arg_ptr: *mut u8) {
+        let arg = unsafe { GCCopyingArg::from_ptr(arg_ptr) };
 
-        unsafe {
-            //オブジェクトがあるはずの場所をCopiedValueとして無理やり解釈する。
-            //※有効な値とは絶対にかぶらないような値構造になっているため安全。
-            //※まだコピーされていない有効な値の場合、最初のフィールドにはtypeinfoへのポインタが入っている。
-            //※コピー済みの場合はポインタではない特別なImmidiate Valueが入っているため区別できる。
-            let copied = alloc_ptr as *mut CopiedValue;
-            //まだコピーされていないオブジェクトなら
-            if CopiedValue::is_copied(copied) == false {
-                let header = &mut *(alloc_ptr as *mut GCHeader);
-
-                //新しい領域にオブジェクトをコピー
-                let size = Self::get_allocation_size(v, header.typeinfo);
-                let new_ptr = arg.free_ptr;
-                std::ptr::copy_nonoverlapping(alloc_ptr, new_ptr, size);
+        if Self::is_valid_value(v.as_ref(), arg) {
+            //コピー元の領域を書き換えるため、参照ではなく値を指す生ポインタからGCHeaderの位置を求める
+            let new_ptr = unsafe {
+                let header_ptr = (crate::value::ptr_value(v) as *mut u8).sub(mem::size_of::<GCHeader>());
+                Self::grow_copying_copy(header_ptr, arg)
+            };
+            //コピー先の新しいポインタで、保持しているポインタを上書きする
+            v.gc_update_pointer(new_ptr as *mut Any);
+        }
+    }
 
-                //古い領域のコピー済み領域に、マークとコピー先のポインタを保存する
-                CopiedValue::mark_copied(copied, new_ptr.add(std::mem::size_of::<GCHeader>()));
-                //※注意、これ以降は元の領域にアクセスすると壊れたデータになっている!!
+    unsafe fn copying_children(v_ptr: *mut u8, arg: &mut GCCopyingArg) {
+        let header = &mut *(v_ptr.sub(mem::size_of::<GCHeader>()) as *mut GCHeader);
+        if let Some(func) = header.typeinfo.child_traversal_func {
+            let v = &mut *(v_ptr as *mut Any);
+            func(v, arg.as_ptr(), Self::copying_reference);
+        }
+    }
 
-                //使用した分空き領域を指すポインタを進める
-                arg.free_ptr = arg.free_ptr.add(size);
-                arg.used += size;
+    ///
+    /// コピー先の領域をscan_addrから順に走査して、コピー済みの値が保持している子オブジェクトをコピーする。
+    /// 走査中にコピーされた値も末尾に追加されるため、空き領域の先頭に追いつくまで繰り返す。
+    unsafe fn copying_scan(scan_addr: *mut u8, arg: &mut GCCopyingArg) {
+        let mut ptr = scan_addr;
+        while ptr < arg.free_ptr {
+            let header = &mut *(ptr as *mut GCHeader);
+            let v_ptr = ptr.add(mem::size_of::<GCHeader>());
+            let size = Self::get_allocation_size(&*(v_ptr as *const Any), header.typeinfo);
 
-                //コピー先の領域にあるデータ参照するように諸々のローカル変数を更新
-                let header = &mut *(new_ptr as *mut GCHeader);
-                let v = &mut *(new_ptr.add(mem::size_of::<GCHeader>()) as *mut Any);
+            Self::copying_children(v_ptr, arg);
 
-                //コピーしたオブジェクトが子オブジェクトを持っているなら、再帰的にコピー処理を行う
-                if let Some(func) = header.typeinfo.child_traversal_func {
-                    func(v, arg.as_ptr(), |child, arg_ptr| {
-                        let arg = GCCopyingArg::from_ptr(arg_ptr);
+            ptr = ptr.add(size);
+        }
+    }
 
-                        if Self::is_valid_value(child.as_ref(), arg) {
-                            let new_child_ptr = Self::grow_copying_copy(child.as_ref(), arg);
+    ///
+    /// コピーGC後の領域を走査して、コピーされなかった値を開放する
+    unsafe fn finalize_uncopied(start_addr: *mut u8, end_addr: *mut u8) {
+        let mut ptr = start_addr;
+        while ptr < end_addr {
+            let copied = ptr as *mut CopiedValue;
+            let size = if CopiedValue::is_copied(copied) {
+                //コピー元の領域は壊れているため、コピー先の値からサイズを取得する
+                let v_ptr = CopiedValue::forwarding_pointer(copied);
+                let header = &*(v_ptr.sub(mem::size_of::<GCHeader>()) as *const GCHeader);
+                Self::get_allocation_size(&*(v_ptr as *const Any), header.typeinfo)
 
-                            //コピー先の新しいポインタで、内部で保持している子オブジェクトへのポインタを上書きする
-                            child.gc_update_pointer(new_child_ptr as *mut Any);
-                        }
-                    });
+            } else {
+                let header = &mut *(ptr as *mut GCHeader);
+                let v = &mut *(ptr.add(mem::size_of::<GCHeader>()) as *mut Any);
+                let size = Self::get_allocation_size(v, header.typeinfo);
+                if let Some(finalize) = header.typeinfo.finalize {
+                    finalize(v);
                 }
-            }
+                size
+            };
+
+            ptr = ptr.add(size);
+        }
+    }
 
-            //戻り値としてコピーした先のポインタを返す
-            CopiedValue::forwarding_pointer(copied)
+    ///
+    /// header_ptrが指すGCHeaderから始まる値を新しい領域へコピーし、コピー先の値を指すポインタを返す。
+    /// コピー元の領域はコピー済みのマークで上書きするため、参照を経由せずにすべて生ポインタで読み書きする。
+    ///
+    unsafe fn grow_copying_copy(header_ptr: *mut u8, arg: &mut GCCopyingArg) -> *mut u8 {
+        //オブジェクトがあるはずの場所をCopiedValueとして無理やり解釈する。
+        //※有効な値とは絶対にかぶらないような値構造になっているため安全。
+        //※まだコピーされていない有効な値の場合、最初のフィールドにはtypeinfoへのポインタが入っている。
+        //※コピー済みの場合はポインタではない特別なImmidiate Valueが入っているため区別できる。
+        let copied = header_ptr as *mut CopiedValue;
+        //まだコピーされていないオブジェクトなら
+        if CopiedValue::is_copied(copied) == false {
+            let typeinfo = (*(header_ptr as *const GCHeader)).typeinfo;
+
+            //新しい領域にオブジェクトをコピー
+            let size = Self::get_allocation_size(&*(header_ptr.add(mem::size_of::<GCHeader>()) as *const Any), typeinfo);
+            let new_ptr = arg.free_ptr;
+            std::ptr::copy_nonoverlapping(header_ptr, new_ptr, size);
+
+            //古い領域のコピー済み領域に、マークとコピー先のポインタを保存する
+            CopiedValue::mark_copied(copied, new_ptr.add(std::mem::size_of::<GCHeader>()));
+            //※注意、これ以降は元の領域にアクセスすると壊れたデータになっている!!
+
+            //使用した分空き領域を指すポインタを進める
+            arg.free_ptr = arg.free_ptr.add(size);
+            arg.used += size;
+
+            //子オブジェクトはcopying_scanでコピーする。
+            //長いリストなどで再帰が深くなりすぎないように、ここでは再帰的なコピーは行わない。
         }
+
+        //戻り値としてコピーした先のポインタを返す
+        CopiedValue::forwarding_pointer(copied)
     }
 
-    fn grow_copying<R: GCRootValueHolder>(&mut self, next_heap_size: HeapSize, root: &mut R) {
+    fn grow_copying<R: GCRootValueHolder>(&mut self, next_heap_size: usize, root: &mut R) {
         //コピー先の新しいヒープを作成
         let new_layout = Self::get_alloc_layout(next_heap_size);
         //println!("copying:{:?} {:?}", next_heap_size, new_layout);
 
         let new_heap_ptr = unsafe { alloc::alloc(new_layout) };
 
-        let mut arg = GCCopyingArg::new(
-            self.pool_ptr,
-            unsafe { self.pool_ptr.add(self.used) },
-            new_heap_ptr,
-        );
+        //古い世代の値とnurseryの値を、まとめて新しい領域へコピーする
+        let old_end = unsafe { self.pool_ptr.add(self.used) };
+        let nursery_end = unsafe { self.nursery_ptr.add(self.nursery_used) };
+        let mut arg = GCCopyingArg::new(self.pool_ptr, old_end, new_heap_ptr)
+            .with_nursery(self.nursery_ptr, nursery_end);
 
         //Typeinfoの実装の都合上、クロージャを渡すことができないので、無理やりポインタを経由して値を渡す
         //ルートから辿ることができるオブジェクトをすべて取得して、新しい領域へコピーする
-        root.for_each_alived_value(arg.as_ptr(), |v, arg_ptr| {
-            let arg = unsafe { GCCopyingArg::from_ptr(arg_ptr) };
-            let value = v.as_ref();
-
-            if Self::is_valid_value(value, arg) {
-                let new_ptr = Self::grow_copying_copy(value, arg);
-                //コピー先の新しいポインタで、保持しているポインタを上書きする
-                v.gc_update_pointer(new_ptr as *mut Any);
-            }
-        });
+        root.for_each_alived_value(arg.as_ptr(), Self::copying_reference);
 
-        //古いヒープを削除
         unsafe {
+            //ラージオブジェクトは移動しないが、内部で保持しているヒープ内の値はコピーする必要がある
+            for large in self.large_objects.iter() {
+                Self::copying_children(large.ptr.add(mem::size_of::<GCHeader>()), &mut arg);
+            }
+
+            //コピーした値から辿れる値をコピーする
+            Self::copying_scan(new_heap_ptr, &mut arg);
+
+            //コピーされなかった値を開放する
+            Self::finalize_uncopied(self.pool_ptr, old_end);
+            Self::finalize_uncopied(self.nursery_ptr, nursery_end);
+
+            //古いヒープを削除
             alloc::dealloc(self.pool_ptr, self.page_layout);
         }
 
-        self.heap_size = next_heap_size;
         self.page_layout = new_layout;
         self.pool_ptr = new_heap_ptr;
         self.used = arg.used;
+        //nurseryの値はすべて移動済み
+        self.nursery_used = 0;
+        self.remembered.clear();
     }
 
 
@@ -849,6 +1360,25 @@ impl Drop for Heap {
             }
 
             alloc::dealloc(self.pool_ptr, self.page_layout);
+
+            if let Some(layout) = self.nursery_layout {
+                Self::for_each_value(self.nursery_ptr, self.nursery_ptr.add(self.nursery_used), |v, typeinfo| {
+                    if let Some(finalize) = typeinfo.finalize {
+                        finalize(v);
+                    }
+                });
+                alloc::dealloc(self.nursery_ptr, layout);
+            }
+
+            for large in self.large_objects.iter() {
+                let header = &mut *(large.ptr as *mut GCHeader);
+                if let Some(finalize) = header.typeinfo.finalize {
+                    let v = &mut *(large.ptr.add(mem::size_of::<GCHeader>()) as *mut Any);
+                    finalize(v);
+                }
+
+                alloc::dealloc(large.ptr, large.layout);
+            }
         }
     }
 }
@@ -914,4 +1444,171 @@ mod tests {
         obj.do_gc();
         assert_eq!(obj.heap_used(), 0);
     }
+
+    #[test]
+    fn grow_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        //32KBを超えるサイズまでヒープを拡張できる
+        let mut builder = list::ListBuilder::new(obj);
+        for i in 0..5000 {
+            let v = number::Real::alloc(i as f64, obj).unwrap().into_value().reach(obj);
+            builder.push(&v, obj).unwrap();
+        }
+        let list = builder.get().capture(obj);
+
+        obj.do_gc();
+        assert!(1024 * 32 < obj.heap_used());
+
+        let sum: f64 = unsafe { list.as_ref().iter_gcunsafe() }
+            .map(|v| unsafe { v.cast_unchecked::<number::Real>() }.as_ref().num)
+            .sum();
+        assert_eq!(sum, (0..5000).sum::<i32>() as f64);
+    }
+
+    #[test]
+    fn minor_gc_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        let size = std::mem::size_of::<GCHeader>() + std::mem::size_of::<number::Real>();
+        {
+            let _1 = number::Real::alloc(1f64, obj).unwrap().capture(obj);
+            {
+                let _2 = number::Real::alloc(2f64, obj).unwrap().capture(obj);
+                //新しい値はnurseryに確保される
+                assert_eq!(obj.heap.nursery_used, size * 2);
+            }
+
+            //マイナーGCで生き残った値だけが古い世代へ移動する
+            obj.heap.minor_gc(obj.values.get_mut()).unwrap();
+            assert_eq!(obj.heap.nursery_used, 0);
+            assert_eq!(obj.heap.used, size);
+            assert_eq!(_1.as_ref().num, 1f64);
+        }
+
+        obj.do_gc();
+        assert_eq!(obj.heap_used(), 0);
+    }
+
+    #[test]
+    fn write_barrier_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        let mut builder = array::ArrayBuilder::<Any>::new(2, obj).unwrap();
+        let v = number::Real::alloc(1f64, obj).unwrap().into_value().reach(obj);
+        builder.push(&v, obj).unwrap();
+
+        //配列を古い世代へ移動させる
+        obj.heap.minor_gc(obj.values.get_mut()).unwrap();
+
+        //古い世代の配列へnurseryの値を書き込む
+        {
+            let v = number::Real::alloc(2f64, obj).unwrap().into_value().reach(obj);
+            builder.push(&v, obj).unwrap();
+        }
+        assert_eq!(obj.heap.remembered.len(), 1);
+
+        //配列以外から参照されていない値も、マイナーGCで生き残る
+        obj.heap.minor_gc(obj.values.get_mut()).unwrap();
+        assert!(obj.heap.remembered.is_empty());
+
+        let ary = builder.get().capture(obj);
+        let sum: f64 = (0..2)
+            .map(|index| unsafe { ary.as_ref().get(index).cast_unchecked::<number::Real>() }.as_ref().num)
+            .sum();
+        assert_eq!(sum, 3f64);
+    }
+
+    #[test]
+    fn stats_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        let size = std::mem::size_of::<GCHeader>() + std::mem::size_of::<number::Real>();
+        {
+            let _1 = number::Real::alloc(1f64, obj).unwrap().capture(obj);
+            number::Real::alloc(2f64, obj).unwrap();
+
+            let before = obj.heap.stats();
+            obj.do_gc();
+            let stats = obj.heap.stats();
+            assert_eq!(stats.gc_count, before.gc_count + 1);
+            assert_eq!(stats.minor_gc_count, before.minor_gc_count + 1);
+            assert_eq!(stats.reclaimed_bytes, before.reclaimed_bytes + size);
+            assert_eq!(stats.used, size);
+            assert!(stats.last_pause <= stats.total_pause);
+
+            //指定したサイズまでヒープを拡張できる
+            obj.heap.reserve(1024 * 64, obj.values.get_mut()).unwrap();
+            let stats = obj.heap.stats();
+            assert_eq!(stats.heap_size, 1024 * 64);
+            assert_eq!(stats.grow_count, before.grow_count + 1);
+            assert_eq!(_1.as_ref().num, 1f64);
+        }
+    }
+
+    #[test]
+    fn shrink_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        {
+            let mut builder = list::ListBuilder::new(obj);
+            for i in 0..5000 {
+                let v = number::Real::alloc(i as f64, obj).unwrap().into_value().reach(obj);
+                builder.push(&v, obj).unwrap();
+            }
+            let _list = builder.get().capture(obj);
+            obj.do_gc();
+        }
+        //一時的に増えた値を回収する
+        obj.do_gc();
+        let peak = obj.heap.stats();
+
+        //古い世代が一杯にならず、nurseryのGCしか発生しない状態でも、使用量が少ない状態が続くと縮小する
+        let _1 = number::Real::alloc(1f64, obj).unwrap().capture(obj);
+        for i in 0..(NURSERY_SIZE * SHRINK_GC_COUNT * 16 / std::mem::size_of::<number::Real>()) {
+            number::Real::alloc(i as f64, obj).unwrap();
+        }
+        let stats = obj.heap.stats();
+        assert!(stats.heap_size < peak.heap_size);
+        assert_eq!(stats.heap_size, (stats.used * 2 + NURSERY_SIZE + 1).next_power_of_two());
+        assert!(0 < stats.shrink_count);
+        //縮小したヒープがnurseryのGCで再び拡張されることはない
+        assert_eq!(stats.grow_count, peak.grow_count);
+        assert_eq!(stats.gc_count, peak.gc_count);
+        assert!(peak.minor_gc_count < stats.minor_gc_count);
+        assert_eq!(_1.as_ref().num, 1f64);
+    }
+
+    #[test]
+    fn large_object_test() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        {
+            let large_str = "a".repeat(LARGE_OBJECT_SIZE);
+            let _1 = number::Real::alloc(1f64, obj).unwrap().capture(obj);
+            let large = string::NString::alloc(&large_str, obj).unwrap().capture(obj);
+
+            //大きな値はヒープ外に確保される
+            let used = std::mem::size_of::<GCHeader>() + std::mem::size_of::<number::Real>();
+            assert_eq!(obj.heap_used(), used);
+            assert!(LARGE_OBJECT_SIZE <= obj.heap.large_used());
+            assert!(obj.is_in_heap_object(large.as_ref()));
+
+            //GCで移動しない
+            let ptr = large.raw_ptr();
+            obj.do_gc();
+            assert_eq!(large.raw_ptr(), ptr);
+            assert_eq!(AsRef::<str>::as_ref(large.as_ref()), large_str.as_str());
+        }
+
+        obj.do_gc();
+        assert_eq!(obj.heap_used(), 0);
+        assert_eq!(obj.heap.large_used(), 0);
+    }
 }
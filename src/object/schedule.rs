@@ -1,92 +1,368 @@
 use std::cell::RefCell;
+use std::collections::VecDeque;
 use std::thread::{self};
-use std::sync::{mpsc, Arc, Weak};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::time::Instant;
 
 
-use super::Object;
+use super::{Object, Priority};
+
+//Taskの状態
+//待機中。メッセージや返信が届くまでどのキューにも入っていない。
+const TASK_IDLE: u8 = 0;
+//実行キューに入っている
+const TASK_QUEUED: u8 = 1;
+//スケジューラスレッドで実行中
+const TASK_RUNNING: u8 = 2;
+//実行中に新しいメッセージや返信が届いた
+const TASK_NOTIFIED: u8 = 3;
+
+///
+/// オブジェクトの実行後に、次にいつ実行する必要があるかを表す。
+pub(crate) enum NextRun {
+    //まだ処理が残っているので、すぐに実行キューへ戻す
+    Immediately,
+    //指定時刻になったら実行キューへ戻す
+    At(Instant),
+    //メッセージか返信が届くまで実行しない
+    Wait,
+}
+
+///
+/// スケジューラが管理するオブジェクトの実行単位。
+/// MailBoxが強参照を保持し、メッセージや返信を受け取った時にwakeを呼び出して実行キューに入れてもらう。
+pub struct Task {
+    obj: Weak<RefCell<Object>>,
+    state: AtomicU8,
+    //オブジェクトの優先度。実行キューに入れるときに使用する。
+    //オブジェクトを借用せずに参照できるように、実行の度にオブジェクトから写しておく。
+    priority: AtomicU8,
+    //現在Taskが割り当てられているスケジューラ。
+    //バランサーによって別のスケジューラに移動されることがある。
+    scheduler: Mutex<Arc<SchedulerShared>>,
+}
+
+impl Task {
+    pub fn wake(self: &Arc<Self>) {
+        loop {
+            match self.state.load(Ordering::SeqCst) {
+                TASK_IDLE => {
+                    if self.state.compare_exchange(TASK_IDLE, TASK_QUEUED, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                        let scheduler = Arc::clone(&self.scheduler.lock().unwrap());
+                        scheduler.push(Arc::clone(self));
+                        return;
+                    }
+                }
+                TASK_RUNNING => {
+                    //実行中の場合は、実行完了後にスケジューラがもう一度キューに入れる
+                    if self.state.compare_exchange(TASK_RUNNING, TASK_NOTIFIED, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                        return;
+                    }
+                }
+                _ => {
+                    //すでにキューに入っているか、実行後にキューへ戻ることが決まっている
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        //オブジェクトがスケジューラから切り離されたので、割り当て数を減らす
+        self.scheduler.get_mut().unwrap().load.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+//ObjectはRefCellで保持されているためSendではないが、
+//Task内のオブジェクトを実行するのは常にTaskをキューから取り出した一つのスケジューラスレッドのみのため問題ない。
+unsafe impl Send for Task {}
+unsafe impl Sync for Task {}
+
+struct RunQueue {
+    //優先度ごとの実行可能なTask。
+    //実行中のTaskはキューから取り出されているため、バランサーによって別スケジューラに移動されることはない。
+    queues: [VecDeque<Arc<Task>>; 3],
+    //sleepなどで指定時刻まで実行を待っているTask
+    timers: Vec<(Instant, Arc<Task>)>,
+    //キューから取り出した回数
+    tick: usize,
+}
+
+impl RunQueue {
+    fn new() -> Self {
+        RunQueue {
+            queues: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+            timers: Vec::new(),
+            tick: 0,
+        }
+    }
+
+    fn push(&mut self, task: Arc<Task>) {
+        let priority = task.priority.load(Ordering::Relaxed) as usize;
+        self.queues[priority].push_back(task);
+    }
+
+    fn pop(&mut self) -> Option<Arc<Task>> {
+        const LOW: usize = Priority::Low as usize;
+        const NORMAL: usize = Priority::Normal as usize;
+        const HIGH: usize = Priority::High as usize;
+
+        self.tick = self.tick.wrapping_add(1);
+        //基本的には優先度の高いキューから取り出すが、
+        //優先度の低いオブジェクトがいつまでも実行されないことがないように、一定間隔で低い優先度のキューを優先する
+        let order = if self.tick.is_multiple_of(16) {
+            [LOW, NORMAL, HIGH]
+        } else if self.tick.is_multiple_of(4) {
+            [NORMAL, HIGH, LOW]
+        } else {
+            [HIGH, NORMAL, LOW]
+        };
+
+        order.iter().find_map(|index| self.queues[*index].pop_front())
+    }
+}
+
+//スケジューラスレッドとバランサーの間で共有する状態
+pub(crate) struct SchedulerShared {
+    run_queue: Mutex<RunQueue>,
+    //実行キューにTaskが追加されたことをスケジューラスレッドに通知する
+    cond: Condvar,
+
+    //スケジューラに割り当てられているオブジェクト数(待機中、実行中のオブジェクトも含む)。
+    load: AtomicUsize,
+
+    //前回バランサーが確認してから消費したreduction数
+    reductions: AtomicUsize,
+}
+
+impl SchedulerShared {
+    fn push(&self, task: Arc<Task>) {
+        self.run_queue.lock().unwrap().push(task);
+        self.cond.notify_one();
+    }
+}
 
 pub struct Scheduler {
-    tx: mpsc::Sender<Envelope>,
+    shared: Arc<SchedulerShared>,
     #[allow(dead_code)]
     join_handle: thread::JoinHandle<()>,
 }
 
 impl Scheduler {
-    pub fn new() -> Scheduler {
-        let (tx, join_handle) = scheduler_main();
+    pub fn new(id: usize) -> Scheduler {
+        let shared = Arc::new(SchedulerShared {
+            run_queue: Mutex::new(RunQueue::new()),
+            cond: Condvar::new(),
+            load: AtomicUsize::new(0),
+            reductions: AtomicUsize::new(0),
+        });
+        let join_handle = scheduler_main(id, Arc::clone(&shared));
 
         Scheduler {
-            tx,
+            shared,
             join_handle,
         }
     }
 
-    pub fn add_object(&self, obj: Arc<RefCell<Object>>) {
-        let msg = Envelope::NewObj(obj);
-        //TODO sendに失敗した場合、スケジューラスレッドがpanicなどで消えている可能性がある。
-        //再起動処理をするか？
-        self.tx.send(msg).unwrap();
+    pub fn load(&self) -> usize {
+        self.shared.load.load(Ordering::Relaxed)
     }
-}
 
-enum Envelope {
-    NewObj(Arc<RefCell<Object>>),
-}
+    ///
+    /// 前回呼び出されてから消費したreduction数を取得し、カウンタを0に戻す。
+    pub fn take_reductions(&self) -> usize {
+        self.shared.reductions.swap(0, Ordering::Relaxed)
+    }
 
-unsafe impl Send for Envelope {}
+    pub fn add_object(&self, obj: &Arc<RefCell<Object>>) -> Arc<Task> {
+        self.shared.load.fetch_add(1, Ordering::Relaxed);
 
-fn scheduler_main() -> (mpsc::Sender<Envelope>, thread::JoinHandle<()>) {
-    let (tx, rx) = mpsc::channel::<Envelope>();
+        //登録時点でinboxにメッセージが溜まっている可能性があるため、一度は実行させる
+        let task = Arc::new(Task {
+            //弱参照としてオブジェクトを保持する
+            obj: Arc::downgrade(obj),
+            state: AtomicU8::new(TASK_QUEUED),
+            priority: AtomicU8::new(obj.borrow().priority() as u8),
+            scheduler: Mutex::new(Arc::clone(&self.shared)),
+        });
+        self.shared.push(Arc::clone(&task));
 
-    let join_handle = thread::spawn(move || {
-        let mut objects: Vec<Weak<RefCell<Object>>> = Vec::new();
-        let mut index: usize = 0;
+        task
+    }
 
-        loop {
-            match rx.try_recv() {
-                Ok(Envelope::NewObj(obj)) => {
-                    //弱参照としてオブジェクトを保持する
-                    objects.push(Arc::downgrade(&obj));
-                },
-                Err(mpsc::TryRecvError::Empty) => {
-                    //do noting
-                },
-                Err(mpsc::TryRecvError::Disconnected) => {
-                    //End of scheduler thread
-                    break;
-                }
+    ///
+    /// 実行待ちのTaskを最大num個取り出す。
+    /// 取り出したTaskは別のスケジューラにmigrateされることを想定している。
+    pub fn steal(&self, num: usize) -> Vec<Arc<Task>> {
+        let mut run_queue = self.shared.run_queue.lock().unwrap();
+        let mut stolen = Vec::new();
+        //優先度の低いキューから順に取り出す
+        for queue in run_queue.queues.iter_mut() {
+            let num = (num - stolen.len()).min(queue.len());
+            //キューの末尾は直近に実行されたオブジェクトなので、末尾から取り出す
+            let at = queue.len() - num;
+            stolen.extend(queue.split_off(at));
+        }
+
+        stolen
+    }
+
+    pub fn push_objects<I: Iterator<Item=Arc<Task>>>(&self, tasks: I) {
+        for task in tasks {
+            {
+                let mut scheduler = task.scheduler.lock().unwrap();
+                scheduler.load.fetch_sub(1, Ordering::Relaxed);
+                self.shared.load.fetch_add(1, Ordering::Relaxed);
+                *scheduler = Arc::clone(&self.shared);
             }
 
-            //待機中のオブジェクトが存在しているなら
-            if objects.is_empty() == false {
-                //待機中のオブジェクトを実行する
-                index = index % objects.len();
-                let obj = &objects[index];
-                match obj.upgrade() {
-                    Some(obj) => {
-                        //オブジェクトへの参照を取得できたら、実行する。
-                        //TODO ここで発生したOOMは相手先メールボックスのOOMどう対応する？
-                        match obj.borrow_mut().do_work(1000) {
-                            Ok(_) => { }
-                            Err(_oom) => {
-                                panic!("OOM");
+            self.shared.push(task);
+        }
+    }
+}
+
+fn scheduler_main(id: usize, shared: Arc<SchedulerShared>) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name(format!("navi-scheduler-{}", id))
+        .spawn(move || {
+        loop {
+            let task = wait_task(&shared);
+            task.state.store(TASK_RUNNING, Ordering::SeqCst);
+
+            match task.obj.upgrade() {
+                Some(obj) => {
+                    //オブジェクトへの参照を取得できたら、実行する。
+                    let next = {
+                        let mut obj = obj.borrow_mut();
+                        let budget = obj.reduction_budget();
+                        //OOMはオブジェクト内で処理されるため、他のオブジェクトの実行には影響しない
+                        let reductions = obj.do_work_slice(budget);
+                        shared.reductions.fetch_add(reductions, Ordering::Relaxed);
+                        //実行中に優先度が変更されている可能性があるので写しなおす
+                        task.priority.store(obj.priority() as u8, Ordering::Relaxed);
+
+                        obj.next_run()
+                    };
+                    drop(obj);
+
+                    match next {
+                        NextRun::Immediately => {
+                            task.state.store(TASK_QUEUED, Ordering::SeqCst);
+                            shared.push(task);
+                        }
+                        NextRun::At(time) => {
+                            if task.state.compare_exchange(TASK_RUNNING, TASK_IDLE, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                                shared.run_queue.lock().unwrap().timers.push((time, task));
+                            } else {
+                                //実行中にメッセージが届いていたら、すぐにキューへ戻す
+                                task.state.store(TASK_QUEUED, Ordering::SeqCst);
+                                shared.push(task);
+                            }
+                        }
+                        NextRun::Wait => {
+                            if task.state.compare_exchange(TASK_RUNNING, TASK_IDLE, Ordering::SeqCst, Ordering::SeqCst).is_err() {
+                                //実行中にメッセージが届いていたら、すぐにキューへ戻す
+                                task.state.store(TASK_QUEUED, Ordering::SeqCst);
+                                shared.push(task);
                             }
                         }
-
-                        index += 1;
-                    },
-                    None => {
-                        //参照先のオブジェクトは消えてしまっているので、スケジューラー内からも削除する
-                        //TODO swap_removeでもいいのか？
-                        objects.remove(index);
                     }
+                },
+                None => {
+                    //参照先のオブジェクトは消えてしまっているので、Taskを破棄する
+                }
+            }
+        }
+    }).unwrap()
+}
+
+//実行可能なTaskを取り出す。実行可能なTaskが存在しない場合は、Taskが追加されるか時刻を迎えるまでスレッドを停止する。
+fn wait_task(shared: &SchedulerShared) -> Arc<Task> {
+    let mut run_queue = shared.run_queue.lock().unwrap();
+    loop {
+        //指定時刻を過ぎたTaskを実行キューに移す
+        let now = Instant::now();
+        let mut index = 0;
+        while index < run_queue.timers.len() {
+            if run_queue.timers[index].0 <= now {
+                let (_, task) = run_queue.timers.swap_remove(index);
+                //待機中に別の理由で実行キューに入っている場合は何もしない
+                if task.state.compare_exchange(TASK_IDLE, TASK_QUEUED, Ordering::SeqCst, Ordering::SeqCst).is_ok() {
+                    run_queue.push(task);
                 }
+            } else {
+                index += 1;
+            }
+        }
+
+        if let Some(task) = run_queue.pop() {
+            return task;
+        }
+
+        //実行可能なTaskが存在しないので、スレッドを停止する
+        let earliest = run_queue.timers.iter().map(|(time, _)| *time).min();
+        run_queue = match earliest {
+            Some(time) => {
+                let timeout = time.saturating_duration_since(now);
+                shared.cond.wait_timeout(run_queue, timeout).unwrap().0
+            }
+            None => {
+                shared.cond.wait(run_queue).unwrap()
             }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            //TODO 1ループの実行時間を計測し、短すぎる場合は適当なスリープを入れる
-            thread::sleep(std::time::Duration::from_millis(2));
+    #[test]
+    fn test_priority_order() {
+        let shared = Arc::new(SchedulerShared {
+            run_queue: Mutex::new(RunQueue::new()),
+            cond: Condvar::new(),
+            load: AtomicUsize::new(64),
+            reductions: AtomicUsize::new(0),
+        });
+
+        let new_task = |priority: Priority| {
+            Arc::new(Task {
+                obj: Weak::new(),
+                state: AtomicU8::new(TASK_QUEUED),
+                priority: AtomicU8::new(priority as u8),
+                scheduler: Mutex::new(Arc::clone(&shared)),
+            })
+        };
+
+        let mut run_queue = RunQueue::new();
+        for _ in 0..16 {
+            run_queue.push(new_task(Priority::Low));
+            run_queue.push(new_task(Priority::Normal));
+            run_queue.push(new_task(Priority::High));
         }
-    });
 
-    (tx, join_handle)
+        let order: Vec<u8> = (0..16)
+            .map(|_| run_queue.pop().unwrap().priority.load(Ordering::Relaxed))
+            .collect();
+
+        //High優先だが、NormalとLowも一定間隔で実行される
+        let high = order.iter().filter(|p| **p == Priority::High as u8).count();
+        let normal = order.iter().filter(|p| **p == Priority::Normal as u8).count();
+        let low = order.iter().filter(|p| **p == Priority::Low as u8).count();
+        assert_eq!(high, 12);
+        assert_eq!(normal, 3);
+        assert_eq!(low, 1);
+
+        //Highが空ならNormalを優先する
+        let mut run_queue = RunQueue::new();
+        run_queue.push(new_task(Priority::Low));
+        run_queue.push(new_task(Priority::Normal));
+        assert_eq!(run_queue.pop().unwrap().priority.load(Ordering::Relaxed), Priority::Normal as u8);
+        assert_eq!(run_queue.pop().unwrap().priority.load(Ordering::Relaxed), Priority::Low as u8);
+        assert!(run_queue.pop().is_none());
+    }
 }
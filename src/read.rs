@@ -54,15 +54,53 @@ impl Iterator for StdinChars {
     }
 }
 
+///
+/// 読み込んだ式のソース上の位置。行と列はどちらも1から始まる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePos {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl std::fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 pub struct Reader<I: Iterator<Item=char>> {
     input: Peekable<I>,
+    //次に読み込む文字の位置
+    pos: SourcePos,
 }
 
 impl <I: Iterator<Item=char>> Reader<I> {
     pub fn new(input: Peekable<I>) -> Self {
         Reader {
             input: input,
+            pos: SourcePos { line: 1, column: 1 },
+        }
+    }
+
+    pub fn pos(&self) -> SourcePos {
+        self.pos
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.input.next();
+        //読み込んだ文字に合わせて位置を進める
+        match ch {
+            Some('\n') => {
+                self.pos.line += 1;
+                self.pos.column = 1;
+            }
+            Some(_) => {
+                self.pos.column += 1;
+            }
+            None => { }
         }
+
+        ch
     }
 }
 
@@ -86,6 +124,9 @@ impl From<err::MalformedFormat> for ReadException {
 }
 
 pub fn read<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    //前回読み込んだ式の位置情報は破棄する
+    obj.clear_source_positions();
+
     read_internal(reader, obj)
 }
 
@@ -104,8 +145,12 @@ fn read_internal<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Objec
             '}' => return Err(err::MalformedFormat::new(None, format!("{}", "read error }")).into()),
 
             '"' => read_string(reader, obj),
+            '#' => read_sharp(reader, obj),
             '\'' => read_quote(reader, obj),
+            '`' => read_quasiquote(reader, obj),
+            ',' => read_unquote(reader, obj),
             '@' => read_bind(reader, obj),
+            '!' => read_error_pattern(reader, obj),
             '+' | '-' | '0' ..= '9' => read_number_or_symbol(reader, obj),
             ':' => read_keyword(reader, obj),
             _ => read_symbol(reader, obj),
@@ -115,7 +160,14 @@ fn read_internal<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Objec
 
 
 fn read_list<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    let pos = reader.pos();
     let list = read_sequence(')', reader, obj)?;
+
+    //コンパイル時にソース上の位置を参照できるように、リストの位置を記録する
+    if !list.is_nil() {
+        obj.add_source_position(&list, pos);
+    }
+
     Ok(list.into_value())
 }
 
@@ -133,7 +185,7 @@ fn read_tuple<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object)
 
 fn read_sequence<I: Iterator<Item=char>>(end_char:char, reader: &mut Reader<I>, obj: &mut Object) -> NResult<list::List, ReadException> {
     //skip first char
-    reader.input.next();
+    reader.next();
 
     let mut builder = ListBuilder::new(obj);
     loop {
@@ -141,7 +193,7 @@ fn read_sequence<I: Iterator<Item=char>>(end_char:char, reader: &mut Reader<I>,
         match reader.input.peek() {
             None => return Err(err::MalformedFormat::new(None, "シーケンスが完結する前にEOFになった").into()),
             Some(ch) if *ch == end_char => {
-                reader.input.next();
+                reader.next();
                 // complete!
                 return Ok(builder.get());
             }
@@ -156,12 +208,12 @@ fn read_sequence<I: Iterator<Item=char>>(end_char:char, reader: &mut Reader<I>,
 
 fn read_string<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     //skip first char
-    reader.input.next();
+    reader.next();
 
     //終了文字'"'までのすべての文字を読み込み文字列をぶじぇくとを作成する
     let mut acc: Vec<char> = Vec::new();
     loop {
-        match reader.input.next() {
+        match reader.next() {
             None => return Err(err::MalformedFormat::new(None, "文字列が完結する前にEOFになった").into()),
             Some('\"') => {
                 let str: String = acc.into_iter().collect();
@@ -169,6 +221,15 @@ fn read_string<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object)
 
                 return Ok(str.into_value())
             }
+            Some('\\') => {
+                //エスケープシーケンス
+                match reader.next() {
+                    Some('n') => acc.push('\n'),
+                    Some('t') => acc.push('\t'),
+                    Some(ch) => acc.push(ch),
+                    None => return Err(err::MalformedFormat::new(None, "文字列が完結する前にEOFになった").into()),
+                }
+            }
             Some(ch) => {
                 acc.push(ch);
             }
@@ -176,24 +237,106 @@ fn read_string<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object)
     }
 }
 
-#[allow(dead_code)]
-fn read_char<I: Iterator<Item=char>>(_reader: &mut Reader<I>, _ctx: &mut Object) -> NResult<Any, ReadException> {
-    //TODO
-    unimplemented!()
+fn read_sharp<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    //skip first char
+    reader.next();
+
+    match reader.input.peek() {
+        Some('\\') => read_char(reader, obj),
+        Some('{') => read_hash_map(reader, obj),
+        _ => {
+            //それ以外は#から始まるシンボルとして扱う
+            let str = format!("#{}", read_word(reader, obj)?);
+            let symbol = symbol::Symbol::alloc(&str, obj)?;
+            Ok(symbol.into_value())
+        }
+    }
+}
+
+fn read_hash_map<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    let list = read_sequence('}', reader, obj)?;
+    if !list.count().is_multiple_of(2) {
+        return Err(err::MalformedFormat::new(Some(list.into_value()), "hash map literal requires key value pairs").into());
+    }
+
+    let map = hashmap::HashMap::from_list(&list.reach(obj), obj)?;
+    Ok(map.into_value())
+}
+
+fn read_char<I: Iterator<Item=char>>(reader: &mut Reader<I>, _obj: &mut Object) -> NResult<Any, ReadException> {
+    //skip first char
+    reader.next();
+
+    //最初の1文字は区切り文字であってもそのまま文字として扱う
+    let mut acc: Vec<char> = match reader.next() {
+        Some(ch) => vec![ch],
+        None => return Err(err::MalformedFormat::new(None, "文字が完結する前にEOFになった").into()),
+    };
+    while let Some(ch) = reader.input.peek() {
+        if is_delimiter(*ch) {
+            break;
+        }
+        acc.push(*ch);
+        reader.next();
+    }
+
+    let name: String = acc.into_iter().collect();
+    match char::char_from_name(&name) {
+        Some(ch) => Ok(char::Char::new(ch).into_value()),
+        None => Err(err::MalformedFormat::new(None, format!("unknown character name #\\{}", name)).into()),
+    }
 }
 
 fn read_quote<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     read_with_modifier(compile::literal::quote().cast_value(), reader, obj)
 }
 
+fn read_quasiquote<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    read_with_modifier(compile::literal::quasiquote().cast_value(), reader, obj)
+}
+
+fn read_unquote<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    //skip first char
+    reader.next();
+
+    //,@ならunquote-splicing、それ以外はunquote
+    if reader.input.peek() == Some(&'@') {
+        read_with_modifier(compile::literal::unquote_splicing().cast_value(), reader, obj)
+    } else {
+        read_modified_sexp(compile::literal::unquote().cast_value(), reader, obj)
+    }
+}
+
 fn read_bind<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     read_with_modifier(compile::literal::bind().cast_value(), reader, obj)
 }
 
+fn read_error_pattern<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
+    //skip first char
+    reader.next();
+
+    //'!'の直後が'('の場合だけエラーパターンとして扱う。
+    //それ以外は!=のような'!'で始まるシンボルとして読み込む。
+    if reader.input.peek() == Some(&'(') {
+        read_modified_sexp(compile::literal::error_pattern().cast_value(), reader, obj)
+    } else {
+        let rest = match reader.input.peek() {
+            Some(ch) if !is_delimiter(*ch) => read_word(reader, obj)?,
+            _ => String::new(),
+        };
+        let symbol = symbol::Symbol::alloc(format!("!{}", rest), obj)?;
+        Ok(symbol.into_value())
+    }
+}
+
 fn read_with_modifier<I: Iterator<Item=char>>(modifier: &Reachable<Any>, reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     //skip first char
-    reader.input.next();
+    reader.next();
+
+    read_modified_sexp(modifier, reader, obj)
+}
 
+fn read_modified_sexp<I: Iterator<Item=char>>(modifier: &Reachable<Any>, reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     //再帰的に式を一つ読み込んでquoteで囲む
     let sexp = read_internal(reader, obj)?;
     let sexp = sexp.reach(obj);
@@ -206,22 +349,57 @@ fn read_with_modifier<I: Iterator<Item=char>>(modifier: &Reachable<Any>, reader:
 
 fn read_number_or_symbol<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     let str = read_word(reader, obj)?;
+    match parse_number(&str, obj)? {
+        Some(num) => Ok(num),
+        None => {
+            //symbol
+            let symbol = symbol::Symbol::alloc(&str, obj)?;
+            Ok(symbol.into_value())
+        }
+    }
+}
+
+///
+/// 文字列を数値として解釈する。数値の表記でなければNoneを返す。
+pub fn parse_number(str: &str, obj: &mut Object) -> Result<Option<Ref<Any>>, ReadException> {
     match str.parse::<i64>() {
         Ok(num) => {
             //integer
             let num = number::make_integer(num, obj)?;
-            Ok(num.into_value())
+            Ok(Some(num))
         },
         Err(_) => match str.parse::<f64>() {
             Ok(num) => {
+                //i64の範囲を超える整数
+                if let Some(num) = number::BigInt::parse(str) {
+                    let num = number::make_bigint(&num, obj)?;
+                    return Ok(Some(num));
+                }
+
                 //floating number
                 let num = number::Real::alloc(num, obj)?;
-                Ok(num.into_value())
+                Ok(Some(num.into_value()))
             }
             Err(_) => {
-                //symbol
-                let symbol = symbol::Symbol::alloc(&str, obj)?;
-                Ok(symbol.into_value())
+                //分子/分母の形式なら有理数。分母には符号を付けられない
+                if let Some((numerator, denominator)) = str.split_once('/') {
+                    let denominator = if denominator.starts_with(|ch: char| ch.is_ascii_digit()) {
+                        number::BigInt::parse(denominator)
+                    } else {
+                        None
+                    };
+
+                    if let (Some(numerator), Some(denominator)) = (number::BigInt::parse(numerator), denominator) {
+                        if denominator.is_zero() {
+                            return Err(err::MalformedFormat::new(None, format!("division by zero: {}", str)).into());
+                        }
+
+                        let num = number::make_rational(numerator, denominator, obj)?;
+                        return Ok(Some(num));
+                    }
+                }
+
+                Ok(None)
             }
         }
     }
@@ -229,7 +407,7 @@ fn read_number_or_symbol<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &m
 
 fn read_keyword<I: Iterator<Item=char>>(reader: &mut Reader<I>, obj: &mut Object) -> NResult<Any, ReadException> {
     //skip first char
-    reader.input.next();
+    reader.next();
 
     let str = read_word(reader, obj)?;
     let keyword = keyword::Keyword::alloc(&str, obj)?;
@@ -266,7 +444,7 @@ fn read_word<I: Iterator<Item=char>>(reader: &mut Reader<I>, _ctx: &mut Object)
             }
             Some(ch) => {
                 acc.push(*ch);
-                reader.input.next();
+                reader.next();
             }
         }
     }
@@ -293,7 +471,7 @@ const fn is_whitespace(ch: char) -> bool {
 
 fn read_line_comment<I: Iterator<Item=char>>(reader: &mut Reader<I>) {
     loop {
-        if let Some(ch) = reader.input.next() {
+        if let Some(ch) = reader.next() {
             if ch == '\n' {
                 break;
             }
@@ -308,7 +486,7 @@ fn skip_whitespace<I: Iterator<Item=char>>(reader: &mut Reader<I>) {
     while let Some(ch) = next {
         if is_whitespace(*ch) {
             //Skip!!
-            reader.input.next();
+            reader.next();
             next = reader.input.peek();
         } else if *ch == ';' {
             read_line_comment(reader);
@@ -326,6 +504,8 @@ const fn is_delimiter(ch: char) -> bool {
         || match ch {
             '"' |
             '\''|
+            '`' |
+            ',' |
             '(' |
             ')' |
             '[' |
@@ -432,6 +612,16 @@ mod tests {
             let ans = string::NString::alloc(&"3 * (4 / 2) - 12 = -6   ".to_string(), ans_obj).unwrap();
             assert_eq!(result.as_ref(), ans.as_ref());
         }
+
+        {
+            let program = r#"
+            "say \"hi\"\n\t\\"
+            "#;
+
+            let result = read::<string::NString>(program, obj);
+            let ans = string::NString::alloc(&"say \"hi\"\n\t\\".to_string(), ans_obj).unwrap();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
     }
 
     #[test]
@@ -464,6 +654,118 @@ mod tests {
             let ans = number::make_integer(1, ans_obj).unwrap();
             assert_eq!(result.as_ref(), ans.as_ref());
         }
+
+        {
+            let program = "-123456789012345678901234567890";
+
+            let result = read::<Any>(program, obj);
+            assert!(result.as_ref().is::<number::Bignum>());
+            assert_eq!(result.as_ref().to_string(), "-123456789012345678901234567890");
+        }
+    }
+
+    #[test]
+    fn read_char() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        let cases = [
+            (r"#\a", 'a'),
+            (r"#\(", '('),
+            (r"#\ ", ' '),
+            (r"#\space", ' '),
+            (r"#\newline", '\n'),
+            (r"#\x3bb", 'λ'),
+            (r"#\λ", 'λ'),
+            (r"#\x", 'x'),
+        ];
+        for (program, ans) in cases.iter() {
+            let result = read::<char::Char>(program, obj);
+            assert_eq!(result.as_ref().get(), *ans, "{}", program);
+        }
+
+        {
+            //区切り文字の直前までが1つの文字
+            let program = r"(#\a #\))";
+            let result = read::<list::List>(program, obj);
+            assert_eq!(result.count(), 2);
+        }
+
+        {
+            let mut reader = make_reader(r"#\unknown");
+            assert!(crate::read::read(&mut reader, obj).is_err());
+        }
+    }
+
+    #[test]
+    fn read_hash_map() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        {
+            let program = "#{:a 1 \"b\" (2 3)}";
+
+            let result = read::<hashmap::HashMap>(program, obj).reach(obj);
+            assert_eq!(result.as_ref().len(), 2);
+
+            let key = keyword::Keyword::alloc(&"a".to_string(), obj).unwrap().into_value();
+            let ans = number::make_integer(1, obj).unwrap();
+            assert_eq!(result.as_ref().get(key.as_ref()).unwrap().as_ref(), ans.as_ref());
+
+            let key = string::NString::alloc(&"b".to_string(), obj).unwrap().into_value();
+            assert!(result.as_ref().get(key.as_ref()).unwrap().as_ref().is::<list::List>());
+        }
+
+        {
+            //同じキーがあれば後の値が優先される
+            let program = "#{1 2 1 3}";
+
+            let result = read::<hashmap::HashMap>(program, obj);
+            assert_eq!(result.as_ref().to_string(), "#{1 3}");
+        }
+
+        {
+            let program = "#{}";
+
+            let result = read::<hashmap::HashMap>(program, obj);
+            assert_eq!(result.as_ref().len(), 0);
+        }
+
+        {
+            let mut reader = make_reader("#{1 2 3}");
+            assert!(crate::read::read(&mut reader, obj).is_err());
+        }
+    }
+
+    #[test]
+    fn read_rational() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+
+        {
+            let program = "-6/4";
+
+            let result = read::<Any>(program, obj);
+            assert!(result.as_ref().is::<number::Rational>());
+            assert_eq!(result.as_ref().to_string(), "-3/2");
+        }
+
+        {
+            //約分して分母が1になれば整数
+            let program = "4/2";
+
+            let result = read::<Any>(program, obj);
+            let ans = number::make_integer(2, obj).unwrap();
+            assert_eq!(result.as_ref(), ans.as_ref());
+        }
+
+        {
+            //分母に符号があればシンボル
+            let program = "1/-2";
+
+            let result = read::<Any>(program, obj);
+            assert!(result.as_ref().is::<symbol::Symbol>());
+        }
     }
 
     #[test]
@@ -572,6 +874,50 @@ mod tests {
             assert_eq!(result.as_ref(), ans.as_ref());
         }
 
+        //'!'で始まるシンボル
+        {
+            let program = "!= ! (!= 1 2)";
+
+            let mut reader = make_reader(program);
+            let reader = &mut reader;
+
+            let result = read_with_ctx::<symbol::Symbol>(reader, obj);
+            let ans = symbol::Symbol::alloc(&"!=".to_string(), ans_obj).unwrap();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let result = read_with_ctx::<symbol::Symbol>(reader, obj);
+            let ans = symbol::Symbol::alloc(&"!".to_string(), ans_obj).unwrap();
+            assert_eq!(result.as_ref(), ans.as_ref());
+
+            let result = read_with_ctx::<Any>(reader, obj);
+            let mut builder = ListBuilder::new(ans_obj);
+            let symbol = symbol::Symbol::alloc(&"!=".to_string(), ans_obj).unwrap().into_value().reach(ans_obj);
+            builder.push(&symbol, ans_obj).unwrap();
+            builder.push(&number::make_integer(1, ans_obj).unwrap().reach(ans_obj), ans_obj).unwrap();
+            builder.push(&number::make_integer(2, ans_obj).unwrap().reach(ans_obj), ans_obj).unwrap();
+            let ans = builder.get().reach(ans_obj);
+            assert_eq!(result.as_ref(), ans.cast_value().as_ref());
+        }
+
+        //'!'の直後が'('の場合だけエラーパターン
+        {
+            let program = "!(:e)";
+
+            let result = read::<Any>(program, obj);
+
+            let keyword = keyword::Keyword::alloc(&"e".to_string(), ans_obj).unwrap().into_value().reach(ans_obj);
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(&keyword, ans_obj).unwrap();
+            let pattern = builder.get().into_value().reach(ans_obj);
+
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(compile::literal::error_pattern().cast_value(), ans_obj).unwrap();
+            builder.push(&pattern, ans_obj).unwrap();
+            let ans = builder.get().reach(ans_obj);
+
+            assert_eq!(result.as_ref(), ans.cast_value().as_ref());
+        }
+
         //special symbol
         {
             let program = "true false";
@@ -779,4 +1125,46 @@ mod tests {
 
     }
 
+    #[test]
+    fn read_quasiquote() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        {
+            let program = "`(a ,b ,@c)";
+
+            let result = read::<Any>(program, obj);
+
+            let a = symbol::Symbol::alloc(&"a".to_string(), ans_obj).unwrap().into_value().reach(ans_obj);
+            let b = symbol::Symbol::alloc(&"b".to_string(), ans_obj).unwrap().into_value().reach(ans_obj);
+            let c = symbol::Symbol::alloc(&"c".to_string(), ans_obj).unwrap().into_value().reach(ans_obj);
+
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(compile::literal::unquote().cast_value(), ans_obj).unwrap();
+            builder.push(&b, ans_obj).unwrap();
+            let unquote = builder.get().into_value().reach(ans_obj);
+
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(compile::literal::unquote_splicing().cast_value(), ans_obj).unwrap();
+            builder.push(&c, ans_obj).unwrap();
+            let splicing = builder.get().into_value().reach(ans_obj);
+
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(&a, ans_obj).unwrap();
+            builder.push(&unquote, ans_obj).unwrap();
+            builder.push(&splicing, ans_obj).unwrap();
+            let template = builder.get().into_value().reach(ans_obj);
+
+            let mut builder = ListBuilder::new(ans_obj);
+            builder.push(compile::literal::quasiquote().cast_value(), ans_obj).unwrap();
+            builder.push(&template, ans_obj).unwrap();
+            let ans = builder.get().reach(ans_obj);
+
+            assert_eq!(result.as_ref(), ans.cast_value().as_ref());
+        }
+
+    }
+
 }
\ No newline at end of file
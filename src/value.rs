@@ -37,6 +37,8 @@ pub mod any;
 pub mod app;
 pub mod array;
 pub mod bool;
+pub mod r#box;
+pub mod char;
 pub mod compiled;
 pub mod exception;
 pub mod list;
@@ -45,6 +47,7 @@ pub mod string;
 pub mod symbol;
 pub mod keyword;
 pub mod func;
+pub mod hashmap;
 pub mod syntax;
 pub mod tuple;
 pub mod object_ref;
@@ -66,6 +69,7 @@ use once_cell::sync::Lazy;
 //xxxx xxxx xxxx xx0r pointer value(r = 1: has Reply type. r = 0: do not have Reply type.)
 //xxxx xxxx xxxx x110 fixnum
 //xxxx xxxx xxx1 0010 tagged value
+//xxxx xxxx xx10 0010 char
 
 
 pub const IMMIDATE_FIXNUM: usize = 0b110;
@@ -87,6 +91,10 @@ pub(crate) const IMMIDATE_FALSE: usize = tagged_value(3);
 pub(crate) const IMMIDATE_UNIT: usize = tagged_value(4);
 pub(crate) const IMMIDATE_MATCHFAIL: usize = tagged_value(5);
 
+// [char]
+// 上位ビットにUnicodeスカラー値を保持する
+pub(crate) const IMMIDATE_CHAR: usize = 0b10_0010;
+
 #[derive(PartialEq)]
 enum PtrKind {
     Ptr,
@@ -96,10 +104,13 @@ enum PtrKind {
     Unit,
     MatchFail,
     Fixnum,
+    Char,
 }
 
 fn pointer_kind<T>(ptr: *const T) -> PtrKind {
-    let value = mm::ptr_to_usize(ptr);
+    //ptrは&Tから作られることが多く、アラインされたアドレスだとみなされて判定が最適化で消されてしまう。
+    //black_boxを通してアドレスの値そのもので判定する。
+    let value = mm::ptr_to_usize(std::hint::black_box(ptr));
 
     //下位2bitが00なら生ポインタ
     if value & 0b11 == 0 {
@@ -119,6 +130,7 @@ fn pointer_kind<T>(ptr: *const T) -> PtrKind {
                     _ => panic!("invalid tagged value"),
                 }
             }
+            IMMIDATE_CHAR => PtrKind::Char,
             _ => panic!("invalid pointer: {}", value),
         }
     }
@@ -156,6 +168,9 @@ pub fn get_typeinfo<T: NaviType>(this: &T) -> &'static TypeInfo{
         PtrKind::Fixnum => {
             crate::value::number::Fixnum::typeinfo()
         }
+        PtrKind::Char => {
+            crate::value::char::Char::typeinfo()
+        }
         PtrKind::Ptr => {
             mm::get_typeinfo(ptr)
         }
@@ -178,10 +193,10 @@ pub fn check_reply(cap: &mut Cap<Any>, obj: &mut Object) -> Result<bool, OutOfMe
                         //返信があったのでtrueを返す
                         Ok(true)
                     }
-                    Err(err) => {
+                    Err(_) => {
                         //返信があったが、返信の内容がエラーだった。
                         //Exceptionオブジェクトを作成してReplyを上書きする。
-                        let exception = exception::Exception::alloc(err, obj)?;
+                        let exception = reply::Reply::alloc_error_exception(cap.try_cast::<reply::Reply>().unwrap(), obj)?;
                         cap.update_pointer(exception.into_value());
                         Ok(true)
                     }
@@ -74,6 +74,7 @@ impl <T: NaviType> Array<T> {
                 if value::check_reply(&mut child_v, obj)? {
                     //返信があった場合は、内部ポインタを返信結果の値に上書きする
                     cap.as_ref().get_inner(index).update_pointer(child_v.raw_ptr());
+                    obj.write_barrier(cap.as_ref(), child_v.as_ref());
                 } else {
                     //子要素にReplyを含む値が残っている場合は、全体をfalseにする
                     return Ok(false);
@@ -130,7 +131,7 @@ impl Array<Any> {
     pub fn from_list(list: &Reachable<list::List>, size: Option<usize>, obj: &mut Object) -> NResult<Array<Any>, OutOfMemory> {
         let size = match size {
             Some(s) => s,
-            None => list.as_ref().count(),
+            None => list.count(),
         };
 
         let mut array = Self::alloc(size, obj)?;
@@ -149,7 +150,7 @@ impl Array<Any> {
 
 impl <T: NaviType> Ref<Array<T>> {
 
-    fn set<V: ValueHolder<T>>(&mut self, v: &V, index: usize) -> Result<(), OutOfBounds> {
+    pub fn set<V: ValueHolder<T>>(&mut self, v: &V, index: usize) -> Result<(), OutOfBounds> {
         if self.as_ref().len <= index {
             return Err(OutOfBounds::new(self.cast_value().clone(), index));
         }
@@ -297,14 +298,16 @@ impl <T: NaviType> ArrayBuilder<T> {
         })
     }
 
-    pub unsafe fn push_uncheck<V: ValueHolder<T>>(&mut self, v: &V, _obj: &mut Object) {
+    pub unsafe fn push_uncheck<V: ValueHolder<T>>(&mut self, v: &V, obj: &mut Object) {
         self.ary.mut_refer().set_uncheck(v.raw_ptr(), self.index);
+        obj.write_barrier(self.ary.as_ref(), v.as_ref());
 
         self.index += 1;
     }
 
-    pub fn push<V: ValueHolder<T>>(&mut self, v: &V, _obj: &mut Object) -> Result<(), OutOfBounds> {
+    pub fn push<V: ValueHolder<T>>(&mut self, v: &V, obj: &mut Object) -> Result<(), OutOfBounds> {
         self.ary.mut_refer().set(v, self.index)?;
+        obj.write_barrier(self.ary.as_ref(), v.as_ref());
 
         self.index += 1;
 
@@ -358,6 +361,30 @@ fn func_array_ref(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception>
     }
 }
 
+fn func_array_set(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut ary = vm::refer_arg::<Array<Any>>(0, obj);
+    let index = vm::refer_arg::<number::Integer>(1, obj).as_ref().get() as usize;
+    let v = vm::refer_arg::<Any>(2, obj);
+
+    ary.set(&v, index)?;
+    //既存の配列に書き込むため、古い世代の配列から新しい値への参照をGCに伝える
+    obj.write_barrier(ary.as_ref(), v.as_ref());
+
+    Ok(tuple::Tuple::unit().into_ref().into_value())
+}
+
+fn func_array_fill(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut ary = vm::refer_arg::<Array<Any>>(0, obj);
+    let v = vm::refer_arg::<Any>(1, obj);
+
+    for index in 0..ary.as_ref().len() {
+        ary.set_uncheck(v.raw_ptr(), index);
+    }
+    obj.write_barrier(ary.as_ref(), v.as_ref());
+
+    Ok(tuple::Tuple::unit().into_ref().into_value())
+}
+
 static FUNC_ARRAY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     GCAllocationStruct::new(
         Func::new("array", func_array,
@@ -399,11 +426,37 @@ static FUNC_ARRAY_REF: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     )
 });
 
+//書き込む値はforceしておき、配列が後からReplyを含むことがないようにする
+static FUNC_ARRAY_SET: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("array-set!", func_array_set,
+        Parameter::new(&[
+            Param::new_no_force("array", ParamKind::Require, Array::<Any>::typeinfo()),
+            Param::new("index", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ARRAY_FILL: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("array-fill!", func_array_fill,
+        Parameter::new(&[
+            Param::new_no_force("array", ParamKind::Require, Array::<Any>::typeinfo()),
+            Param::new("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("array", &Ref::new(&FUNC_ARRAY.value));
     obj.define_global_value("array?", &Ref::new(&FUNC_IS_ARRAY.value));
     obj.define_global_value("array-len", &Ref::new(&FUNC_ARRAY_LEN.value));
     obj.define_global_value("array-ref", &Ref::new(&FUNC_ARRAY_REF.value));
+    obj.define_global_value("array-set!", &Ref::new(&FUNC_ARRAY_SET.value));
+    obj.define_global_value("array-fill!", &Ref::new(&FUNC_ARRAY_FILL.value));
 }
 
 pub mod literal {
@@ -430,6 +483,7 @@ pub mod literal {
 #[cfg(test)]
 mod tests {
     use crate::value::list::ListBuilder;
+    use crate::eval::tests::check;
     use crate::value::*;
 
     #[test]
@@ -466,4 +520,21 @@ mod tests {
             assert_eq!(ary.as_ref().get(3).as_ref(), ans.as_ref());
         }
     }
+
+    #[test]
+    fn test_mutation() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        crate::eval::exec::<Any>("(let a (array 1 2 3))", obj);
+        check("(array-set! a 1 20)", "{}", obj, ans_obj);
+        check("a", "[1 20 3]", obj, ans_obj);
+        check("(try (array-set! a 3 0) (catch @e (error-kind e)))", ":out-of-bounds", obj, ans_obj);
+
+        crate::eval::exec::<Any>("(array-fill! a '(x))", obj);
+        check("a", "['(x) '(x) '(x)]", obj, ans_obj);
+        check("(array-ref a 2)", "'(x)", obj, ans_obj);
+    }
 }
\ No newline at end of file
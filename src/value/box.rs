@@ -0,0 +1,210 @@
+use crate::value::*;
+use crate::value::app::{Parameter, ParamKind, Param};
+use crate::ptr::*;
+use crate::err::*;
+use crate::vm;
+use std::fmt::{self, Debug, Display};
+
+//
+// 値を1つだけ保持する変更可能な入れ物。
+// 中身はset-box!で書き換えられるため、比較は同一性で行う。
+//
+pub struct NBox {
+    value: Ref<Any>,
+}
+
+static BOX_TYPEINFO: TypeInfo = new_typeinfo!(
+    NBox,
+    "Box",
+    std::mem::size_of::<NBox>(),
+    None,
+    NBox::eq,
+    NBox::clone_inner,
+    Display::fmt,
+    None,
+    None,
+    None,
+    Some(NBox::child_traversal),
+    None,
+    None,
+);
+
+impl NaviType for NBox {
+    fn typeinfo() -> &'static TypeInfo {
+        &BOX_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+        unsafe {
+            let value = Any::clone_inner(self.value.as_ref(), allocator)?.into_reachable();
+            Self::alloc(&value, allocator)
+        }
+    }
+}
+
+impl NBox {
+
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(&mut self.value, arg);
+    }
+
+    fn alloc<A: Allocator>(value: &Reachable<Any>, allocator: &mut A) -> NResult<NBox, OutOfMemory> {
+        let ptr = allocator.alloc::<NBox>()?;
+
+        unsafe {
+            //確保したメモリ内に値を書き込む
+            std::ptr::write(ptr.as_ptr(), NBox {
+                value: value.raw_ptr().into(),
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    pub fn new(value: &Reachable<Any>, obj: &mut Object) -> NResult<NBox, OutOfMemory> {
+        let b = Self::alloc(value, obj)?;
+        obj.write_barrier(b.as_ref(), value.as_ref());
+
+        Ok(b)
+    }
+
+    pub fn get(&self) -> Ref<Any> {
+        self.value.clone()
+    }
+}
+
+impl Ref<NBox> {
+    pub fn set(&mut self, value: &Ref<Any>, obj: &mut Object) {
+        self.as_mut().value = value.clone();
+        obj.write_barrier(self.as_ref(), value.as_ref());
+    }
+}
+
+impl Eq for NBox {}
+
+impl PartialEq for NBox {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+fn display(this: &NBox, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "#box:")?;
+    Display::fmt(this.value.as_ref(), f)
+}
+
+impl Display for NBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display(self, f)
+    }
+}
+
+impl Debug for NBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display(self, f)
+    }
+}
+
+fn func_box(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj).reach(obj);
+
+    let b = NBox::new(&v, obj)?;
+    Ok(b.into_value())
+}
+
+fn func_is_box(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if v.is::<NBox>() {
+        Ok(v)
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_unbox(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let b = vm::refer_arg::<NBox>(0, obj);
+    Ok(b.as_ref().get())
+}
+
+fn func_set_box(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut b = vm::refer_arg::<NBox>(0, obj);
+    let v = vm::refer_arg::<Any>(1, obj);
+
+    b.set(&v, obj);
+    Ok(tuple::Tuple::unit().into_ref().into_value())
+}
+
+//中身はforceしてから格納し、Boxが後からReplyを含むことがないようにする
+static FUNC_BOX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("box", func_box,
+            Parameter::new(&[
+            Param::new("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_BOX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("box?", func_is_box,
+            Parameter::new(&[
+            Param::new_no_force("x", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_UNBOX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("unbox", func_unbox,
+            Parameter::new(&[
+            Param::new("box", ParamKind::Require, NBox::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SET_BOX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("set-box!", func_set_box,
+            Parameter::new(&[
+            Param::new("box", ParamKind::Require, NBox::typeinfo()),
+            Param::new("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+pub fn register_global(obj: &mut Object) {
+    obj.define_global_value("box", &Ref::new(&FUNC_BOX.value));
+    obj.define_global_value("box?", &Ref::new(&FUNC_IS_BOX.value));
+    obj.define_global_value("unbox", &Ref::new(&FUNC_UNBOX.value));
+    obj.define_global_value("set-box!", &Ref::new(&FUNC_SET_BOX.value));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::check;
+    use crate::object::Object;
+
+    #[test]
+    fn test_box() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(unbox (box 1))", "1", obj, ans_obj);
+        check("(unbox (box '(1 2)))", "'(1 2)", obj, ans_obj);
+        check("(local (let b (box 1)) (set-box! b (+ (unbox b) 10)) (unbox b))", "11", obj, ans_obj);
+        check("(local (let b (box 1)) (let f (fun () (set-box! b (+ (unbox b) 1)))) (f) (f) (unbox b))", "3", obj, ans_obj);
+
+        //Boxは同一性で比較する
+        check("(= (box 1) (box 1))", "false", obj, ans_obj);
+        check("(local (let b (box 1)) (= b b))", "true", obj, ans_obj);
+        check("(local (let b (box 1)) (= (box? b) b))", "true", obj, ans_obj);
+        check("(box? 1)", "false", obj, ans_obj);
+    }
+}
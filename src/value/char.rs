@@ -0,0 +1,365 @@
+use crate::value::*;
+use crate::value::func::Func;
+use crate::value::app::{Parameter, ParamKind, Param};
+use crate::ptr::*;
+use crate::err::Exception;
+use crate::object::Object;
+use crate::object::mm::GCAllocationStruct;
+use crate::vm;
+use std::fmt::{self, Debug, Display};
+use std::convert::TryFrom;
+use once_cell::sync::Lazy;
+
+pub struct Char {
+}
+
+static CHAR_TYPEINFO: TypeInfo = new_typeinfo!(
+    Char,
+    "Char",
+    0, None, //Immidiate Valueだけなのでサイズは常に0
+    Char::eq,
+    Char::clone_inner,
+    Display::fmt,
+    None,
+    None,
+    None,
+    None,
+    None,
+    None,
+);
+
+impl NaviType for Char {
+    fn typeinfo() -> &'static TypeInfo {
+        &CHAR_TYPEINFO
+    }
+
+    fn clone_inner(&self, _allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //Char型の値は常にImmidiate Valueなのでそのまま返す
+        Ok(Ref::new(self))
+    }
+}
+
+impl Char {
+
+    #[inline(always)]
+    pub fn new(ch: char) -> Ref<Char> {
+        //上位ビットにUnicodeスカラー値をそのまま埋め込む
+        let v = ((ch as usize) << 16) | IMMIDATE_CHAR;
+        Ref::from(usize_to_ptr::<Char>(v))
+    }
+
+    #[inline(always)]
+    pub fn get(&self) -> char {
+        let v = ptr_to_usize(self as *const Char);
+        //Immidiate Valueとして作成できるのは有効なcharだけなので変換は必ず成功する
+        unsafe { std::char::from_u32_unchecked((v >> 16) as u32) }
+    }
+
+}
+
+impl Eq for Char {}
+
+impl PartialEq for Char {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self as *const Char, other as *const Char)
+    }
+}
+
+impl Display for Char {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.get(), f)
+    }
+}
+
+impl Debug for Char {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.get(), f)
+    }
+}
+
+impl std::hash::Hash for Char {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get().hash(state);
+    }
+}
+
+static CHAR_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("newline", '\n'),
+    ("tab", '\t'),
+    ("return", '\r'),
+    ("null", '\0'),
+    ("alarm", '\u{7}'),
+    ("backspace", '\u{8}'),
+    ("delete", '\u{7f}'),
+    ("escape", '\u{1b}'),
+];
+
+///
+/// #\に続く名前から文字を求める。1文字の名前、文字名、x<16進数>の形式に対応する。
+pub fn char_from_name(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => return Some(ch),
+        (None, _) => return None,
+        _ => { }
+    }
+
+    if let Some((_, ch)) = CHAR_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*ch);
+    }
+
+    //#\x3bbのような16進数表記
+    name.strip_prefix('x')
+        .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+        .and_then(std::char::from_u32)
+}
+
+///
+/// 文字を#\に続く表記に変換する。名前を持つ文字は名前を返す。
+pub fn char_name(ch: char) -> String {
+    match CHAR_NAMES.iter().find(|(_, c)| *c == ch) {
+        Some((name, _)) => name.to_string(),
+        None => ch.to_string(),
+    }
+}
+
+fn func_is_char(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if v.as_ref().is::<Char>() {
+        Ok(v.clone())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn char_predicate(pred: fn(char) -> bool, obj: &mut Object) -> NResult<Any, Exception> {
+    let ch = vm::refer_arg::<Char>(0, obj).as_ref().get();
+    if pred(ch) {
+        Ok(bool::Bool::true_().into_ref().into_value())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_is_char_alphabetic(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    char_predicate(char::is_alphabetic, obj)
+}
+
+fn func_is_char_numeric(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    char_predicate(char::is_numeric, obj)
+}
+
+fn func_is_char_whitespace(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    char_predicate(char::is_whitespace, obj)
+}
+
+fn func_is_char_upper_case(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    char_predicate(char::is_uppercase, obj)
+}
+
+fn func_is_char_lower_case(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    char_predicate(char::is_lowercase, obj)
+}
+
+fn func_char_upcase(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let ch = vm::refer_arg::<Char>(0, obj).as_ref().get();
+    //複数文字に変換される場合は元の文字のままにする
+    let mut upper = ch.to_uppercase();
+    let ch = match (upper.next(), upper.next()) {
+        (Some(upper), None) => upper,
+        _ => ch,
+    };
+    Ok(Char::new(ch).into_value())
+}
+
+fn func_char_downcase(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let ch = vm::refer_arg::<Char>(0, obj).as_ref().get();
+    let mut lower = ch.to_lowercase();
+    let ch = match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => ch,
+    };
+    Ok(Char::new(ch).into_value())
+}
+
+fn func_char_to_integer(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let ch = vm::refer_arg::<Char>(0, obj).as_ref().get();
+    let num = number::make_integer(ch as i64, obj)?;
+    Ok(num)
+}
+
+fn func_integer_to_char(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let code = vm::refer_arg::<number::Integer>(0, obj).as_ref().get();
+    match u32::try_from(code).ok().and_then(std::char::from_u32) {
+        Some(ch) => Ok(Char::new(ch).into_value()),
+        None => Err(Exception::Other(format!("integer->char: {} is not a valid code point", code))),
+    }
+}
+
+fn func_char_to_string(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let ch = vm::refer_arg::<Char>(0, obj).as_ref().get();
+    let str = string::NString::alloc(&ch.to_string(), obj)?;
+    Ok(str.into_value())
+}
+
+static FUNC_IS_CHAR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char?", func_is_char,
+        Parameter::new(&[
+            Param::new_no_force("x", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_CHAR_ALPHABETIC: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-alphabetic?", func_is_char_alphabetic,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_CHAR_NUMERIC: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-numeric?", func_is_char_numeric,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_CHAR_WHITESPACE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-whitespace?", func_is_char_whitespace,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_CHAR_UPPER_CASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-upper-case?", func_is_char_upper_case,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_CHAR_LOWER_CASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-lower-case?", func_is_char_lower_case,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CHAR_UPCASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-upcase", func_char_upcase,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CHAR_DOWNCASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char-downcase", func_char_downcase,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CHAR_TO_INTEGER: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char->integer", func_char_to_integer,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_INTEGER_TO_CHAR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("integer->char", func_integer_to_char,
+        Parameter::new(&[
+            Param::new("code", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CHAR_TO_STRING: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("char->string", func_char_to_string,
+        Parameter::new(&[
+            Param::new("ch", ParamKind::Require, Char::typeinfo()),
+            ])
+        )
+    )
+});
+
+pub fn register_global(obj: &mut Object) {
+    obj.define_global_value("char?", &Ref::new(&FUNC_IS_CHAR.value));
+    obj.define_global_value("char-alphabetic?", &Ref::new(&FUNC_IS_CHAR_ALPHABETIC.value));
+    obj.define_global_value("char-numeric?", &Ref::new(&FUNC_IS_CHAR_NUMERIC.value));
+    obj.define_global_value("char-whitespace?", &Ref::new(&FUNC_IS_CHAR_WHITESPACE.value));
+    obj.define_global_value("char-upper-case?", &Ref::new(&FUNC_IS_CHAR_UPPER_CASE.value));
+    obj.define_global_value("char-lower-case?", &Ref::new(&FUNC_IS_CHAR_LOWER_CASE.value));
+    obj.define_global_value("char-upcase", &Ref::new(&FUNC_CHAR_UPCASE.value));
+    obj.define_global_value("char-downcase", &Ref::new(&FUNC_CHAR_DOWNCASE.value));
+    obj.define_global_value("char->integer", &Ref::new(&FUNC_CHAR_TO_INTEGER.value));
+    obj.define_global_value("integer->char", &Ref::new(&FUNC_INTEGER_TO_CHAR.value));
+    obj.define_global_value("char->string", &Ref::new(&FUNC_CHAR_TO_STRING.value));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::check;
+    use crate::object::Object;
+
+    #[test]
+    fn test_char() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check(r#"(char? #\a)"#, r#"#\a"#, obj, ans_obj);
+        check(r#"(char? "a")"#, "false", obj, ans_obj);
+        check(r#"(= #\a #\a)"#, "true", obj, ans_obj);
+        check(r#"(= #\a #\b)"#, "false", obj, ans_obj);
+
+        check(r#"(char->integer #\A)"#, "65", obj, ans_obj);
+        check(r#"(char->integer #\x3bb)"#, "955", obj, ans_obj);
+        check(r#"(= (integer->char 955) #\λ)"#, "true", obj, ans_obj);
+        check(r#"(try (integer->char 55296) (catch @e (error-kind e)))"#, ":other", obj, ans_obj);
+
+        check(r#"(char-alphabetic? #\λ)"#, "true", obj, ans_obj);
+        check(r#"(char-numeric? #\7)"#, "true", obj, ans_obj);
+        check(r#"(char-numeric? #\a)"#, "false", obj, ans_obj);
+        check(r#"(char-whitespace? #\space)"#, "true", obj, ans_obj);
+        check(r#"(char-upper-case? #\A)"#, "true", obj, ans_obj);
+        check(r#"(char-lower-case? #\A)"#, "false", obj, ans_obj);
+        check(r#"(char-upcase #\é)"#, r#"#\É"#, obj, ans_obj);
+        check(r#"(char-downcase #\A)"#, r#"#\a"#, obj, ans_obj);
+        check(r#"(char->string #\newline)"#, r#""\n""#, obj, ans_obj);
+
+        check(r#"(= (string-ref "héllo" 1) #\é)"#, "true", obj, ans_obj);
+        check(r#"(format "~a ~s ~s" #\a #\a #\space)"#, r#""a #\\a #\\space""#, obj, ans_obj);
+    }
+}
@@ -3,10 +3,89 @@ use crate::ptr::*;
 use std::fmt::{Debug, Display};
 use crate::new_app_typeinfo;
 use crate::value::app::{AppTypeInfo, APP_EXTRATYPE_ID};
+use crate::read::SourcePos;
+
+///
+/// プログラム内の位置(PC)とソース上の位置の対応表。
+/// 例外発生時のバックトレース作成に使用する。
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    //コードを持つ関数の名前。無名関数やトップレベルのコードではNone
+    name: Option<String>,
+    //関数呼び出し命令列の範囲(start..end)と、呼び出し式のソース上の位置
+    calls: Vec<(usize, usize, SourcePos)>,
+    //プログラム内に含まれるクロージャ本体の範囲(start..end)と、クロージャの名前
+    funs: Vec<(usize, usize, Option<String>)>,
+    //クロージャ本体のコードであればtrue
+    closure: bool,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn is_closure(&self) -> bool {
+        self.closure
+    }
+
+    pub fn add_call(&mut self, start: usize, end: usize, pos: SourcePos) {
+        self.calls.push((start, end, pos));
+    }
+
+    pub fn add_fun(&mut self, start: usize, end: usize, name: Option<String>) {
+        self.funs.push((start, end, name));
+    }
+
+    ///
+    /// 別バッファで作成したコードの対応表を、offsetの位置に配置したものとして取り込む
+    pub fn append(&mut self, offset: usize, other: SourceMap) {
+        self.calls.extend(other.calls.into_iter()
+            .map(|(start, end, pos)| (start + offset, end + offset, pos)));
+        self.funs.extend(other.funs.into_iter()
+            .map(|(start, end, name)| (start + offset, end + offset, name)));
+    }
+
+    ///
+    /// プログラムのstart..endの範囲を切り出したクロージャ本体用の対応表を作成する
+    pub fn slice(&self, start: usize, end: usize) -> SourceMap {
+        let contains = |s: usize, e: usize| start <= s && e <= end;
+
+        SourceMap {
+            name: self.funs.iter()
+                .find(|(s, e, _)| *s == start && *e == end)
+                .and_then(|(_, _, name)| name.clone()),
+            calls: self.calls.iter()
+                .filter(|(s, e, _)| contains(*s, *e))
+                .map(|(s, e, pos)| (s - start, e - start, *pos))
+                .collect(),
+            funs: self.funs.iter()
+                .filter(|(s, e, _)| contains(*s, *e) && (*s, *e) != (start, end))
+                .map(|(s, e, name)| (s - start, e - start, name.clone()))
+                .collect(),
+            closure: true,
+        }
+    }
+
+    ///
+    /// 指定位置の命令を含む、最も内側の呼び出し式の位置を取得する
+    pub fn lookup(&self, pc: usize) -> Option<SourcePos> {
+        self.calls.iter()
+            .filter(|(start, end, _)| *start <= pc && pc < *end)
+            .max_by_key(|(start, _, _)| *start)
+            .map(|(_, _, pos)| *pos)
+    }
+}
 
 pub struct Code {
     program: Vec<u8>,
     constants: Vec<Ref<Any>>,
+    //コンパイラが生成したコードだけが持つ。VM内部で一時的に作るコードはNone
+    source_map: Option<Box<SourceMap>>,
 }
 
 static CODE_TYPEINFO: TypeInfo = new_typeinfo!(
@@ -44,6 +123,7 @@ impl NaviType for Code {
             std::ptr::write(ptr.as_ptr(), Code {
                 program: program,
                 constants: constants,
+                source_map: self.source_map.clone(),
             });
 
             Ok(ptr.into_ref())
@@ -57,11 +137,13 @@ impl Code {
         self.constants.iter_mut().for_each(|v| callback(v, arg));
     }
 
-    pub fn alloc<A: Allocator>(program: Vec<u8>, constants: Vec<Cap<Any>>, allocator: &mut A) -> NResult<Self, OutOfMemory> {
+    pub fn alloc<A: Allocator>(program: Vec<u8>, constants: Vec<Cap<Any>>, source_map: SourceMap, allocator: &mut A) -> NResult<Self, OutOfMemory> {
         let ptr = allocator.alloc::<Code>()?;
 
+        let mut code = Self::new(program, constants);
+        code.source_map = Some(Box::new(source_map));
         unsafe {
-            std::ptr::write(ptr.as_ptr(), Self::new(program, constants))
+            std::ptr::write(ptr.as_ptr(), code)
         }
 
         Ok(ptr.into_ref())
@@ -75,6 +157,7 @@ impl Code {
         Code {
             program: program,
             constants: constants,
+            source_map: None,
         }
     }
 
@@ -90,6 +173,10 @@ impl Code {
         &self.constants[start..end]
     }
 
+    pub fn source_map(&self) -> Option<&SourceMap> {
+        self.source_map.as_deref()
+    }
+
 }
 
 impl Eq for Code { }
@@ -158,7 +245,8 @@ impl NaviType for Closure {
             ;
         let constants = constants?;
 
-        let mut closure = Self::alloc(program, &constants, self.parameter.clone(), self.num_free_vars, allocator)?;
+        let source_map = self.code.source_map.as_deref().cloned();
+        let mut closure = Self::alloc(program, &constants, source_map, self.parameter.clone(), self.num_free_vars, allocator)?;
 
         for index in 0 .. self.num_free_vars {
             let child = self.get_inner(index);
@@ -202,6 +290,7 @@ impl Closure {
                 if crate::value::check_reply(&mut child_v, obj)? {
                     //返信があった場合は、内部ポインタを返信結果の値に上書きする
                     cap.as_ref().get_inner(index).update_pointer(child_v.raw_ptr());
+                    obj.write_barrier(cap.as_ref(), child_v.as_ref());
                 } else {
                     //子要素にReplyを含む値が残っている場合は、全体をfalseにする
                     return Ok(false);
@@ -215,7 +304,7 @@ impl Closure {
         Ok(true)
     }
 
-    pub fn alloc<A: Allocator>(program: Vec<u8>, constants: &[Ref<Any>], parameter: app::Parameter, num_free_vars: usize, allocator: &mut A) -> NResult<Self, OutOfMemory> {
+    pub fn alloc<A: Allocator>(program: Vec<u8>, constants: &[Ref<Any>], source_map: Option<SourceMap>, parameter: app::Parameter, num_free_vars: usize, allocator: &mut A) -> NResult<Self, OutOfMemory> {
         let ptr = allocator.alloc_with_additional_size::<Closure>(num_free_vars * std::mem::size_of::<Ref<Any>>())?;
 
         let constants = constants.into_iter()
@@ -228,6 +317,7 @@ impl Closure {
                 code: Code {
                     program: program,
                     constants: constants,
+                    source_map: source_map.map(Box::new),
                 },
                 parameter,
                 num_free_vars,
@@ -238,7 +328,10 @@ impl Closure {
     }
 
     pub fn name(&self) -> &str {
-        "Closure"
+        //letで名前を付けられたクロージャであればその名前を返す
+        self.code.source_map()
+            .and_then(|map| map.name())
+            .unwrap_or("Closure")
     }
 
     #[inline]
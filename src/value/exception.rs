@@ -1,11 +1,14 @@
 use crate::value::*;
+use crate::value::app::{Parameter, ParamKind, Param};
 use crate::ptr::*;
 use crate::err;
 use std::fmt::{self, Debug, Display};
 
 
 pub struct Exception {
-    err: err::Exception
+    err: err::Exception,
+    //ハンドラで捕捉された例外は、投げなおしたときのために発生した位置のバックトレースを保持する
+    backtrace: Option<err::Backtrace>,
 }
 
 static EXCEPTION_TYPEINFO: TypeInfo = new_typeinfo!(
@@ -17,7 +20,7 @@ static EXCEPTION_TYPEINFO: TypeInfo = new_typeinfo!(
     Exception::clone_inner,
     Display::fmt,
     None,
-    None,
+    Some(Exception::finalize),
     None,
     Some(Exception::child_traversal),
     None,
@@ -31,7 +34,9 @@ impl NaviType for Exception {
 
     fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
         let err = unsafe { self.err.value_clone_gcunsafe(allocator) }?;
-        Self::alloc(err, allocator)
+        let mut exception = Self::alloc(err, allocator)?;
+        exception.as_mut().backtrace = self.backtrace.clone();
+        Ok(exception)
     }
 }
 
@@ -41,18 +46,64 @@ impl Exception {
         self.err.for_each_alived_value(arg, callback);
     }
 
+    fn finalize(&mut self) {
+        //バックトレースはヒープ外のメモリを所有しているため、GCで回収されるときに解放する
+        self.backtrace = None;
+    }
+
     pub fn alloc<A: Allocator>(err: err::Exception, allocator: &mut A) -> NResult<Exception, OutOfMemory> {
         let ptr = allocator.alloc::<Exception>()?;
 
         unsafe {
             std::ptr::write(ptr.as_ptr(), Exception {
                 err: err,
+                backtrace: None,
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    ///
+    /// 確保が終わってからget_errでエラーを取り出してExceptionを作成する。
+    /// 確保中にGCが走っても、GCで更新された後のエラーを使用できる。
+    ///
+    pub fn alloc_with<A: Allocator>(get_err: impl FnOnce() -> err::Exception, allocator: &mut A) -> NResult<Exception, OutOfMemory> {
+        let ptr = allocator.alloc::<Exception>()?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), Exception {
+                err: get_err(),
+                backtrace: None,
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    ///
+    /// ハンドラで捕捉した例外を、発生した位置のバックトレースと一緒に保持するExceptionを作成する。
+    pub fn alloc_with_backtrace<A: Allocator>(err: err::Exception, backtrace: err::Backtrace, allocator: &mut A) -> NResult<Exception, OutOfMemory> {
+        let ptr = allocator.alloc::<Exception>()?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), Exception {
+                err,
+                backtrace: Some(backtrace),
             });
         }
 
         Ok(ptr.into_ref())
     }
 
+    pub fn err(&self) -> &err::Exception {
+        &self.err
+    }
+
+    pub fn backtrace(&self) -> Option<&err::Backtrace> {
+        self.backtrace.as_ref()
+    }
+
 }
 
 impl Eq for Exception {}
@@ -64,7 +115,7 @@ impl PartialEq for Exception {
 }
 
 fn display(this: &Exception, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    std::fmt::Display::fmt(this, f)
+    std::fmt::Display::fmt(&this.err, f)
 }
 
 impl Display for Exception {
@@ -77,4 +128,201 @@ impl Debug for Exception {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         display(self, f)
     }
-}
\ No newline at end of file
+}
+
+fn func_raise(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+
+    if let Some(exception) = v.try_cast::<Exception>() {
+        //ハンドラで捕捉したExceptionオブジェクトは、元のエラーとバックトレースのまま投げなおす
+        vm::set_backtrace(exception.as_ref().backtrace().cloned(), obj);
+        Err(exception.as_ref().err().clone())
+    } else {
+        Err(err::Exception::Raise(err::Raise::new(v)))
+    }
+}
+
+fn func_caught_value(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Exception>(0, obj);
+
+    //try式のcatch節には、raiseで投げられた値はそのままの値、それ以外はExceptionオブジェクトとして渡す
+    match v.as_ref().err() {
+        err::Exception::Raise(inner) => Ok(inner.value.clone()),
+        _ => Ok(v.into_value()),
+    }
+}
+
+fn func_error(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let kind = vm::refer_arg::<keyword::Keyword>(0, obj);
+    let message = vm::refer_arg::<string::NString>(1, obj);
+    //payloadが省略された場合はUnitが渡される
+    let payload = vm::refer_arg::<Any>(2, obj);
+
+    Err(err::Exception::UserError(err::UserError::new(kind, message, payload)))
+}
+
+fn func_is_error(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if v.is::<Exception>() {
+        Ok(v)
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_error_kind(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Exception>(0, obj);
+
+    match v.as_ref().err() {
+        err::Exception::UserError(inner) => {
+            Ok(inner.kind.clone().into_value())
+        }
+        err => {
+            //組み込みの例外は例外の種類に対応したキーワードを返す
+            let name = err.kind_name().unwrap();
+            let kind = keyword::Keyword::alloc(name, obj)?;
+            Ok(kind.into_value())
+        }
+    }
+}
+
+fn func_error_message(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Exception>(0, obj);
+
+    match v.as_ref().err() {
+        err::Exception::UserError(inner) => {
+            Ok(inner.message.clone().into_value())
+        }
+        err => {
+            let message = err.message();
+            let message = string::NString::alloc(&message, obj)?;
+            Ok(message.into_value())
+        }
+    }
+}
+
+fn func_error_payload(_num_rest: usize, obj: &mut Object) -> NResult<Any, err::Exception> {
+    let v = vm::refer_arg::<Exception>(0, obj);
+
+    match v.as_ref().err() {
+        err::Exception::UserError(inner) => {
+            Ok(inner.payload.clone())
+        }
+        err::Exception::Raise(inner) => {
+            //raiseで投げられた値をペイロードとして扱う
+            Ok(inner.value.clone())
+        }
+        _ => {
+            Ok(tuple::Tuple::unit().into_ref().into_value())
+        }
+    }
+}
+
+static FUNC_RAISE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("raise", func_raise,
+            Parameter::new(&[
+            Param::new("v", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CAUGHT_VALUE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("caught-value", func_caught_value,
+            Parameter::new(&[
+            Param::new("error", ParamKind::Require, Exception::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ERROR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("error", func_error,
+            Parameter::new(&[
+            Param::new("kind", ParamKind::Require, keyword::Keyword::typeinfo()),
+            Param::new("message", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("payload", ParamKind::Optional, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_ERROR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("error?", func_is_error,
+            Parameter::new(&[
+            Param::new("x", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ERROR_KIND: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("error-kind", func_error_kind,
+            Parameter::new(&[
+            Param::new("error", ParamKind::Require, Exception::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ERROR_MESSAGE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("error-message", func_error_message,
+            Parameter::new(&[
+            Param::new("error", ParamKind::Require, Exception::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ERROR_PAYLOAD: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("error-payload", func_error_payload,
+            Parameter::new(&[
+            Param::new("error", ParamKind::Require, Exception::typeinfo()),
+            ])
+        )
+    )
+});
+
+pub fn register_global(obj: &mut Object) {
+    obj.define_global_value("raise", &Ref::new(&FUNC_RAISE.value));
+    obj.define_global_value("error", &Ref::new(&FUNC_ERROR.value));
+    obj.define_global_value("error?", &Ref::new(&FUNC_IS_ERROR.value));
+    obj.define_global_value("error-kind", &Ref::new(&FUNC_ERROR_KIND.value));
+    obj.define_global_value("error-message", &Ref::new(&FUNC_ERROR_MESSAGE.value));
+    obj.define_global_value("error-payload", &Ref::new(&FUNC_ERROR_PAYLOAD.value));
+}
+
+pub mod literal {
+    use super::*;
+
+    pub fn raise() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_RAISE.value)
+    }
+
+    pub fn caught_value() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_CAUGHT_VALUE.value)
+    }
+
+    pub fn is_error() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_IS_ERROR.value)
+    }
+
+    pub fn error_kind() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_ERROR_KIND.value)
+    }
+
+    pub fn error_message() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_ERROR_MESSAGE.value)
+    }
+
+    pub fn error_payload() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_ERROR_PAYLOAD.value)
+    }
+}
@@ -0,0 +1,1497 @@
+use crate::value::{*, self};
+use crate::value::app::{Parameter, ParamKind, Param};
+use crate::value::list::ListBuilder;
+use crate::ptr::*;
+use crate::err::*;
+use crate::vm;
+use std::fmt::{self, Debug};
+use std::hash::Hasher;
+use std::collections::hash_map::DefaultHasher;
+
+//
+// 永続的なハッシュマップ。
+// 一度作成したマップは変更せず、要素の追加や削除は新しいマップを作成して返す。
+// 内部はキーのハッシュ値を5bitずつ使って分岐するトライ木(HAMT)になっている。
+// 追加や削除では根から対象のエントリまでの経路上にあるノードだけをコピーし、
+// それ以外のノードは元のマップと共有する。
+//
+#[repr(C)]
+pub struct HashMap {
+    len: usize,
+    root: Ref<HashMapNode>,
+}
+
+static HASHMAP_TYPEINFO : TypeInfo = new_typeinfo!(
+    HashMap,
+    "HashMap",
+    std::mem::size_of::<HashMap>(),
+    None,
+    HashMap::eq,
+    HashMap::clone_inner,
+    HashMap::fmt,
+    None,
+    None,
+    None,
+    Some(HashMap::child_traversal),
+    Some(HashMap::check_reply),
+    None,
+);
+
+impl NaviType for HashMap {
+    fn typeinfo() -> &'static TypeInfo {
+        &HASHMAP_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+        unsafe {
+            let root = HashMapNode::clone_inner(self.root.as_ref(), allocator)?.into_reachable();
+            Self::alloc(self.len, &root, allocator)
+        }
+    }
+}
+
+impl HashMap {
+
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(self.root.cast_mut_value(), arg);
+    }
+
+    fn check_reply(cap: &mut Cap<HashMap>, obj: &mut Object) -> Result<bool, OutOfMemory> {
+        let mut root = cap.as_ref().root.clone().capture(obj);
+        if !HashMapNode::check_reply_slots(&mut root, obj)? {
+            return Ok(false);
+        }
+
+        //内部にReply型を含まなくなったのでフラグを下す
+        value::clear_has_replytype_flag(cap.mut_refer());
+
+        Ok(true)
+    }
+
+    fn alloc<A: Allocator>(len: usize, root: &Reachable<HashMapNode>, allocator: &mut A) -> NResult<HashMap, OutOfMemory> {
+        let ptr = allocator.alloc::<HashMap>()?;
+
+        unsafe {
+            //確保したメモリ内に値を書き込む
+            std::ptr::write(ptr.as_ptr(), HashMap {
+                len,
+                root: root.raw_ptr().into(),
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    fn empty(obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let root = HashMapNode::alloc(0, 0, obj)?.reach(obj);
+        let map = Self::alloc(0, &root, obj)?;
+        obj.write_barrier(map.as_ref(), root.as_ref());
+
+        Ok(map)
+    }
+
+    //rootを持つ新しいマップを作成する。Replyを持つ可能性があればフラグを立てる
+    fn with_root(len: usize, root: &Reachable<HashMapNode>, has_replytype: bool, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let mut map = Self::alloc(len, root, obj)?;
+        obj.write_barrier(map.as_ref(), root.as_ref());
+
+        if has_replytype {
+            value::set_has_replytype_flag(&mut map);
+        }
+
+        Ok(map)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    //すべてのエントリをトライ木の並び順に処理する
+    fn for_each_entry<F: FnMut(&Slot)>(&self, mut func: F) {
+        self.root.as_ref().for_each_entry(&mut func);
+    }
+
+    pub fn keys(&self) -> Vec<Ref<Any>> {
+        let mut result = Vec::with_capacity(self.len);
+        self.for_each_entry(|slot| result.push(slot.key.clone()));
+        result
+    }
+
+    pub fn values(&self) -> Vec<Ref<Any>> {
+        let mut result = Vec::with_capacity(self.len);
+        self.for_each_entry(|slot| result.push(slot.value.clone()));
+        result
+    }
+
+    //キーを検索する。見つかればそのエントリを返す。
+    fn find(&self, key: &Any, hash: u64) -> Option<&Slot> {
+        let mut node = self.root.as_ref();
+        let mut shift = 0;
+        loop {
+            //ハッシュ値を使い切ったノードは、衝突したエントリを順番に比較する
+            if shift >= HASH_BITS {
+                return node.slots().iter().find(|slot| slot.hash == hash && slot.key.as_ref() == key);
+            }
+
+            let bit = bit_of(hash, shift);
+            if node.bitmap & bit == 0 {
+                return None;
+            }
+
+            let slot = &node.slots()[node.slot_index(bit)];
+            match slot.key.try_cast::<HashMapNode>() {
+                Some(child) => {
+                    node = child.as_ref();
+                    shift += BITS_PER_LEVEL;
+                }
+                None if slot.hash == hash && slot.key.as_ref() == key => return Some(slot),
+                None => return None,
+            }
+        }
+    }
+
+    pub fn get(&self, key: &Any) -> Option<Ref<Any>> {
+        self.find(key, value_hash(key))
+            .map(|slot| slot.value.clone())
+    }
+
+    pub fn contains_key(&self, key: &Any) -> bool {
+        self.find(key, value_hash(key)).is_some()
+    }
+
+    ///
+    /// (キー, 値)の組からマップを作成する。同じキーが複数あれば後の値が優先される。
+    /// get_pairはアロケーションの前後で呼び出されるため、呼び出しのたびに最新の値を返すこと。
+    fn build<F: FnMut(usize, &mut Object) -> (Ref<Any>, Ref<Any>)>(count: usize, mut get_pair: F, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let mut map = Self::empty(obj)?.reach(obj);
+        for index in 0..count {
+            let (key, value) = get_pair(index, obj);
+            let key = key.reach(obj);
+            let value = value.reach(obj);
+            map = Self::assoc(&map, &key, &value, obj)?.reach(obj);
+        }
+
+        Ok(map.make())
+    }
+
+    pub fn from_list(list: &Reachable<list::List>, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        //要素を順番に参照できるように配列に変換する
+        let ary = array::Array::from_list(list, None, obj)?.reach(obj);
+        let count = ary.as_ref().len() / 2;
+
+        Self::build(count, |index, _obj| {
+            (ary.as_ref().get(index * 2), ary.as_ref().get(index * 2 + 1))
+        }, obj)
+    }
+
+    ///
+    /// キーと値の組を追加した新しいマップを作成する。キーがすでに存在すれば値を置き換える。
+    pub fn assoc(map: &Reachable<HashMap>, key: &Reachable<Any>, value: &Reachable<Any>, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let hash = value_hash(key.as_ref());
+        let len = match map.as_ref().find(key.as_ref(), hash) {
+            Some(_) => map.as_ref().len(),
+            None => map.as_ref().len() + 1,
+        };
+
+        let root = map.as_ref().root.clone().reach(obj);
+        let root = HashMapNode::assoc(&root, 0, hash, key, value, obj)?.reach(obj);
+
+        //値を置き換えた場合でも、元のマップがReplyを持っていればフラグを引き継ぐ
+        let has_replytype = map.has_replytype() || value.has_replytype();
+        Self::with_root(len, &root, has_replytype, obj)
+    }
+
+    ///
+    /// キーを取り除いた新しいマップを作成する。キーが存在しなければ元のマップをそのまま返す。
+    pub fn dissoc(map: &Reachable<HashMap>, key: &Reachable<Any>, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let hash = value_hash(key.as_ref());
+        if map.as_ref().find(key.as_ref(), hash).is_none() {
+            return Ok(map.make());
+        }
+
+        let root = map.as_ref().root.clone().reach(obj);
+        let root = HashMapNode::dissoc(&root, 0, hash, key, obj)?.reach(obj);
+
+        Self::with_root(map.as_ref().len() - 1, &root, map.has_replytype(), obj)
+    }
+}
+
+//トライ木の1段で使うハッシュ値のbit数
+const BITS_PER_LEVEL: u32 = 5;
+//ハッシュ値全体のbit数。これ以上深いノードはハッシュ値が衝突したエントリを並べるだけになる
+const HASH_BITS: u32 = u64::BITS;
+
+//shiftの位置から始まる5bitに対応する、ノードのbitmap上のbit
+fn bit_of(hash: u64, shift: u32) -> u32 {
+    1 << ((hash >> shift) & ((1 << BITS_PER_LEVEL) - 1))
+}
+
+//
+// HashMapのトライ木のノード。
+// HashMapNode構造体の後ろに、(ハッシュ値, キー, 値)のスロットがlen個続く。
+// bitmapは各スロットがハッシュ値のどの5bitに対応するかを表し、スロットはbitの位置の順に並ぶ。
+// スロットのキーがHashMapNodeであれば、そのスロットは子ノードを表す(値にはfalseを入れておく)。
+// ハッシュ値を使い切った深さのノードはbitmapを使わず、衝突したエントリを並べるだけになる。
+//
+#[repr(C)]
+struct HashMapNode {
+    bitmap: u32,
+    len: usize,
+}
+
+#[repr(C)]
+struct Slot {
+    hash: u64,
+    key: Ref<Any>,
+    value: Ref<Any>,
+}
+
+static HASHMAPNODE_TYPEINFO : TypeInfo = new_typeinfo!(
+    HashMapNode,
+    "HashMapNode",
+    0,
+    Some(HashMapNode::size_of),
+    HashMapNode::eq,
+    HashMapNode::clone_inner,
+    HashMapNode::fmt,
+    None,
+    None,
+    None,
+    Some(HashMapNode::child_traversal),
+    None,
+    None,
+);
+
+impl NaviType for HashMapNode {
+    fn typeinfo() -> &'static TypeInfo {
+        &HASHMAPNODE_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        let mut node = Self::alloc(self.bitmap, self.len, allocator)?;
+
+        for (index, slot) in self.slots().iter().enumerate() {
+            //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+            let key = Any::clone_inner(slot.key.as_ref(), allocator)?;
+            let value = Any::clone_inner(slot.value.as_ref(), allocator)?;
+
+            node.set_slot_uncheck(index, slot.hash, &key, &value);
+        }
+
+        Ok(node)
+    }
+}
+
+//ノードをコピーするときに、1つのスロットに加える変更
+#[derive(Clone, Copy)]
+enum Edit<'a> {
+    Insert(usize, u64, &'a Reachable<Any>, &'a Reachable<Any>),
+    Replace(usize, u64, &'a Reachable<Any>, &'a Reachable<Any>),
+    Remove(usize),
+}
+
+impl HashMapNode {
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<HashMapNode>()
+            + self.len * std::mem::size_of::<Slot>()
+    }
+
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        for slot in self.slots_mut() {
+            callback(&mut slot.key, arg);
+            callback(&mut slot.value, arg);
+        }
+    }
+
+    fn check_reply_slots(cap: &mut Cap<HashMapNode>, obj: &mut Object) -> Result<bool, OutOfMemory> {
+        for index in 0..cap.as_ref().len {
+            let slot = &cap.as_ref().slots()[index];
+
+            if let Some(child) = slot.key.try_cast::<HashMapNode>() {
+                let mut child = child.clone().capture(obj);
+                if !Self::check_reply_slots(&mut child, obj)? {
+                    return Ok(false);
+                }
+
+            //キーは追加時に必ず評価済みになっているため、値だけを確認する
+            } else if slot.value.has_replytype() {
+                //返信がないか確認する
+                let mut child_v = slot.value.clone().capture(obj);
+                if value::check_reply(&mut child_v, obj)? {
+                    //返信があった場合は、内部ポインタを返信結果の値に上書きする
+                    cap.mut_refer().as_mut().slots_mut()[index].value.update_pointer(child_v.raw_ptr());
+                    obj.write_barrier(cap.as_ref(), child_v.as_ref());
+                } else {
+                    //子要素にReplyを含む値が残っている場合は、全体をfalseにする
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn alloc<A: Allocator>(bitmap: u32, len: usize, allocator: &mut A) -> NResult<HashMapNode, OutOfMemory> {
+        let ptr = allocator.alloc_with_additional_size::<HashMapNode>(len * std::mem::size_of::<Slot>())?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), HashMapNode { bitmap, len });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    fn slots(&self) -> &[Slot] {
+        let ptr = self as *const HashMapNode;
+        unsafe {
+            //ポインタをHashMapNode構造体の後ろに移す
+            let ptr = ptr.add(1) as *const Slot;
+            std::slice::from_raw_parts(ptr, self.len)
+        }
+    }
+
+    fn slots_mut(&mut self) -> &mut [Slot] {
+        let ptr = self as *mut HashMapNode;
+        unsafe {
+            let ptr = ptr.add(1) as *mut Slot;
+            std::slice::from_raw_parts_mut(ptr, self.len)
+        }
+    }
+
+    //bitに対応するスロットの位置
+    fn slot_index(&self, bit: u32) -> usize {
+        (self.bitmap & (bit - 1)).count_ones() as usize
+    }
+
+    fn for_each_entry<F: FnMut(&Slot)>(&self, func: &mut F) {
+        for slot in self.slots() {
+            match slot.key.try_cast::<HashMapNode>() {
+                Some(child) => child.as_ref().for_each_entry(func),
+                None => func(slot),
+            }
+        }
+    }
+
+    //srcのスロットにeditの変更を加えた新しいノードを作成する
+    fn copy_with(src: &Reachable<HashMapNode>, bitmap: u32, edit: Edit, obj: &mut Object) -> NResult<HashMapNode, OutOfMemory> {
+        let src_len = src.as_ref().len;
+        let len = match edit {
+            Edit::Insert(..) => src_len + 1,
+            Edit::Replace(..) => src_len,
+            Edit::Remove(_) => src_len - 1,
+        };
+        let mut node = Self::alloc(bitmap, len, obj)?;
+
+        let mut dst = 0;
+        for (index, slot) in src.as_ref().slots().iter().enumerate() {
+            match edit {
+                Edit::Insert(position, hash, key, value) if position == index => {
+                    node.set_slot_uncheck(dst, hash, &key.make(), &value.make());
+                    node.set_slot_uncheck(dst + 1, slot.hash, &slot.key, &slot.value);
+                    dst += 2;
+                }
+                Edit::Replace(position, hash, key, value) if position == index => {
+                    node.set_slot_uncheck(dst, hash, &key.make(), &value.make());
+                    dst += 1;
+                }
+                Edit::Remove(position) if position == index => { }
+                _ => {
+                    node.set_slot_uncheck(dst, slot.hash, &slot.key, &slot.value);
+                    dst += 1;
+                }
+            }
+        }
+
+        //末尾への挿入
+        if let Edit::Insert(position, hash, key, value) = edit {
+            if position == src_len {
+                node.set_slot_uncheck(dst, hash, &key.make(), &value.make());
+            }
+        }
+        node.after_set_slots(obj);
+
+        Ok(node)
+    }
+
+    //子ノードを指すスロットを、index位置に持つ新しいノードを作成する
+    fn copy_with_child(src: &Reachable<HashMapNode>, index: usize, child: &Reachable<HashMapNode>, obj: &mut Object) -> NResult<HashMapNode, OutOfMemory> {
+        let placeholder = bool::Bool::false_().into_ref().into_value().reach(obj);
+        Self::copy_with(src, src.as_ref().bitmap, Edit::Replace(index, 0, child.cast_value(), &placeholder), obj)
+    }
+
+    fn assoc(node: &Reachable<HashMapNode>, shift: u32, hash: u64, key: &Reachable<Any>, value: &Reachable<Any>, obj: &mut Object) -> NResult<HashMapNode, OutOfMemory> {
+        let bitmap = node.as_ref().bitmap;
+
+        if shift >= HASH_BITS {
+            let slots = node.as_ref().slots();
+            return match slots.iter().position(|slot| slot.hash == hash && slot.key.as_ref() == key.as_ref()) {
+                Some(index) => Self::copy_with(node, bitmap, Edit::Replace(index, hash, key, value), obj),
+                None => Self::copy_with(node, bitmap, Edit::Insert(slots.len(), hash, key, value), obj),
+            };
+        }
+
+        let bit = bit_of(hash, shift);
+        let index = node.as_ref().slot_index(bit);
+        if bitmap & bit == 0 {
+            return Self::copy_with(node, bitmap | bit, Edit::Insert(index, hash, key, value), obj);
+        }
+
+        let slot = &node.as_ref().slots()[index];
+        if let Some(child) = slot.key.try_cast::<HashMapNode>() {
+            let child = child.clone().reach(obj);
+            let child = Self::assoc(&child, shift + BITS_PER_LEVEL, hash, key, value, obj)?.reach(obj);
+            Self::copy_with_child(node, index, &child, obj)
+
+        } else if slot.hash == hash && slot.key.as_ref() == key.as_ref() {
+            Self::copy_with(node, bitmap, Edit::Replace(index, hash, key, value), obj)
+
+        } else {
+            //同じ位置にある既存のエントリと新しいエントリを、まとめて子ノードへ移す
+            let other_hash = slot.hash;
+            let other_key = slot.key.clone().reach(obj);
+            let other_value = slot.value.clone().reach(obj);
+            let child = Self::merge(shift + BITS_PER_LEVEL, (other_hash, &other_key, &other_value), (hash, key, value), obj)?.reach(obj);
+            Self::copy_with_child(node, index, &child, obj)
+        }
+    }
+
+    //2つのエントリだけを持つノードを作成する
+    fn merge(shift: u32, e1: (u64, &Reachable<Any>, &Reachable<Any>), e2: (u64, &Reachable<Any>, &Reachable<Any>), obj: &mut Object) -> NResult<HashMapNode, OutOfMemory> {
+        let (bit1, bit2) = if shift >= HASH_BITS {
+            (0, 0)
+        } else {
+            (bit_of(e1.0, shift), bit_of(e2.0, shift))
+        };
+
+        if shift < HASH_BITS && bit1 == bit2 {
+            //まだ区別できないので、さらに深い子ノードを作る
+            let child = Self::merge(shift + BITS_PER_LEVEL, e1, e2, obj)?.into_value().reach(obj);
+            let placeholder = bool::Bool::false_().into_ref().into_value().reach(obj);
+
+            let mut node = Self::alloc(bit1, 1, obj)?;
+            node.set_slot_uncheck(0, 0, &child.make(), &placeholder.make());
+            node.after_set_slots(obj);
+            return Ok(node);
+        }
+
+        let (first, second) = if bit1 <= bit2 { (e1, e2) } else { (e2, e1) };
+        let mut node = Self::alloc(bit1 | bit2, 2, obj)?;
+        node.set_slot_uncheck(0, first.0, &first.1.make(), &first.2.make());
+        node.set_slot_uncheck(1, second.0, &second.1.make(), &second.2.make());
+        node.after_set_slots(obj);
+
+        Ok(node)
+    }
+
+    //keyが存在していることを前提に、取り除いた新しいノードを作成する
+    fn dissoc(node: &Reachable<HashMapNode>, shift: u32, hash: u64, key: &Reachable<Any>, obj: &mut Object) -> NResult<HashMapNode, OutOfMemory> {
+        let bitmap = node.as_ref().bitmap;
+
+        if shift >= HASH_BITS {
+            let index = node.as_ref().slots().iter()
+                .position(|slot| slot.hash == hash && slot.key.as_ref() == key.as_ref())
+                .unwrap();
+            return Self::copy_with(node, bitmap, Edit::Remove(index), obj);
+        }
+
+        let bit = bit_of(hash, shift);
+        let index = node.as_ref().slot_index(bit);
+
+        let child = match node.as_ref().slots()[index].key.try_cast::<HashMapNode>() {
+            Some(child) => child.clone().reach(obj),
+            None => return Self::copy_with(node, bitmap & !bit, Edit::Remove(index), obj),
+        };
+        let child = Self::dissoc(&child, shift + BITS_PER_LEVEL, hash, key, obj)?.reach(obj);
+
+        //子ノードにエントリが1つしか残らなければ、子ノードをやめてこのノードに直接持たせる。
+        //こうしておくと、同じキーの集合を持つマップは常に同じ形のトライ木になる。
+        let rest = &child.as_ref().slots()[0];
+        if child.as_ref().len == 1 && !rest.key.is::<HashMapNode>() {
+            let rest_hash = rest.hash;
+            let rest_key = rest.key.clone().reach(obj);
+            let rest_value = rest.value.clone().reach(obj);
+            Self::copy_with(node, bitmap, Edit::Replace(index, rest_hash, &rest_key, &rest_value), obj)
+        } else {
+            Self::copy_with_child(node, index, &child, obj)
+        }
+    }
+}
+
+impl Ref<HashMapNode> {
+
+    fn set_slot_uncheck(&mut self, index: usize, hash: u64, key: &Ref<Any>, value: &Ref<Any>) {
+        let ptr = self.as_mut() as *mut HashMapNode;
+        unsafe {
+            //ポインタをHashMapNode構造体の後ろに移す
+            let slot_ptr = ptr.add(1) as *mut Slot;
+            std::ptr::write(slot_ptr.add(index), Slot {
+                hash,
+                key: key.clone(),
+                value: value.clone(),
+            });
+        }
+    }
+
+    //全スロットの書き込みが終わった後に、ライトバリアを実行する
+    fn after_set_slots(&mut self, obj: &mut Object) {
+        for slot in self.as_ref().slots() {
+            obj.write_barrier(self.as_ref(), slot.key.as_ref());
+            obj.write_barrier(self.as_ref(), slot.value.as_ref());
+        }
+    }
+}
+
+impl Eq for HashMapNode { }
+
+impl PartialEq for HashMapNode {
+    fn eq(&self, other: &Self) -> bool {
+        //ノード単体ではなくHashMap全体で比較するため、同一性で比較する
+        std::ptr::eq(self, other)
+    }
+}
+
+fn display_node(this: &HashMapNode, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#hash-map-node:{}", this.len)
+}
+
+impl std::fmt::Display for HashMapNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_node(self, f)
+    }
+}
+
+impl Debug for HashMapNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_node(self, f)
+    }
+}
+
+///
+/// 値のハッシュ値を計算する。等しい値は必ず同じハッシュ値になる。
+/// 中身を考慮しない型は型名だけからハッシュ値を計算するため、比較はeqに任せることになる。
+pub fn value_hash(v: &Any) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_value_into(v, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_value_into(v: &Any, hasher: &mut DefaultHasher) {
+    if v.is_type(number::Number::typeinfo()) {
+        //1と1.0のように型が異なっても等しい数値があるため、すべてf64に変換してからハッシュ値を計算する
+        let num = number::number_to_f64(v);
+        let num = if num == 0.0 { 0.0 } else { num };
+        hasher.write_u8(0);
+        hasher.write_u64(num.to_bits());
+        return;
+    }
+
+    let typeinfo = get_typeinfo(v);
+    hasher.write(typeinfo.name.as_bytes());
+
+    if let Some(str) = v.try_cast::<string::NString>() {
+        hasher.write(str.as_string().as_bytes());
+    } else if let Some(symbol) = v.try_cast::<symbol::Symbol>() {
+        let name: &str = symbol.as_ref();
+        hasher.write(name.as_bytes());
+    } else if let Some(keyword) = v.try_cast::<keyword::Keyword>() {
+        let name: &str = keyword.as_ref();
+        hasher.write(name.as_bytes());
+    } else if let Some(ch) = v.try_cast::<char::Char>() {
+        hasher.write_u32(ch.get() as u32);
+    } else if let Some(b) = v.try_cast::<bool::Bool>() {
+        hasher.write_u8(b.is_true() as u8);
+    } else if let Some(list) = v.try_cast::<list::List>() {
+        for v in unsafe { list.iter_gcunsafe() } {
+            hash_value_into(v.as_ref(), hasher);
+        }
+    } else if let Some(ary) = v.try_cast::<array::Array<Any>>() {
+        for index in 0..ary.len() {
+            hash_value_into(ary.get(index).as_ref(), hasher);
+        }
+    } else if let Some(tuple) = v.try_cast::<tuple::Tuple>() {
+        for index in 0..tuple.len() {
+            hash_value_into(tuple.get(index).as_ref(), hasher);
+        }
+    } else if let Some(map) = v.try_cast::<HashMap>() {
+        //エントリの並び順に依存しないように、キーのハッシュ値の和を使う
+        let mut sum = 0u64;
+        map.for_each_entry(|slot| sum = sum.wrapping_add(slot.hash));
+        hasher.write_usize(map.len());
+        hasher.write_u64(sum);
+    }
+}
+
+impl Eq for HashMap { }
+
+impl PartialEq for HashMap {
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() == other.len() {
+            let mut result = true;
+            self.for_each_entry(|slot| {
+                result = result && match other.find(slot.key.as_ref(), slot.hash) {
+                    Some(other_slot) => slot.value.as_ref() == other_slot.value.as_ref(),
+                    None => false,
+                };
+            });
+
+            result
+        } else {
+            false
+        }
+    }
+}
+
+fn display(this: &HashMap, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#{{")?;
+    for (index, (key, value)) in this.keys().iter().zip(this.values().iter()).enumerate() {
+        if index != 0 {
+            write!(f, " ")?
+        }
+
+        key.as_ref().fmt(f)?;
+        write!(f, " ")?;
+        value.as_ref().fmt(f)?;
+    }
+    write!(f, "}}")
+}
+
+impl std::fmt::Display for HashMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display(self, f)
+    }
+}
+
+impl Debug for HashMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display(self, f)
+    }
+}
+
+//
+// 変更可能なハッシュテーブル。
+// バケットの配列を持ち、各バケットはfalse(空)か永続的なHashMapになる。
+// 要素の追加や削除はバケットのマップを新しいマップに差し替えることで行う。
+//
+pub struct HashTable {
+    count: usize,
+    buckets: Ref<array::Array<Any>>,
+}
+
+static HASHTABLE_TYPEINFO : TypeInfo = new_typeinfo!(
+    HashTable,
+    "HashTable",
+    std::mem::size_of::<HashTable>(),
+    None,
+    HashTable::eq,
+    HashTable::clone_inner,
+    std::fmt::Display::fmt,
+    None,
+    None,
+    None,
+    Some(HashTable::child_traversal),
+    None,
+    None,
+);
+
+const INITIAL_BUCKETS: usize = 8;
+
+impl NaviType for HashTable {
+    fn typeinfo() -> &'static TypeInfo {
+        &HASHTABLE_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+        unsafe {
+            let buckets = array::Array::<Any>::clone_inner(self.buckets.as_ref(), allocator)?.into_reachable();
+            Self::alloc(self.count, &buckets, allocator)
+        }
+    }
+}
+
+impl HashTable {
+
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(self.buckets.cast_mut_value(), arg);
+    }
+
+    fn alloc<A: Allocator>(count: usize, buckets: &Reachable<array::Array<Any>>, allocator: &mut A) -> NResult<HashTable, OutOfMemory> {
+        let ptr = allocator.alloc::<HashTable>()?;
+
+        unsafe {
+            //確保したメモリ内に値を書き込む
+            std::ptr::write(ptr.as_ptr(), HashTable {
+                count,
+                buckets: buckets.raw_ptr().into(),
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    //すべてのバケットが空の配列を作成する
+    fn alloc_buckets(len: usize, obj: &mut Object) -> NResult<array::Array<Any>, OutOfMemory> {
+        let empty = bool::Bool::false_().into_ref().into_value().reach(obj);
+        let mut builder = array::ArrayBuilder::<Any>::new(len, obj)?;
+        for _ in 0..len {
+            unsafe { builder.push_uncheck(&empty, obj) };
+        }
+
+        Ok(builder.get())
+    }
+
+    pub fn new(obj: &mut Object) -> NResult<HashTable, OutOfMemory> {
+        let buckets = Self::alloc_buckets(INITIAL_BUCKETS, obj)?.reach(obj);
+        let table = Self::alloc(0, &buckets, obj)?;
+        obj.write_barrier(table.as_ref(), buckets.as_ref());
+
+        Ok(table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn bucket_index(&self, hash: u64) -> usize {
+        (hash % self.buckets.as_ref().len() as u64) as usize
+    }
+
+    fn bucket(&self, hash: u64) -> Option<Ref<HashMap>> {
+        let bucket = self.buckets.as_ref().get(self.bucket_index(hash));
+        bucket.try_cast::<HashMap>().cloned()
+    }
+
+    pub fn get(&self, key: &Any) -> Option<Ref<Any>> {
+        self.bucket(value_hash(key))
+            .and_then(|map| map.as_ref().get(key))
+    }
+
+    pub fn contains_key(&self, key: &Any) -> bool {
+        self.get(key).is_some()
+    }
+
+    //バケットのマップを順番に処理する
+    fn for_each_bucket<F: FnMut(&HashMap)>(&self, mut func: F) {
+        let buckets = self.buckets.as_ref();
+        for index in 0..buckets.len() {
+            if let Some(map) = buckets.get(index).try_cast::<HashMap>() {
+                func(map.as_ref());
+            }
+        }
+    }
+
+    pub fn keys(&self) -> Vec<Ref<Any>> {
+        let mut result = Vec::with_capacity(self.count);
+        self.for_each_bucket(|map| result.extend(map.keys()));
+        result
+    }
+
+    pub fn values(&self) -> Vec<Ref<Any>> {
+        let mut result = Vec::with_capacity(self.count);
+        self.for_each_bucket(|map| result.extend(map.values()));
+        result
+    }
+
+    ///
+    /// キーと値の組を追加する。キーがすでに存在すれば値を置き換える。
+    pub fn insert(table: &mut Reachable<HashTable>, key: &Reachable<Any>, value: &Reachable<Any>, obj: &mut Object) -> Result<(), OutOfMemory> {
+        let hash = value_hash(key.as_ref());
+        let bucket_index = table.as_ref().bucket_index(hash);
+
+        let (map, added) = match table.as_ref().bucket(hash) {
+            Some(map) => {
+                let map = map.reach(obj);
+                let before = map.as_ref().len();
+                let map = HashMap::assoc(&map, key, value, obj)?;
+                let added = before != map.as_ref().len();
+                (map, added)
+            }
+            None => {
+                let map = HashMap::build(1, |_, _| (key.make(), value.make()), obj)?;
+                (map, true)
+            }
+        };
+        Self::set_bucket(table, bucket_index, &map, obj);
+
+        if added {
+            table.as_mut().count += 1;
+
+            //バケット当たりの要素数が増えすぎたらバケットを増やす
+            if table.as_ref().count > table.as_ref().buckets.as_ref().len() * 2 {
+                Self::resize(table, obj)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// キーを取り除く。キーが存在していればtrueを返す。
+    pub fn remove(table: &mut Reachable<HashTable>, key: &Reachable<Any>, obj: &mut Object) -> Result<bool, OutOfMemory> {
+        let hash = value_hash(key.as_ref());
+        let bucket_index = table.as_ref().bucket_index(hash);
+
+        let map = match table.as_ref().bucket(hash) {
+            Some(map) if map.as_ref().contains_key(key.as_ref()) => map.reach(obj),
+            _ => return Ok(false),
+        };
+
+        let map = HashMap::dissoc(&map, key, obj)?;
+        if map.as_ref().is_empty() {
+            let empty = bool::Bool::false_().into_ref().into_value();
+            table.as_mut().buckets.set(&empty, bucket_index).unwrap();
+        } else {
+            Self::set_bucket(table, bucket_index, &map, obj);
+        }
+        table.as_mut().count -= 1;
+
+        Ok(true)
+    }
+
+    fn set_bucket(table: &mut Reachable<HashTable>, bucket_index: usize, map: &Ref<HashMap>, obj: &mut Object) {
+        let map = map.cast_value();
+        let buckets = &mut table.as_mut().buckets;
+        buckets.set(map, bucket_index).unwrap();
+        //古い世代にあるバケットの配列から新しいマップへの参照をGCに伝える
+        obj.write_barrier(buckets.as_ref(), map.as_ref());
+    }
+
+    //バケットの数を2倍にして、すべての要素を入れなおす
+    fn resize(table: &mut Reachable<HashTable>, obj: &mut Object) -> Result<(), OutOfMemory> {
+        let len = table.as_ref().buckets.as_ref().len() * 2;
+        let old_buckets = table.as_ref().buckets.clone().reach(obj);
+        let buckets = Self::alloc_buckets(len, obj)?;
+
+        table.as_mut().buckets = buckets;
+        table.as_mut().count = 0;
+        obj.write_barrier(table.as_ref(), table.as_ref().buckets.as_ref());
+
+        for bucket_index in 0..old_buckets.as_ref().len() {
+            let map = match old_buckets.as_ref().get(bucket_index).try_cast::<HashMap>() {
+                Some(map) => map.clone().reach(obj),
+                None => continue,
+            };
+
+            let keys: Vec<Reachable<Any>> = map.as_ref().keys().into_iter()
+                .map(|key| key.reach(obj))
+                .collect();
+            let values: Vec<Reachable<Any>> = map.as_ref().values().into_iter()
+                .map(|value| value.reach(obj))
+                .collect();
+
+            for (key, value) in keys.iter().zip(values.iter()) {
+                Self::insert(table, key, value, obj)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// 現在の内容を永続的なHashMapとして取り出す。
+    pub fn to_hash_map(table: &Reachable<HashTable>, obj: &mut Object) -> NResult<HashMap, OutOfMemory> {
+        let keys: Vec<Reachable<Any>> = table.as_ref().keys().into_iter()
+            .map(|key| key.reach(obj))
+            .collect();
+
+        HashMap::build(keys.len(), |index, _obj| {
+            let key = keys[index].make();
+            let value = table.as_ref().get(key.as_ref()).unwrap();
+            (key, value)
+        }, obj)
+    }
+}
+
+impl Eq for HashTable { }
+
+impl PartialEq for HashTable {
+    fn eq(&self, other: &Self) -> bool {
+        //中身が変更されるため、同一性で比較する
+        std::ptr::eq(self, other)
+    }
+}
+
+fn display_table(this: &HashTable, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#hash-table:{}", this.count)
+}
+
+impl std::fmt::Display for HashTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        display_table(self, f)
+    }
+}
+
+impl Debug for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_table(self, f)
+    }
+}
+
+fn func_hash_map(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    if !num_rest.is_multiple_of(2) {
+        return Err(Exception::Other("hash-map: keys and values must be given in pairs".to_string()));
+    }
+
+    let map = HashMap::build(num_rest / 2, |index, obj| {
+        (vm::refer_rest_arg::<Any>(0, index * 2, obj), vm::refer_rest_arg::<Any>(0, index * 2 + 1, obj))
+    }, obj)?;
+
+    Ok(map.into_value())
+}
+
+fn func_is_hash_map(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if v.is_type(HashMap::typeinfo()) {
+        Ok(v.clone())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_hash_map_count(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj);
+
+    let num = number::make_integer(map.as_ref().len() as i64, obj)?;
+    Ok(num)
+}
+
+fn func_hash_map_ref(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj);
+    let key = vm::refer_arg::<Any>(1, obj);
+
+    match map.as_ref().get(key.as_ref()) {
+        Some(v) => Ok(v),
+        None => {
+            //デフォルト値が省略されていればエラーにする
+            let default = vm::refer_arg::<Any>(2, obj);
+            match default.try_cast::<tuple::Tuple>() {
+                Some(tuple) if tuple.as_ref().is_unit() => {
+                    Err(Exception::Other(format!("hash-map-ref: key {} is not found", key.as_ref())))
+                }
+                _ => Ok(default),
+            }
+        }
+    }
+}
+
+fn func_hash_map_contains(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj);
+    let key = vm::refer_arg::<Any>(1, obj);
+
+    if map.as_ref().contains_key(key.as_ref()) {
+        Ok(bool::Bool::true_().into_ref().into_value())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_hash_map_set(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj).reach(obj);
+    let key = vm::refer_arg::<Any>(1, obj).reach(obj);
+    let value = vm::refer_arg::<Any>(2, obj).reach(obj);
+
+    let map = HashMap::assoc(&map, &key, &value, obj)?;
+    Ok(map.into_value())
+}
+
+fn func_hash_map_remove(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj).reach(obj);
+    let key = vm::refer_arg::<Any>(1, obj).reach(obj);
+
+    let map = HashMap::dissoc(&map, &key, obj)?;
+    Ok(map.into_value())
+}
+
+fn func_hash_map_keys(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj);
+    let keys: Vec<Reachable<Any>> = map.as_ref().keys().into_iter()
+        .map(|key| key.reach(obj))
+        .collect();
+
+    let mut builder = ListBuilder::new(obj);
+    for key in keys.iter() {
+        builder.push(key, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_hash_map_values(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let map = vm::refer_arg::<HashMap>(0, obj);
+    let values: Vec<Reachable<Any>> = map.as_ref().values().into_iter()
+        .map(|value| value.reach(obj))
+        .collect();
+
+    let mut builder = ListBuilder::new(obj);
+    for value in values.iter() {
+        builder.push(value, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_make_hash_table(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = HashTable::new(obj)?;
+    Ok(table.into_value())
+}
+
+fn func_is_hash_table(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if v.is_type(HashTable::typeinfo()) {
+        Ok(v.clone())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_hash_table_count(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj);
+
+    let num = number::make_integer(table.as_ref().len() as i64, obj)?;
+    Ok(num)
+}
+
+fn func_hash_table_ref(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj);
+    let key = vm::refer_arg::<Any>(1, obj);
+
+    match table.as_ref().get(key.as_ref()) {
+        Some(v) => Ok(v),
+        None => {
+            //デフォルト値が省略されていればエラーにする
+            let default = vm::refer_arg::<Any>(2, obj);
+            match default.try_cast::<tuple::Tuple>() {
+                Some(tuple) if tuple.as_ref().is_unit() => {
+                    Err(Exception::Other(format!("hash-table-ref: key {} is not found", key.as_ref())))
+                }
+                _ => Ok(default),
+            }
+        }
+    }
+}
+
+fn func_hash_table_contains(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj);
+    let key = vm::refer_arg::<Any>(1, obj);
+
+    if table.as_ref().contains_key(key.as_ref()) {
+        Ok(bool::Bool::true_().into_ref().into_value())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_hash_table_set(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut table = vm::refer_arg::<HashTable>(0, obj).reach(obj);
+    let key = vm::refer_arg::<Any>(1, obj).reach(obj);
+    let value = vm::refer_arg::<Any>(2, obj).reach(obj);
+
+    HashTable::insert(&mut table, &key, &value, obj)?;
+    Ok(tuple::Tuple::unit().into_ref().into_value())
+}
+
+fn func_hash_table_delete(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut table = vm::refer_arg::<HashTable>(0, obj).reach(obj);
+    let key = vm::refer_arg::<Any>(1, obj).reach(obj);
+
+    if HashTable::remove(&mut table, &key, obj)? {
+        Ok(bool::Bool::true_().into_ref().into_value())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_hash_table_keys(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj);
+    let keys: Vec<Reachable<Any>> = table.as_ref().keys().into_iter()
+        .map(|key| key.reach(obj))
+        .collect();
+
+    let mut builder = ListBuilder::new(obj);
+    for key in keys.iter() {
+        builder.push(key, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_hash_table_values(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj);
+    let values: Vec<Reachable<Any>> = table.as_ref().values().into_iter()
+        .map(|value| value.reach(obj))
+        .collect();
+
+    let mut builder = ListBuilder::new(obj);
+    for value in values.iter() {
+        builder.push(value, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_hash_table_to_hash_map(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let table = vm::refer_arg::<HashTable>(0, obj).reach(obj);
+
+    let map = HashTable::to_hash_map(&table, obj)?;
+    Ok(map.into_value())
+}
+
+static FUNC_HASH_MAP: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map", func_hash_map,
+            Parameter::new(&[
+            Param::new("kvs", ParamKind::Rest, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_HASH_MAP: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map?", func_is_hash_map,
+            Parameter::new(&[
+            Param::new_no_force("x", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_COUNT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-count", func_hash_map_count,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_REF: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-ref", func_hash_map_ref,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new_no_force("default", ParamKind::Optional, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_CONTAINS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-contains?", func_hash_map_contains,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_SET: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-set", func_hash_map_set,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new_no_force("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_REMOVE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-remove", func_hash_map_remove,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_KEYS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-keys", func_hash_map_keys,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_MAP_VALUES: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-map-values", func_hash_map_values,
+            Parameter::new(&[
+            Param::new_no_force("map", ParamKind::Require, HashMap::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_MAKE_HASH_TABLE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("make-hash-table", func_make_hash_table,
+            Parameter::new(&[
+            ])
+        )
+    )
+});
+
+static FUNC_IS_HASH_TABLE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table?", func_is_hash_table,
+            Parameter::new(&[
+            Param::new_no_force("x", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_COUNT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-count", func_hash_table_count,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_REF: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-ref", func_hash_table_ref,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new_no_force("default", ParamKind::Optional, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_CONTAINS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-contains?", func_hash_table_contains,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+//値はforceしてから格納し、テーブルが後からReplyを含むことがないようにする
+static FUNC_HASH_TABLE_SET: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-set!", func_hash_table_set,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new("value", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_DELETE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-delete!", func_hash_table_delete,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_KEYS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-keys", func_hash_table_keys,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_VALUES: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table-values", func_hash_table_values,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_HASH_TABLE_TO_HASH_MAP: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("hash-table->hash-map", func_hash_table_to_hash_map,
+            Parameter::new(&[
+            Param::new("table", ParamKind::Require, HashTable::typeinfo()),
+            ])
+        )
+    )
+});
+
+pub fn register_global(obj: &mut Object) {
+    obj.define_global_value("hash-map", &Ref::new(&FUNC_HASH_MAP.value));
+    obj.define_global_value("hash-map?", &Ref::new(&FUNC_IS_HASH_MAP.value));
+    obj.define_global_value("hash-map-count", &Ref::new(&FUNC_HASH_MAP_COUNT.value));
+    obj.define_global_value("hash-map-ref", &Ref::new(&FUNC_HASH_MAP_REF.value));
+    obj.define_global_value("hash-map-contains?", &Ref::new(&FUNC_HASH_MAP_CONTAINS.value));
+    obj.define_global_value("hash-map-set", &Ref::new(&FUNC_HASH_MAP_SET.value));
+    obj.define_global_value("hash-map-remove", &Ref::new(&FUNC_HASH_MAP_REMOVE.value));
+    obj.define_global_value("hash-map-keys", &Ref::new(&FUNC_HASH_MAP_KEYS.value));
+    obj.define_global_value("hash-map-values", &Ref::new(&FUNC_HASH_MAP_VALUES.value));
+    obj.define_global_value("make-hash-table", &Ref::new(&FUNC_MAKE_HASH_TABLE.value));
+    obj.define_global_value("hash-table?", &Ref::new(&FUNC_IS_HASH_TABLE.value));
+    obj.define_global_value("hash-table-count", &Ref::new(&FUNC_HASH_TABLE_COUNT.value));
+    obj.define_global_value("hash-table-ref", &Ref::new(&FUNC_HASH_TABLE_REF.value));
+    obj.define_global_value("hash-table-contains?", &Ref::new(&FUNC_HASH_TABLE_CONTAINS.value));
+    obj.define_global_value("hash-table-set!", &Ref::new(&FUNC_HASH_TABLE_SET.value));
+    obj.define_global_value("hash-table-delete!", &Ref::new(&FUNC_HASH_TABLE_DELETE.value));
+    obj.define_global_value("hash-table-keys", &Ref::new(&FUNC_HASH_TABLE_KEYS.value));
+    obj.define_global_value("hash-table-values", &Ref::new(&FUNC_HASH_TABLE_VALUES.value));
+    obj.define_global_value("hash-table->hash-map", &Ref::new(&FUNC_HASH_TABLE_TO_HASH_MAP.value));
+}
+
+pub mod literal {
+    use crate::ptr::*;
+    use crate::value::func::Func;
+    use super::*;
+
+    pub fn hash_map() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_HASH_MAP.value)
+    }
+
+    pub fn is_hash_map() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_IS_HASH_MAP.value)
+    }
+
+    pub fn hash_map_contains() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_HASH_MAP_CONTAINS.value)
+    }
+
+    pub fn hash_map_ref() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_HASH_MAP_REF.value)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::exec;
+    use crate::eval::tests::check;
+    use crate::value::*;
+    use crate::object::Object;
+
+    #[test]
+    fn test_hash_map() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(hash-map-count (hash-map :a 1 :b 2 :a 3))", "2", obj, ans_obj);
+        check("(hash-map-ref (hash-map :a 1 :b 2 :a 3) :a)", "3", obj, ans_obj);
+        check("(hash-map-ref #{:a 1} :b 0)", "0", obj, ans_obj);
+        check("(try (hash-map-ref #{:a 1} :b) (catch @e (error-kind e)))", ":other", obj, ans_obj);
+        check("(try (hash-map :a) (catch @e (error-kind e)))", ":other", obj, ans_obj);
+
+        //数値は型が違っても等しければ同じキーになる
+        check("(hash-map-ref #{1 :one} 1.0)", ":one", obj, ans_obj);
+        check("(hash-map-ref #{\"s\" 1 's 2} 's)", "2", obj, ans_obj);
+        check("(hash-map-ref #{[1 '(2 3)] :found} [1 '(2 3)])", ":found", obj, ans_obj);
+        check("(hash-map-ref #{#{:a 1 :b 2} :found} #{:b 2 :a 1})", ":found", obj, ans_obj);
+
+        //追加や削除は元のマップを変更しない
+        check("(local (let m #{:a 1}) (let m2 (hash-map-set m :b 2)) (list (hash-map-count m) (hash-map-count m2)))", "'(1 2)", obj, ans_obj);
+        check("(hash-map-ref (hash-map-set #{:a 1} :a 10) :a)", "10", obj, ans_obj);
+        check("(local (let m #{:a 1 :b 2}) (let m2 (hash-map-remove m :a)) (list (hash-map-contains? m :a) (hash-map-contains? m2 :a)))", "'(true false)", obj, ans_obj);
+        check("(hash-map-count (hash-map-remove #{:a 1} :z))", "1", obj, ans_obj);
+
+        check("(= #{:a 1 :b 2} (hash-map :b 2 :a 1))", "true", obj, ans_obj);
+        check("(= #{:a 1 :b 2} #{:a 1 :b 3})", "false", obj, ans_obj);
+        check("(= #{:a 1} #{:a 1 :b 2})", "false", obj, ans_obj);
+        check("(hash-map? #{})", "#{}", obj, ans_obj);
+        check("(hash-map? {})", "false", obj, ans_obj);
+
+        check("(local (let m #{:a 1}) (apply + (hash-map-values (hash-map-set m :b 2))))", "3", obj, ans_obj);
+        check("(list-len (hash-map-keys #{:a 1 :b 2 :c 3}))", "3", obj, ans_obj);
+
+        //キーと値は評価される
+        check("(local (let x 5) (hash-map-ref #{x (+ x 1)} 5))", "6", obj, ans_obj);
+        check("(hash-map-ref '#{a (+ 1 2)} 'a)", "'(+ 1 2)", obj, ans_obj);
+
+        //多数の要素を持つマップでも追加と削除が正しく行われる
+        exec::<Any>("(let fill-map (fun (m i n) (if (< i n) (fill-map (hash-map-set m i (* i 2)) (+ i 1) n) m)))", obj);
+        exec::<Any>("(let drop-map (fun (m i n) (if (< i n) (drop-map (hash-map-remove m i) (+ i 2) n) m)))", obj);
+        exec::<Any>("(let big (fill-map #{} 0 2000))", obj);
+        check("(hash-map-count big)", "2000", obj, ans_obj);
+        check("(hash-map-ref big 1999)", "3998", obj, ans_obj);
+        check("(list-len (hash-map-keys big))", "2000", obj, ans_obj);
+        check("(= big (fill-map #{} 0 2000))", "true", obj, ans_obj);
+        exec::<Any>("(let half (drop-map big 0 2000))", obj);
+        check("(hash-map-count half)", "1000", obj, ans_obj);
+        check("(list (hash-map-contains? half 10) (hash-map-contains? half 11) (hash-map-contains? big 10))", "'(false true true)", obj, ans_obj);
+        check("(= (drop-map half 1 2000) #{})", "true", obj, ans_obj);
+    }
+
+    #[test]
+    fn test_hash_table() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        exec::<Any>("(let t (make-hash-table))", obj);
+        check("(hash-table-count t)", "0", obj, ans_obj);
+        check("(hash-table-set! t :a 1)", "{}", obj, ans_obj);
+        check("(hash-table-ref t :a)", "1", obj, ans_obj);
+        check("(hash-table-ref t :b 0)", "0", obj, ans_obj);
+        check("(try (hash-table-ref t :b) (catch @e (error-kind e)))", ":other", obj, ans_obj);
+
+        //既存のキーへの書き込みは値を置き換える
+        exec::<Any>("(hash-table-set! t :a 10)", obj);
+        check("(hash-table-ref t :a)", "10", obj, ans_obj);
+        check("(hash-table-count t)", "1", obj, ans_obj);
+        check("(hash-table-ref t 1.0 :none)", ":none", obj, ans_obj);
+        exec::<Any>("(hash-table-set! t 1 :one)", obj);
+        check("(hash-table-ref t 1.0)", ":one", obj, ans_obj);
+
+        check("(hash-table-delete! t :a)", "true", obj, ans_obj);
+        check("(hash-table-delete! t :a)", "false", obj, ans_obj);
+        check("(hash-table-contains? t :a)", "false", obj, ans_obj);
+        check("(hash-table-contains? t 1)", "true", obj, ans_obj);
+        check("(hash-table-count t)", "1", obj, ans_obj);
+        check("(hash-table->hash-map t)", "#{1 :one}", obj, ans_obj);
+
+        //バケットの拡張を跨いでも要素が失われない
+        exec::<Any>("(let fill (fun (i) (if (< i 40) (local (hash-table-set! t i (* i i)) (fill (+ i 1))) 0)))", obj);
+        exec::<Any>("(fill 0)", obj);
+        check("(hash-table-count t)", "40", obj, ans_obj);
+        check("(hash-table-ref t 39)", "1521", obj, ans_obj);
+        check("(apply + (hash-table-keys t))", "780", obj, ans_obj);
+        check("(list-len (hash-table-values t))", "40", obj, ans_obj);
+
+        //テーブルは同一性で比較する
+        check("(= (make-hash-table) (make-hash-table))", "false", obj, ans_obj);
+        check("(= t t)", "true", obj, ans_obj);
+        check("(= (hash-table? t) t)", "true", obj, ans_obj);
+        check("(hash-table? #{})", "false", obj, ans_obj);
+    }
+}
@@ -4,6 +4,7 @@ use std::fmt::{Display, Debug};
 use crate::ptr::*;
 use crate::err::*;
 use crate::object::{AnyAllocator, Allocator};
+use crate::read::SourcePos;
 use crate::value::symbol;
 
 use super::array::Array;
@@ -166,9 +167,10 @@ pub enum IFormKind {
     AndOr,
     DefRecv,
     ObjectSwitch,
+    Try,
 }
 
-const IFORM_KIND_ARY: [IFormKind; 12] = [
+const IFORM_KIND_ARY: [IFormKind; 13] = [
     IFormKind::Let,
     IFormKind::If,
     IFormKind::Local,
@@ -181,9 +183,10 @@ const IFORM_KIND_ARY: [IFormKind; 12] = [
     IFormKind::AndOr,
     IFormKind::DefRecv,
     IFormKind::ObjectSwitch,
+    IFormKind::Try,
 ];
 
-static IFORM_TYPEINFO_ARY: [TypeInfo; 12] = [
+static IFORM_TYPEINFO_ARY: [TypeInfo; 13] = [
     new_typeinfo!(
         IFormLet,
         "IFormLet",
@@ -364,6 +367,21 @@ static IFORM_TYPEINFO_ARY: [TypeInfo; 12] = [
         None,
         None,
     ),
+    new_typeinfo!(
+        IFormTry,
+        "IFormTry",
+        std::mem::size_of::<IFormTry>(),
+        None,
+        IFormTry::eq,
+        IFormTry::clone_inner,
+        Display::fmt,
+        Some(IFormTry::is_type),
+        None,
+        None,
+        Some(IFormTry::child_traversal),
+        None,
+        None,
+    ),
 ];
 
 
@@ -750,7 +768,9 @@ impl AsIForm for IFormGRef {}
 
 pub struct IFormFun {
     params: Ref<Array<Symbol>>,
-    body: Ref<IForm>
+    body: Ref<IForm>,
+    //letで束縛されたクロージャの名前
+    name: Option<Ref<Symbol>>,
 }
 
 impl NaviType for IFormFun {
@@ -763,8 +783,17 @@ impl NaviType for IFormFun {
         unsafe {
             let params = Array::clone_inner(self.params.as_ref(), allocator)?.into_reachable();
             let body = IForm::clone_inner(self.body.as_ref(), allocator)?.into_reachable();
+            let name = match self.name.as_ref() {
+                Some(name) => Some(Symbol::clone_inner(name.as_ref(), allocator)?.into_reachable()),
+                None => None,
+            };
+
+            let mut fun = Self::alloc(&params, &body, allocator)?;
+            if let Some(name) = name {
+                fun.as_mut().set_name(&name);
+            }
 
-            Self::alloc(&params, &body, allocator)
+            Ok(fun)
         }
     }
 }
@@ -778,6 +807,9 @@ impl IFormFun {
     fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, arg: *mut u8)) {
         callback(self.params.cast_mut_value(), arg);
         callback(self.body.cast_mut_value(), arg);
+        if let Some(name) = self.name.as_mut() {
+            callback(name.cast_mut_value(), arg);
+        }
     }
 
     pub fn alloc<A: Allocator>(params: &Reachable<Array<Symbol>>, body: &Reachable<IForm>, allocator: &mut A) -> NResult<Self, OutOfMemory> {
@@ -786,12 +818,21 @@ impl IFormFun {
             std::ptr::write(ptr.as_ptr(), IFormFun {
                     params: params.raw_ptr().into(),
                     body: body.raw_ptr().into(),
+                    name: None,
                 });
         }
 
         Ok(ptr.into_ref())
     }
 
+    pub fn set_name(&mut self, name: &Reachable<Symbol>) {
+        self.name = Some(name.make());
+    }
+
+    pub fn name(&self) -> Option<Ref<Symbol>> {
+        self.name.clone()
+    }
+
     pub fn len_params(&self) -> usize {
         self.params.as_ref().len()
     }
@@ -902,6 +943,8 @@ pub struct IFormCall {
     app: Ref<IForm>,
     args: Ref<Array<IForm>>,
     is_tail: bool,
+    //呼び出し式のソース上の位置
+    source_pos: Option<SourcePos>,
 }
 
 impl NaviType for IFormCall {
@@ -915,7 +958,10 @@ impl NaviType for IFormCall {
             let app = IForm::clone_inner(self.app.as_ref(), allocator)?.into_reachable();
             let args = Array::clone_inner(self.args.as_ref(), allocator)?.into_reachable();
 
-            Self::alloc(&app, &args, self.is_tail, allocator)
+            let mut call = Self::alloc(&app, &args, self.is_tail, allocator)?;
+            call.as_mut().source_pos = self.source_pos;
+
+            Ok(call)
         }
     }
 }
@@ -938,6 +984,7 @@ impl IFormCall {
                     app: app.raw_ptr().into(),
                     args: args.raw_ptr().into(),
                     is_tail,
+                    source_pos: None,
                 });
         }
 
@@ -960,6 +1007,14 @@ impl IFormCall {
         self.is_tail
     }
 
+    pub fn set_source_pos(&mut self, pos: Option<SourcePos>) {
+        self.source_pos = pos;
+    }
+
+    pub fn source_pos(&self) -> Option<SourcePos> {
+        self.source_pos
+    }
+
     fn fmt(&self, _is_debug: bool, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if self.is_tail {
             write!(f, "(IFTailCall {} {})", self.app.as_ref(), self.args.as_ref())
@@ -1300,4 +1355,90 @@ impl Debug for IFormObjectSwitch {
     }
 }
 
-impl AsIForm for IFormObjectSwitch {}
\ No newline at end of file
+impl AsIForm for IFormObjectSwitch {}
+
+pub struct IFormTry {
+    body: Ref<IForm>,
+    //ハンドラ内で捕捉した例外を束縛する変数
+    var: Ref<Symbol>,
+    handler: Ref<IForm>,
+}
+
+impl NaviType for IFormTry {
+    fn typeinfo() -> &'static TypeInfo {
+        &IFORM_TYPEINFO_ARY[IFormKind::Try as usize]
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+        unsafe {
+            let body = IForm::clone_inner(self.body.as_ref(), allocator)?.into_reachable();
+            let var = Symbol::clone_inner(self.var.as_ref(), allocator)?.into_reachable();
+            let handler = IForm::clone_inner(self.handler.as_ref(), allocator)?.into_reachable();
+
+            Self::alloc(&body, &var, &handler, allocator)
+        }
+    }
+}
+
+impl IFormTry {
+    fn is_type(other_typeinfo: &TypeInfo) -> bool {
+        &IFORM_TYPEINFO_ARY[IFormKind::Try as usize] == other_typeinfo
+        || &IFORM_TYPEINFO == other_typeinfo
+    }
+
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, arg: *mut u8)) {
+        callback(self.body.cast_mut_value(), arg);
+        callback(self.var.cast_mut_value(), arg);
+        callback(self.handler.cast_mut_value(), arg);
+    }
+
+    pub fn alloc<A: Allocator>(body: &Reachable<IForm>, var: &Reachable<Symbol>, handler: &Reachable<IForm>, allocator: &mut A) -> NResult<Self, OutOfMemory> {
+        let ptr = allocator.alloc::<IFormTry>()?;
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), IFormTry {
+                    body: body.raw_ptr().into(),
+                    var: var.raw_ptr().into(),
+                    handler: handler.raw_ptr().into(),
+                });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    pub fn body(&self) -> Ref<IForm> {
+        self.body.clone()
+    }
+
+    pub fn var(&self) -> Ref<Symbol> {
+        self.var.clone()
+    }
+
+    pub fn handler(&self) -> Ref<IForm> {
+        self.handler.clone()
+    }
+
+    fn fmt(&self, _is_debug: bool, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(IFTry {} {} {})", self.body.as_ref(), self.var.as_ref(), self.handler.as_ref())
+    }
+}
+
+impl PartialEq for IFormTry {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Display for IFormTry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt(false, f)
+    }
+}
+
+impl Debug for IFormTry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt(true, f)
+    }
+}
+
+impl AsIForm for IFormTry {}
@@ -53,7 +53,7 @@ impl AsRef<str> for Keyword {
 }
 
 fn display(this: &Keyword, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    this.inner.as_string().fmt(f)
+    write!(f, ":{}", &(*this.inner.as_string()))
 }
 
 impl Display for Keyword {
@@ -42,7 +42,11 @@ impl NaviType for List {
             //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
             unsafe {
                 let v = crate::value::Any::clone_inner(self.v.as_ref(), allocator)?.into_reachable();
-                let next = Self::clone_inner(self.next.as_ref(), allocator)?.into_reachable();
+                let next = if self.next.is_nil() {
+                    Self::nil()
+                } else {
+                    Self::clone_inner(self.next.as_ref(), allocator)?.into_reachable()
+                };
 
                 Self::alloc(&v, &next, allocator)
             }
@@ -64,6 +68,7 @@ impl List {
             let mut head = cap.as_ref().head().capture(obj);
             if value::check_reply(&mut head, obj)? {
                 cap.as_mut().v.update_pointer(head.raw_ptr());
+                obj.write_barrier(cap.as_ref(), head.as_ref());
             } else {
                 //Replyがまだ返信を受け取っていなかったのでfalseを返す
                 return Ok(false);
@@ -76,6 +81,7 @@ impl List {
             let mut tail = cap.as_ref().tail().capture(obj);
             if  Self::check_reply(&mut tail, obj)? {
                 cap.as_mut().next.update_pointer(tail.raw_ptr());
+                obj.write_barrier(cap.as_ref(), tail.as_ref());
 
             } else {
                 //Replyがまだ返信を受け取っていなかったのでfalseを返す
@@ -94,7 +100,9 @@ impl List {
     }
 
     pub fn is_nil(&self) -> bool {
-        std::ptr::eq(self as *const List, IMMIDATE_NIL as *const List)
+        //&ListはアラインされたアドレスだとみなされるためNilとの比較が最適化で消されてしまう。
+        //black_boxを通してアドレスの値そのもので比較する。
+        std::ptr::eq(std::hint::black_box(self as *const List), IMMIDATE_NIL as *const List)
     }
 
     pub fn alloc<A: Allocator>(v: &Reachable<Any>, next: &Reachable<List>, allocator: &mut A) -> NResult<List, OutOfMemory> {
@@ -142,16 +150,45 @@ impl List {
         self.next.clone()
     }
 
+    pub fn get(&self, mut index: usize) -> Ref<Any> {
+        for v in unsafe { self.iter_gcunsafe() } {
+            if index == 0 {
+                return v;
+            } else {
+                index = index - 1;
+            }
+        }
+
+        panic!("out of bounds {}: {}", index, self)
+    }
+
+    pub unsafe fn iter_gcunsafe(&self) -> ListIteratorGCUnsafe {
+        ListIteratorGCUnsafe {
+            //selfがNilの場合に判定が最適化で消されないよう、black_boxを通してRefを作る
+            cur: Ref::from(std::hint::black_box(self as *const List as *mut List)),
+        }
+    }
+
+}
+
+//NilはImmidiate Valueのため、&Listとして参照すると不正なアドレスへの参照になってしまう。
+//リストをたどる処理では、必ずRefの段階でNilかどうかを確認してからas_refを呼び出す。
+impl Ref<List> {
+    pub fn is_nil(&self) -> bool {
+        //参照を作らずにポインタの値だけで比較する
+        std::ptr::eq(self.raw_ptr(), IMMIDATE_NIL as *const List)
+    }
+
     pub fn count(&self) -> usize {
         let mut count = 0;
 
-        let mut l = self;
+        let mut l = self.clone();
         loop {
             if l.is_nil() {
                 break
             } else {
                 count += 1;
-                l = l.next.as_ref();
+                l = l.as_ref().tail();
             }
         }
 
@@ -160,7 +197,7 @@ impl List {
 
     pub fn len_more_than(&self, count: usize) -> bool {
         let mut count = count;
-        let mut l = self;
+        let mut l = self.clone();
         loop {
             if l.is_nil() {
                 break
@@ -169,7 +206,7 @@ impl List {
                 if count == 0 {
                     break
                 }
-                l = l.next.as_ref();
+                l = l.as_ref().tail();
             }
         }
 
@@ -178,13 +215,13 @@ impl List {
 
     pub fn len_exactly(&self, count: usize) -> bool {
         let mut count = count;
-        let mut l = self;
+        let mut l = self.clone();
         loop {
             if l.is_nil() {
                 break
             } else {
                 count -= 1;
-                l = l.next.as_ref();
+                l = l.as_ref().tail();
                 if count == 0 {
                     break
                 }
@@ -194,27 +231,6 @@ impl List {
         count == 0 && l.is_nil()
     }
 
-    pub fn get(&self, mut index: usize) -> Ref<Any> {
-        for v in unsafe { self.iter_gcunsafe() } {
-            if index == 0 {
-                return v;
-            } else {
-                index = index - 1;
-            }
-        }
-
-        panic!("out of bounds {}: {}", index, self)
-    }
-
-    pub unsafe fn iter_gcunsafe(&self) -> ListIteratorGCUnsafe {
-        ListIteratorGCUnsafe {
-            cur: Ref::new(self),
-        }
-    }
-
-}
-
-impl Ref<List> {
     pub fn set_has_reply_flag(&mut self, to_index: usize) {
         value::set_has_replytype_flag(self);
         if 0 < to_index {
@@ -235,7 +251,11 @@ impl PartialEq for List {
         } else if other.is_nil() {
             false
         } else {
-            self.v.as_ref().eq(&other.v.as_ref()) && self.next.as_ref().eq(&other.next.as_ref())
+            if self.next.is_nil() || other.next.is_nil() {
+                self.v.as_ref().eq(other.v.as_ref()) && self.next.is_nil() && other.next.is_nil()
+            } else {
+                self.v.as_ref().eq(other.v.as_ref()) && self.next.as_ref().eq(other.next.as_ref())
+            }
         }
     }
 }
@@ -271,7 +291,29 @@ impl Debug for List {
     }
 }
 
+impl Cap<List> {
+    pub fn is_nil(&self) -> bool {
+        self.refer().is_nil()
+    }
+}
+
 impl Reachable<List> {
+    pub fn is_nil(&self) -> bool {
+        std::ptr::eq(self.raw_ptr(), IMMIDATE_NIL as *const List)
+    }
+
+    pub fn count(&self) -> usize {
+        self.make().count()
+    }
+
+    pub fn len_more_than(&self, count: usize) -> bool {
+        self.make().len_more_than(count)
+    }
+
+    pub fn len_exactly(&self, count: usize) -> bool {
+        self.make().len_exactly(count)
+    }
+
     pub fn iter(&self, obj: &mut Object) -> ListIterator {
         ListIterator::new(self, obj)
     }
@@ -289,7 +331,7 @@ impl std::iter::Iterator for ListIteratorGCUnsafe {
     type Item = Ref<Any>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur.as_ref().is_nil() {
+        if self.cur.is_nil() {
             None
         } else {
             let v = self.cur.as_ref().head();
@@ -315,7 +357,7 @@ impl std::iter::Iterator for ListIterator {
     type Item = Ref<Any>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur.as_ref().is_nil() {
+        if self.cur.is_nil() {
             None
         } else {
             let v = self.cur.as_ref().head();
@@ -345,12 +387,12 @@ impl std::iter::Iterator for ListIteratorWithInfo {
     type Item = (Ref<Any>, ListIteratorInfo);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.cur.as_ref().is_nil() {
+        if self.cur.is_nil() {
             None
         } else {
             let v = self.cur.as_ref().head();
             let next = self.cur.as_ref().tail();
-            let is_tail = next.as_ref().is_nil();
+            let is_tail = next.is_nil();
 
             self.cur.update_pointer(next);
             Some((v,ListIteratorInfo { is_tail: is_tail }))
@@ -383,6 +425,7 @@ impl ListBuilder {
         } else {
             let end = self.end.as_mut().unwrap();
             end.as_mut().next.update_pointer(cell.raw_ptr());
+            obj.write_barrier(end.as_ref(), cell.as_ref());
 
             end.update_pointer(cell);
         }
@@ -406,10 +449,11 @@ impl ListBuilder {
         result
     }
 
-    pub fn append_get(mut self, tail: &Ref<List>) -> Ref<List> {
+    pub fn append_get(mut self, tail: &Ref<List>, obj: &mut Object) -> Ref<List> {
         match &mut self.end {
             Some(end) => {
                 end.as_mut().next.update_pointer(tail.raw_ptr());
+                obj.write_barrier(end.as_ref(), tail.as_ref());
                 self.start.unwrap().take()
             }
             None => {
@@ -472,7 +516,7 @@ fn func_is_list(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
 fn func_list_len(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
     let v = vm::refer_arg::<List>(0, obj);
 
-    let num = number::make_integer(v.as_ref().count() as i64, obj)?;
+    let num = number::make_integer(v.count() as i64, obj)?;
     Ok(num)
 }
 
@@ -508,7 +552,7 @@ fn func_append(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
         if value::has_replytype(&last) {
             last_hasreply_index = num_rest - 1;
         }
-        let mut list = builder.append_get(&last);
+        let mut list = builder.append_get(&last, obj);
 
         //Replyを持つセルの前方のリストに対してフラグを立てたいので、
         //一番最初のセルにだけReplyを持っている場合は何もしない。
@@ -594,6 +638,10 @@ pub fn register_global(obj: &mut Object) {
 pub mod literal {
     use super::*;
 
+    pub fn list() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_LIST.value)
+    }
+
     pub fn is_list() -> Reachable<Func> {
         Reachable::new_static(&FUNC_IS_LIST.value)
     }
@@ -606,6 +654,10 @@ pub mod literal {
         Reachable::new_static(&FUNC_LIST_REF.value)
     }
 
+    pub fn append() -> Reachable<Func> {
+        Reachable::new_static(&FUNC_APPEND.value)
+    }
+
 }
 
 #[cfg(test)]
@@ -689,7 +741,7 @@ mod tests {
         {
             let program = "(list)";
             let result = exec::<List>(program, standalone.mut_object());
-            assert!(result.as_ref().is_nil());
+            assert!(result.is_nil());
 
             let program = "(list 1 2 3 4 5 6 7)";
             let result = exec::<List>(program, standalone.mut_object());
@@ -711,7 +763,7 @@ mod tests {
         {
             let program = "(append)";
             let result = exec::<List>(program, standalone.mut_object());
-            assert!(result.as_ref().is_nil());
+            assert!(result.is_nil());
 
             let program = "(append '(1 2) '(3 4) '(5 6 7))";
             let result = exec::<List>(program, standalone.mut_object());
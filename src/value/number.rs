@@ -4,13 +4,18 @@ use crate::value::*;
 use crate::value::func::Func;
 use crate::value::app::{Parameter, ParamKind, Param};
 use crate::object::Object;
-use crate::object::mm::GCAllocationStruct;
+use crate::object::mm::{self, GCAllocationStruct};
 use crate::vm;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::hash::Hash;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
 use once_cell::sync::Lazy;
 
+mod bigint;
+pub use self::bigint::BigInt;
+
 static FIXNUM_TYPEINFO : TypeInfo = new_typeinfo!(
     Fixnum,
     "Fixnum",
@@ -21,7 +26,7 @@ static FIXNUM_TYPEINFO : TypeInfo = new_typeinfo!(
     Display::fmt,
     Some(Fixnum::is_type),
     None,
-    Some(Fixnum::is_comparable),
+    Some(is_comparable),
     None,
     None,
     None,
@@ -44,16 +49,11 @@ impl Fixnum {
     fn is_type(other_typeinfo: &TypeInfo) -> bool {
         &FIXNUM_TYPEINFO == other_typeinfo
         || &INTEGER_TYPEINFO == other_typeinfo
+        || &RATIONAL_TYPEINFO == other_typeinfo
         || &REAL_TYPEINFO == other_typeinfo
         || &NUMBER_TYPEINFO == other_typeinfo
     }
 
-    fn is_comparable(other_typeinfo: &TypeInfo) -> bool {
-        &FIXNUM_TYPEINFO == other_typeinfo
-        || &INTEGER_TYPEINFO == other_typeinfo
-        || &REAL_TYPEINFO == other_typeinfo
-    }
-
     #[inline]
     pub fn get(&self) -> i64 {
         let v = ptr_to_usize(self);
@@ -67,18 +67,11 @@ impl Eq for Fixnum { }
 
 impl PartialEq for Fixnum {
     fn eq(&self, other: &Self) -> bool {
-        let other_typeinfo = get_typeinfo(other);
-        if Fixnum::typeinfo() == other_typeinfo {
+        if Fixnum::typeinfo() == get_typeinfo(other) {
             std::ptr::eq(self, other)
-
-        } else if Integer::typeinfo() == other_typeinfo {
-            let other = unsafe { std::mem::transmute::<&Fixnum, &Integer>(other) };
-            self.get() == other.num
-
         } else {
-            //otherはReal型か？
-            let other = unsafe { std::mem::transmute::<&Fixnum, &Real>(other) };
-            self.get() as f64 == other.num
+            //数値型同士は比較可能なため、otherには他の数値型の参照が来る可能性がある
+            number_eq(self, other)
         }
     }
 }
@@ -111,7 +104,7 @@ static INTEGER_TYPEINFO : TypeInfo = new_typeinfo!(
     Display::fmt,
     Some(Integer::is_type),
     None,
-    Some(Integer::is_comparable),
+    Some(is_comparable),
     None,
     None,
     None,
@@ -132,16 +125,11 @@ impl Integer {
     fn is_type(other_typeinfo: &TypeInfo) -> bool {
         &FIXNUM_TYPEINFO == other_typeinfo
         || &INTEGER_TYPEINFO == other_typeinfo
+        || &RATIONAL_TYPEINFO == other_typeinfo
         || &REAL_TYPEINFO == other_typeinfo
         || &NUMBER_TYPEINFO == other_typeinfo
     }
 
-    fn is_comparable(other_typeinfo: &TypeInfo) -> bool {
-        &FIXNUM_TYPEINFO == other_typeinfo
-        || &INTEGER_TYPEINFO == other_typeinfo
-        || &REAL_TYPEINFO == other_typeinfo
-    }
-
     fn alloc<A: Allocator>(num: i64, allocator : &mut A) -> NResult<Integer, OutOfMemory> {
         let ptr = allocator.alloc::<Integer>()?;
 
@@ -152,11 +140,18 @@ impl Integer {
         Ok(ptr.into_ref())
     }
 
+    ///
+    /// i64の範囲の値を取得する。
+    /// Bignumの場合はi64の範囲に収まるように最大値か最小値に切り詰めた値を返す。
     #[inline]
     pub fn get(&self) -> i64 {
-        if Fixnum::typeinfo() == get_typeinfo(self) {
+        let typeinfo = get_typeinfo(self);
+        if Fixnum::typeinfo() == typeinfo {
             let this = unsafe { std::mem::transmute::<&Integer, &Fixnum>(self) };
             this.get()
+        } else if Bignum::typeinfo() == typeinfo {
+            let this = unsafe { std::mem::transmute::<&Integer, &Bignum>(self) };
+            if this.negative { i64::MIN } else { i64::MAX }
         } else {
             self.num
         }
@@ -168,19 +163,11 @@ impl Eq for Integer { }
 impl PartialEq for Integer {
     //IntegerはReal型とも比較可能なため、other変数にはRealの参照が来る可能性がある
     fn eq(&self, other: &Self) -> bool {
-        let other_typeinfo = get_typeinfo(other);
-        if Fixnum::typeinfo() == other_typeinfo {
-            let other = unsafe { std::mem::transmute::<&Integer, &Fixnum>(other) };
-            self.num == other.get()
-
-        } else if Integer::typeinfo() == other_typeinfo {
+        if Integer::typeinfo() == get_typeinfo(other) {
             //Integer 同士なら通常通りnumの比較を行う
             self.num == other.num
-
         } else {
-            //otherはReal型か？
-            let other = unsafe { std::mem::transmute::<&Integer, &Real>(other) };
-            self.num as f64 == other.num
+            number_eq(self, other)
         }
     }
 }
@@ -225,14 +212,226 @@ pub fn get_integer<V: ValueHolder<Any>>(v: &V) -> i64 {
         let fixnum: &Fixnum = v.as_ref().cast_unchecked();
         fixnum.get()
 
-    } else if Integer::typeinfo() == typeinfo {
+    } else if Integer::typeinfo() == typeinfo || Bignum::typeinfo() == typeinfo {
         let integer: &Integer = v.as_ref().cast_unchecked();
-        integer.num
+        integer.get()
     } else {
         panic!("invalid number")
     }
 }
 
+///
+/// 任意精度の整数から値を作成する。i64の範囲に収まる場合はFixnumかIntegerになる。
+pub fn make_bigint<A: Allocator>(number: &BigInt, allocator: &mut A) -> NResult<Any, OutOfMemory> {
+    match number.to_i64() {
+        Some(number) => make_integer(number, allocator),
+        None => into_value(Bignum::alloc(number, allocator)),
+    }
+}
+
+///
+/// 分子と分母から約分済みの有理数を作成する。分母が1になる場合は整数になる。
+/// 分母は0以外であること。
+pub fn make_rational(numerator: BigInt, denominator: BigInt, obj: &mut Object) -> NResult<Any, OutOfMemory> {
+    Num::ratio(numerator, denominator).into_value(obj)
+}
+
+//
+// Bignum
+// i64の範囲を超える整数。絶対値の各桁を構造体の直後に保持する。
+//
+#[repr(C)]
+pub struct Bignum {
+    negative: bool,
+    len: usize,
+}
+
+static BIGNUM_TYPEINFO : TypeInfo = new_typeinfo!(
+    Bignum,
+    "Bignum",
+    0,
+    Some(Bignum::size_of),
+    Bignum::eq,
+    Bignum::clone_inner,
+    Display::fmt,
+    Some(Bignum::is_type),
+    None,
+    Some(is_comparable),
+    None,
+    None,
+    None,
+);
+
+impl NaviType for Bignum {
+    fn typeinfo() -> &'static TypeInfo {
+        &BIGNUM_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        Self::alloc(&self.get(), allocator)
+    }
+}
+
+impl Bignum {
+    fn is_type(other_typeinfo: &TypeInfo) -> bool {
+        &BIGNUM_TYPEINFO == other_typeinfo
+        || &INTEGER_TYPEINFO == other_typeinfo
+        || &RATIONAL_TYPEINFO == other_typeinfo
+        || &REAL_TYPEINFO == other_typeinfo
+        || &NUMBER_TYPEINFO == other_typeinfo
+    }
+
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<Bignum>() + self.len * std::mem::size_of::<u32>()
+    }
+
+    fn alloc<A: Allocator>(number: &BigInt, allocator : &mut A) -> NResult<Bignum, OutOfMemory> {
+        let digits = number.digits();
+        let ptr = allocator.alloc_with_additional_size::<Bignum>(std::mem::size_of_val(digits))?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), Bignum { negative: number.is_negative(), len: digits.len() });
+            let digits_ptr = ptr.as_ptr().offset(1) as *mut u32;
+            std::ptr::copy_nonoverlapping(digits.as_ptr(), digits_ptr, digits.len());
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    fn digits(&self) -> &[u32] {
+        unsafe {
+            let ptr = (self as *const Bignum).offset(1) as *const u32;
+            std::slice::from_raw_parts(ptr, self.len)
+        }
+    }
+
+    pub fn get(&self) -> BigInt {
+        BigInt::from_parts(self.negative, self.digits().to_vec())
+    }
+}
+
+impl PartialEq for Bignum {
+    fn eq(&self, other: &Self) -> bool {
+        number_eq(self, other)
+    }
+}
+
+impl Display for Bignum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
+
+impl Debug for Bignum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get())
+    }
+}
+
+//
+// Rational Number
+// 既約分数で表した正確な有理数。分母は常に2以上で、符号は分子が持つ。
+// 分子の各桁に続けて分母の各桁を構造体の直後に保持する。
+//
+#[repr(C)]
+pub struct Rational {
+    negative: bool,
+    numerator_len: usize,
+    denominator_len: usize,
+}
+
+static RATIONAL_TYPEINFO : TypeInfo = new_typeinfo!(
+    Rational,
+    "Rational",
+    0,
+    Some(Rational::size_of),
+    Rational::eq,
+    Rational::clone_inner,
+    Display::fmt,
+    Some(Rational::is_type),
+    None,
+    Some(is_comparable),
+    None,
+    None,
+    None,
+);
+
+impl NaviType for Rational {
+    fn typeinfo() -> &'static TypeInfo {
+        &RATIONAL_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        Self::alloc(&self.numerator(), &self.denominator(), allocator)
+    }
+}
+
+impl Rational {
+    fn is_type(other_typeinfo: &TypeInfo) -> bool {
+        &RATIONAL_TYPEINFO == other_typeinfo
+        || &REAL_TYPEINFO == other_typeinfo
+        || &NUMBER_TYPEINFO == other_typeinfo
+    }
+
+    fn size_of(&self) -> usize {
+        std::mem::size_of::<Rational>() + (self.numerator_len + self.denominator_len) * std::mem::size_of::<u32>()
+    }
+
+    //分子と分母は約分済みで、分母が2以上であること
+    fn alloc<A: Allocator>(numerator: &BigInt, denominator: &BigInt, allocator : &mut A) -> NResult<Rational, OutOfMemory> {
+        let numerator_digits = numerator.digits();
+        let denominator_digits = denominator.digits();
+        let len = numerator_digits.len() + denominator_digits.len();
+        let ptr = allocator.alloc_with_additional_size::<Rational>(len * std::mem::size_of::<u32>())?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), Rational {
+                negative: numerator.is_negative(),
+                numerator_len: numerator_digits.len(),
+                denominator_len: denominator_digits.len(),
+            });
+            let digits_ptr = ptr.as_ptr().offset(1) as *mut u32;
+            std::ptr::copy_nonoverlapping(numerator_digits.as_ptr(), digits_ptr, numerator_digits.len());
+            std::ptr::copy_nonoverlapping(denominator_digits.as_ptr(), digits_ptr.add(numerator_digits.len()), denominator_digits.len());
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    fn digits(&self) -> &[u32] {
+        unsafe {
+            let ptr = (self as *const Rational).offset(1) as *const u32;
+            std::slice::from_raw_parts(ptr, self.numerator_len + self.denominator_len)
+        }
+    }
+
+    pub fn numerator(&self) -> BigInt {
+        BigInt::from_parts(self.negative, self.digits()[.. self.numerator_len].to_vec())
+    }
+
+    pub fn denominator(&self) -> BigInt {
+        BigInt::from_parts(false, self.digits()[self.numerator_len ..].to_vec())
+    }
+}
+
+impl PartialEq for Rational {
+    fn eq(&self, other: &Self) -> bool {
+        number_eq(self, other)
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator(), self.denominator())
+    }
+}
+
+impl Debug for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numerator(), self.denominator())
+    }
+}
+
 //
 // Real Number
 //
@@ -252,7 +451,7 @@ static REAL_TYPEINFO : TypeInfo = new_typeinfo!(
     Display::fmt,
     Some(Real::is_type),
     None,
-    Some(Real::is_comparable),
+    Some(is_comparable),
     None,
     None,
     None,
@@ -275,12 +474,6 @@ impl Real {
         || &NUMBER_TYPEINFO == other_typeinfo
     }
 
-    fn is_comparable(other_typeinfo: &TypeInfo) -> bool {
-        &REAL_TYPEINFO == other_typeinfo
-        || &INTEGER_TYPEINFO == other_typeinfo
-        || &FIXNUM_TYPEINFO == other_typeinfo
-    }
-
     pub fn alloc<A: Allocator>(num: f64, allocator : &mut A) -> NResult<Real, OutOfMemory> {
         let ptr = allocator.alloc::<Real>()?;
 
@@ -297,20 +490,10 @@ impl PartialEq for Real {
 
     //RealはInteger型とも比較可能なため、other変数にはIntegerの参照が来る可能性がある
     fn eq(&self, other: &Self) -> bool {
-        //otherはReal型か？
-        let other_typeinfo = get_typeinfo(other);
-        if Real::typeinfo() == other_typeinfo {
+        if Real::typeinfo() == get_typeinfo(other) {
             self.num == other.num
-
-        } else if Fixnum::typeinfo() == other_typeinfo {
-            let other = unsafe { std::mem::transmute::<&Real, &Fixnum>(other) };
-            self.num == other.get() as f64
-
         } else {
-            //OtherがIntegerなら、Integer型参照に無理やり戻してから比較
-            let other = unsafe { std::mem::transmute::<&Real, &Integer>(other) };
-
-            self.num == other.num as f64
+            number_eq(self, other)
         }
     }
 }
@@ -369,11 +552,117 @@ impl Display for Number {
     }
 }
 
+fn is_comparable(other_typeinfo: &TypeInfo) -> bool {
+    //数値型同士はすべて比較可能
+    &FIXNUM_TYPEINFO == other_typeinfo
+    || &INTEGER_TYPEINFO == other_typeinfo
+    || &BIGNUM_TYPEINFO == other_typeinfo
+    || &RATIONAL_TYPEINFO == other_typeinfo
+    || &REAL_TYPEINFO == other_typeinfo
+}
+
+///
+/// 数値をf64に変換する。数値同士の等価性と矛盾しないハッシュ値の計算に使用する。
+pub(crate) fn number_to_f64(v: &Any) -> f64 {
+    number_to(v).to_f64()
+}
+
+fn number_eq<T: NaviType, U: NaviType>(this: &T, other: &U) -> bool {
+    let this = unsafe { std::mem::transmute::<&T, &Any>(this) };
+    let other = unsafe { std::mem::transmute::<&U, &Any>(other) };
+    compare(&number_to(this), &number_to(other)) == Some(Ordering::Equal)
+}
+
+//
+// 数値演算
+// 演算中は値をNumに変換して扱い、結果を再び値として確保する。
+//
 enum Num {
     Int(i64),
+    Big(BigInt),
+    //約分済みで分母が2以上の有理数
+    Rat(BigInt, BigInt),
     Real(f64),
 }
 
+impl Num {
+    fn big(num: BigInt) -> Num {
+        match num.to_i64() {
+            Some(num) => Num::Int(num),
+            None => Num::Big(num),
+        }
+    }
+
+    //分母は0以外であること
+    fn ratio(numerator: BigInt, denominator: BigInt) -> Num {
+        //約分して、符号を分子に寄せる
+        let gcd = numerator.gcd(&denominator);
+        let (mut numerator, _) = numerator.divrem(&gcd);
+        let (mut denominator, _) = denominator.divrem(&gcd);
+        if denominator.is_negative() {
+            numerator = numerator.neg();
+            denominator = denominator.neg();
+        }
+
+        if denominator.is_one() {
+            Num::big(numerator)
+        } else {
+            Num::Rat(numerator, denominator)
+        }
+    }
+
+    fn is_real(&self) -> bool {
+        matches!(self, Num::Real(_))
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(self, Num::Int(_) | Num::Big(_))
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Num::Int(num) => *num == 0,
+            Num::Big(_) | Num::Rat(_, _) => false,
+            Num::Real(num) => *num == 0.0,
+        }
+    }
+
+    fn to_f64(&self) -> f64 {
+        match self {
+            Num::Int(num) => *num as f64,
+            Num::Big(num) => num.to_f64(),
+            Num::Rat(numerator, denominator) => numerator.to_f64() / denominator.to_f64(),
+            Num::Real(num) => *num,
+        }
+    }
+
+    //正確な整数であること
+    fn into_big(self) -> BigInt {
+        match self {
+            Num::Int(num) => BigInt::from_i64(num),
+            Num::Big(num) => num,
+            _ => unreachable!(),
+        }
+    }
+
+    //正確な数であること
+    fn into_ratio(self) -> (BigInt, BigInt) {
+        match self {
+            Num::Rat(numerator, denominator) => (numerator, denominator),
+            num => (num.into_big(), BigInt::from_i64(1)),
+        }
+    }
+
+    fn into_value(self, obj: &mut Object) -> NResult<Any, OutOfMemory> {
+        match self {
+            Num::Int(num) => make_integer(num, obj),
+            Num::Big(num) => make_bigint(&num, obj),
+            Num::Rat(numerator, denominator) => into_value(Rational::alloc(&numerator, &denominator, obj)),
+            Num::Real(num) => into_value(Real::alloc(num, obj)),
+        }
+    }
+}
+
 fn number_to(v: &Any) -> Num {
     let typeinfo = get_typeinfo(v);
 
@@ -385,144 +674,1008 @@ fn number_to(v: &Any) -> Num {
         let integer: &Integer = v.cast_unchecked();
         Num::Int(integer.num)
 
+    } else if Bignum::typeinfo() == typeinfo {
+        let bignum: &Bignum = v.cast_unchecked();
+        Num::Big(bignum.get())
+
+    } else if Rational::typeinfo() == typeinfo {
+        let rational: &Rational = v.cast_unchecked();
+        Num::Rat(rational.numerator(), rational.denominator())
+
     } else {
         let real: &Real = v.cast_unchecked();
         Num::Real(real.num)
     }
 }
 
-fn func_add(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
-    let v = vm::refer_arg::<Any>(0, obj);
+fn add(a: Num, b: Num) -> Num {
+    match (a, b) {
+        (Num::Int(a), Num::Int(b)) => match a.checked_add(b) {
+            Some(num) => Num::Int(num),
+            None => Num::big(BigInt::from_i64(a).add(&BigInt::from_i64(b))),
+        },
+        (a, b) if a.is_real() || b.is_real() => Num::Real(a.to_f64() + b.to_f64()),
+        (a, b) if a.is_integer() && b.is_integer() => Num::big(a.into_big().add(&b.into_big())),
+        (a, b) => {
+            let (an, ad) = a.into_ratio();
+            let (bn, bd) = b.into_ratio();
+            Num::ratio(an.mul(&bd).add(&bn.mul(&ad)), ad.mul(&bd))
+        }
+    }
+}
 
-    let (mut int,mut real) = match number_to(&v.as_ref()) {
-        Num::Int(num) => (Some(num), None),
-        Num::Real(num) => (None, Some(num)),
-    };
+fn negate(a: Num) -> Num {
+    match a {
+        Num::Int(num) => match num.checked_neg() {
+            Some(num) => Num::Int(num),
+            None => Num::big(BigInt::from_i64(num).neg()),
+        },
+        Num::Big(num) => Num::big(num.neg()),
+        Num::Rat(numerator, denominator) => Num::Rat(numerator.neg(), denominator),
+        Num::Real(num) => Num::Real(-num),
+    }
+}
 
-    for index in 0 .. num_rest {
-        let v = vm::refer_rest_arg::<Any>(1, index, obj);
-        match (number_to(&v.as_ref()), int, real) {
-            (Num::Int(num), Some(acc), None) => {
-                int = Some(acc + num);
-            }
-            (Num::Real(num), Some(acc), None) => {
-                int = None;
-                real = Some(acc as f64 + num);
-            }
-            (Num::Int(num), None, Some(acc)) => {
-                real = Some(acc + num as f64);
-            }
-            (Num::Real(num), None, Some(acc)) => {
-                real = Some(acc + num);
-            }
-            _ => unreachable!(),
+fn sub(a: Num, b: Num) -> Num {
+    match (a, b) {
+        (Num::Int(a), Num::Int(b)) => match a.checked_sub(b) {
+            Some(num) => Num::Int(num),
+            None => Num::big(BigInt::from_i64(a).sub(&BigInt::from_i64(b))),
+        },
+        (a, b) => add(a, negate(b)),
+    }
+}
+
+fn mul(a: Num, b: Num) -> Num {
+    match (a, b) {
+        (Num::Int(a), Num::Int(b)) => match a.checked_mul(b) {
+            Some(num) => Num::Int(num),
+            None => Num::big(BigInt::from_i64(a).mul(&BigInt::from_i64(b))),
+        },
+        (a, b) if a.is_real() || b.is_real() => Num::Real(a.to_f64() * b.to_f64()),
+        (a, b) if a.is_integer() && b.is_integer() => Num::big(a.into_big().mul(&b.into_big())),
+        (a, b) => {
+            let (an, ad) = a.into_ratio();
+            let (bn, bd) = b.into_ratio();
+            Num::ratio(an.mul(&bn), ad.mul(&bd))
         }
     }
+}
+
+fn div(a: Num, b: Num) -> Result<Num, Exception> {
+    if a.is_real() || b.is_real() {
+        //浮動小数点数の0除算は無限大かNaNになる
+        Ok(Num::Real(a.to_f64() / b.to_f64()))
+
+    } else if b.is_zero() {
+        Err(Exception::DivisionByZero)
 
-    if int.is_some() {
-        let num = number::make_integer(int.unwrap(), obj)?;
-        Ok(num)
     } else {
-        let num = number::Real::alloc(real.unwrap(), obj)?;
-        Ok(num.into_value())
+        let (an, ad) = a.into_ratio();
+        let (bn, bd) = b.into_ratio();
+        Ok(Num::ratio(an.mul(&bd), ad.mul(&bn)))
     }
 }
 
-fn func_sub(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
-    let v = vm::refer_arg::<Any>(0, obj);
+fn compare(a: &Num, b: &Num) -> Option<Ordering> {
+    match (a, b) {
+        (Num::Int(a), Num::Int(b)) => Some(a.cmp(b)),
+        (Num::Real(_), _) | (_, Num::Real(_)) => a.to_f64().partial_cmp(&b.to_f64()),
+        (Num::Big(a), Num::Big(b)) => Some(a.cmp(b)),
+        (Num::Int(a), Num::Big(b)) => Some(BigInt::from_i64(*a).cmp(b)),
+        (Num::Big(a), Num::Int(b)) => Some(a.cmp(&BigInt::from_i64(*b))),
+        _ => {
+            //分母は常に正なので、分母を払って比較する
+            let (an, ad) = clone_ratio(a);
+            let (bn, bd) = clone_ratio(b);
+            Some(an.mul(&bd).cmp(&bn.mul(&ad)))
+        }
+    }
+}
 
-    let (mut int,mut real) = match number_to(&v.as_ref()) {
-        Num::Int(num) => (Some(num), None),
-        Num::Real(num) => (None, Some(num)),
-    };
+fn clone_ratio(num: &Num) -> (BigInt, BigInt) {
+    match num {
+        Num::Int(num) => (BigInt::from_i64(*num), BigInt::from_i64(1)),
+        Num::Big(num) => (num.clone(), BigInt::from_i64(1)),
+        Num::Rat(numerator, denominator) => (numerator.clone(), denominator.clone()),
+        Num::Real(_) => unreachable!(),
+    }
+}
 
-    if num_rest == 0 {
-        if let Some(num) = int {
-            int = Some(-num);
-        } else {
-            real = Some(- real.unwrap());
-        }
+#[derive(Clone, Copy)]
+enum Rounding {
+    Floor,
+    Ceiling,
+    Round,
+    Truncate,
+}
 
-    } else {
-        for index in 0 .. num_rest {
-            let v = vm::refer_rest_arg::<Any>(1, index, obj);
-            match (number_to(&v.as_ref()), int, real) {
-                (Num::Int(num), Some(acc), None) => {
-                    int = Some(acc - num);
-                }
-                (Num::Real(num), Some(acc), None) => {
-                    int = None;
-                    real = Some(acc as f64 - num);
-                }
-                (Num::Int(num), None, Some(acc)) => {
-                    real = Some(acc - num as f64);
+fn round(a: Num, rounding: Rounding) -> Num {
+    match a {
+        Num::Real(num) => Num::Real(match rounding {
+            Rounding::Floor => num.floor(),
+            Rounding::Ceiling => num.ceil(),
+            Rounding::Round => num.round_ties_even(),
+            Rounding::Truncate => num.trunc(),
+        }),
+        Num::Rat(numerator, denominator) => {
+            let floor = numerator.div_floor(&denominator);
+            let one = BigInt::from_i64(1);
+            let result = match rounding {
+                Rounding::Floor => floor,
+                Rounding::Ceiling => floor.add(&one),
+                Rounding::Truncate => {
+                    if numerator.is_negative() { floor.add(&one) } else { floor }
                 }
-                (Num::Real(num), None, Some(acc)) => {
-                    real = Some(acc - num);
+                Rounding::Round => {
+                    //端数の2倍と分母を比べて、ちょうど半分なら偶数側に丸める
+                    let twice = numerator.sub(&floor.mul(&denominator)).mul(&BigInt::from_i64(2));
+                    match twice.cmp(&denominator) {
+                        Ordering::Less => floor,
+                        Ordering::Greater => floor.add(&one),
+                        Ordering::Equal if floor.is_even() => floor,
+                        Ordering::Equal => floor.add(&one),
+                    }
                 }
-                _ => unreachable!(),
-            }
+            };
+            Num::big(result)
         }
-    }
-
-    if int.is_some() {
-        let num = number::make_integer(int.unwrap(), obj)?;
-        Ok(num)
-    } else {
-        let num = number::Real::alloc(real.unwrap(), obj)?;
-        Ok(num.into_value())
+        //整数はそのまま
+        num => num,
     }
 }
 
-fn func_abs(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
-    let v = vm::refer_arg(0, obj);
+fn sqrt(a: Num) -> Num {
+    //正確な数の平方根が正確に求まる場合は正確な数を返す
+    let exact_sqrt = |num: &BigInt| {
+        let root = num.sqrt_floor();
+        if &root.mul(&root) == num { Some(root) } else { None }
+    };
 
-    match number_to(v.as_ref()) {
-        Num::Int(num) => {
-            let num = number::make_integer(num.abs(), obj)?;
-            Ok(num)
+    match &a {
+        Num::Int(_) | Num::Big(_) if compare(&a, &Num::Int(0)) != Some(Ordering::Less) => {
+            let num = clone_ratio(&a).0;
+            match exact_sqrt(&num) {
+                Some(root) => Num::big(root),
+                None => Num::Real(a.to_f64().sqrt()),
+            }
         }
-        Num::Real(num) => {
-            let num = number::Real::alloc(num.abs(), obj)?;
-            Ok(num.into_value())
+        Num::Rat(numerator, denominator) if !numerator.is_negative() => {
+            match (exact_sqrt(numerator), exact_sqrt(denominator)) {
+                (Some(numerator), Some(denominator)) => Num::ratio(numerator, denominator),
+                _ => Num::Real(a.to_f64().sqrt()),
+            }
         }
+        //負の数の平方根はNaNになる
+        _ => Num::Real(a.to_f64().sqrt()),
     }
 }
 
-static FUNC_ADD: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
-    GCAllocationStruct::new(
-        Func::new("+", func_add,
-            Parameter::new(&[
-            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
-            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
-            ])
-        )
-    )
-});
+fn expt(base: Num, power: Num, quota: Option<usize>) -> Result<Num, Exception> {
+    if power.is_integer() && !base.is_real() {
+        //正確な数の整数乗は、指数の大きさに関係なく正確な数になる
+        return expt_exact(base, power, quota);
+    }
 
-static FUNC_SUB: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
-    GCAllocationStruct::new(
-        Func::new("-", func_sub,
-            Parameter::new(&[
-            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
-            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
-            ])
-        )
-    )
-});
+    match &power {
+        Num::Int(num) if i32::try_from(*num).is_ok() => {
+            Ok(Num::Real(base.to_f64().powi(*num as i32)))
+        }
+        _ => Ok(Num::Real(base.to_f64().powf(power.to_f64()))),
+    }
+}
 
-static FUNC_ABS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
-    GCAllocationStruct::new(
-        Func::new("abs", func_abs,
-            Parameter::new(&[
-            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
-            ])
-        )
-    )
-});
+fn expt_exact(base: Num, power: Num, quota: Option<usize>) -> Result<Num, Exception> {
+    let (negative, even) = match &power {
+        Num::Int(num) => (*num < 0, num % 2 == 0),
+        Num::Big(num) => (num.is_negative(), num.is_even()),
+        _ => unreachable!(),
+    };
+
+    //底が0と±1の場合は、計算しなくても結果が決まる
+    match &base {
+        Num::Int(0) if power.is_zero() => return Ok(Num::Int(1)),
+        Num::Int(0) if negative => return div(Num::Int(1), Num::Int(0)),
+        Num::Int(0) => return Ok(Num::Int(0)),
+        Num::Int(1) => return Ok(Num::Int(1)),
+        Num::Int(-1) => return Ok(Num::Int(if even { 1 } else { -1 })),
+        _ => { }
+    }
+
+    //i64に収まらない指数は、それだけで結果がヒープに収まらない
+    let abs = match &power {
+        Num::Int(num) => num.unsigned_abs() as u128,
+        _ => u128::MAX,
+    };
+    let (numerator, denominator) = base.into_ratio();
+
+    //計算結果がヒープに収まらないほど大きくなる場合は、計算を始める前に失敗させる
+    //(結果のビット数は少なくとも (底のビット数 - 1) * 指数 になる)
+    let too_large = || -> Exception { OutOfMemory { quota_exceeded: quota.is_some() }.into() };
+    let bit_len = numerator.bit_len().max(denominator.bit_len());
+    let bytes = (bit_len.saturating_sub(1) as u128).saturating_mul(abs) / 8;
+    if (quota.unwrap_or(mm::MAX_HEAP_SIZE) as u128) < bytes {
+        return Err(too_large());
+    }
+    let abs = u32::try_from(abs).map_err(|_| too_large())?;
+
+    let result = Num::ratio(numerator.pow(abs), denominator.pow(abs));
+    if negative {
+        div(Num::Int(1), result)
+    } else {
+        Ok(result)
+    }
+}
+
+fn fold_numbers(num_rest: usize, obj: &mut Object, op: fn(Num, Num) -> Result<Num, Exception>) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let mut acc = number_to(v.as_ref());
+
+    for index in 0 .. num_rest {
+        let v = vm::refer_rest_arg::<Any>(1, index, obj);
+        acc = op(acc, number_to(v.as_ref()))?;
+    }
+
+    let num = acc.into_value(obj)?;
+    Ok(num)
+}
+
+fn func_add(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    fold_numbers(num_rest, obj, |a, b| Ok(add(a, b)))
+}
+
+fn func_sub(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    if num_rest == 0 {
+        let v = vm::refer_arg::<Any>(0, obj);
+        let num = negate(number_to(v.as_ref())).into_value(obj)?;
+        Ok(num)
+    } else {
+        fold_numbers(num_rest, obj, |a, b| Ok(sub(a, b)))
+    }
+}
+
+fn func_mul(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    fold_numbers(num_rest, obj, |a, b| Ok(mul(a, b)))
+}
+
+fn func_div(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    if num_rest == 0 {
+        //引数が一つなら逆数
+        let v = vm::refer_arg::<Any>(0, obj);
+        let num = div(Num::Int(1), number_to(v.as_ref()))?.into_value(obj)?;
+        Ok(num)
+    } else {
+        fold_numbers(num_rest, obj, div)
+    }
+}
+
+fn func_abs(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg(0, obj);
+
+    let num = number_to(v.as_ref());
+    let num = if compare(&num, &Num::Int(0)) == Some(Ordering::Less) {
+        negate(num)
+    } else {
+        num
+    };
+
+    let num = num.into_value(obj)?;
+    Ok(num)
+}
+
+#[derive(Clone, Copy)]
+enum IntegerDivision {
+    Quotient,
+    Remainder,
+    Modulo,
+}
+
+fn integer_division(obj: &mut Object, division: IntegerDivision) -> NResult<Any, Exception> {
+    let a = number_to(vm::refer_arg::<Any>(0, obj).as_ref());
+    let b = number_to(vm::refer_arg::<Any>(1, obj).as_ref());
+
+    if b.is_zero() {
+        return Err(Exception::DivisionByZero);
+    }
+
+    let num = match (a, b) {
+        //-1での除算はi64の範囲を超える可能性があるので多倍長で計算する
+        (Num::Int(a), Num::Int(b)) if b != -1 => Num::Int(match division {
+            IntegerDivision::Quotient => a / b,
+            IntegerDivision::Remainder => a % b,
+            IntegerDivision::Modulo => {
+                let rem = a % b;
+                if rem != 0 && (rem < 0) != (b < 0) { rem + b } else { rem }
+            }
+        }),
+        (a, b) => {
+            let (a, b) = (a.into_big(), b.into_big());
+            Num::big(match division {
+                IntegerDivision::Quotient => a.divrem(&b).0,
+                IntegerDivision::Remainder => a.divrem(&b).1,
+                IntegerDivision::Modulo => a.mod_floor(&b),
+            })
+        }
+    };
+
+    let num = num.into_value(obj)?;
+    Ok(num)
+}
+
+fn func_quotient(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    integer_division(obj, IntegerDivision::Quotient)
+}
+
+fn func_remainder(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    integer_division(obj, IntegerDivision::Remainder)
+}
+
+fn func_modulo(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    integer_division(obj, IntegerDivision::Modulo)
+}
+
+fn compare_numbers(num_rest: usize, obj: &mut Object, pred: fn(Ordering) -> bool) -> NResult<Any, Exception> {
+    let mut left = number_to(vm::refer_arg::<Any>(0, obj).as_ref());
+
+    //隣り合うすべての組が条件を満たすか確認する
+    for index in 0 .. num_rest + 1 {
+        let right = if index == 0 {
+            vm::refer_arg::<Any>(1, obj)
+        } else {
+            vm::refer_rest_arg::<Any>(2, index - 1, obj)
+        };
+        let right = number_to(right.as_ref());
+
+        //NaNとの比較は常に偽
+        if !compare(&left, &right).is_some_and(pred) {
+            return Ok(bool::Bool::false_().into_ref().into_value());
+        }
+        left = right;
+    }
+
+    Ok(bool::Bool::true_().into_ref().into_value())
+}
+
+fn func_lt(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    compare_numbers(num_rest, obj, |ord| ord == Ordering::Less)
+}
+
+fn func_le(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    compare_numbers(num_rest, obj, |ord| ord != Ordering::Greater)
+}
+
+fn func_gt(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    compare_numbers(num_rest, obj, |ord| ord == Ordering::Greater)
+}
+
+fn func_ge(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    compare_numbers(num_rest, obj, |ord| ord != Ordering::Less)
+}
+
+fn select_number(num_rest: usize, obj: &mut Object, select: Ordering) -> NResult<Any, Exception> {
+    let mut result = number_to(vm::refer_arg::<Any>(0, obj).as_ref());
+    let mut inexact = result.is_real();
+
+    for index in 0 .. num_rest {
+        let v = number_to(vm::refer_rest_arg::<Any>(1, index, obj).as_ref());
+        inexact |= v.is_real();
+        if compare(&v, &result) == Some(select) {
+            result = v;
+        }
+    }
+
+    //一つでも不正確な数が含まれていれば結果も不正確な数になる
+    if inexact {
+        result = Num::Real(result.to_f64());
+    }
+
+    let num = result.into_value(obj)?;
+    Ok(num)
+}
+
+fn func_min(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    select_number(num_rest, obj, Ordering::Less)
+}
+
+fn func_max(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    select_number(num_rest, obj, Ordering::Greater)
+}
+
+fn round_number(obj: &mut Object, rounding: Rounding) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let num = round(number_to(v.as_ref()), rounding).into_value(obj)?;
+    Ok(num)
+}
+
+fn func_floor(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    round_number(obj, Rounding::Floor)
+}
+
+fn func_ceiling(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    round_number(obj, Rounding::Ceiling)
+}
+
+fn func_round(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    round_number(obj, Rounding::Round)
+}
+
+fn func_truncate(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    round_number(obj, Rounding::Truncate)
+}
+
+fn func_numerator(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let (numerator, _) = number_to(v.as_ref()).into_ratio();
+    let num = make_bigint(&numerator, obj)?;
+    Ok(num)
+}
+
+fn func_denominator(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let (_, denominator) = number_to(v.as_ref()).into_ratio();
+    let num = make_bigint(&denominator, obj)?;
+    Ok(num)
+}
+
+fn func_sqrt(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let num = sqrt(number_to(v.as_ref())).into_value(obj)?;
+    Ok(num)
+}
+
+fn func_expt(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let base = number_to(vm::refer_arg::<Any>(0, obj).as_ref());
+    let power = number_to(vm::refer_arg::<Any>(1, obj).as_ref());
+    let num = expt(base, power, obj.memory_limit())?.into_value(obj)?;
+    Ok(num)
+}
+
+fn real_function(obj: &mut Object, func: fn(f64) -> f64) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    let num = Real::alloc(func(number_to(v.as_ref()).to_f64()), obj)?;
+    Ok(num.into_value())
+}
+
+//省略可能な数値の引数を取得する。省略されている場合はUnitが入っている。
+fn optional_number(index: usize, obj: &mut Object) -> Option<f64> {
+    let v = vm::refer_arg::<Any>(index, obj);
+    if v.as_ref().is::<Number>() {
+        Some(number_to(v.as_ref()).to_f64())
+    } else {
+        None
+    }
+}
+
+fn func_exp(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::exp)
+}
+
+fn func_log(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let num = number_to(vm::refer_arg::<Any>(0, obj).as_ref()).to_f64();
+    let num = match optional_number(1, obj) {
+        Some(base) => num.ln() / base.ln(),
+        None => num.ln(),
+    };
+
+    let num = Real::alloc(num, obj)?;
+    Ok(num.into_value())
+}
+
+fn func_sin(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::sin)
+}
+
+fn func_cos(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::cos)
+}
+
+fn func_tan(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::tan)
+}
+
+fn func_asin(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::asin)
+}
+
+fn func_acos(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    real_function(obj, f64::acos)
+}
+
+fn func_atan(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let y = number_to(vm::refer_arg::<Any>(0, obj).as_ref()).to_f64();
+    //引数が二つならy/xの偏角を求める
+    let num = match optional_number(1, obj) {
+        Some(x) => y.atan2(x),
+        None => y.atan(),
+    };
+
+    let num = Real::alloc(num, obj)?;
+    Ok(num.into_value())
+}
+
+static FUNC_ADD: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("+", func_add,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SUB: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("-", func_sub,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ABS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("abs", func_abs,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_MUL: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("*", func_mul,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_DIV: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("/", func_div,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_QUOTIENT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("quotient", func_quotient,
+            Parameter::new(&[
+            Param::new("n1", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("n2", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_REMAINDER: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("remainder", func_remainder,
+            Parameter::new(&[
+            Param::new("n1", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("n2", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_MODULO: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("modulo", func_modulo,
+            Parameter::new(&[
+            Param::new("n1", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("n2", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_LT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("<", func_lt,
+            Parameter::new(&[
+            Param::new("left", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("right", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_LE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("<=", func_le,
+            Parameter::new(&[
+            Param::new("left", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("right", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_GT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new(">", func_gt,
+            Parameter::new(&[
+            Param::new("left", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("right", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_GE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new(">=", func_ge,
+            Parameter::new(&[
+            Param::new("left", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("right", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_MIN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("min", func_min,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_MAX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("max", func_max,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("rest", ParamKind::Rest, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_FLOOR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("floor", func_floor,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_CEILING: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("ceiling", func_ceiling,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ROUND: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("round", func_round,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_TRUNCATE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("truncate", func_truncate,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_NUMERATOR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("numerator", func_numerator,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Rational::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_DENOMINATOR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("denominator", func_denominator,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Rational::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SQRT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("sqrt", func_sqrt,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_EXPT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("expt", func_expt,
+            Parameter::new(&[
+            Param::new("base", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("power", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_EXP: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("exp", func_exp,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_LOG: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("log", func_log,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("base", ParamKind::Optional, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SIN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("sin", func_sin,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_COS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("cos", func_cos,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_TAN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("tan", func_tan,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ASIN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("asin", func_asin,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ACOS: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("acos", func_acos,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_ATAN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("atan", func_atan,
+            Parameter::new(&[
+            Param::new("y", ParamKind::Require, number::Number::typeinfo()),
+            Param::new("x", ParamKind::Optional, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
 
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("+", &Ref::new(&FUNC_ADD.value));
     obj.define_global_value("-", &Ref::new(&FUNC_SUB.value));
     obj.define_global_value("abs", &Ref::new(&FUNC_ABS.value));
+    obj.define_global_value("*", &Ref::new(&FUNC_MUL.value));
+    obj.define_global_value("/", &Ref::new(&FUNC_DIV.value));
+    obj.define_global_value("quotient", &Ref::new(&FUNC_QUOTIENT.value));
+    obj.define_global_value("remainder", &Ref::new(&FUNC_REMAINDER.value));
+    obj.define_global_value("modulo", &Ref::new(&FUNC_MODULO.value));
+    obj.define_global_value("<", &Ref::new(&FUNC_LT.value));
+    obj.define_global_value("<=", &Ref::new(&FUNC_LE.value));
+    obj.define_global_value(">", &Ref::new(&FUNC_GT.value));
+    obj.define_global_value(">=", &Ref::new(&FUNC_GE.value));
+    obj.define_global_value("min", &Ref::new(&FUNC_MIN.value));
+    obj.define_global_value("max", &Ref::new(&FUNC_MAX.value));
+    obj.define_global_value("floor", &Ref::new(&FUNC_FLOOR.value));
+    obj.define_global_value("ceiling", &Ref::new(&FUNC_CEILING.value));
+    obj.define_global_value("round", &Ref::new(&FUNC_ROUND.value));
+    obj.define_global_value("truncate", &Ref::new(&FUNC_TRUNCATE.value));
+    obj.define_global_value("numerator", &Ref::new(&FUNC_NUMERATOR.value));
+    obj.define_global_value("denominator", &Ref::new(&FUNC_DENOMINATOR.value));
+    obj.define_global_value("sqrt", &Ref::new(&FUNC_SQRT.value));
+    obj.define_global_value("expt", &Ref::new(&FUNC_EXPT.value));
+    obj.define_global_value("exp", &Ref::new(&FUNC_EXP.value));
+    obj.define_global_value("log", &Ref::new(&FUNC_LOG.value));
+    obj.define_global_value("sin", &Ref::new(&FUNC_SIN.value));
+    obj.define_global_value("cos", &Ref::new(&FUNC_COS.value));
+    obj.define_global_value("tan", &Ref::new(&FUNC_TAN.value));
+    obj.define_global_value("asin", &Ref::new(&FUNC_ASIN.value));
+    obj.define_global_value("acos", &Ref::new(&FUNC_ACOS.value));
+    obj.define_global_value("atan", &Ref::new(&FUNC_ATAN.value));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::exec;
+    use crate::eval::tests::check;
+    use crate::value::*;
+    use crate::object::Object;
+
+    #[test]
+    fn test_arithmetic() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(* 2 3 4)", "24", obj, ans_obj);
+        check("(- 10 1 2)", "7", obj, ans_obj);
+        check("(- 5)", "-5", obj, ans_obj);
+        check("(/ 6 3)", "2", obj, ans_obj);
+        check("(/ 1 3)", "1/3", obj, ans_obj);
+        check("(/ 4)", "1/4", obj, ans_obj);
+        check("(+ 1/3 2/3)", "1", obj, ans_obj);
+        check("(* 2/3 3/4)", "1/2", obj, ans_obj);
+        check("(- 1/2 1)", "-1/2", obj, ans_obj);
+        check("(/ 1.0 4)", "0.25", obj, ans_obj);
+        check("(+ 1/2 0.25)", "0.75", obj, ans_obj);
+
+        check("(quotient 17 -5)", "-3", obj, ans_obj);
+        check("(remainder -17 5)", "-2", obj, ans_obj);
+        check("(modulo -17 5)", "3", obj, ans_obj);
+        check("(modulo 17 -5)", "-3", obj, ans_obj);
+
+        check("(try (/ 1 0) (catch @e (error-kind e)))", ":division-by-zero", obj, ans_obj);
+        check("(try (modulo 1 0) (catch @e (error-kind e)))", ":division-by-zero", obj, ans_obj);
+    }
+
+    #[test]
+    fn test_bignum() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        //i64の範囲を超えると多倍長整数になる
+        check("(+ 9223372036854775807 1)", "9223372036854775808", obj, ans_obj);
+        check("(- -9223372036854775808 1)", "-9223372036854775809", obj, ans_obj);
+        check("(- -9223372036854775808)", "9223372036854775808", obj, ans_obj);
+        check("(* 99999999999 99999999999 99999999999)", "999999999970000000000299999999999", obj, ans_obj);
+        check("(expt 2 100)", "1267650600228229401496703205376", obj, ans_obj);
+        check("(quotient (expt 10 30) 7)", "142857142857142857142857142857", obj, ans_obj);
+        check("(modulo (- (expt 10 30)) 7)", "6", obj, ans_obj);
+
+        //範囲内に戻ればFixnumになる
+        check("(- (+ 9223372036854775807 1) 1)", "9223372036854775807", obj, ans_obj);
+        let result = exec::<Any>("(/ (expt 2 100) (expt 2 98))", obj);
+        assert!(result.as_ref().is::<number::Fixnum>());
+
+        check("(= (expt 2 64) 18446744073709551616)", "true", obj, ans_obj);
+        check("(< (expt 2 64) (expt 2 65))", "true", obj, ans_obj);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(< 1 2 3)", "true", obj, ans_obj);
+        check("(< 1 3 2)", "false", obj, ans_obj);
+        check("(<= 1 1 2)", "true", obj, ans_obj);
+        check("(> 3 2 1)", "true", obj, ans_obj);
+        check("(>= 3 3 4)", "false", obj, ans_obj);
+        check("(< 1/3 0.5)", "true", obj, ans_obj);
+        check("(= 1/2 0.5)", "true", obj, ans_obj);
+        check("(min 3 1 2)", "1", obj, ans_obj);
+        check("(max 1/2 1/3)", "1/2", obj, ans_obj);
+        check("(max 1 2.5)", "2.5", obj, ans_obj);
+
+        //上限付きのループ
+        exec::<Any>("(let sum (fun (i acc) (if (< i 10) (sum (+ i 1) (+ acc i)) acc)))", obj);
+        check("(sum 0 0)", "45", obj, ans_obj);
+    }
+
+    #[test]
+    fn test_rounding_and_functions() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check("(floor 7/2)", "3", obj, ans_obj);
+        check("(ceiling -7/2)", "-3", obj, ans_obj);
+        check("(truncate -7/2)", "-3", obj, ans_obj);
+        check("(round 5/2)", "2", obj, ans_obj);
+        check("(round 7/2)", "4", obj, ans_obj);
+        check("(round -5/2)", "-2", obj, ans_obj);
+        check("(floor 2.5)", "2", obj, ans_obj);
+        check("(round 3.5)", "4", obj, ans_obj);
+        check("(numerator 6/4)", "3", obj, ans_obj);
+        check("(denominator 6/4)", "2", obj, ans_obj);
+        check("(abs -1/2)", "1/2", obj, ans_obj);
+
+        check("(sqrt 16)", "4", obj, ans_obj);
+        assert!(exec::<Any>("(sqrt 16)", obj).as_ref().is::<number::Integer>());
+        check("(sqrt 9/4)", "3/2", obj, ans_obj);
+        check("(sqrt 2.25)", "1.5", obj, ans_obj);
+        check("(expt 2 -2)", "1/4", obj, ans_obj);
+        check("(expt 2/3 2)", "4/9", obj, ans_obj);
+        check("(expt 2.0 3)", "8", obj, ans_obj);
+        check("(expt 4 0.5)", "2", obj, ans_obj);
+        //結果が大きくなりすぎる計算は、計算を始める前にエラーになる
+        check("(try (expt 3 4000000000) (catch @e (error-kind e)))", ":out-of-memory", obj, ans_obj);
+        obj.set_memory_limit(Some(1024 * 1024));
+        check("(try (expt 3 100000000) (catch @e (error-kind e)))", ":memory-quota-exceeded", obj, ans_obj);
+        check("(try (expt 2 10000000000) (catch @e (error-kind e)))", ":memory-quota-exceeded", obj, ans_obj);
+        obj.set_memory_limit(None);
+        //u32に収まらない指数でも、正確な数の計算として扱う
+        check("(try (expt 2 10000000000) (catch @e (error-kind e)))", ":out-of-memory", obj, ans_obj);
+        check("(try (expt 2 -10000000000) (catch @e (error-kind e)))", ":out-of-memory", obj, ans_obj);
+        check("(try (expt 1/2 100000000000000000000) (catch @e (error-kind e)))", ":out-of-memory", obj, ans_obj);
+        check("(expt 0 10000000000)", "0", obj, ans_obj);
+        check("(expt 1 -10000000000)", "1", obj, ans_obj);
+        check("(expt -1 10000000001)", "-1", obj, ans_obj);
+        check("(expt -1 100000000000000000000)", "1", obj, ans_obj);
+        check("(try (expt 0 -10000000000) (catch @e (error-kind e)))", ":division-by-zero", obj, ans_obj);
+        check("(exp 0)", "1", obj, ans_obj);
+        check("(log 1)", "0", obj, ans_obj);
+        check("(log 8 2)", "3", obj, ans_obj);
+        check("(sin 0)", "0", obj, ans_obj);
+        check("(cos 0)", "1", obj, ans_obj);
+        check("(atan 0 1)", "0", obj, ans_obj);
+    }
 }
@@ -0,0 +1,484 @@
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+const BASE: u64 = 1 << 32;
+
+///
+/// 任意精度の整数。
+/// 絶対値を32bitごとの桁に分けて、下位の桁から順に保持する。
+/// 0は桁を持たず、符号は常に正になるように正規化されている。
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        BigInt {
+            negative: false,
+            digits: Vec::new(),
+        }
+    }
+
+    pub fn from_parts(negative: bool, digits: Vec<u32>) -> Self {
+        let mut digits = digits;
+        trim(&mut digits);
+
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    pub fn from_i64(num: i64) -> Self {
+        let abs = num.unsigned_abs();
+        Self::from_parts(num < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+
+    pub fn parse(str: &str) -> Option<Self> {
+        let (negative, body) = match str.as_bytes().first() {
+            Some(b'-') => (true, &str[1..]),
+            Some(b'+') => (false, &str[1..]),
+            _ => (false, str),
+        };
+
+        if body.is_empty() || !body.bytes().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+
+        //10進数の各桁を上位から順に掛け合わせる
+        let mut digits: Vec<u32> = Vec::new();
+        for ch in body.bytes() {
+            let mut carry = (ch - b'0') as u64;
+            for digit in digits.iter_mut() {
+                let v = *digit as u64 * 10 + carry;
+                *digit = v as u32;
+                carry = v >> 32;
+            }
+            if carry != 0 {
+                digits.push(carry as u32);
+            }
+        }
+
+        Some(Self::from_parts(negative, digits))
+    }
+
+    pub fn digits(&self) -> &[u32] {
+        &self.digits
+    }
+
+    ///
+    /// 絶対値を表すのに必要なビット数を返す。0の場合は0になる。
+    pub fn bit_len(&self) -> u64 {
+        match self.digits.last() {
+            Some(top) => self.digits.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_even(&self) -> bool {
+        self.digits.first().is_none_or(|digit| digit & 1 == 0)
+    }
+
+    pub fn is_one(&self) -> bool {
+        !self.negative && self.digits == [1]
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if 2 < self.digits.len() {
+            return None;
+        }
+
+        let abs = self.digits.iter().rev().fold(0u64, |acc, digit| (acc << 32) | *digit as u64);
+        if self.negative {
+            if abs <= i64::MAX as u64 + 1 {
+                Some((abs as i64).wrapping_neg())
+            } else {
+                None
+            }
+        } else if abs <= i64::MAX as u64 {
+            Some(abs as i64)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let abs = self.digits.iter().rev().fold(0f64, |acc, digit| acc * BASE as f64 + *digit as f64);
+        if self.negative { -abs } else { abs }
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::from_parts(!self.negative, self.digits.clone())
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::from_parts(false, self.digits.clone())
+    }
+
+    pub fn add(&self, other: &BigInt) -> Self {
+        if self.negative == other.negative {
+            Self::from_parts(self.negative, add_digits(&self.digits, &other.digits))
+        } else {
+            //符号が異なる場合は絶対値の大きい方から小さい方を引く
+            match cmp_digits(&self.digits, &other.digits) {
+                Ordering::Less => Self::from_parts(other.negative, sub_digits(&other.digits, &self.digits)),
+                _ => Self::from_parts(self.negative, sub_digits(&self.digits, &other.digits)),
+            }
+        }
+    }
+
+    pub fn sub(&self, other: &BigInt) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &BigInt) -> Self {
+        Self::from_parts(self.negative != other.negative, mul_digits(&self.digits, &other.digits))
+    }
+
+    ///
+    /// 0方向に丸めた商と、被除数と同じ符号を持つ余りを返す。
+    /// 除数が0でないことは呼び出し側で確認しておくこと。
+    pub fn divrem(&self, other: &BigInt) -> (Self, Self) {
+        let (quotient, remainder) = divrem_digits(&self.digits, &other.digits);
+        (
+            Self::from_parts(self.negative != other.negative, quotient),
+            Self::from_parts(self.negative, remainder),
+        )
+    }
+
+    ///
+    /// 負の無限大方向に丸めた商を返す。
+    pub fn div_floor(&self, other: &BigInt) -> Self {
+        let (quotient, remainder) = self.divrem(other);
+        if !remainder.is_zero() && remainder.negative != other.negative {
+            quotient.sub(&Self::from_i64(1))
+        } else {
+            quotient
+        }
+    }
+
+    ///
+    /// 除数と同じ符号を持つ余りを返す。
+    pub fn mod_floor(&self, other: &BigInt) -> Self {
+        let (_, remainder) = self.divrem(other);
+        if !remainder.is_zero() && remainder.negative != other.negative {
+            remainder.add(other)
+        } else {
+            remainder
+        }
+    }
+
+    pub fn gcd(&self, other: &BigInt) -> Self {
+        let mut a = self.abs();
+        let mut b = other.abs();
+        while !b.is_zero() {
+            let (_, remainder) = a.divrem(&b);
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    pub fn pow(&self, exp: u32) -> Self {
+        let mut result = Self::from_i64(1);
+        let mut base = self.clone();
+        let mut exp = exp;
+        while 0 < exp {
+            if exp & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exp >>= 1;
+            if 0 < exp {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+
+    ///
+    /// 平方根の整数部分を返す。負の値に対して呼び出さないこと。
+    pub fn sqrt_floor(&self) -> Self {
+        if self.is_zero() {
+            return Self::zero();
+        }
+
+        //平方根以上であることが分かっている2^(32 * 桁数の半分)から、ニュートン法で上から近づける
+        let mut x = Self::from_parts(false, {
+            let mut digits = vec![0u32; self.digits.len().div_ceil(2) + 1];
+            *digits.last_mut().unwrap() = 1;
+            digits
+        });
+        loop {
+            let (quotient, _) = self.divrem(&x);
+            let (next, _) = x.add(&quotient).divrem(&Self::from_i64(2));
+            if x <= next {
+                return x;
+            }
+            x = next;
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_digits(&self.digits, &other.digits),
+            (true, true) => cmp_digits(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        //10^9ごとに区切って下位から取り出す
+        let mut chunks: Vec<u32> = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let (quotient, remainder) = divrem_small(&digits, 1_000_000_000);
+            chunks.push(remainder);
+            digits = quotient;
+        }
+
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", chunks.last().unwrap())?;
+        for chunk in chunks.iter().rev().skip(1) {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+
+fn cmp_digits(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() < b.len() { (b, a) } else { (a, b) };
+
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (index, digit) in long.iter().enumerate() {
+        let v = *digit as u64 + short.get(index).copied().unwrap_or(0) as u64 + carry;
+        result.push(v as u32);
+        carry = v >> 32;
+    }
+    if carry != 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+//a >= bであること
+fn sub_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (index, digit) in a.iter().enumerate() {
+        let v = *digit as i64 - b.get(index).copied().unwrap_or(0) as i64 - borrow;
+        result.push(v as u32);
+        borrow = if v < 0 { 1 } else { 0 };
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let v = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = v as u32;
+            carry = v >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+fn divrem_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = 0u64;
+    for index in (0 .. a.len()).rev() {
+        let v = (remainder << 32) | a[index] as u64;
+        quotient[index] = (v / divisor as u64) as u32;
+        remainder = v % divisor as u64;
+    }
+    trim(&mut quotient);
+    (quotient, remainder as u32)
+}
+
+fn shift_left(a: &[u32], shift: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for digit in a.iter() {
+        if shift == 0 {
+            result.push(*digit);
+        } else {
+            result.push((*digit << shift) | carry);
+            carry = *digit >> (32 - shift);
+        }
+    }
+    result.push(carry);
+    result
+}
+
+fn shift_right(a: &[u32], shift: u32) -> Vec<u32> {
+    let mut result = vec![0u32; a.len()];
+    for index in 0 .. a.len() {
+        if shift == 0 {
+            result[index] = a[index];
+        } else {
+            let high = a.get(index + 1).copied().unwrap_or(0);
+            result[index] = (a[index] >> shift) | (high << (32 - shift));
+        }
+    }
+    trim(&mut result);
+    result
+}
+
+//KnuthのAlgorithm Dによる多倍長の除算
+fn divrem_digits(u: &[u32], v: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_digits(u, v) == Ordering::Less {
+        return (Vec::new(), u.to_vec());
+    }
+    if v.len() == 1 {
+        let (quotient, remainder) = divrem_small(u, v[0]);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+
+    //除数の最上位桁の最上位bitが立つように正規化する
+    let shift = v.last().unwrap().leading_zeros();
+    let mut vn = shift_left(v, shift);
+    vn.pop();
+    let mut un = shift_left(u, shift);
+
+    let n = v.len();
+    let m = u.len() - n;
+    let mut quotient = vec![0u32; m + 1];
+
+    for j in (0 ..= m).rev() {
+        //商の一桁を上位2桁から見積もる
+        let num = ((un[j + n] as u64) << 32) | un[j + n - 1] as u64;
+        let mut qhat = num / vn[n - 1] as u64;
+        let mut rhat = num % vn[n - 1] as u64;
+        while qhat >= BASE || qhat * vn[n - 2] as u64 > ((rhat << 32) | un[j + n - 2] as u64) {
+            qhat -= 1;
+            rhat += vn[n - 1] as u64;
+            if rhat >= BASE {
+                break;
+            }
+        }
+
+        //見積もった商と除数の積を引く
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0 .. n {
+            let p = qhat * vn[i] as u64 + carry;
+            carry = p >> 32;
+            let t = un[i + j] as i64 - borrow - (p & 0xFFFF_FFFF) as i64;
+            un[i + j] = t as u32;
+            borrow = if t < 0 { 1 } else { 0 };
+        }
+        let t = un[j + n] as i64 - borrow - carry as i64;
+        un[j + n] = t as u32;
+
+        //引きすぎた場合は一度だけ足し戻す
+        if t < 0 {
+            qhat -= 1;
+            let mut carry = 0u64;
+            for i in 0 .. n {
+                let s = un[i + j] as u64 + vn[i] as u64 + carry;
+                un[i + j] = s as u32;
+                carry = s >> 32;
+            }
+            un[j + n] = un[j + n].wrapping_add(carry as u32);
+        }
+
+        quotient[j] = qhat as u32;
+    }
+
+    trim(&mut quotient);
+    let remainder = shift_right(&un[.. n], shift);
+    (quotient, remainder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(str: &str) -> BigInt {
+        BigInt::parse(str).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = big("123456789012345678901234567890");
+        let b = big("-987654321098765432109876543210");
+
+        assert_eq!(a.to_string(), "123456789012345678901234567890");
+        assert_eq!(b.to_string(), "-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(a.mul(&b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+
+        let (q, r) = b.divrem(&a);
+        assert_eq!(q.to_string(), "-8");
+        assert_eq!(r.to_string(), "-9000000000900000000090");
+        assert_eq!(b.div_floor(&a).to_string(), "-9");
+        assert_eq!(b.mod_floor(&a).to_string(), "123456780012345678001234567800");
+
+        let (q, r) = a.mul(&b).divrem(&b);
+        assert_eq!(q, a);
+        assert!(r.is_zero());
+
+        assert_eq!(big("2").pow(100).to_string(), "1267650600228229401496703205376");
+        assert_eq!(big("1267650600228229401496703205376").sqrt_floor().to_string(), "1125899906842624");
+        assert_eq!(big("16").sqrt_floor().to_string(), "4");
+        assert_eq!(big("15").sqrt_floor().to_string(), "3");
+        assert_eq!(big("12").gcd(&big("-18")).to_string(), "6");
+
+        assert_eq!(BigInt::from_i64(i64::MIN).to_i64(), Some(i64::MIN));
+        assert_eq!(BigInt::from_i64(i64::MAX).add(&big("1")).to_i64(), None);
+        assert!(a < big("123456789012345678901234567891"));
+        assert!(b < a);
+        assert_eq!(big("-0"), BigInt::zero());
+    }
+}
@@ -64,6 +64,11 @@ impl ObjectRef {
         mailbox.recv_message(msg, reply_to_mailbox)
     }
 
+    pub fn recv_cast(&self, msg: MessageKind) -> Result<(), OutOfMemory> {
+        let mut mailbox = self.mailbox.lock().unwrap();
+        mailbox.recv_cast(msg)
+    }
+
     fn finalize(&mut self) {
         unsafe {
             std::ptr::drop_in_place(self)
@@ -92,15 +97,28 @@ impl Debug for ObjectRef {
     }
 }
 
-fn func_spawn(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
-    if let Some(target_obj) = vm::refer_arg::<Any>(0, obj).try_cast::<ObjectRef>() {
-        let target_obj = target_obj.clone().reach(obj);
+fn func_spawn(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    //先頭の引数がObjectなら、そのObjectの複製を作成する
+    let target_obj = if 0 < num_rest {
+        vm::refer_rest_arg::<Any>(0, 0, obj).try_cast::<ObjectRef>().cloned()
+    } else {
+        None
+    };
+
+    if let Some(target_obj) = target_obj {
+        if 1 < num_rest {
+            //複製されたObjectはスケジューリングの設定も複製元から引き継ぐ
+            return Err(Exception::Other("spawn options can not be specified when duplicating an object.".to_string()));
+        }
+
+        let target_obj = target_obj.reach(obj);
         let message = MessageKind::Duplicate;
 
         obj.send_message(&target_obj, message)
 
     } else {
-        let standalone = object::new_object();
+        let mut standalone = object::new_object();
+        apply_spawn_options(num_rest, standalone.mut_object(), obj)?;
 
         let id = standalone.object().id();
 
@@ -113,6 +131,84 @@ fn func_spawn(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
     }
 }
 
+//(spawn :priority :high :reduction-budget 2000 :heap-size 65536 :memory-limit 1048576)のような、
+//新しいObjectに対するオプション指定を反映させる
+fn apply_spawn_options(num_rest: usize, new_obj: &mut Object, obj: &mut Object) -> Result<(), Exception> {
+    if !num_rest.is_multiple_of(2) {
+        return Err(Exception::Other("spawn options must be keyword and value pairs.".to_string()));
+    }
+
+    for index in (0..num_rest).step_by(2) {
+        let key = vm::refer_rest_arg::<Any>(0, index, obj);
+        let value = vm::refer_rest_arg::<Any>(0, index + 1, obj);
+
+        let key = match key.try_cast::<keyword::Keyword>() {
+            Some(key) => key,
+            None => {
+                return Err(Exception::TypeMismatch(TypeMismatch::new(key, keyword::Keyword::typeinfo())));
+            }
+        };
+
+        match key.as_ref().as_ref() {
+            "priority" => {
+                match value.try_cast::<keyword::Keyword>() {
+                    Some(priority) => {
+                        new_obj.set_priority(object::keyword_to_priority(priority.as_ref())?);
+                    }
+                    None => {
+                        return Err(Exception::TypeMismatch(TypeMismatch::new(value, keyword::Keyword::typeinfo())));
+                    }
+                }
+            }
+            "reduction-budget" => {
+                match value.try_cast::<number::Integer>() {
+                    Some(budget) => {
+                        new_obj.set_reduction_budget(object::integer_to_reduction_budget(budget.as_ref())?);
+                    }
+                    None => {
+                        return Err(Exception::TypeMismatch(TypeMismatch::new(value, number::Integer::typeinfo())));
+                    }
+                }
+            }
+            "heap-size" => {
+                match value.try_cast::<number::Integer>() {
+                    Some(size) => {
+                        new_obj.reserve_heap(object::integer_to_heap_size(size.as_ref())?)?;
+                    }
+                    None => {
+                        return Err(Exception::TypeMismatch(TypeMismatch::new(value, number::Integer::typeinfo())));
+                    }
+                }
+            }
+            "memory-limit" => {
+                match value.try_cast::<number::Integer>() {
+                    Some(limit) => {
+                        new_obj.set_memory_limit(Some(object::integer_to_memory_limit(limit.as_ref())?));
+                    }
+                    None => {
+                        return Err(Exception::TypeMismatch(TypeMismatch::new(value, number::Integer::typeinfo())));
+                    }
+                }
+            }
+            "gc-grow-threshold" => {
+                match value.try_cast::<number::Integer>() {
+                    Some(threshold) => {
+                        new_obj.set_gc_grow_threshold(object::integer_to_grow_threshold(threshold.as_ref())?);
+                    }
+                    None => {
+                        return Err(Exception::TypeMismatch(TypeMismatch::new(value, number::Integer::typeinfo())));
+                    }
+                }
+            }
+            other => {
+                return Err(Exception::Other(format!("unknown spawn option :{}", other)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn func_send(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
     let target_obj = vm::refer_arg::<ObjectRef>(0, obj).reach(obj);
 
@@ -123,11 +219,21 @@ fn func_send(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
     obj.send_message(&target_obj, message)
 }
 
+fn func_cast(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let target_obj = vm::refer_arg::<ObjectRef>(0, obj);
+
+    //返信を受け取らないため、Replyを作成せずにメッセージを送るだけ
+    let message = vm::refer_arg::<Any>(1, obj);
+    target_obj.as_ref().recv_cast(MessageKind::Cast(message))?;
+
+    Ok(tuple::Tuple::unit().into_ref().into_value())
+}
+
 static FUNC_SPAWN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     GCAllocationStruct::new(
         Func::new("spawn", func_spawn,
             Parameter::new(&[
-            Param::new("object", ParamKind::Optional, ObjectRef::typeinfo()),
+            Param::new("object-or-options", ParamKind::Rest, Any::typeinfo()),
             ])
         )
     )
@@ -144,10 +250,22 @@ static FUNC_SEND: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     )
 });
 
+static FUNC_CAST: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("cast", func_cast,
+            Parameter::new(&[
+            Param::new("object", ParamKind::Require, ObjectRef::typeinfo()),
+            Param::new("message", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
 
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("spawn", &Ref::new(&FUNC_SPAWN.value));
     obj.define_global_value("send", &Ref::new(&FUNC_SEND.value));
+    obj.define_global_value("cast", &Ref::new(&FUNC_CAST.value));
 }
 
 #[cfg(test)]
@@ -195,6 +313,9 @@ mod tests {
             let program = "(def-recv (@a @b) (+ a b))";
             exec::<Any>(program, standalone.mut_object());
 
+            let program = "(def-recv #{:k @v} (hash-map-set #{:k v} :double (* v 2)))";
+            exec::<Any>(program, standalone.mut_object());
+
             //操作対象のオブジェクトを最初のオブジェクトに戻す
             standalone = object::return_object_switch(standalone).unwrap();
         }
@@ -279,6 +400,25 @@ mod tests {
             assert_eq!(ans.as_ref().get(), 20);
         }
 
+        {
+            //ハッシュマップの値はReply型の値をそのまま受け取る
+            let program = "(let h (hash-map-set #{:b 0} :a (send obj 1)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(hash-map-ref h :a)";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert!(ans.as_ref().is::<reply::Reply>());
+
+            let program = "(force (hash-map-ref h :a))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 10);
+
+            //ハッシュマップは他のオブジェクトとの間でやり取りできる
+            let program = "(hash-map-ref (force (send obj #{:k 21})) :double)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 42);
+        }
+
     }
 
     #[test]
@@ -357,6 +497,720 @@ mod tests {
             assert_eq!(count_resultbox, 0);
 
         }
+
+        {
+            //返信が届いた後にReplyが削除された場合も、返信は破棄される
+            let program = "(send obj {:add-one 6})";
+            exec::<Any>(program, standalone.mut_object()).capture(standalone.mut_object());
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            standalone.mut_object().do_gc();
+
+            let count_resultbox = {
+                let mailbox = standalone.mailbox().lock().unwrap();
+                mailbox.count_resultbox()
+            };
+            assert_eq!(count_resultbox, 0);
+
+            //受け取られない返信を大量に発生させる
+            let program = "(let loop (fun (n) (if (= n 0) 0 (begin (send obj {:add-one n}) (loop (- n 1))))))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(loop 30)";
+            exec::<Any>(program, standalone.mut_object());
+            standalone.mut_object().do_gc();
+
+            //次の返信を受け取った時点で、破棄された返信はメールボックスに残っていない
+            let program = "(force (send obj {:add-one 1}))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+
+            let count_resultbox = {
+                let mailbox = standalone.mailbox().lock().unwrap();
+                mailbox.count_resultbox()
+            };
+            assert_eq!(count_resultbox, 0);
+        }
+    }
+
+    #[test]
+    fn test_message_order() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(let log (box '()))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv :wait (sleep 50))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:log @n} (set-box! log (cons n (unbox log))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv :get (unbox log))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //処理中に溜まったメッセージは届いた順番に処理される
+            let program = "(list (cast obj :wait) (cast obj {:log 1}) (cast obj {:log 2}) (cast obj {:log 3}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(force (send obj :get))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "(3 2 1)");
+        }
+    }
+
+    #[test]
+    fn test_sleep() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv @n (begin (sleep 50) (+ n 1)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //sleep中のオブジェクトは指定時間経過後にスケジューラから再開される
+            let start = std::time::Instant::now();
+            let program = "(force (send obj 1))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+            assert!(50 <= start.elapsed().as_millis());
+
+            //続けて送ったメッセージも処理される
+            let program = "(+ (send obj 2) (send obj 3))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 7);
+        }
+    }
+
+    #[test]
+    fn test_force_timeout() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv {:slow @n} (begin (sleep 300) n))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv @n (+ n 1))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //指定時間内に返信が届かなければタイムアウトの例外になる
+            let start = std::time::Instant::now();
+            let program = "(try (force (send obj {:slow 1}) :timeout 30) (catch @e (error-kind e)))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "timeout");
+            assert!(start.elapsed().as_millis() < 300);
+
+            //時間内に返信が届けば値を返す
+            let program = "(force (send obj 1) :timeout 1000)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+
+            let program = "(try (force 1 :limit 10) (catch @e (error-kind e)))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "other");
+        }
+
+        {
+            //返信を待たずに状態を確認できる
+            let program = "(let r (send obj {:slow 5}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(list (reply-ready? r) (try-force r :none) (reply-ready? 1) (try-force 1 :none))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "(false :none true 1)");
+
+            let program = "(force r)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 5);
+
+            let program = "(list (reply-ready? r) (try-force r :none))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "(true 5)");
+        }
+
+        {
+            //スケジューラ上で実行されているオブジェクトも、タイムアウトで待機をやめる
+            let program = "(let obj2 (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv {:ask @target} (try (force (send target {:slow 1}) :timeout 30) (catch @e (error-kind e))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+
+            let start = std::time::Instant::now();
+            let program = "(force (send obj2 {:ask obj}))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "timeout");
+            assert!(start.elapsed().as_millis() < 300);
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(let counter (box 0))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:add @n} (set-box! counter (+ (unbox counter) n)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv :get (unbox counter))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //castはReplyを作らずにUnitを返す
+            let program = "(list (cast obj {:add 1}) (cast obj {:add 2}) (cast obj {:add 3}))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "({} {} {})");
+
+            //castのメッセージが処理されるまで待つ
+            let program = "(sleep 100)";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(force (send obj :get))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 6);
+
+            //castに対する返信は届かない
+            let count_resultbox = {
+                let mailbox = standalone.mailbox().lock().unwrap();
+                mailbox.count_resultbox()
+            };
+            assert_eq!(count_resultbox, 0);
+        }
+    }
+
+    #[test]
+    fn test_receive() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv {:start @ms} (receive ({:go @n} (* n 10)) (after ms :timeout)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:noise @n} n)";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv :wait (receive ({:go @n} n) ({:stop} :stopped)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv :check (receive ({:fail} (error :bad \"bad\"))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //マッチしないメッセージを飛ばして、マッチしたメッセージを受け取る
+            let program = "(let r (send obj {:start 1000}))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(let noise (send obj {:noise 1}))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(let go (send obj {:go 4}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(force r)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 40);
+
+            //receiveで受け取ったメッセージの送信元には、マッチした節の本体の結果が返信される
+            let program = "(force go)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 40);
+
+            //飛ばされたメッセージは受信箱に残り、通常のレシーバーで処理される
+            let program = "(force noise)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 1);
+        }
+
+        {
+            //時間内にメッセージが届かなければafter節を実行する
+            let start = std::time::Instant::now();
+            let program = "(force (send obj {:start 30}))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "timeout");
+            assert!(start.elapsed().as_millis() < 1000);
+        }
+
+        {
+            //after節がなければメッセージが届くまで待ち続ける
+            let program = "(let r (send obj :wait))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(sleep 50)";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(reply-ready? r)";
+            let ans = exec::<bool::Bool>(program, standalone.mut_object());
+            assert!(!ans.as_ref().is_true());
+
+            let program = "(cast obj {:stop})";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(force r)";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "stopped");
+        }
+
+        {
+            //本体で発生した例外は、receiveしたメッセージの送信元と外側のメッセージの送信元の両方に返信される
+            let program = "(let r (send obj :check))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(let fail (send obj {:fail}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(match (force fail) (!(:bad) :bad))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "bad");
+
+            let program = "(match (force r) (!(:bad) :bad))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "bad");
+        }
+    }
+
+    #[test]
+    fn test_reply_combinators() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let slow (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv {:slow @n} (begin (sleep 300) n))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            let program = "(let fast (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv {:add-one @n} (+ n 1))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:fail} (error :failed \"failed\" {}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:ask @other @n} (reply-all (list (send other {:slow n}) n)))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:chain @other @n} (reply-then n (fun (v) (force (send other {:slow v})))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //すべての返信がそろってから結果のリストになる
+            let program = "(force (reply-all (list (send fast {:add-one 1}) (send fast {:add-one 2}) 3)))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "(2 3 3)");
+
+            let program = "(reply-all '())";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "()");
+        }
+
+        {
+            //遅い返信を待たずに最初に届いた返信を受け取る
+            let start = std::time::Instant::now();
+            let program = "(force (reply-race (list (send slow {:slow 1}) (send fast {:add-one 1}))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+            assert!(start.elapsed().as_millis() < 300);
+
+            //raceはエラーの返信もそのまま結果にする
+            let program = "(match (reply-race (list (send fast {:fail}))) (!(:failed) :failed))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "failed");
+        }
+
+        {
+            //anyはエラーの返信を飛ばして、最初に成功した返信を受け取る
+            let program = "(force (reply-any (list (send fast {:fail}) (send slow {:slow 5}))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 5);
+
+            //すべてエラーなら最初のエラーになる
+            let program = "(match (reply-any (list (send fast {:fail}) (send fast {:fail}))) (!(:failed) :all-failed))";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "all-failed");
+        }
+
+        {
+            //返信の値に関数を適用する
+            let program = "(force (reply-then (send fast {:add-one 1}) (fun (v) (* v 10))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 20);
+
+            //関数の結果がReplyなら、その返信まで待つ
+            let program = "(force (reply-then (send fast {:add-one 1}) (fun (v) (send fast {:add-one v}))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 3);
+
+            //返信を待つ必要がなければ、その場で関数を適用した値になる
+            let program = "(reply-then 3 (fun (v) (+ v 1)))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 4);
+
+            //関数の中でforceしても、返信が届くまで中断してから続きを実行する
+            let program = "(force (reply-then (send fast {:add-one 1}) (fun (v) (+ (force (send fast {:add-one v})) 100))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 103);
+
+            let program = "(force (reply-then 3 (fun (v) (force (send slow {:slow v})))))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 3);
+
+            //レシーバーの結果として返した場合も、関数内のforceの返信を待ってから返信される
+            let program = "(force (send fast {:chain slow 5}))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 5);
+        }
+
+        {
+            //レシーバーの結果が返信を含む場合は、すべての返信がそろってから返信される
+            let program = "(force (send fast {:ask slow 7}))";
+            let ans = exec::<Any>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().to_string(), "(7 7)");
+        }
+
+        {
+            //reply-thenの関数もオブジェクトのreduction数の範囲で実行され、
+            //一度に実行しきれない場合は中断して次の実行で続きから再開する
+            let program = "(let busy (spawn :reduction-budget 100))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(let-global count (fun (n acc) (if (= n 0) acc (count (- n 1) (+ acc 1)))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(def-recv {:count @n} (reply-then n (fun (v) (count v 1))))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+
+            let program = "(force (send busy {:count 100000}))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 100001);
+        }
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        let mut standalone = object::new_object();
+        //返信を受け取るメールボックスのヒープサイズを制限する
+        standalone.mailbox().lock().unwrap().set_heap_max_size(1024 * 32);
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            //返信を受け取るメールボックスのヒープに収まらないサイズの値を返す
+            let items = (0..250).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+            let program = format!("(def-recv :big '({}))", items);
+            exec::<Any>(&program, standalone.mut_object());
+
+            let program = "(def-recv @n (+ n 1))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            let mut replies: Vec<Cap<reply::Reply>> = (0..6).map(|_| {
+                let program = "(send obj :big)";
+                exec::<reply::Reply>(program, standalone.mut_object()).capture(standalone.mut_object())
+            }).collect();
+
+            //すべての返信がメールボックスに届くまで待つ
+            std::thread::sleep(std::time::Duration::from_millis(100));
+
+            let mut num_oom = 0;
+            for reply in replies.iter_mut() {
+                match get_reply_value(reply, standalone.mut_object()) {
+                    Ok(v) => {
+                        assert!(v.try_cast::<list::List>().is_some());
+                    }
+                    Err(Exception::OutOfMemory) => {
+                        num_oom += 1;
+                    }
+                    Err(e) => {
+                        panic!("unexpected exception {}", e);
+                    }
+                }
+            }
+            //メールボックスのOOMは、送信元へのエラー返信になる
+            assert!(0 < num_oom);
+
+            //OOMが発生した後もオブジェクトはメッセージを処理できる
+            let program = "(force (send obj 1))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+        }
+    }
+
+    #[test]
+    fn test_error_reply() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv @n (error :not-found \"no entry\" {n '(1 2)}))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            let program = "(send obj 3)";
+            let mut reply = exec::<reply::Reply>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            //ペイロードは送信元のヒープへクローンされて返信される
+            match get_reply_value(&mut reply, standalone.mut_object()) {
+                Err(Exception::UserError(err)) => {
+                    assert_eq!(err.kind.as_ref().as_ref(), "not-found");
+                    assert_eq!(AsRef::<str>::as_ref(err.message.as_ref()), "no entry");
+                    assert!(standalone.mut_object().is_in_heap_object(err.payload.as_ref()));
+                }
+                Err(e) => panic!("unexpected exception {}", e),
+                Ok(_) => panic!("error was not replied"),
+            }
+
+            //返信されたエラーはmatchで判別できる
+            let program = "(match (send obj 3) (!(:timeout) 0) (!(:not-found @_ {@n (@_ @m)}) (+ n m)))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 5);
+        }
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let obj (spawn :memory-limit 65536))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(object-memory-limit)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 65536);
+
+            //上限を超えるサイズのリストを作成する
+            let items = (0..400).map(|i| i.to_string()).collect::<Vec<_>>().join(" ");
+            let program = format!("(let xs '({}))", items);
+            exec::<Any>(&program, standalone.mut_object());
+            let program = "(def-recv :big (append xs xs xs xs xs xs xs xs xs xs))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(def-recv @n (+ n 1))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            let program = "(send obj :big)";
+            let mut reply = exec::<reply::Reply>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            //メモリ上限の超過はOutOfMemoryとは別の例外になる
+            match get_reply_value(&mut reply, standalone.mut_object()) {
+                Err(Exception::MemoryQuotaExceeded) => { }
+                Err(e) => panic!("unexpected exception {}", e),
+                Ok(_) => panic!("memory limit was not applied"),
+            }
+
+            //上限を超えた後もオブジェクトはメッセージを処理できる
+            let program = "(force (send obj 1))";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+        }
+
+        let obj_ref = exec::<ObjectRef>("obj", standalone.mut_object()).capture(standalone.mut_object());
+        standalone = object::object_switch(standalone, obj_ref.as_ref()).unwrap();
+        {
+            //上限は後から解除できる
+            let program = "(set-object-memory-limit false)";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(object-memory-limit)";
+            let ans = exec::<bool::Bool>(program, standalone.mut_object());
+            assert!(ans.as_ref().is_false());
+        }
+        standalone = object::return_object_switch(standalone).unwrap();
+
+        let program = "(force (send obj :big))";
+        let ans = exec::<list::List>(program, standalone.mut_object());
+        assert_eq!(ans.count(), 4000);
+    }
+
+    #[test]
+    fn test_spawn_options() {
+        let mut standalone = object::new_object();
+
+        let program = "(let obj (spawn :priority :high :reduction-budget 200))";
+        let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+        standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+        {
+            let program = "(object-priority)";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "high");
+
+            let program = "(object-reduction-budget)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 200);
+
+            //実行中に変更できる
+            let program = "(set-object-priority :low)";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(object-priority)";
+            let ans = exec::<keyword::Keyword>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().as_ref(), "low");
+
+            let program = "(def-recv @n (+ n 1))";
+            exec::<Any>(program, standalone.mut_object());
+        }
+        standalone = object::return_object_switch(standalone).unwrap();
+
+        //変更後の設定でもメッセージは処理される
+        let program = "(force (send obj 1))";
+        let ans = exec::<number::Integer>(program, standalone.mut_object());
+        assert_eq!(ans.as_ref().get(), 2);
+    }
+
+    #[test]
+    fn test_spawn_heap_options() {
+        let mut standalone = object::new_object();
+
+        let program = "(let obj (spawn :heap-size 65536 :gc-grow-threshold 50))";
+        let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+        standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+        {
+            assert!(65536 <= standalone.object().heap_stats().heap_size);
+
+            let gc_count = standalone.object().heap_stats().gc_count;
+            let program = "(gc)";
+            exec::<Any>(program, standalone.mut_object());
+            assert_eq!(standalone.object().heap_stats().gc_count, gc_count + 1);
+
+            //キーワードと値が交互に並んだリストを返す
+            let program = "(gc-stats)";
+            let ans = exec::<list::List>(program, standalone.mut_object());
+            assert_eq!(ans.count(), 22);
+            let key = unsafe { ans.as_ref().head().cast_unchecked::<keyword::Keyword>() }.clone();
+            assert_eq!(key.as_ref().as_ref(), "gc-count");
+        }
+        standalone = object::return_object_switch(standalone).unwrap();
+
+        //範囲外の値はエラーになる
+        let program = "(spawn :gc-grow-threshold 0)";
+        let mut reader = crate::read::Reader::new(program.chars().peekable());
+        let sexp = crate::read::read(&mut reader, standalone.mut_object()).unwrap().reach(standalone.mut_object());
+        assert!(crate::eval::eval(&sexp, standalone.mut_object()).is_err());
+    }
+
+    #[test]
+    fn test_reply_token_collision() {
+        let mut standalone = object::new_object();
+
+        {
+            let program = "(let slow (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv @n (begin (sleep 50) n))";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            let program = "(let fast (spawn))";
+            let new_obj_ref = exec::<ObjectRef>(program, standalone.mut_object()).capture(standalone.mut_object());
+
+            standalone = object::object_switch(standalone, new_obj_ref.as_ref()).unwrap();
+
+            let program = "(def-recv @n n)";
+            exec::<Any>(program, standalone.mut_object());
+
+            standalone = object::return_object_switch(standalone).unwrap();
+        }
+
+        {
+            //異なる送信先への返信は、同じメールボックスに届いても取り違えない
+            let program = "(let r1 (send slow 1))";
+            exec::<Any>(program, standalone.mut_object());
+            let program = "(let r2 (send fast 2))";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(sleep 20)";
+            exec::<Any>(program, standalone.mut_object());
+
+            let program = "(+ r1 0)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 1);
+
+            let program = "(+ r2 0)";
+            let ans = exec::<number::Integer>(program, standalone.mut_object());
+            assert_eq!(ans.as_ref().get(), 2);
+        }
     }
 
     #[test]
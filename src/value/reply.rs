@@ -1,4 +1,4 @@
-use crate::object::mailbox::ReplyToken;
+use crate::object::mailbox::{ReplyToken, DiscardedReplies};
 use crate::ptr::*;
 use crate::err::*;
 use crate::value::*;
@@ -7,8 +7,8 @@ use crate::value::app::{Parameter, ParamKind, Param};
 use std::fmt::{Debug, Display};
 use std::sync::{Arc, Mutex};
 
-//TODO 返信をメールボックスから受け取る前にReplyの値が削除されてしまうと
-//一生メールボックス内に返信が残り続けてしまう。どうにかして解決しないと。
+//返信をメールボックスから受け取る前にReplyの値が削除された場合は、
+//finalizeでReplyTokenを破棄済みとして登録し、メールボックス内に返信が残り続けないようにする。
 
 pub struct Reply {
     reply_token: ReplyToken,
@@ -17,6 +17,7 @@ pub struct Reply {
 
     myself_mailbox: Option<Arc<Mutex<crate::object::mailbox::MailBox>>>,
     dest_mailbox: Option<Arc<Mutex<crate::object::mailbox::MailBox>>>,
+    discarded: DiscardedReplies,
 }
 
 static REPLY_TYPEINFO : TypeInfo = new_typeinfo!(
@@ -97,6 +98,7 @@ impl Reply {
                             match crate::value::value_clone(&result, &mut allocator) {
                                 Ok(cloned) => {
                                     cap.as_mut().reply_value = Some(Ok(cloned));
+                                    obj.remember(cap.as_ref());
                                     //値を受け取ったので、MailBoxへの参照を削除する
                                     cap.as_mut().myself_mailbox = None;
                                     cap.as_mut().dest_mailbox = None;
@@ -113,6 +115,7 @@ impl Reply {
                             match unsafe { err.value_clone_gcunsafe(&mut allocator) } {
                                 Ok(cloned) => {
                                     cap.as_mut().reply_value = Some(Err(cloned));
+                                    obj.remember(cap.as_ref());
                                     //値を受け取ったので、MailBoxへの参照を削除する
                                     cap.as_mut().myself_mailbox = None;
                                     cap.as_mut().dest_mailbox = None;
@@ -133,6 +136,19 @@ impl Reply {
         }
     }
 
+    ///
+    /// エラーの返信から、Exceptionの値を作成する。
+    /// エラーが保持する値はReply内でGCから更新されるため、確保が終わってからReplyのエラーを取り出す。
+    ///
+    pub fn alloc_error_exception(cap: &Cap<Reply>, obj: &mut Object) -> NResult<exception::Exception, OutOfMemory> {
+        exception::Exception::alloc_with(|| {
+            match cap.as_ref().reply_value.as_ref() {
+                Some(Err(err)) => err.clone(),
+                _ => unreachable!(),
+            }
+        }, obj)
+    }
+
     fn _check_reply_dummy(_cap: &mut Cap<Reply>, _obj: &mut Object) -> Result<bool, OutOfMemory> {
         //本来この関数が呼ばれることはない。
         //不具合を検出できるようにダミーでパニックするだけの関数を登録できるようにする
@@ -142,6 +158,7 @@ impl Reply {
     pub fn alloc<A: Allocator>(token: ReplyToken
         , myself_mailbox: Arc<Mutex<crate::object::mailbox::MailBox>>
         , dest_mailbox: Arc<Mutex<crate::object::mailbox::MailBox>>
+        , discarded: DiscardedReplies
         , allocator: &mut A) -> NResult<Reply, OutOfMemory> {
         let ptr = allocator.alloc::<Reply>()?;
 
@@ -151,6 +168,7 @@ impl Reply {
                 reply_value: None,
                 myself_mailbox: Some(myself_mailbox),
                 dest_mailbox: Some(dest_mailbox),
+                discarded,
             });
         }
 
@@ -164,15 +182,8 @@ impl Reply {
     fn finalize(&mut self) {
         //まだ返信を受け取っていなければ
         if let Some(mailbox) = self.myself_mailbox.as_ref() {
-            match mailbox.lock() {
-                Ok(mut mailbox) => {
-                    //メールボックスに対して、受け取った返信を破棄するように指定しておく
-                    mailbox.discard_reply(self.reply_token);
-                }
-                Err(_) => {
-                    //do nothing
-                }
-            }
+            //GC中は自分自身のメールボックスのロックを取得済みの場合があるため、ロックの取得を待たずに破棄させる
+            crate::object::mailbox::MailBox::discard_reply(mailbox, &self.discarded, self.reply_token);
         }
 
         //内部で保持しているArcをデクリメントしないといけないのでDrop処理を実行する
@@ -206,16 +217,582 @@ impl Debug for Reply {
     }
 }
 
-fn func_force(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
-    //関数にわたってきている時点でReplyから実際の値になっているので引数の値をそのまま返す
-    Ok(vm::refer_arg::<Any>(0, obj))
+//check_reply_funcに渡されるCapはもともとCap<Any>なので、解決した値を指すようにポインタを置き換える
+fn update_to_resolved<T: NaviType>(cap: &mut Cap<T>, value: Ref<Any>) {
+    let cap: &mut Cap<Any> = unsafe { std::mem::transmute(cap) };
+    cap.update_pointer(value);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyGroupKind {
+    //すべての返信がそろったら、結果のリストを返す
+    All,
+    //最初に届いたエラー以外の返信を返す。すべてエラーなら最初のエラーを返す
+    Any,
+    //最初に届いた返信を、エラーかどうかに関わらず返す
+    Race,
+}
+
+impl ReplyGroupKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReplyGroupKind::All => "reply-all",
+            ReplyGroupKind::Any => "reply-any",
+            ReplyGroupKind::Race => "reply-race",
+        }
+    }
+}
+
+//複数のReplyをまとめて一つの返信として扱う値
+pub struct ReplyGroup {
+    kind: ReplyGroupKind,
+    replies: Ref<list::List>,
+    //一度解決した結果。any/raceで確認するたびに結果が変わらないように保持しておく
+    resolved: Option<Ref<Any>>,
+}
+
+static REPLYGROUP_TYPEINFO : TypeInfo = new_typeinfo!(
+    ReplyGroup,
+    "ReplyGroup",
+    std::mem::size_of::<ReplyGroup>(),
+    None,
+    ReplyGroup::eq,
+    ReplyGroup::clone_inner,
+    Display::fmt,
+    None,
+    None,
+    None,
+    Some(ReplyGroup::child_traversal),
+    Some(ReplyGroup::check_reply),
+    None,
+);
+
+impl NaviType for ReplyGroup {
+    fn typeinfo() -> &'static TypeInfo {
+        &REPLYGROUP_TYPEINFO
+    }
+
+    fn clone_inner(&self, _allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        unreachable!()
+    }
+}
+
+impl ReplyGroup {
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(self.replies.cast_mut_value(), arg);
+        if let Some(resolved) = self.resolved.as_mut() {
+            callback(resolved, arg);
+        }
+    }
+
+    fn check_reply(cap: &mut Cap<ReplyGroup>, obj: &mut Object) -> Result<bool, OutOfMemory> {
+        if let Some(resolved) = cap.as_ref().resolved.clone() {
+            update_to_resolved(cap, resolved);
+            return Ok(true);
+        }
+
+        let resolved = match cap.as_ref().kind {
+            ReplyGroupKind::All => {
+                match try_resolve(cap.as_ref().replies.clone().into_value(), obj)? {
+                    Some(replies) => replies,
+                    None => return Ok(false),
+                }
+            }
+            kind => {
+                let count = cap.as_ref().replies.count();
+                let mut result = None;
+                let mut first_error: Option<Cap<Any>> = None;
+                let mut pending = false;
+
+                //check_reply内でGCが走る可能性があるため、毎回Capで保持しているReplyGroupから要素を取り出す
+                for index in 0..count {
+                    let child = match try_resolve(cap.as_ref().replies.as_ref().get(index), obj)? {
+                        Some(child) => child,
+                        None => {
+                            pending = true;
+                            continue;
+                        }
+                    };
+
+                    if kind == ReplyGroupKind::Race || !child.as_ref().is::<exception::Exception>() {
+                        result = Some(child);
+                        break;
+                    } else if first_error.is_none() {
+                        first_error = Some(child.capture(obj));
+                    }
+                }
+
+                match (result, first_error) {
+                    (Some(result), _) => result,
+                    //すべての返信がエラーだった
+                    (None, Some(first_error)) if !pending => first_error.take(),
+                    _ => return Ok(false),
+                }
+            }
+        };
+
+        cap.as_mut().resolved = Some(resolved.clone());
+        obj.write_barrier(cap.as_ref(), resolved.as_ref());
+        update_to_resolved(cap, resolved);
+
+        Ok(true)
+    }
+
+    pub fn alloc<A: Allocator>(kind: ReplyGroupKind, replies: &Reachable<list::List>, allocator: &mut A) -> NResult<ReplyGroup, OutOfMemory> {
+        let ptr = allocator.alloc::<ReplyGroup>()?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), ReplyGroup {
+                kind,
+                replies: Ref::from(replies.raw_ptr()),
+                resolved: None,
+            });
+        }
+
+        let mut result = ptr.into_ref();
+        //内部にReplyを持つ値として目印のフラグを立てる。
+        crate::value::set_has_replytype_flag(&mut result);
+
+        Ok(result)
+    }
+}
+
+impl Eq for ReplyGroup { }
+
+impl PartialEq for ReplyGroup {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Display for ReplyGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.kind.name())
+    }
+}
+
+impl Debug for ReplyGroup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+//返信が届いた後に、その値へ関数を適用した結果を返信として扱う値
+pub struct ReplyThen {
+    reply: Ref<Any>,
+    //まだ適用していない関数。適用後はNoneにして、関数の結果を待つ
+    then: Option<Ref<app::App>>,
+    //関数の実行が返信待ちなどで中断した場合の、実行途中のVMState。次の確認時に続きから再開する
+    suspended: Option<Box<vm::VMState>>,
+    //関数の実行がオブジェクトの削除や終了で打ち切られた場合はtrue。結果が出ることはない
+    stopped: bool,
+}
+
+static REPLYTHEN_TYPEINFO : TypeInfo = new_typeinfo!(
+    ReplyThen,
+    "ReplyThen",
+    std::mem::size_of::<ReplyThen>(),
+    None,
+    ReplyThen::eq,
+    ReplyThen::clone_inner,
+    Display::fmt,
+    None,
+    Some(ReplyThen::finalize),
+    None,
+    Some(ReplyThen::child_traversal),
+    Some(ReplyThen::check_reply),
+    None,
+);
+
+impl NaviType for ReplyThen {
+    fn typeinfo() -> &'static TypeInfo {
+        &REPLYTHEN_TYPEINFO
+    }
+
+    fn clone_inner(&self, _allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        unreachable!()
+    }
+}
+
+impl ReplyThen {
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(&mut self.reply, arg);
+        if let Some(then) = self.then.as_mut() {
+            callback(then.cast_mut_value(), arg);
+        }
+        if let Some(state) = self.suspended.as_mut() {
+            state.for_each_all_alived_value(arg, callback);
+        }
+    }
+
+    fn finalize(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self)
+        }
+    }
+
+    fn check_reply(cap: &mut Cap<ReplyThen>, obj: &mut Object) -> Result<bool, OutOfMemory> {
+        if cap.as_ref().stopped {
+            return Ok(false);
+        }
+
+        loop {
+            //中断している関数の実行があれば、先に続きを実行する
+            if let Some(state) = cap.as_mut().suspended.take() {
+                let (result, state) = Self::run_then(*state, None, obj);
+
+                match Self::apply_result(cap, result, state, obj)? {
+                    Some(result) => {
+                        cap.as_mut().reply = result.clone();
+                        obj.write_barrier(cap.as_ref(), result.as_ref());
+                    }
+                    None => return Ok(false),
+                }
+            }
+
+            let reply = match try_resolve(cap.as_ref().reply.clone(), obj)? {
+                Some(reply) => reply.capture(obj),
+                None => return Ok(false),
+            };
+            cap.as_mut().reply.update_pointer(reply.raw_ptr());
+            obj.write_barrier(cap.as_ref(), reply.as_ref());
+
+            let then = match cap.as_ref().then.clone() {
+                Some(then) => then.reach(obj),
+                None => {
+                    update_to_resolved(cap, reply.take());
+                    return Ok(true);
+                }
+            };
+            //関数は一度だけ適用する
+            cap.as_mut().then = None;
+
+            let (result, state) = Self::run_then(vm::VMState::new(), Some((&then, reply.take())), obj);
+
+            match Self::apply_result(cap, result, state, obj)? {
+                Some(result) => {
+                    //関数の結果がReplyを含む場合は、さらにその返信を待つ
+                    cap.as_mut().reply = result.clone();
+                    obj.write_barrier(cap.as_ref(), result.as_ref());
+                }
+                None => return Ok(false),
+            }
+        }
+    }
+
+    //返信を待っているオブジェクトの通常の処理として関数を実行する。
+    //Funcの実行中に呼ばれる場合もあるため、呼び出し元のVMStateを壊さないように別のVMStateで実行する。
+    //実行時間は呼び出し元に残っているreduction数を引き継ぎ、消費した分は呼び出し元からも減らす。
+    //applyがあれば関数を新しく適用し、なければ中断していた実行を再開する。
+    fn run_then(state: vm::VMState, apply: Option<(&Reachable<app::App>, Ref<Any>)>, obj: &mut Object) -> (Result<Ref<Any>, vm::ExecException>, vm::VMState) {
+        let reductions = obj.vm_state().remain_reductions();
+        obj.push_vm_state(state);
+
+        let limit = vm::WorkTimeLimit::Reductions(reductions);
+        let result = match apply {
+            Some((then, arg)) => vm::app_call(then, std::iter::once(arg), limit, obj),
+            None => vm::resume(limit, obj),
+        };
+
+        let state = obj.pop_vm_state();
+        obj.vm_state().set_remain_reductions(state.remain_reductions());
+
+        (result, state)
+    }
+
+    //関数の実行結果を、ReplyThenが待つ値に変換する。
+    //実行が中断した場合はVMStateを保存してNoneを返す。
+    fn apply_result(cap: &mut Cap<ReplyThen>, result: Result<Ref<Any>, vm::ExecException>, state: vm::VMState, obj: &mut Object) -> Result<Option<Ref<Any>>, OutOfMemory> {
+        match result {
+            Ok(result) => Ok(Some(result)),
+            Err(vm::ExecException::Exception(err)) if err.is_catchable() => {
+                //関数内で発生したエラーはExceptionの値として結果にする
+                let e = exception::Exception::alloc(err, obj)?;
+                Ok(Some(e.into_value()))
+            }
+            Err(vm::ExecException::Exception(Exception::WaitReply)) => {
+                //返信待ちによる中断は、返信が届いた後の確認時に続きから再開する
+                cap.as_mut().suspended = Some(Box::new(state));
+                Ok(None)
+            }
+            Err(vm::ExecException::Exception(Exception::TimeLimit)) => {
+                //実行時間による中断は、返信の到着を待たずに次の実行で続きから再開する
+                cap.as_mut().suspended = Some(Box::new(state));
+                obj.set_resume_immediately();
+                Ok(None)
+            }
+            Err(vm::ExecException::Exception(_)) => {
+                //オブジェクトの削除や終了は値にせず、結果が出ないままにしておく
+                cap.as_mut().stopped = true;
+                Ok(None)
+            }
+            Err(vm::ExecException::ObjectSwitch(_)) => {
+                //Objectの切り替えはグローバル環境のトップレベルでのみ許可されているため、ここでは絶対に発生しない。
+                unreachable!()
+            }
+        }
+    }
+
+    pub fn alloc<A: Allocator>(reply: &Reachable<Any>, then: &Reachable<app::App>, allocator: &mut A) -> NResult<ReplyThen, OutOfMemory> {
+        let ptr = allocator.alloc::<ReplyThen>()?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), ReplyThen {
+                reply: Ref::from(reply.raw_ptr()),
+                then: Some(Ref::from(then.raw_ptr())),
+                suspended: None,
+                stopped: false,
+            });
+        }
+
+        let mut result = ptr.into_ref();
+        //内部にReplyを持つ値として目印のフラグを立てる。
+        crate::value::set_has_replytype_flag(&mut result);
+
+        Ok(result)
+    }
+}
+
+impl Eq for ReplyThen { }
+
+impl PartialEq for ReplyThen {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Display for ReplyThen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#reply-then")
+    }
+}
+
+impl Debug for ReplyThen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+//Replyを含む値の返信を確認する。
+//すべての返信が届いていれば、Replyを返信の値に置き換えた値を返す。
+fn try_resolve(v: Ref<Any>, obj: &mut Object) -> Result<Option<Ref<Any>>, OutOfMemory> {
+    if !v.has_replytype() {
+        return Ok(Some(v));
+    }
+
+    //check_reply内でGCが走る可能性があるため、値をキャプチャしてから確認する
+    let mut cap = v.capture(obj);
+    if crate::value::check_reply(&mut cap, obj)? {
+        Ok(Some(cap.take()))
+    } else {
+        Ok(None)
+    }
+}
+
+//(force reply :timeout 100)のようなオプション指定から、タイムアウトのミリ秒を取得する
+fn force_timeout(num_rest: usize, obj: &mut Object) -> Result<Option<u64>, Exception> {
+    if num_rest == 0 {
+        return Ok(None);
+    } else if num_rest != 2 {
+        return Err(Exception::Other("force options must be :timeout and milliseconds.".to_string()));
+    }
+
+    let key = vm::refer_rest_arg::<Any>(1, 0, obj);
+    match key.try_cast::<keyword::Keyword>() {
+        Some(key) if key.as_ref().as_ref() == "timeout" => { }
+        _ => {
+            return Err(Exception::Other(format!("unknown force option {}. expected :timeout", key.as_ref())));
+        }
+    }
+
+    let timeout = vm::refer_rest_arg::<Any>(1, 1, obj);
+    match timeout.try_cast::<number::Integer>() {
+        Some(timeout) if 0 <= timeout.as_ref().get() => {
+            Ok(Some(timeout.as_ref().get() as u64))
+        }
+        Some(timeout) => {
+            Err(Exception::Other(format!("timeout must not be negative: {}", timeout.as_ref().get())))
+        }
+        None => {
+            Err(Exception::TypeMismatch(TypeMismatch::new(timeout, number::Integer::typeinfo())))
+        }
+    }
+}
+
+fn func_force(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let deadline = force_timeout(num_rest, obj)?
+        .map(|timeout| std::time::Instant::now() + std::time::Duration::from_millis(timeout));
+
+    func_force_result(deadline, obj)
+}
+
+fn func_force_resume(obj: &mut Object) -> NResult<Any, Exception> {
+    let deadline: Option<std::time::Instant> = obj.vm_state().stack().pop();
+
+    func_force_result(deadline, obj)
+}
+
+#[inline]
+fn func_force_result(deadline: Option<std::time::Instant>, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+    if let Some(v) = try_resolve(v, obj)? {
+        return Ok(v);
+    }
+
+    match deadline {
+        Some(deadline) if deadline <= std::time::Instant::now() => {
+            //指定時間内に返信が届かなかった
+            Err(Exception::ReplyTimeout)
+        }
+        Some(deadline) => {
+            //返信が届くか指定時刻になるまで関数の処理を一時停止
+            obj.vm_state().stack().push(Some(deadline));
+            vm::save_func_suspend_info(func_force_resume, obj);
+            //返信が届いたときはMailBoxから、届かなければ指定時刻にスケジューラから起こされる
+            obj.set_sleep_until(deadline);
+
+            Err(Exception::TimeLimit)
+        }
+        None => {
+            //返信が届くまで関数の処理を一時停止
+            obj.vm_state().stack().push(deadline);
+            vm::save_func_suspend_info(func_force_resume, obj);
+
+            Err(Exception::WaitReply)
+        }
+    }
+}
+
+fn func_is_reply_ready(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+
+    if try_resolve(v, obj)?.is_some() {
+        Ok(bool::Bool::true_().into_ref().into_value())
+    } else {
+        Ok(bool::Bool::false_().into_ref().into_value())
+    }
+}
+
+fn func_try_force(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let v = vm::refer_arg::<Any>(0, obj);
+
+    match try_resolve(v, obj)? {
+        Some(v) => Ok(v),
+        //まだ返信が届いていなければ、待たずにデフォルト値を返す
+        None => Ok(vm::refer_arg::<Any>(1, obj)),
+    }
+}
+
+//新しく作ったReplyGroupやReplyThenの返信を確認する。
+//すでに返信がそろっていれば、待つ必要のない結果の値をそのまま返す
+fn resolve_or_keep(v: Ref<Any>, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut cap = v.capture(obj);
+    if cap.has_replytype() {
+        crate::value::check_reply(&mut cap, obj)?;
+    }
+    Ok(cap.take())
+}
+
+fn reply_group(kind: ReplyGroupKind, obj: &mut Object) -> NResult<Any, Exception> {
+    let replies = vm::refer_arg::<list::List>(0, obj).reach(obj);
+    if kind != ReplyGroupKind::All && replies.is_nil() {
+        return Err(Exception::Other(format!("{} requires at least one reply.", kind.name())));
+    }
+
+    let group = ReplyGroup::alloc(kind, &replies, obj)?;
+    resolve_or_keep(group.into_value(), obj)
+}
+
+fn func_reply_all(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    reply_group(ReplyGroupKind::All, obj)
+}
+
+fn func_reply_any(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    reply_group(ReplyGroupKind::Any, obj)
+}
+
+fn func_reply_race(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    reply_group(ReplyGroupKind::Race, obj)
+}
+
+fn func_reply_then(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let reply = vm::refer_arg::<Any>(0, obj).reach(obj);
+    let then = vm::refer_arg::<app::App>(1, obj).reach(obj);
+
+    let reply_then = ReplyThen::alloc(&reply, &then, obj)?;
+    resolve_or_keep(reply_then.into_value(), obj)
 }
 
+//Replyの確認は関数内で行うため、引数はforceせずに受け取る
 static FUNC_FORCE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
     GCAllocationStruct::new(
         Func::new("force", func_force,
             Parameter::new(&[
-            Param::new("v", ParamKind::Require, Any::typeinfo()),
+            Param::new_no_force("v", ParamKind::Require, Any::typeinfo()),
+            Param::new("options", ParamKind::Rest, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_IS_REPLY_READY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("reply-ready?", func_is_reply_ready,
+            Parameter::new(&[
+            Param::new_no_force("v", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_TRY_FORCE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("try-force", func_try_force,
+            Parameter::new(&[
+            Param::new_no_force("v", ParamKind::Require, Any::typeinfo()),
+            Param::new_no_force("default", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+//reply-all, reply-any, reply-raceはリスト内のReplyの返信を待たずに受け取る
+static FUNC_REPLY_ALL: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("reply-all", func_reply_all,
+            Parameter::new(&[
+            Param::new_no_force("replies", ParamKind::Require, list::List::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_REPLY_ANY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("reply-any", func_reply_any,
+            Parameter::new(&[
+            Param::new_no_force("replies", ParamKind::Require, list::List::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_REPLY_RACE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("reply-race", func_reply_race,
+            Parameter::new(&[
+            Param::new_no_force("replies", ParamKind::Require, list::List::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_REPLY_THEN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("reply-then", func_reply_then,
+            Parameter::new(&[
+            Param::new_no_force("v", ParamKind::Require, Any::typeinfo()),
+            Param::new("f", ParamKind::Require, app::App::typeinfo()),
             ])
         )
     )
@@ -223,4 +800,10 @@ static FUNC_FORCE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
 
 pub fn register_global(obj: &mut Object) {
     obj.define_global_value("force", &Ref::new(&FUNC_FORCE.value));
+    obj.define_global_value("reply-ready?", &Ref::new(&FUNC_IS_REPLY_READY.value));
+    obj.define_global_value("try-force", &Ref::new(&FUNC_TRY_FORCE.value));
+    obj.define_global_value("reply-all", &Ref::new(&FUNC_REPLY_ALL.value));
+    obj.define_global_value("reply-any", &Ref::new(&FUNC_REPLY_ANY.value));
+    obj.define_global_value("reply-race", &Ref::new(&FUNC_REPLY_RACE.value));
+    obj.define_global_value("reply-then", &Ref::new(&FUNC_REPLY_THEN.value));
 }
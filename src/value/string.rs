@@ -1,8 +1,16 @@
 use crate::value::*;
+use crate::value::func::Func;
+use crate::value::app::{Parameter, ParamKind, Param};
+use crate::value::list::ListBuilder;
 use crate::ptr::*;
+use crate::err::{self, Exception};
+use crate::object::Object;
+use crate::object::mm::GCAllocationStruct;
+use crate::vm;
 use core::panic;
 use std::fmt::{self, Debug, Display};
 use std::io::Read;
+use once_cell::sync::Lazy;
 
 type StringRef = std::mem::ManuallyDrop<String>;
 
@@ -162,4 +170,464 @@ pub fn static_string<T: Into<String>>(str: T) -> StaticString {
     (&str.as_bytes()[..]).read(&mut static_str.buf).unwrap();
 
     static_str
-}
\ No newline at end of file
+}
+
+fn alloc_string(str: String, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = NString::alloc(&str, obj)?;
+    Ok(str.into_value())
+}
+
+//文字のインデックスを範囲内のusizeに変換する。範囲外ならOutOfBoundsエラー
+fn char_index(str: &Ref<NString>, index: i64, len: usize) -> Result<usize, Exception> {
+    if 0 <= index && index as usize <= len {
+        Ok(index as usize)
+    } else {
+        Err(Exception::OutOfBounds(err::OutOfBounds::new(str.clone().into_value(), index.max(0) as usize)))
+    }
+}
+
+//省略可能な引数を取得する。省略されている場合はUnitが入っている。
+fn optional_arg<T: NaviType>(index: usize, obj: &mut Object) -> Option<Ref<T>> {
+    let v = vm::refer_arg::<Any>(index, obj);
+    v.try_cast::<T>().cloned()
+}
+
+fn func_string_length(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj);
+    //バイト数ではなくUnicodeスカラー値の数を返す
+    let len = str.as_ref().as_string().chars().count();
+
+    let num = number::make_integer(len as i64, obj)?;
+    Ok(num)
+}
+
+fn func_string_ref(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj);
+    let index = vm::refer_arg::<number::Integer>(1, obj).as_ref().get();
+
+    let ch = if 0 <= index {
+        str.as_ref().as_string().chars().nth(index as usize)
+    } else {
+        None
+    };
+
+    match ch {
+        Some(ch) => Ok(char::Char::new(ch).into_value()),
+        None => Err(Exception::OutOfBounds(err::OutOfBounds::new(str.into_value(), index.max(0) as usize))),
+    }
+}
+
+fn func_substring(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj);
+    let chars: Vec<char> = str.as_ref().as_string().chars().collect();
+
+    let start = vm::refer_arg::<number::Integer>(1, obj).as_ref().get();
+    let start = char_index(&str, start, chars.len())?;
+    //終了位置が省略されていれば末尾まで
+    let end = match optional_arg::<number::Integer>(2, obj) {
+        Some(end) => char_index(&str, end.as_ref().get(), chars.len())?,
+        None => chars.len(),
+    };
+
+    if end < start {
+        return Err(Exception::OutOfBounds(err::OutOfBounds::new(str.into_value(), end)));
+    }
+
+    alloc_string(chars[start .. end].iter().collect(), obj)
+}
+
+fn func_string_append(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let mut result = String::new();
+    for index in 0 .. num_rest {
+        let str = vm::refer_rest_arg::<NString>(0, index, obj);
+        result.push_str(&str.as_ref().as_string());
+    }
+
+    alloc_string(result, obj)
+}
+
+fn func_string_index(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj);
+    let pattern = vm::refer_arg::<NString>(1, obj);
+
+    let str = str.as_ref().as_string();
+    match str.find(pattern.as_ref().as_string().as_str()) {
+        Some(byte_index) => {
+            //バイト位置を文字の位置に変換する
+            let index = str[.. byte_index].chars().count();
+            let num = number::make_integer(index as i64, obj)?;
+            Ok(num)
+        }
+        None => Ok(bool::Bool::false_().into_ref().into_value()),
+    }
+}
+
+fn func_string_split(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_string();
+    let separator = vm::refer_arg::<NString>(1, obj).as_ref().as_string().to_string();
+
+    //区切り文字列が空なら一文字ずつに分割する
+    let parts: Vec<String> = if separator.is_empty() {
+        str.chars().map(|ch| ch.to_string()).collect()
+    } else {
+        str.split(separator.as_str()).map(|part| part.to_string()).collect()
+    };
+
+    let mut builder = ListBuilder::new(obj);
+    for part in parts {
+        let part = NString::alloc(&part, obj)?.into_value().reach(obj);
+        builder.push(&part, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+fn func_string_join(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let strs = vm::refer_arg::<list::List>(0, obj);
+    let separator = match optional_arg::<NString>(1, obj) {
+        Some(separator) => separator.as_ref().as_string().to_string(),
+        None => String::new(),
+    };
+
+    let mut parts: Vec<String> = Vec::new();
+    for v in unsafe { strs.as_ref().iter_gcunsafe() } {
+        match v.try_cast::<NString>() {
+            Some(str) => parts.push(str.as_ref().as_string().to_string()),
+            None => return Err(Exception::TypeMismatch(err::TypeMismatch::new(v, NString::typeinfo()))),
+        }
+    }
+
+    alloc_string(parts.join(&separator), obj)
+}
+
+fn func_string_upcase(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_uppercase();
+    alloc_string(str, obj)
+}
+
+fn func_string_downcase(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_lowercase();
+    alloc_string(str, obj)
+}
+
+fn func_string_to_symbol(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_string();
+    let symbol = symbol::Symbol::alloc(str, obj)?;
+    Ok(symbol.into_value())
+}
+
+fn func_symbol_to_string(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let symbol = vm::refer_arg::<symbol::Symbol>(0, obj);
+    let str: &str = symbol.as_ref().as_ref();
+    alloc_string(str.to_string(), obj)
+}
+
+fn func_number_to_string(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let num = vm::refer_arg::<Any>(0, obj);
+    alloc_string(num.as_ref().to_string(), obj)
+}
+
+fn func_string_to_number(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let str = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_string();
+
+    match crate::read::parse_number(str.trim(), obj) {
+        Ok(Some(num)) => Ok(num),
+        Ok(None) | Err(crate::read::ReadException::MalformedFormat(_)) | Err(crate::read::ReadException::EOF) => {
+            //数値として解釈できなければfalse
+            Ok(bool::Bool::false_().into_ref().into_value())
+        }
+        Err(crate::read::ReadException::OutOfMemory) => Err(Exception::OutOfMemory),
+    }
+}
+
+//~sの出力。文字列はダブルクォートで囲み、それ以外の値は表示と同じ形式で出力する
+fn write_value(v: &Any, out: &mut String) {
+    match v.try_cast::<NString>() {
+        Some(str) => {
+            out.push('"');
+            for ch in str.as_string().chars() {
+                match ch {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    ch => out.push(ch),
+                }
+            }
+            out.push('"');
+        }
+        None => {
+            if let Some(ch) = v.try_cast::<char::Char>() {
+                out.push_str("#\\");
+                out.push_str(&char::char_name(ch.get()));
+            } else {
+                out.push_str(&v.to_string());
+            }
+        }
+    }
+}
+
+fn func_format(num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let format = vm::refer_arg::<NString>(0, obj).as_ref().as_string().to_string();
+
+    let mut result = String::new();
+    let mut arg_index = 0;
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '~' {
+            result.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some(directive @ ('a' | 'A' | 's' | 'S')) => {
+                if num_rest <= arg_index {
+                    return Err(Exception::Other(format!("format: not enough arguments for \"{}\"", format)));
+                }
+
+                let v = vm::refer_rest_arg::<Any>(1, arg_index, obj);
+                arg_index += 1;
+
+                if directive == 'a' || directive == 'A' {
+                    result.push_str(&v.as_ref().to_string());
+                } else {
+                    write_value(v.as_ref(), &mut result);
+                }
+            }
+            Some('%') => result.push('\n'),
+            Some('~') => result.push('~'),
+            Some(other) => {
+                return Err(Exception::Other(format!("format: unknown directive ~{}", other)));
+            }
+            None => {
+                return Err(Exception::Other("format: the format string ends with ~".to_string()));
+            }
+        }
+    }
+
+    if arg_index < num_rest {
+        return Err(Exception::Other(format!("format: too many arguments for \"{}\"", format)));
+    }
+
+    alloc_string(result, obj)
+}
+
+static FUNC_STRING_LENGTH: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-length", func_string_length,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_REF: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-ref", func_string_ref,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("index", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SUBSTRING: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("substring", func_substring,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("start", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("end", ParamKind::Optional, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_APPEND: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-append", func_string_append,
+            Parameter::new(&[
+            Param::new("strs", ParamKind::Rest, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_INDEX: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-index", func_string_index,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("pattern", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_SPLIT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-split", func_string_split,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("separator", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_JOIN: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-join", func_string_join,
+            Parameter::new(&[
+            Param::new("strs", ParamKind::Require, list::List::typeinfo()),
+            Param::new("separator", ParamKind::Optional, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_UPCASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-upcase", func_string_upcase,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_DOWNCASE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string-downcase", func_string_downcase,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_TO_SYMBOL: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string->symbol", func_string_to_symbol,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_SYMBOL_TO_STRING: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("symbol->string", func_symbol_to_string,
+            Parameter::new(&[
+            Param::new("symbol", ParamKind::Require, symbol::Symbol::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_NUMBER_TO_STRING: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("number->string", func_number_to_string,
+            Parameter::new(&[
+            Param::new("num", ParamKind::Require, number::Number::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_STRING_TO_NUMBER: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("string->number", func_string_to_number,
+            Parameter::new(&[
+            Param::new("str", ParamKind::Require, string::NString::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_FORMAT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("format", func_format,
+            Parameter::new(&[
+            Param::new("format", ParamKind::Require, string::NString::typeinfo()),
+            Param::new("args", ParamKind::Rest, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+pub fn register_global(obj: &mut Object) {
+    obj.define_global_value("string-length", &Ref::new(&FUNC_STRING_LENGTH.value));
+    obj.define_global_value("string-ref", &Ref::new(&FUNC_STRING_REF.value));
+    obj.define_global_value("substring", &Ref::new(&FUNC_SUBSTRING.value));
+    obj.define_global_value("string-append", &Ref::new(&FUNC_STRING_APPEND.value));
+    obj.define_global_value("string-index", &Ref::new(&FUNC_STRING_INDEX.value));
+    obj.define_global_value("string-split", &Ref::new(&FUNC_STRING_SPLIT.value));
+    obj.define_global_value("string-join", &Ref::new(&FUNC_STRING_JOIN.value));
+    obj.define_global_value("string-upcase", &Ref::new(&FUNC_STRING_UPCASE.value));
+    obj.define_global_value("string-downcase", &Ref::new(&FUNC_STRING_DOWNCASE.value));
+    obj.define_global_value("string->symbol", &Ref::new(&FUNC_STRING_TO_SYMBOL.value));
+    obj.define_global_value("symbol->string", &Ref::new(&FUNC_SYMBOL_TO_STRING.value));
+    obj.define_global_value("number->string", &Ref::new(&FUNC_NUMBER_TO_STRING.value));
+    obj.define_global_value("string->number", &Ref::new(&FUNC_STRING_TO_NUMBER.value));
+    obj.define_global_value("format", &Ref::new(&FUNC_FORMAT.value));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::tests::check;
+    use crate::object::Object;
+
+    #[test]
+    fn test_string() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        //インデックスはUnicodeスカラー値単位
+        check(r#"(string-length "héllo")"#, "5", obj, ans_obj);
+        check(r#"(string-ref "héllo" 1)"#, r#"#\é"#, obj, ans_obj);
+        check(r#"(substring "héllo world" 6)"#, r#""world""#, obj, ans_obj);
+        check(r#"(substring "héllo world" 1 4)"#, r#""éll""#, obj, ans_obj);
+        check(r#"(string-index "héllo" "llo")"#, "2", obj, ans_obj);
+        check(r#"(string-index "hello" "z")"#, "false", obj, ans_obj);
+
+        check(r#"(string-append "ab" "cd" "")"#, r#""abcd""#, obj, ans_obj);
+        check(r#"(list-len (string-split "a,b,,c" ","))"#, "4", obj, ans_obj);
+        check(r#"(string-join (string-split "a,b,,c" ",") "-")"#, r#""a-b--c""#, obj, ans_obj);
+        check(r#"(string-join (string-split "abc" ""))"#, r#""abc""#, obj, ans_obj);
+        check(r#"(string-upcase "Héllo")"#, r#""HÉLLO""#, obj, ans_obj);
+        check(r#"(string-downcase "ABC")"#, r#""abc""#, obj, ans_obj);
+
+        check(r#"(= (string->symbol "foo") 'foo)"#, "true", obj, ans_obj);
+        check(r#"(symbol->string 'bar)"#, r#""bar""#, obj, ans_obj);
+        check(r#"(number->string 1/3)"#, r#""1/3""#, obj, ans_obj);
+        check(r#"(string->number "42")"#, "42", obj, ans_obj);
+        check(r#"(string->number " 1/2 ")"#, "1/2", obj, ans_obj);
+        check(r#"(string->number "abc")"#, "false", obj, ans_obj);
+
+        check(r#"(try (string-ref "abc" 3) (catch @e (error-kind e)))"#, ":out-of-bounds", obj, ans_obj);
+        check(r#"(try (substring "abc" 2 1) (catch @e (error-kind e)))"#, ":out-of-bounds", obj, ans_obj);
+    }
+
+    #[test]
+    fn test_format() {
+        let mut obj = Object::new_for_test();
+        let obj = &mut obj;
+        let mut ans_obj = Object::new_for_test();
+        let ans_obj = &mut ans_obj;
+
+        check(r#"(format "x=~a y=~a" 1 'sym)"#, r#""x=1 y=sym""#, obj, ans_obj);
+        check(r#"(format "~a and ~s" "str" "q\"t")"#, r#""str and \"q\\\"t\"""#, obj, ans_obj);
+        check(r#"(format "100~~~%")"#, r#""100~\n""#, obj, ans_obj);
+        check(r#"(format "~a" '(1 2))"#, r#""(1 2)""#, obj, ans_obj);
+
+        check(r#"(try (format "~a") (catch @e (error-kind e)))"#, ":other", obj, ans_obj);
+        check(r#"(try (format "~a" 1 2) (catch @e (error-kind e)))"#, ":other", obj, ans_obj);
+        check(r#"(try (format "~q" 1) (catch @e (error-kind e)))"#, ":other", obj, ans_obj);
+    }
+}
@@ -9,7 +9,9 @@ use crate::compile;
 use std::fmt::{Debug, Display};
 
 
+pub mod r#macro;
 pub mod r#match;
+pub mod receive;
 
 pub struct Syntax {
     name: String,
@@ -61,7 +63,7 @@ impl Syntax {
     }
 
     pub fn check_arguments(&self, args: &Reachable<list::List>) -> bool {
-        let count = args.as_ref().count();
+        let count = args.count();
         if count < self.require {
             false
         } else if self.has_rest == false && count > self.require + self.optional {
@@ -0,0 +1,570 @@
+use crate::compile::SyntaxException;
+use crate::value::list::{List, ListBuilder, ListIteratorGCUnsafe};
+use crate::value::symbol::Symbol;
+use crate::value::tuple::Tuple;
+use crate::value::array::Array;
+use crate::ptr::*;
+use crate::err::{self, NResult, OutOfMemory};
+use crate::value::*;
+
+use std::fmt::{Debug, Display};
+
+//
+// def-syntaxでユーザーが定義したマクロ。
+// (syntax-rules (literal ...) (pattern template) ...)の形式で定義された変換規則を保持する
+pub struct Macro {
+    name: Ref<Symbol>,
+    literals: Ref<List>,
+    rules: Ref<List>,
+}
+
+static MACRO_TYPEINFO: TypeInfo = new_typeinfo!(
+    Macro,
+    "Macro",
+    std::mem::size_of::<Macro>(),
+    None,
+    Macro::eq,
+    Macro::clone_inner,
+    Display::fmt,
+    None,
+    None,
+    None,
+    Some(Macro::child_traversal),
+    None,
+    None,
+);
+
+impl NaviType for Macro {
+    fn typeinfo() -> &'static TypeInfo {
+        &MACRO_TYPEINFO
+    }
+
+    fn clone_inner(&self, allocator: &mut AnyAllocator) -> NResult<Self, OutOfMemory> {
+        //clone_innerの文脈の中だけ、Ptrをキャプチャせずに扱うことが許されている
+        unsafe {
+            let name = Symbol::clone_inner(self.name.as_ref(), allocator)?.into_reachable();
+            let literals = List::clone_inner(self.literals.as_ref(), allocator)?.into_reachable();
+            let rules = List::clone_inner(self.rules.as_ref(), allocator)?.into_reachable();
+
+            Self::alloc(&name, &literals, &rules, allocator)
+        }
+    }
+}
+
+//パターン、テンプレート内の繰り返しを表すシンボル
+const ELLIPSIS: &str = "...";
+//パターン内で何にでもマッチし、束縛を行わないシンボル
+const UNDERSCORE: &str = "_";
+
+//パターン変数に束縛された値。...の内側にある変数は繰り返しの回数分の値を持つ
+enum Binding {
+    One(Reachable<Any>),
+    Many(Vec<Binding>),
+}
+
+type Bindings = Vec<(String, Binding)>;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum SeqKind {
+    List,
+    Tuple,
+    Array,
+}
+
+impl Macro {
+    fn child_traversal(&mut self, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+        callback(self.name.cast_mut_value(), arg);
+        callback(self.literals.cast_mut_value(), arg);
+        callback(self.rules.cast_mut_value(), arg);
+    }
+
+    pub fn alloc<A: Allocator>(name: &Reachable<Symbol>, literals: &Reachable<List>, rules: &Reachable<List>, allocator: &mut A) -> NResult<Self, OutOfMemory> {
+        let ptr = allocator.alloc::<Macro>()?;
+
+        unsafe {
+            std::ptr::write(ptr.as_ptr(), Macro {
+                name: name.raw_ptr().into(),
+                literals: literals.raw_ptr().into(),
+                rules: rules.raw_ptr().into(),
+            });
+        }
+
+        Ok(ptr.into_ref())
+    }
+
+    ///
+    /// (syntax-rules (literal ...) (pattern template) ...)の形式をチェックしてMacroを作成する
+    pub fn from_syntax_rules(name: &Reachable<Symbol>, spec: &Reachable<Any>, obj: &mut Object) -> NResult<Macro, SyntaxException> {
+        let malformed = |message: &str| -> SyntaxException {
+            err::MalformedFormat::new(Some(spec.make()), message).into()
+        };
+
+        let spec_list = match spec.try_cast::<List>() {
+            Some(list) if list.len_more_than(2) => list,
+            _ => return Err(malformed("def-syntax requires (syntax-rules (literal ...) (pattern template) ...).")),
+        };
+
+        let is_syntax_rules = spec_list.as_ref().head().try_cast::<Symbol>()
+            .is_some_and(|symbol| symbol.as_ref().as_ref() == "syntax-rules");
+        if !is_syntax_rules {
+            return Err(malformed("def-syntax requires (syntax-rules (literal ...) (pattern template) ...)."));
+        }
+
+        //リテラルはシンボルのリスト
+        let literals = spec_list.as_ref().tail().as_ref().head();
+        match literals.try_cast::<List>() {
+            Some(literals) if items(literals.as_ref()).all(|v| v.is::<Symbol>()) => { }
+            _ => return Err(malformed("syntax-rules literals must be a list of symbols.")),
+        }
+
+        //各規則は(pattern template)の形式で、パターンはリスト
+        let rules = spec_list.as_ref().tail().as_ref().tail();
+        for rule in items(rules.as_ref()) {
+            let is_rule = rule.try_cast::<List>()
+                .is_some_and(|rule| rule.len_exactly(2) && rule.as_ref().head().is::<List>());
+            if !is_rule {
+                return Err(malformed("syntax-rules rule must be (pattern template)."));
+            }
+        }
+
+        let literals = unsafe { literals.cast_unchecked::<List>() }.clone().reach(obj);
+        let rules = rules.reach(obj);
+        Ok(Self::alloc(name, &literals, &rules, obj)?)
+    }
+
+    pub fn name(&self) -> Ref<Symbol> {
+        self.name.clone()
+    }
+
+    ///
+    /// マクロ呼び出し式を一段階だけ展開する。
+    /// localsには呼び出し側で見えているローカル変数の名前を渡す。
+    pub fn expand(this: &Reachable<Macro>, form: &Reachable<List>, locals: &[String], obj: &mut Object) -> NResult<Any, SyntaxException> {
+        let rules = this.as_ref().rules.clone().reach(obj);
+
+        for rule in rules.iter(obj) {
+            let rule = unsafe { rule.cast_unchecked::<List>() }.clone().reach(obj);
+            //パターンの先頭はマクロ名の位置なので、引数部分だけをマッチさせる
+            let pattern = unsafe { rule.as_ref().head().cast_unchecked::<List>() }.as_ref().tail().into_value().reach(obj);
+            let args = form.as_ref().tail().into_value().reach(obj);
+
+            let mut bindings = Bindings::new();
+            if match_pattern(&pattern, &args, this.as_ref().literals.as_ref(), &mut bindings, obj) {
+                let template = rule.as_ref().tail().as_ref().head().reach(obj);
+
+                //テンプレート内で束縛を導入するシンボルは、呼び出し側の変数を捕捉しないようにgensymで置き換える
+                let mut binders: Vec<String> = Vec::new();
+                collect_binders(template.as_ref(), &bindings, &mut |name| {
+                    if !binders.iter().any(|n| n == name) {
+                        binders.push(name.to_string());
+                    }
+                });
+                let mut renames: Vec<(String, Reachable<Symbol>)> = Vec::new();
+                for name in binders.into_iter() {
+                    let symbol = Symbol::gensym(name.as_str(), obj)?.reach(obj);
+                    renames.push((name, symbol));
+                }
+
+                let mut ctx = ExpandCtx {
+                    form,
+                    bindings: &bindings,
+                    renames: &renames,
+                    locals,
+                    quoted: false,
+                    indexes: Vec::new(),
+                };
+                return instantiate(&template, &mut ctx, obj);
+            }
+        }
+
+        Err(err::MalformedFormat::new(Some(form.make().into_value())
+            , format!("no syntax-rules pattern of {} matched.", this.as_ref().name.as_ref())).into())
+    }
+}
+
+fn sequence_kind(v: &Any) -> Option<SeqKind> {
+    let typeinfo = get_typeinfo(v);
+    if typeinfo == List::typeinfo() {
+        Some(SeqKind::List)
+    } else if typeinfo == Tuple::typeinfo() {
+        Some(SeqKind::Tuple)
+    } else if typeinfo == Array::<Any>::typeinfo() {
+        Some(SeqKind::Array)
+    } else {
+        None
+    }
+}
+
+fn elements(v: &Reachable<Any>, obj: &mut Object) -> Vec<Reachable<Any>> {
+    if let Some(list) = v.try_cast::<List>() {
+        list.iter(obj).map(|v| v.reach(obj)).collect()
+    } else if let Some(tuple) = v.try_cast::<Tuple>() {
+        (0..tuple.as_ref().len()).map(|index| tuple.as_ref().get(index).reach(obj)).collect()
+    } else if let Some(array) = v.try_cast::<Array<Any>>() {
+        (0..array.as_ref().len()).map(|index| array.as_ref().get(index).reach(obj)).collect()
+    } else {
+        Vec::new()
+    }
+}
+
+//GCが発生しない処理の中でリストの要素を辿る
+fn items(list: &List) -> ListIteratorGCUnsafe {
+    unsafe { list.iter_gcunsafe() }
+}
+
+fn symbol_name(v: &Any) -> Option<&str> {
+    if get_typeinfo(v) == Symbol::typeinfo() {
+        let symbol = unsafe { &*(v as *const Any as *const Symbol) };
+        Some(symbol.as_ref())
+    } else {
+        None
+    }
+}
+
+fn is_literal(name: &str, literals: &List) -> bool {
+    items(literals).any(|v| symbol_name(v.as_ref()) == Some(name))
+}
+
+fn is_ellipsis(v: &Any) -> bool {
+    symbol_name(v) == Some(ELLIPSIS)
+}
+
+fn lookup<'a>(bindings: &'a [(String, Binding)], name: &str) -> Option<&'a Binding> {
+    bindings.iter().find(|(n, _)| n == name).map(|(_, binding)| binding)
+}
+
+//
+// パターンに含まれるパターン変数の名前を集める
+fn pattern_vars(pattern: &Any, literals: &List, acc: &mut Vec<String>) {
+    if let Some(name) = symbol_name(pattern) {
+        if name != ELLIPSIS && name != UNDERSCORE && !is_literal(name, literals) {
+            acc.push(name.to_string());
+        }
+    } else if sequence_kind(pattern).is_some() {
+        for_each_element(pattern, &mut |v| pattern_vars(v, literals, acc));
+    }
+}
+
+fn for_each_element(v: &Any, f: &mut dyn FnMut(&Any)) {
+    match sequence_kind(v) {
+        Some(SeqKind::List) => {
+            let list = unsafe { &*(v as *const Any as *const List) };
+            items(list).for_each(|v| f(v.as_ref()));
+        }
+        Some(SeqKind::Tuple) => {
+            let tuple = unsafe { &*(v as *const Any as *const Tuple) };
+            (0..tuple.len()).for_each(|index| f(tuple.get(index).as_ref()));
+        }
+        Some(SeqKind::Array) => {
+            let array = unsafe { &*(v as *const Any as *const Array<Any>) };
+            (0..array.len()).for_each(|index| f(array.get(index).as_ref()));
+        }
+        None => { }
+    }
+}
+
+fn match_pattern(pattern: &Reachable<Any>, form: &Reachable<Any>, literals: &List, bindings: &mut Bindings, obj: &mut Object) -> bool {
+    if let Some(name) = symbol_name(pattern.as_ref()) {
+        if name == UNDERSCORE {
+            true
+        } else if is_literal(name, literals) {
+            //リテラルは同じ名前のシンボルにだけマッチする
+            symbol_name(form.as_ref()) == Some(name)
+        } else {
+            bindings.push((name.to_string(), Binding::One(form.clone(obj))));
+            true
+        }
+
+    } else if let Some(kind) = sequence_kind(pattern.as_ref()) {
+        if sequence_kind(form.as_ref()) != Some(kind) {
+            return false;
+        }
+
+        let pats = elements(pattern, obj);
+        let forms = elements(form, obj);
+
+        match pats.iter().position(|v| is_ellipsis(v.as_ref())) {
+            Some(pos) if 0 < pos => {
+                //pattern ...の前後にある固定部分の長さ
+                let before = pos - 1;
+                let after = pats.len() - pos - 1;
+                if forms.len() < before + after {
+                    return false;
+                }
+
+                for index in 0..before {
+                    if !match_pattern(&pats[index], &forms[index], literals, bindings, obj) {
+                        return false;
+                    }
+                }
+                for index in 0..after {
+                    let form_index = forms.len() - after + index;
+                    if !match_pattern(&pats[pos + 1 + index], &forms[form_index], literals, bindings, obj) {
+                        return false;
+                    }
+                }
+
+                //繰り返し部分は、各変数ごとに繰り返した回数分の値を持たせる
+                let repeat = &pats[before];
+                let mut vars = Vec::new();
+                pattern_vars(repeat.as_ref(), literals, &mut vars);
+                let mut seqs: Vec<Vec<Binding>> = vars.iter().map(|_| Vec::new()).collect();
+
+                for form in forms[before .. forms.len() - after].iter() {
+                    let mut inner = Bindings::new();
+                    if !match_pattern(repeat, form, literals, &mut inner, obj) {
+                        return false;
+                    }
+
+                    for (name, binding) in inner.into_iter() {
+                        if let Some(index) = vars.iter().position(|v| *v == name) {
+                            seqs[index].push(binding);
+                        }
+                    }
+                }
+
+                for (name, seq) in vars.into_iter().zip(seqs) {
+                    bindings.push((name, Binding::Many(seq)));
+                }
+                true
+            }
+            _ => {
+                pats.len() == forms.len()
+                    && pats.iter().zip(forms.iter()).all(|(pat, form)| match_pattern(pat, form, literals, bindings, obj))
+            }
+        }
+
+    } else {
+        //数値や文字列などはそのまま比較する
+        pattern.as_ref() == form.as_ref()
+    }
+}
+
+//
+// テンプレート内で、let、fun、bind(@x)によって束縛を導入しているシンボルを集める
+fn collect_binders(template: &Any, bindings: &Bindings, f: &mut dyn FnMut(&str)) {
+    if sequence_kind(template) != Some(SeqKind::List) {
+        //タプルや配列の中にあるパターン(@x)も対象にする
+        if sequence_kind(template).is_some() {
+            for_each_element(template, &mut |v| collect_binders(v, bindings, f));
+        }
+        return;
+    }
+
+    let list = Ref::<List>::from(template as *const Any as *mut List);
+    if list.is_nil() {
+        return;
+    }
+    let has_binder = list.len_more_than(2);
+    let list = list.as_ref();
+
+    let mut binder = |v: &Any| {
+        if let Some(name) = symbol_name(v) {
+            if name != UNDERSCORE && name != ELLIPSIS && lookup(bindings, name).is_none() {
+                f(name);
+            }
+        }
+    };
+
+    match symbol_name(list.head().as_ref()) {
+        Some("let") | Some("bind") if has_binder => {
+            binder(list.get(1).as_ref());
+        }
+        Some("fun") if has_binder => {
+            for_each_element(list.get(1).as_ref(), &mut |v| binder(v));
+        }
+        _ => { }
+    }
+
+    items(list).for_each(|v| collect_binders(v.as_ref(), bindings, f));
+}
+
+struct ExpandCtx<'a> {
+    form: &'a Reachable<List>,
+    bindings: &'a Bindings,
+    renames: &'a [(String, Reachable<Symbol>)],
+    //マクロ呼び出し側で見えているローカル変数の名前
+    locals: &'a [String],
+    //quoteされたデータの中を展開している場合はtrue
+    quoted: bool,
+    //展開中の...の繰り返し位置
+    indexes: Vec<usize>,
+}
+
+impl <'a> ExpandCtx<'a> {
+    //現在の繰り返し位置に対応する束縛を取得する
+    fn resolve(&self, name: &str) -> Option<&'a Binding> {
+        let mut binding = lookup(self.bindings, name)?;
+        for index in self.indexes.iter() {
+            match binding {
+                Binding::Many(seq) => {
+                    binding = seq.get(*index)?;
+                }
+                Binding::One(_) => break,
+            }
+        }
+        Some(binding)
+    }
+
+    fn error(&self, message: &str) -> SyntaxException {
+        err::MalformedFormat::new(Some(self.form.make().into_value()), message).into()
+    }
+}
+
+//
+// テンプレート内で、現在の繰り返し位置でまだ繰り返しを持つパターン変数から繰り返し回数を決める
+fn repeat_count(template: &Any, ctx: &ExpandCtx) -> Result<Option<usize>, SyntaxException> {
+    let mut count: Option<usize> = None;
+    let mut mismatch = false;
+
+    fn visit(v: &Any, ctx: &ExpandCtx, count: &mut Option<usize>, mismatch: &mut bool) {
+        if let Some(name) = symbol_name(v) {
+            if let Some(Binding::Many(seq)) = ctx.resolve(name) {
+                match count {
+                    Some(count) if *count != seq.len() => *mismatch = true,
+                    _ => *count = Some(seq.len()),
+                }
+            }
+        } else {
+            for_each_element(v, &mut |v| visit(v, ctx, count, mismatch));
+        }
+    }
+    visit(template, ctx, &mut count, &mut mismatch);
+
+    if mismatch {
+        Err(ctx.error("pattern variables under the same ellipsis have different lengths."))
+    } else {
+        Ok(count)
+    }
+}
+
+fn instantiate(template: &Reachable<Any>, ctx: &mut ExpandCtx, obj: &mut Object) -> NResult<Any, SyntaxException> {
+    if let Some(name) = symbol_name(template.as_ref()) {
+        match ctx.resolve(name) {
+            Some(Binding::One(v)) => Ok(v.make()),
+            Some(Binding::Many(_)) => Err(ctx.error("pattern variable is used without ellipsis.")),
+            None => {
+                if let Some((_, symbol)) = ctx.renames.iter().find(|(n, _)| n == name) {
+                    Ok(symbol.make().into_value())
+                } else if !ctx.quoted && ctx.locals.iter().any(|n| n == name) && is_global(template.as_ref(), obj) {
+                    //テンプレート内の自由なシンボルはマクロを定義したグローバル環境の値を参照する。
+                    //呼び出し側の同名のローカル変数に隠されないように(global-ref symbol)に置き換える
+                    let mut builder = ListBuilder::new(obj);
+                    builder.push(crate::compile::literal::global_ref().cast_value(), obj)?;
+                    builder.push(template, obj)?;
+                    Ok(builder.get().into_value())
+                } else {
+                    Ok(template.make())
+                }
+            }
+        }
+
+    } else if let Some(kind) = sequence_kind(template.as_ref()) {
+        let items = elements(template, obj);
+
+        //(... ...)は...そのものに展開する
+        if kind == SeqKind::List && items.len() == 2 && items.iter().all(|v| is_ellipsis(v.as_ref())) {
+            return Ok(items[1].make());
+        }
+
+        //quoteの中身はデータなので、グローバルの参照に置き換えない。quasiquoteの中でもunquoteされた部分は式になる
+        let quoted = ctx.quoted;
+        if kind == SeqKind::List {
+            if let Some(head) = items.first() {
+                if is_quote(head.as_ref()) {
+                    ctx.quoted = true;
+                } else if is_unquote(head.as_ref()) {
+                    ctx.quoted = false;
+                }
+            }
+        }
+        let result = instantiate_items(&items, ctx, obj);
+        ctx.quoted = quoted;
+        let list = result?;
+
+        match kind {
+            SeqKind::List => Ok(list.into_value()),
+            SeqKind::Tuple => {
+                let list = list.reach(obj);
+                Ok(Tuple::from_list(&list, None, obj)?.into_value())
+            }
+            SeqKind::Array => {
+                let list = list.reach(obj);
+                Ok(Array::from_list(&list, None, obj)?.into_value())
+            }
+        }
+
+    } else {
+        Ok(template.make())
+    }
+}
+
+fn instantiate_items(items: &[Reachable<Any>], ctx: &mut ExpandCtx, obj: &mut Object) -> NResult<List, SyntaxException> {
+    let mut builder = ListBuilder::new(obj);
+    let mut index = 0;
+    while index < items.len() {
+        let item = &items[index];
+        let followed_by_ellipsis = items.get(index + 1).is_some_and(|v| is_ellipsis(v.as_ref()));
+
+        if followed_by_ellipsis {
+            let count = match repeat_count(item.as_ref(), ctx)? {
+                Some(count) => count,
+                None => return Err(ctx.error("ellipsis follows a template without pattern variables.")),
+            };
+
+            for repeat in 0..count {
+                ctx.indexes.push(repeat);
+                let v = instantiate(item, ctx, obj);
+                ctx.indexes.pop();
+
+                builder.push(&v?.reach(obj), obj)?;
+            }
+            index += 2;
+        } else {
+            let v = instantiate(item, ctx, obj)?;
+            builder.push(&v.reach(obj), obj)?;
+            index += 1;
+        }
+    }
+
+    Ok(builder.get())
+}
+
+fn is_global(symbol: &Any, obj: &mut Object) -> bool {
+    let symbol = unsafe { &*(symbol as *const Any as *const Symbol) };
+    obj.find_global_value(symbol).is_some()
+}
+
+fn is_quote(v: &Any) -> bool {
+    let quote = crate::compile::literal::quote();
+    let quasiquote = crate::compile::literal::quasiquote();
+    std::ptr::eq(v, quote.cast_value().as_ref()) || std::ptr::eq(v, quasiquote.cast_value().as_ref())
+        || matches!(symbol_name(v), Some("quote") | Some("quasiquote"))
+}
+
+fn is_unquote(v: &Any) -> bool {
+    let unquote = crate::compile::literal::unquote();
+    let unquote_splicing = crate::compile::literal::unquote_splicing();
+    std::ptr::eq(v, unquote.cast_value().as_ref()) || std::ptr::eq(v, unquote_splicing.cast_value().as_ref())
+        || matches!(symbol_name(v), Some("unquote") | Some("unquote-splicing"))
+}
+
+impl Eq for Macro { }
+
+impl PartialEq for Macro {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self as *const Self, other as *const Self)
+    }
+}
+
+impl Display for Macro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#macro:{}", self.name.as_ref())
+    }
+}
+
+impl Debug for Macro {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#macro:{}", self.name.as_ref())
+    }
+}
@@ -62,9 +62,11 @@ enum PatKind {
     List,
     Array,
     Tuple,
+    HashMap,
     Literal,
     Unquote,
     Bind,
+    Error,
     Empty,
 }
 
@@ -78,7 +80,7 @@ pub fn translate(args: &Reachable<List>, obj: &mut Object) -> NResult<List, Synt
         //matchの各節のパターン部分と実行式を分解する。
         for pat in args.as_ref().tail().reach(obj).iter(obj) {
             if let Some(pat) = pat.try_cast::<List>() {
-                if pat.as_ref().len_more_than(2) == false {
+                if pat.len_more_than(2) == false {
                     return Err(err::MalformedFormat::new( Some(pat.cast_value().clone()), "match clause require more than 2 length list.").into());
                 }
                 //パターン部分と対応する実行式を分解する
@@ -138,7 +140,7 @@ fn translate_inner(exprs: Vec<Reachable<Any>>, patterns: Vec<MatchClause>, obj:
                     , &list::literal::is_list()
                     , &list::literal::list_len()
                     , &list::literal::list_ref()
-                    , list::List::count
+                    , |list| list.count()
                     , list::List::get
                     , obj)
             }
@@ -147,7 +149,7 @@ fn translate_inner(exprs: Vec<Reachable<Any>>, patterns: Vec<MatchClause>, obj:
                     , &array::literal::is_array()
                     , &array::literal::array_len()
                     , &array::literal::array_ref()
-                    , array::Array::len
+                    , |array| array.as_ref().len()
                     , array::Array::get
                     , obj)
             }
@@ -156,10 +158,13 @@ fn translate_inner(exprs: Vec<Reachable<Any>>, patterns: Vec<MatchClause>, obj:
                     , &tuple::literal::is_tuple()
                     , &tuple::literal::tuple_len()
                     , &tuple::literal::tuple_ref()
-                    , tuple::Tuple::len
+                    , |tuple| tuple.as_ref().len()
                     , tuple::Tuple::get
                     , obj)
             }
+            PatKind::HashMap => {
+                translate_hash_map(exprs, patterns, obj)
+            }
             PatKind::Literal => {
                 translate_literal(exprs, patterns, obj)
             }
@@ -169,6 +174,9 @@ fn translate_inner(exprs: Vec<Reachable<Any>>, patterns: Vec<MatchClause>, obj:
             PatKind::Bind => {
                 translate_bind(exprs, patterns, obj)
             }
+            PatKind::Error => {
+                translate_error(exprs, patterns, obj)
+            }
             PatKind::Empty => {
                 translate_empty(exprs, patterns, obj)
             }
@@ -208,7 +216,7 @@ fn pattern_grouping(patterns: Vec<MatchClause>) -> Vec<(PatKind, Vec<MatchClause
             if tf == list::List::typeinfo() {
                 let list =  unsafe { pat.last().unwrap().cast_unchecked::<List>() };
                 //長さがちょうど２のリストで
-                if list.as_ref().len_exactly(2) {
+                if list.len_exactly(2) {
                     let head = list.as_ref().head();
 
                     if head.as_ref().eq(compile::literal::bind().cast_value().as_ref()) {
@@ -218,6 +226,9 @@ fn pattern_grouping(patterns: Vec<MatchClause>) -> Vec<(PatKind, Vec<MatchClause
                     } else if head.as_ref().eq(compile::literal::unquote().cast_value().as_ref()) {
                         //(unqote x)なら
                         PatKind::Unquote
+                    } else if head.as_ref().eq(compile::literal::error_pattern().cast_value().as_ref()) {
+                        //(error-pattern (kind message payload))なら
+                        PatKind::Error
                     } else {
                         PatKind::List
                     }
@@ -229,6 +240,8 @@ fn pattern_grouping(patterns: Vec<MatchClause>) -> Vec<(PatKind, Vec<MatchClause
                 PatKind::Array
             } else if tf == tuple::Tuple::typeinfo() {
                 PatKind::Tuple
+            } else if tf == hashmap::HashMap::typeinfo() {
+                PatKind::HashMap
             } else {
                 PatKind::Literal
             }
@@ -251,7 +264,7 @@ fn pattern_grouping(patterns: Vec<MatchClause>) -> Vec<(PatKind, Vec<MatchClause
 
 fn translate_container_match<T: NaviType>(exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>
     , is_type_func: &Reachable<Func>, len_func: &Reachable<Func>, ref_func: &Reachable<Func>
-    , pattern_len_func: fn(&T) -> usize, pattern_ref_func: fn(&T, usize) -> Ref<Any>
+    , pattern_len_func: fn(&Ref<T>) -> usize, pattern_ref_func: fn(&T, usize) -> Ref<Any>
     , obj: &mut Object) -> NResult<Any, SyntaxException> {
 
     //長さごとにパターンを集めたVec
@@ -260,7 +273,7 @@ fn translate_container_match<T: NaviType>(exprs: &Vec<Reachable<Any>>, patterns:
     //同じサイズのコンテナごとにグルーピング
     for (pat, body) in patterns.clone().into_iter() {
         let container_pat = unsafe { pat.last().unwrap().cast_unchecked::<T>() };
-        let len = pattern_len_func(container_pat.as_ref());
+        let len = pattern_len_func(&container_pat.make());
 
         //ReachableはCloneトレイトを実装していないので手動でクローンする
         let pat = clone_veccap(pat, obj);
@@ -392,6 +405,108 @@ fn translate_container_match<T: NaviType>(exprs: &Vec<Reachable<Any>>, patterns:
     Ok(builder_if.get().into_value())
 }
 
+fn translate_hash_map(exprs: &[Reachable<Any>], patterns: &[MatchClause], obj: &mut Object) -> NResult<Any, SyntaxException> {
+    //キーの並びが同じパターンごとにグルーピング
+    let mut group: Vec<(Reachable<hashmap::HashMap>, Vec<MatchClause>)> = Vec::new();
+    for (pat, body) in patterns.iter() {
+        //ReachableはCloneトレイトを実装していないので手動でクローンする
+        let pat = clone_veccap(pat, obj);
+        let body = body.clone(obj);
+        let map_pat = unsafe { pat.last().unwrap().cast_unchecked::<hashmap::HashMap>() }.clone(obj);
+
+        if let Some((_, clauses)) = group.iter_mut().find(|(keys, _)| is_same_keys(keys.as_ref(), map_pat.as_ref())) {
+            clauses.push((pat, body));
+
+        } else {
+            group.push((map_pat, vec![(pat, body)]));
+        }
+    }
+
+    let mut exprs = clone_veccap(exprs, obj);
+    let target_expr = exprs.pop().unwrap();
+
+    let mut builder_if = ListBuilder::new(obj);
+    builder_if.push(compile::literal::if_().cast_value(), obj)?;
+
+    //predicate
+    builder_if.push(&cons_list2(hashmap::literal::is_hash_map().cast_value(), &target_expr, obj)?.reach(obj), obj)?;
+
+    // true clause
+    //キーの組み合わせが異なるパターンは同時に成立しうるため、失敗したら次のグループを試す
+    let mut builder_catch = ListBuilder::new(obj);
+    builder_catch.push(compile::literal::fail_catch().cast_value(), obj)?;
+
+    for (map_keys, mut clauses) in group.into_iter() {
+        let keys: Vec<Reachable<Any>> = map_keys.as_ref().keys().into_iter()
+            .map(|key| key.reach(obj))
+            .collect();
+        let len = keys.len();
+
+        //(and (hash-map-contains? target 'k0) (hash-map-contains? target 'k1) ...)
+        let mut builder_and = ListBuilder::new(obj);
+        builder_and.push(compile::literal::and().cast_value(), obj)?;
+        for key in keys.iter() {
+            let key = cons_list2(compile::literal::quote().cast_value(), key, obj)?.reach(obj);
+            let contains = cons_list3(hashmap::literal::hash_map_contains().cast_value(), &target_expr, &key, obj)?;
+            builder_and.push(&contains.reach(obj), obj)?;
+        }
+        let and = builder_and.get().into_value().reach(obj);
+
+        //(local)
+        let mut builder_local = ListBuilder::new(obj);
+        builder_local.push(compile::literal::local().cast_value(), obj)?;
+
+        let mut exprs = clone_veccap(&exprs, obj);
+
+        //後々の処理の都合上、コンテナのマッチと同様に降順で値を取得する
+        //(local ... (let v1 (hash-map-ref target 'k1)) (let v0 (hash-map-ref target 'k0)))
+        for index in (0..len).rev() {
+            let symbol = symbol::Symbol::gensym(String::from("v") + &index.to_string() , obj)?.into_value().reach(obj);
+
+            let key = cons_list2(compile::literal::quote().cast_value(), &keys[index], obj)?.reach(obj);
+            let map_ref = cons_list3(hashmap::literal::hash_map_ref().cast_value(), &target_expr, &key, obj)?.reach(obj);
+            let let_ = cons_list3(compile::literal::let_().cast_value(), &symbol, &map_ref, obj)?;
+            builder_local.push(&let_.reach(obj), obj)?;
+
+            exprs.push(symbol);
+        }
+
+        //各Clauseの先頭要素にあるマップを展開して、値のパターンをPattern配列に追加する
+        for (pat, _) in clauses.iter_mut() {
+            let map_pat = pat.pop().unwrap();
+            let map_pat = unsafe { map_pat.cast_unchecked::<hashmap::HashMap>() };
+            let values = map_pat.as_ref().values();
+            for value in values.into_iter().rev() {
+                pat.push(value.reach(obj));
+            }
+        }
+
+        let matcher = translate_inner(exprs, clauses, obj)?;
+        builder_local.push(&matcher.reach(obj), obj)?;
+
+        //(if (and ...) (local ...) FAIL)
+        let mut builder_group_if = ListBuilder::new(obj);
+        builder_group_if.push(compile::literal::if_().cast_value(), obj)?;
+        builder_group_if.push(&and, obj)?;
+        builder_group_if.push(&builder_local.get().into_value().reach(obj), obj)?;
+        builder_group_if.push(MatchFail::fail().cast_value(), obj)?;
+
+        builder_catch.push(&builder_group_if.get().into_value().reach(obj), obj)?;
+    }
+
+    builder_if.push(&builder_catch.get().into_value().reach(obj), obj)?;
+
+    //マッチ失敗用の値をfalse節に追加
+    builder_if.push(MatchFail::fail().cast_value(), obj)?;
+
+    Ok(builder_if.get().into_value())
+}
+
+fn is_same_keys(map1: &hashmap::HashMap, map2: &hashmap::HashMap) -> bool {
+    map1.len() == map2.len()
+        && map1.keys().iter().zip(map2.keys().iter()).all(|(key1, key2)| key1.as_ref() == key2.as_ref())
+}
+
 fn translate_literal(exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>, obj: &mut Object) -> NResult<Any, SyntaxException> {
     let mut group = Vec::<(Reachable<Any>, Vec<MatchClause>)>::new();
 
@@ -471,7 +586,8 @@ fn translate_bind(exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>, obj:
         let bind = pattern.pop().unwrap();
         //必ず(bind ???)というような形式になっているのでリストに変換
         let bind = unsafe { bind.cast_unchecked::<List>() };
-        let val = bind.as_ref().tail().as_ref().head();
+        //以降のアロケーションでGCが発生しても参照できるようにReachableにしておく
+        let val = bind.as_ref().tail().as_ref().head().reach(obj);
 
         if let Some(symbol) = val.try_cast::<Symbol>() {
             //束縛対象がアンダースコアなら束縛を行わない
@@ -490,7 +606,7 @@ fn translate_bind(exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>, obj:
                 let let_ = {
                     let mut builder_let = ListBuilder::new(obj);
                     builder_let.push(compile::literal::let_().cast_value(), obj)?;
-                    builder_let.push(&val.reach(obj), obj)?;
+                    builder_let.push(&val, obj)?;
                     builder_let.push(&target, obj)?;
                     builder_let.get().into_value()
                 };
@@ -506,13 +622,93 @@ fn translate_bind(exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>, obj:
                 builder_catch.push(&builder_local.get().into_value().reach(obj), obj)?;
             }
         } else {
-            return Err(err::TypeMismatch::new(val, symbol::Symbol::typeinfo()).into());
+            return Err(err::TypeMismatch::new(val.make(), symbol::Symbol::typeinfo()).into());
         }
     }
 
     Ok(builder_catch.get().into_value())
 }
 
+fn translate_error(exprs: &[Reachable<Any>], patterns: &[MatchClause], obj: &mut Object) -> NResult<Any, SyntaxException> {
+    let mut exprs = clone_veccap(exprs, obj);
+    let target_expr = exprs.pop().unwrap();
+
+    let mut builder_if = ListBuilder::new(obj);
+    builder_if.push(compile::literal::if_().cast_value(), obj)?;
+
+    //predicate
+    builder_if.push(&cons_list2(exception::literal::is_error().cast_value(), &target_expr, obj)?.reach(obj), obj)?;
+
+    // true clause
+    let true_clause = {
+        let mut builder_local = ListBuilder::new(obj);
+        //(local)
+        builder_local.push(compile::literal::local().cast_value(), obj)?;
+
+        //後々の処理の都合上、コンテナのマッチと同様に降順で値を取得する
+        //(local (let v2 (error-payload target)) (let v1 (error-message target)) (let v0 (error-kind target)))
+        let accessors = [
+            exception::literal::error_payload(),
+            exception::literal::error_message(),
+            exception::literal::error_kind(),
+        ];
+        for (index, accessor) in accessors.iter().enumerate() {
+            let index = accessors.len() - index - 1;
+            let symbol = symbol::Symbol::gensym(String::from("v") + &index.to_string() , obj)?.into_value().reach(obj);
+
+            let let_ = cons_list3(compile::literal::let_().cast_value()
+                , &symbol
+                , &cons_list2(accessor.cast_value(), &target_expr, obj)?.reach(obj)
+                , obj)?;
+            builder_local.push(&let_.reach(obj), obj)?;
+
+            exprs.push(symbol);
+        }
+
+        //各Clauseの先頭要素にある(error ...)を展開して、Pattern配列に追加する
+        //省略された部分は(bind _)で補う
+        let mut clauses: Vec<MatchClause> = Vec::new();
+        for (pat, body) in patterns.iter() {
+            let mut pat = clone_veccap(pat, obj);
+            let body = body.clone(obj);
+
+            //(error-pattern (kind message payload))の内側のリストを取り出す
+            let error_pat = pat.pop().unwrap();
+            let error_pat = unsafe { error_pat.cast_unchecked::<List>() };
+            let inner = error_pat.as_ref().tail().as_ref().head();
+            let error_pat = match inner.try_cast::<List>() {
+                Some(list) if !list.is_nil() && !list.len_more_than(4) => list.clone().reach(obj),
+                _ => return Err(err::MalformedFormat::new(Some(inner), "error pattern requires (kind [message [payload]])").into()),
+            };
+            let count = error_pat.count();
+
+            for index in (0..accessors.len()).rev() {
+                if index < count {
+                    pat.push(error_pat.as_ref().get(index).reach(obj));
+                } else {
+                    let ignore = symbol::Symbol::alloc("_", obj)?.into_value().reach(obj);
+                    let bind = cons_list2(compile::literal::bind().cast_value(), &ignore, obj)?;
+                    pat.push(bind.reach(obj));
+                }
+            }
+
+            clauses.push((pat, body));
+        }
+
+        let matcher = translate_inner(exprs, clauses, obj)?;
+        builder_local.push(&matcher.reach(obj), obj)?;
+
+        builder_local.get()
+    };
+
+    builder_if.push(&true_clause.into_value().reach(obj), obj)?;
+
+    //マッチ失敗用の値をfalse節に追加
+    builder_if.push(MatchFail::fail().cast_value(), obj)?;
+
+    Ok(builder_if.get().into_value())
+}
+
 fn translate_empty(_exprs: &Vec<Reachable<Any>>, patterns: &Vec<MatchClause>, obj: &mut Object) -> NResult<Any, SyntaxException> {
     let (_, body) = patterns.first().unwrap();
 
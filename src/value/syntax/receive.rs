@@ -0,0 +1,391 @@
+use crate::compile::{SyntaxException, self};
+use crate::value::list::{List, ListBuilder};
+use crate::value::symbol::Symbol;
+use crate::value::app::{Parameter, ParamKind, Param};
+use crate::ptr::*;
+use crate::err::{self, *};
+use crate::value::*;
+use crate::vm;
+
+use std::time::{Duration, Instant};
+
+//afterのタイムアウト時刻を整数値として持ち回るための基準時刻
+static RECEIVE_EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+fn elapsed_millis() -> i64 {
+    RECEIVE_EPOCH.elapsed().as_millis() as i64
+}
+
+fn is_after_clause(clause: &List) -> bool {
+    match clause.head().try_cast::<Symbol>() {
+        Some(symbol) => symbol.as_ref().as_ref() == "after",
+        None => false,
+    }
+}
+
+fn build_list(values: &[&Reachable<Any>], obj: &mut Object) -> NResult<Any, OutOfMemory> {
+    let mut builder = ListBuilder::new(obj);
+    for v in values.iter() {
+        builder.push(v, obj)?;
+    }
+
+    Ok(builder.get().into_value())
+}
+
+///
+/// (receive (pattern body ...) ... (after timeout body ...)) を次の式に変換する。
+///
+/// (local
+///   (let d (%receive-deadline timeout))
+///   (let loop (fun (loop prev)
+///     (local
+///       (let i (%receive-wait prev d))
+///       (if i
+///         (match (%receive-peek i)
+///           (pattern (local
+///             (let r (%receive-take i))
+///             (%receive-reply r (try body ... (catch @e (%receive-reply-error r e))))))
+///           ...
+///           (@_ (loop loop i)))
+///         (begin body ...)))))
+///   (loop loop -1))
+///
+/// どのパターンにもマッチしなかったメッセージは受信箱に残したまま、次に届いているメッセージを試す。
+/// sendで送られたメッセージには、マッチした節の本体の結果(もしくは本体で発生した例外)を返信する。
+/// localのletで定義した関数は自分自身を参照できないため、ループ用の関数は引数で自分自身を受け取る。
+pub fn translate(args: &Reachable<List>, obj: &mut Object) -> NResult<Any, SyntaxException> {
+    let mut clauses: Vec<Reachable<List>> = Vec::new();
+    let mut after: Option<Reachable<List>> = None;
+
+    for clause in args.iter(obj) {
+        if after.is_some() {
+            return Err(err::MalformedFormat::new(Some(clause), "after clause must be the last clause of receive.").into());
+        }
+
+        if let Some(clause) = clause.try_cast::<List>() {
+            if is_after_clause(clause.as_ref()) {
+                //(after timeout body ...)
+                if !clause.len_more_than(3) {
+                    return Err(err::MalformedFormat::new(Some(clause.cast_value().clone()), "after clause require timeout and body.").into());
+                }
+                after = Some(clause.clone().reach(obj));
+
+            } else {
+                if !clause.len_more_than(2) {
+                    return Err(err::MalformedFormat::new(Some(clause.cast_value().clone()), "receive clause require more than 2 length list.").into());
+                }
+                clauses.push(clause.clone().reach(obj));
+            }
+        } else {
+            return Err(err::TypeMismatch::new(clause, list::List::typeinfo()).into());
+        }
+    }
+
+    //generate unique symbol
+    let deadline_symbol = Symbol::gensym("d", obj)?.into_value().reach(obj);
+    let loop_symbol = Symbol::gensym("loop", obj)?.into_value().reach(obj);
+    let prev_symbol = Symbol::gensym("prev", obj)?.into_value().reach(obj);
+    let index_symbol = Symbol::gensym("i", obj)?.into_value().reach(obj);
+    let reply_symbol = Symbol::gensym("r", obj)?.into_value().reach(obj);
+    let exception_symbol = Symbol::gensym("e", obj)?.into_value().reach(obj);
+
+    //(let d (%receive-deadline timeout))
+    //afterがなければタイムアウトしないのでfalseを入れておく
+    let let_deadline = {
+        let deadline = match after.as_ref() {
+            Some(after) => {
+                let timeout = after.as_ref().tail().as_ref().head().reach(obj);
+                build_list(&[Reachable::new_static(&FUNC_RECEIVE_DEADLINE.value).cast_value(), &timeout], obj)?
+            }
+            None => {
+                bool::Bool::false_().into_value().make()
+            }
+        }.reach(obj);
+
+        build_list(&[compile::literal::let_().cast_value(), &deadline_symbol, &deadline], obj)?.reach(obj)
+    };
+
+    //(match (%receive-peek i) (pattern (%receive-take i) body ...) ... (@_ (loop loop i)))
+    let match_ = {
+        let mut builder_match = ListBuilder::new(obj);
+        builder_match.push(compile::literal::match_().cast_value(), obj)?;
+
+        let peek = build_list(&[Reachable::new_static(&FUNC_RECEIVE_PEEK.value).cast_value(), &index_symbol], obj)?.reach(obj);
+        builder_match.push(&peek, obj)?;
+
+        for clause in clauses.iter() {
+            let mut builder_clause = ListBuilder::new(obj);
+            //パターン部分
+            builder_clause.push(&clause.as_ref().head().reach(obj), obj)?;
+            //マッチしたメッセージを受信箱から取り除いてから本体を実行する
+            //(let r (%receive-take i))
+            let take = build_list(&[Reachable::new_static(&FUNC_RECEIVE_TAKE.value).cast_value(), &index_symbol], obj)?.reach(obj);
+            let let_reply = build_list(&[compile::literal::let_().cast_value(), &reply_symbol, &take], obj)?.reach(obj);
+
+            //(catch @e (%receive-reply-error r e))
+            let catch = {
+                let bind = build_list(&[compile::literal::bind().cast_value(), &exception_symbol], obj)?.reach(obj);
+                let reply_error = build_list(&[Reachable::new_static(&FUNC_RECEIVE_REPLY_ERROR.value).cast_value(), &reply_symbol, &exception_symbol], obj)?.reach(obj);
+                build_list(&[&compile::literal::catch_symbol().into_value(), &bind, &reply_error], obj)?.reach(obj)
+            };
+
+            //(try body ... catch)
+            let try_ = {
+                let mut builder_try = ListBuilder::new(obj);
+                builder_try.push(compile::literal::try_().cast_value(), obj)?;
+                for body in clause.as_ref().tail().reach(obj).iter(obj) {
+                    builder_try.push(&body.reach(obj), obj)?;
+                }
+                builder_try.push(&catch, obj)?;
+                builder_try.get().into_value().reach(obj)
+            };
+
+            //(local (let r ...) (%receive-reply r (try ...)))
+            let reply = build_list(&[Reachable::new_static(&FUNC_RECEIVE_REPLY.value).cast_value(), &reply_symbol, &try_], obj)?.reach(obj);
+            let local = build_list(&[compile::literal::local().cast_value(), &let_reply, &reply], obj)?.reach(obj);
+            builder_clause.push(&local, obj)?;
+
+            builder_match.push(&builder_clause.get().into_value().reach(obj), obj)?;
+        }
+
+        //どのパターンにもマッチしなければ次のメッセージを試す
+        let ignore = Symbol::alloc("_", obj)?.into_value().reach(obj);
+        let bind = build_list(&[compile::literal::bind().cast_value(), &ignore], obj)?.reach(obj);
+        let next = build_list(&[&loop_symbol, &loop_symbol, &index_symbol], obj)?.reach(obj);
+        let skip = build_list(&[&bind, &next], obj)?.reach(obj);
+        builder_match.push(&skip, obj)?;
+
+        builder_match.get().into_value().reach(obj)
+    };
+
+    //タイムアウトした時に実行する式
+    let timeout_expr = match after.as_ref() {
+        Some(after) => {
+            let mut builder_begin = ListBuilder::new(obj);
+            builder_begin.push(compile::literal::begin().cast_value(), obj)?;
+            for body in after.as_ref().tail().as_ref().tail().reach(obj).iter(obj) {
+                builder_begin.push(&body.reach(obj), obj)?;
+            }
+
+            builder_begin.get().into_value()
+        }
+        None => {
+            bool::Bool::false_().into_value().make()
+        }
+    }.reach(obj);
+
+    //(fun (loop prev) (local (let i (%receive-wait prev d)) (if i match timeout_expr)))
+    let fun = {
+        let wait = build_list(&[Reachable::new_static(&FUNC_RECEIVE_WAIT.value).cast_value(), &prev_symbol, &deadline_symbol], obj)?.reach(obj);
+        let let_index = build_list(&[compile::literal::let_().cast_value(), &index_symbol, &wait], obj)?.reach(obj);
+        let if_ = build_list(&[compile::literal::if_().cast_value(), &index_symbol, &match_, &timeout_expr], obj)?.reach(obj);
+        let local = build_list(&[compile::literal::local().cast_value(), &let_index, &if_], obj)?.reach(obj);
+
+        let params = build_list(&[&loop_symbol, &prev_symbol], obj)?.reach(obj);
+        build_list(&[compile::literal::fun().cast_value(), &params, &local], obj)?.reach(obj)
+    };
+
+    //(let loop fun)
+    let let_loop = build_list(&[compile::literal::let_().cast_value(), &loop_symbol, &fun], obj)?.reach(obj);
+
+    //(loop loop -1)
+    let start = number::make_integer(-1, obj)?.reach(obj);
+    let call_loop = build_list(&[&loop_symbol, &loop_symbol, &start], obj)?.reach(obj);
+
+    Ok(build_list(&[compile::literal::local().cast_value(), &let_deadline, &let_loop, &call_loop], obj)?)
+}
+
+fn func_receive_deadline(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let timeout = vm::refer_arg::<number::Integer>(0, obj).as_ref().get();
+    //基準時刻からのミリ秒でタイムアウト時刻を表す
+    let deadline = elapsed_millis().saturating_add(timeout.max(0));
+
+    Ok(number::make_integer(deadline, obj)?)
+}
+
+fn func_receive_wait(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let prev = vm::refer_arg::<number::Integer>(0, obj).as_ref().get();
+    let deadline = vm::refer_arg::<Any>(1, obj).try_cast::<number::Integer>().map(|deadline| deadline.as_ref().get());
+
+    let mailbox = match obj.mailbox() {
+        Some(mailbox) => mailbox,
+        //自分自身のオブジェクトが削除されようとしているので処理を継続できない
+        None => return Err(Exception::MySelfObjectDeleted),
+    };
+
+    //前回試したメッセージの次から、届いているメッセージを探す
+    let start = (prev + 1).max(0) as usize;
+    let found = mailbox.lock().unwrap().find_inbox_message(start);
+    if let Some(index) = found {
+        return Ok(number::make_integer(index as i64, obj)?);
+    }
+
+    //まだメッセージが届いていなければ、届くまで関数の処理を一時停止する
+    //再開時は同じ引数のままもう一度メッセージを探す
+    match deadline {
+        Some(deadline) => {
+            let remain = deadline - elapsed_millis();
+            if remain <= 0 {
+                //タイムアウト
+                Ok(bool::Bool::false_().into_value().make())
+
+            } else {
+                vm::save_func_suspend_info(func_receive_wait_resume, obj);
+                //タイムアウト時刻まで実行を止めてもらう。途中でメッセージが届けばその時点で再開される。
+                obj.set_sleep_until(Instant::now() + Duration::from_millis(remain as u64));
+                Err(Exception::TimeLimit)
+            }
+        }
+        None => {
+            vm::save_func_suspend_info(func_receive_wait_resume, obj);
+            //メッセージが届いたときにMailBoxから起こされる
+            Err(Exception::WaitReply)
+        }
+    }
+}
+
+fn func_receive_wait_resume(obj: &mut Object) -> NResult<Any, Exception> {
+    func_receive_wait(0, obj)
+}
+
+fn func_receive_peek(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let index = vm::refer_arg::<number::Integer>(0, obj).as_ref().get() as usize;
+
+    let mailbox = match obj.mailbox() {
+        Some(mailbox) => mailbox,
+        None => return Err(Exception::MySelfObjectDeleted),
+    };
+
+    //メールボックスから取得した値をObject内のヒープにcloneするまでロックを保持しておく
+    let mailbox = mailbox.lock().unwrap();
+    //%receive-waitで見つけた位置なので、必ずメッセージが存在する
+    let msg = mailbox.peek_inbox(index).unwrap().clone();
+    //MailBoxのヒープはロック中にGCされないためinto_reachableを行う
+    let msg = unsafe { msg.into_reachable() };
+
+    //msgはMailBox内のヒープに確保された値なので、Object内ヒープに値をクローンする
+    let mut allocator = AnyAllocator::Object(obj);
+    let cloned = crate::value::value_clone(&msg, &mut allocator)?;
+
+    Ok(cloned)
+}
+
+fn func_receive_take(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let index = vm::refer_arg::<number::Integer>(0, obj).as_ref().get() as usize;
+
+    let mailbox = match obj.mailbox() {
+        Some(mailbox) => mailbox,
+        None => return Err(Exception::MySelfObjectDeleted),
+    };
+
+    let data = mailbox.lock().unwrap().take_inbox(index);
+
+    //sendで送られたメッセージなら、本体の結果を返信するまで返信先を保持しておく
+    //castで送られたメッセージには返信先がないためfalseを返す
+    match data.and_then(|data| data.reply_to) {
+        Some(reply_to) => {
+            let key = obj.hold_receiving(reply_to);
+            Ok(number::make_integer(key as i64, obj)?)
+        }
+        None => {
+            Ok(bool::Bool::false_().into_value().make())
+        }
+    }
+}
+
+fn func_receive_reply(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let key = vm::refer_arg::<Any>(0, obj).try_cast::<number::Integer>().map(|key| key.as_ref().get() as usize);
+    let result = vm::refer_arg::<Any>(1, obj);
+
+    if let Some((reply_to_mailbox, reply_token)) = key.and_then(|key| obj.take_receiving(key)) {
+        //resultの値は返信先のヒープにコピーされるだけで、自分自身のGCは発生しない
+        let reachable = unsafe { result.clone().into_reachable() };
+        let mut reply_to_mailbox = reply_to_mailbox.lock().unwrap();
+        if reply_to_mailbox.recv_reply(Ok(&reachable), reply_token).is_err() {
+            //エラーの返信は返信先のヒープを消費しないため、失敗しない
+            let _ = reply_to_mailbox.recv_reply(Err(Exception::OutOfMemory), reply_token);
+        }
+    }
+
+    Ok(result)
+}
+
+fn func_receive_reply_error(_num_rest: usize, obj: &mut Object) -> NResult<Any, Exception> {
+    let key = vm::refer_arg::<Any>(0, obj).try_cast::<number::Integer>().map(|key| key.as_ref().get() as usize);
+    let err = vm::refer_arg::<exception::Exception>(1, obj).as_ref().err().clone();
+
+    if let Some((reply_to_mailbox, reply_token)) = key.and_then(|key| obj.take_receiving(key)) {
+        let mut reply_to_mailbox = reply_to_mailbox.lock().unwrap();
+        if reply_to_mailbox.recv_reply(Err(err.clone()), reply_token).is_err() {
+            let _ = reply_to_mailbox.recv_reply(Err(Exception::OutOfMemory), reply_token);
+        }
+    }
+
+    //送信元に返信した後、例外はそのまま外側へ投げなおす
+    Err(err)
+}
+
+//以下の関数はreceiveの変換結果から直接参照されるため、グローバルには定義しない
+
+static FUNC_RECEIVE_DEADLINE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-deadline", func_receive_deadline,
+            Parameter::new(&[
+            Param::new("timeout", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_RECEIVE_WAIT: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-wait", func_receive_wait,
+            Parameter::new(&[
+            Param::new("prev", ParamKind::Require, number::Integer::typeinfo()),
+            Param::new("deadline", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_RECEIVE_PEEK: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-peek", func_receive_peek,
+            Parameter::new(&[
+            Param::new("index", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_RECEIVE_TAKE: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-take", func_receive_take,
+            Parameter::new(&[
+            Param::new("index", ParamKind::Require, number::Integer::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_RECEIVE_REPLY: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-reply", func_receive_reply,
+            Parameter::new(&[
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new("result", ParamKind::Require, Any::typeinfo()),
+            ])
+        )
+    )
+});
+
+static FUNC_RECEIVE_REPLY_ERROR: Lazy<GCAllocationStruct<Func>> = Lazy::new(|| {
+    GCAllocationStruct::new(
+        Func::new("%receive-reply-error", func_receive_reply_error,
+            Parameter::new(&[
+            Param::new("key", ParamKind::Require, Any::typeinfo()),
+            Param::new("err", ParamKind::Require, exception::Exception::typeinfo()),
+            ])
+        )
+    )
+});
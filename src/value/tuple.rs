@@ -74,6 +74,7 @@ impl Tuple {
                 if value::check_reply(&mut child_v, obj)? {
                     //返信があった場合は、内部ポインタを返信結果の値に上書きする
                     cap.as_ref().get_inner(index).update_pointer(child_v.raw_ptr());
+                    obj.write_barrier(cap.as_ref(), child_v.as_ref());
                 } else {
                     //子要素にReplyを含む値が残っている場合は、全体をfalseにする
                     return Ok(false);
@@ -95,7 +96,8 @@ impl Tuple {
 
     #[inline(always)]
     pub fn is_unit(&self) -> bool {
-        std::ptr::eq(self as *const Self, IMMIDATE_UNIT as *const Self)
+        //UnitはImmidiate Valueなので、List::is_nilと同じくアドレスの値そのもので比較する
+        std::ptr::eq(std::hint::black_box(self as *const Self), IMMIDATE_UNIT as *const Self)
     }
 
     fn alloc<A: Allocator>(size: usize, allocator: &mut A) -> NResult<Tuple, OutOfMemory> {
@@ -127,10 +129,12 @@ impl Tuple {
     }
 
     pub fn len(&self) -> usize {
-        if self.is_unit() {
+        //Unitの場合にlenの読み出しを判定より前に移動されないよう、black_boxを通したポインタから読み出す
+        let this = std::hint::black_box(self as *const Self);
+        if std::ptr::eq(this, IMMIDATE_UNIT as *const Self) {
             0
         } else {
-            self.len
+            unsafe { (*this).len }
         }
     }
 
@@ -158,7 +162,7 @@ impl Tuple {
     pub fn from_list(list: &Reachable<list::List>, size: Option<usize>, obj: &mut Object) -> NResult<Tuple, OutOfMemory> {
         let size = match size {
             Some(s) => s,
-            None => list.as_ref().count(),
+            None => list.count(),
         };
 
         if size == 0 {
@@ -279,8 +283,9 @@ impl TupleBuilder {
         })
     }
 
-    pub fn push<V: ValueHolder<Any>>(&mut self, v: &V, _obj: &mut Object) -> Result<(), OutOfBounds> {
+    pub fn push<V: ValueHolder<Any>>(&mut self, v: &V, obj: &mut Object) -> Result<(), OutOfBounds> {
         self.tuple.mut_refer().set(v, self.index)?;
+        obj.write_barrier(self.tuple.as_ref(), v.as_ref());
         self.index += 1;
 
         Ok(())
@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::collections::HashSet;
 use std::io::Cursor;
 use std::io::Read;
 use std::io::Seek;
@@ -15,6 +16,7 @@ use crate::object::mm::GCAllocationStruct;
 use crate::object::mm::usize_to_ptr;
 
 use crate::object::Object;
+use crate::object::Allocator;
 use crate::value::*;
 use crate::value::any::Any;
 use crate::value::app;
@@ -52,8 +54,10 @@ pub mod tag {
     pub const AND:u8 = 19;
     pub const OR:u8 = 20;
     pub const MATCH_SUCCESS:u8 = 21;
+    pub const TRY:u8 = 30;
+    pub const TRY_END:u8 = 31;
 
-    //next number 30
+    //next number 32
 }
 
 #[derive(Debug)]
@@ -63,8 +67,8 @@ pub enum ExecException {
 }
 
 impl From<err::OutOfMemory> for ExecException {
-    fn from(_: err::OutOfMemory) -> Self {
-        ExecException::Exception(err::Exception::OutOfMemory)
+    fn from(this: err::OutOfMemory) -> Self {
+        ExecException::Exception(err::Exception::from(this))
     }
 }
 
@@ -95,6 +99,22 @@ struct Environment {
     //ローカル変数への参照がsize分だけココ以降のデータ内に保存されている。
 }
 
+#[derive(Debug)]
+#[repr(C)]
+struct Handler {
+    //一つ外側のハンドラを指すポインタ
+    prev: *mut Handler,
+    //ハンドラ設置時点のVMの状態。例外が発生したときはこの状態まで巻き戻す
+    cont: *mut Continuation,
+    env: *mut Environment,
+    argp: *mut Environment,
+    code: Ref<compiled::Code>,
+    //ハンドラ本体の開始位置
+    pc: u64,
+    //ハンドラを設置した時点のcode_execute(resume)のネストの深さ
+    depth: usize,
+}
+
 #[repr(C)]
 struct FuncSuspendInfo {
     code: Ref<compiled::Code>,
@@ -219,6 +239,18 @@ pub struct VMState {
     cont: *mut Continuation,
     env: *mut Environment,
     argp: *mut Environment,
+    //try式で設置された例外ハンドラのスタック
+    handler: *mut Handler,
+    //code_execute(resume)のネストの深さ。apply内などで別のコード実行がネストしている場合に増える
+    exec_depth: usize,
+    //ハンドラに渡す値を作成している間、エラー内の値がGCで回収されないように保持しておく
+    raised: Option<err::Exception>,
+    //例外が発生したネイティブ関数。Funcは静的に確保されているためGCのルートにする必要はない
+    failed_func: Option<Ref<func::Func>>,
+    //最後にVMの外へ脱出した例外のバックトレース
+    backtrace: Option<err::Backtrace>,
+    //トップレベルの実行開始時点のContinuation。これより外側は以前の実行の残りなのでバックトレースに含めない
+    base_cont: *mut Continuation,
 }
 
 impl VMState {
@@ -232,6 +264,12 @@ impl VMState {
             cont: std::ptr::null_mut(),
             env: std::ptr::null_mut(),
             argp: std::ptr::null_mut(),
+            handler: std::ptr::null_mut(),
+            exec_depth: 0,
+            raised: None,
+            failed_func: None,
+            backtrace: None,
+            base_cont: std::ptr::null_mut(),
         }
     }
 
@@ -240,6 +278,29 @@ impl VMState {
         self.reductions
     }
 
+    #[inline(always)]
+    pub(crate) fn set_remain_reductions(&mut self, reductions: usize) {
+        self.reductions = reductions;
+    }
+
+    ///
+    /// 実行途中の状態をすべて破棄して、初期状態に戻す。
+    pub(crate) fn reset(&mut self) {
+        self.code = Ref::from(std::ptr::null_mut());
+        self.pc = 0;
+        self.acc = bool::Bool::false_().into_ref().into_value();
+        self.stack.pos = self.stack.stack;
+        self.cont = std::ptr::null_mut();
+        self.env = std::ptr::null_mut();
+        self.argp = std::ptr::null_mut();
+        self.handler = std::ptr::null_mut();
+        self.exec_depth = 0;
+        self.raised = None;
+        self.failed_func = None;
+        self.backtrace = None;
+        self.base_cont = std::ptr::null_mut();
+    }
+
     #[inline(always)]
     pub fn stack(&mut self) -> &mut VMStack {
         &mut self.stack
@@ -265,22 +326,70 @@ impl VMState {
             }
         }
 
+        {
+            let mut handler = self.handler;
+            while !handler.is_null() {
+                unsafe {
+                    callback((*handler).code.cast_mut_value(), arg);
+
+                    handler = (*handler).prev;
+                }
+            }
+        }
+
+        if let Some(raised) = self.raised.as_mut() {
+            raised.for_each_alived_value(arg, callback);
+        }
+
+        fn for_each_frame_value(env: *mut Environment, arg: *mut u8, callback: fn(&mut Ref<Any>, *mut u8)) {
+            unsafe {
+                //ローカルフレーム内の変数の数
+                let len = (*env).size;
+
+                //ローカルフレームは環境ヘッダの後ろ側にある
+                let frame_ptr = env.add(1) as *mut Ref<Any>;
+                for index in 0 .. len {
+                    let cell = frame_ptr.add(index as usize);
+                    let v = &mut *cell;
+                    callback(v, arg);
+                }
+            }
+        }
+
+        //同じフレームを二重に辿らないように、辿ったフレームを記録しておく
+        let mut visited: HashSet<*mut Environment> = HashSet::new();
         {
             let mut env = self.env;
             while env.is_null() == false {
+                for_each_frame_value(env, arg, callback);
+                visited.insert(env);
+
+                env = unsafe { (*env).up };
+            }
+        }
+
+        {
+            //引数を構築中のフレーム(argp)は、呼び出しが完了するまでenvのリストにつながっていないため個別に辿る
+            let mut argps: Vec<*mut Environment> = vec![self.argp];
+            let mut cont = self.cont;
+            while !cont.is_null() {
                 unsafe {
-                    //ローカルフレーム内の変数の数
-                    let len = (*env).size;
-
-                    //ローカルフレームは環境ヘッダの後ろ側にある
-                    let frame_ptr = env.add(1) as *mut Ref<Any>;
-                    for index in 0 .. len {
-                        let cell = frame_ptr.add(index as usize);
-                        let v = &mut *cell;
-                        callback(v, arg);
-                    }
+                    argps.push((*cont).argp);
+                    cont = (*cont).prev;
+                }
+            }
+            let mut handler = self.handler;
+            while !handler.is_null() {
+                unsafe {
+                    argps.push((*handler).argp);
+                    handler = (*handler).prev;
+                }
+            }
 
-                    env = (*env).up;
+            for argp in argps {
+                //insertは未登録の場合にだけtrueを返す
+                if !argp.is_null() && visited.insert(argp) {
+                    for_each_frame_value(argp, arg, callback);
                 }
             }
         }
@@ -346,6 +455,21 @@ pub fn app_call(app: &Reachable<app::App>, args_iter: impl Iterator<Item=Ref<Any
 }
 
 pub fn code_execute(code: &Reachable<compiled::Code>, limit: WorkTimeLimit, obj: &mut Object) -> Result<Ref<Any>, ExecException> {
+    let state = obj.vm_state();
+    if state.exec_depth == 0 {
+        //新しいトップレベルの実行が始まるので、前回の実行で残ったバックトレースを破棄する
+        state.failed_func = None;
+        state.backtrace = None;
+        state.base_cont = state.cont;
+    }
+    state.exec_depth += 1;
+    let result = code_execute_inner(code, limit, obj);
+    leave_execute(&result, obj);
+
+    result
+}
+
+fn code_execute_inner(code: &Reachable<compiled::Code>, limit: WorkTimeLimit, obj: &mut Object) -> Result<Ref<Any>, ExecException> {
     //実行対象のコードを設定
     obj.vm_state().code = code.make();
     obj.vm_state().pc = 0;
@@ -406,13 +530,191 @@ pub fn resume(limit: WorkTimeLimit, obj: &mut Object) -> Result<Ref<Any>, ExecEx
         WorkTimeLimit::TakeOver => obj.vm_state().reductions,
         };
 
-    execute(obj)
+    obj.vm_state().exec_depth += 1;
+    let result = execute(obj);
+    leave_execute(&result, obj);
+
+    result
+}
+
+fn leave_execute(result: &Result<Ref<Any>, ExecException>, obj: &mut Object) {
+    let state = obj.vm_state();
+    state.exec_depth -= 1;
+
+    //中断ではなくエラーで実行が終了した場合は、終了した実行の中で設置されたハンドラを破棄する
+    if let Err(ExecException::Exception(err)) = result {
+        if err.is_catchable() {
+            discard_handlers(state.exec_depth, state);
+        }
+    }
+}
+
+fn discard_handlers(depth: usize, state: &mut VMState) {
+    //指定の深さより内側の実行で設置されたハンドラは既に無効になっている
+    while !state.handler.is_null() && depth < unsafe { (*state.handler).depth } {
+        state.handler = unsafe { (*state.handler).prev };
+    }
 }
 
 fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
+    loop {
+        match execute_inner(obj) {
+            Err(ExecException::Exception(err)) if err.is_catchable() => {
+                //ハンドラが設置されていれば、ハンドラまで巻き戻して実行を継続する
+                match unwind(err, obj) {
+                    Ok(_) => {
+                        let state = obj.vm_state();
+                        state.failed_func = None;
+                        state.backtrace = None;
+                    }
+                    Err(err) => {
+                        let state = obj.vm_state();
+                        //ネストした実行の中で既に作成されていれば、例外が発生した位置に近いそちらを優先する
+                        if state.backtrace.is_none() {
+                            state.backtrace = Some(build_backtrace(state));
+                        }
+                        state.failed_func = None;
+
+                        return Err(ExecException::Exception(err));
+                    }
+                }
+            }
+            result => {
+                return result;
+            }
+        }
+    }
+}
+
+fn build_backtrace(state: &mut VMState) -> err::Backtrace {
+    let mut frames = Vec::new();
+
+    //ネイティブ関数内で発生した例外は、その関数を一番内側のフレームにする
+    if let Some(func) = state.failed_func.take() {
+        frames.push(err::BacktraceFrame::new(func.as_ref().name().to_string(), None));
+    }
+
+    let mut last: Option<(*const compiled::Code, u64)> = None;
+    let mut push_frame = |code: &Ref<compiled::Code>, pc: u64| {
+        //ネイティブ関数呼び出し時のContinuationは現在の位置と同じになるため重複して追加しない
+        let key = (code.raw_ptr() as *const compiled::Code, pc);
+        if last == Some(key) {
+            return;
+        }
+        last = Some(key);
+
+        //VM内部で作成されたコードはソースを持たないためバックトレースに含めない
+        if let Some(map) = code.as_ref().source_map() {
+            let name = match map.name() {
+                Some(name) => name.to_string(),
+                None if map.is_closure() => "fun".to_string(),
+                None => "<toplevel>".to_string(),
+            };
+            //pcは実行中の命令の次を指しているため、一つ前の位置で検索する
+            frames.push(err::BacktraceFrame::new(name, map.lookup(pc.saturating_sub(1) as usize)));
+        }
+    };
+
+    if !state.code.raw_ptr().is_null() {
+        push_frame(&state.code, state.pc);
+    }
+
+    let mut cont = state.cont;
+    while !cont.is_null() && cont != state.base_cont {
+        unsafe {
+            if let Some(code) = (*cont).code.as_ref() {
+                push_frame(code, (*cont).pc);
+            }
+            cont = (*cont).prev;
+        }
+    }
+
+    err::Backtrace::new(frames)
+}
+
+///
+/// 最後にVMの外へ脱出した例外のバックトレースを取り出す。
+pub fn take_backtrace(obj: &mut Object) -> Option<err::Backtrace> {
+    obj.vm_state().backtrace.take()
+}
+
+///
+/// raiseで投げなおす例外に、最初に発生した位置のバックトレースを設定する。
+pub fn set_backtrace(backtrace: Option<err::Backtrace>, obj: &mut Object) {
+    obj.vm_state().backtrace = backtrace;
+}
+
+fn unwind(err: err::Exception, obj: &mut Object) -> Result<(), err::Exception> {
+    let depth = obj.vm_state().exec_depth;
+    discard_handlers(depth, obj.vm_state());
+
+    let handler = obj.vm_state().handler;
+    //現在の実行の中で設置されたハンドラがなければ、エラーをそのまま呼び出し元へ返す
+    if handler.is_null() || unsafe { (*handler).depth } != depth {
+        return Err(err);
+    }
+
+    //巻き戻す前に、例外が発生した位置のバックトレースを作成しておく
+    //ネストした実行の中やraiseで投げなおされた例外で既に保持していれば、そちらを優先する
+    let backtrace = {
+        let state = obj.vm_state();
+        match state.backtrace.take() {
+            Some(backtrace) => backtrace,
+            None => build_backtrace(state),
+        }
+    };
+
+    //ハンドラに渡す値を作成
+    let value = exception_value(err, backtrace, obj)?;
+
+    //ハンドラ設置時点の状態に巻き戻す
+    unsafe {
+        let state = obj.vm_state();
+        //スタック内でHandlerの値があるアドレスをスタックポインタにする
+        state.stack.pos = handler as *mut u8;
+        state.cont = (*handler).cont;
+        state.env = (*handler).env;
+        state.argp = (*handler).argp;
+        state.code = (*handler).code.clone();
+        state.pc = (*handler).pc;
+        state.handler = (*handler).prev;
+        state.acc = value;
+    }
+
+    Ok(())
+}
+
+fn exception_value(err: err::Exception, backtrace: err::Backtrace, obj: &mut Object) -> Result<Ref<Any>, err::Exception> {
+    //raiseで投げられた値も含めて、元のエラーとバックトレースを保持したExceptionオブジェクトをハンドラに渡す
+    //Exceptionオブジェクトの領域を確保している間のGCでエラー内の値が回収されないように、VMStateで保持しておく
+    obj.vm_state().raised = Some(err);
+    let size = crate::object::mm::Heap::calc_alloc_size::<exception::Exception>(0);
+    let result = obj.force_allocation_space(size);
+    let err = obj.vm_state().raised.take().unwrap();
+
+    match result {
+        Ok(_) => {
+            //領域は確保済みなのでここではGCが発生しない
+            let exception = exception::Exception::alloc_with_backtrace(err, backtrace, obj)?;
+            Ok(exception.into_value())
+        }
+        Err(oom) => {
+            Err(err::Exception::from(oom))
+        }
+    }
+}
+
+fn execute_inner(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
     let mut program = Cursor::new(obj.vm_state().code.as_ref().program());
     program.seek(SeekFrom::Start(obj.vm_state().pc as u64)).unwrap();
 
+    macro_rules! save_pc {
+        () => {
+            //バックトレースで例外が発生した位置を参照できるようにPCを更新
+            obj.vm_state().pc = program.position();
+        };
+    }
+
     macro_rules! tag_return {
         () => {
             //Continuationに保存されている状態を復元
@@ -550,6 +852,7 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                     obj.vm_state().acc = v;
 
                 } else {
+                    save_pc!();
                     return Err(ExecException::Exception(err::Exception::UnboundVariable(
                         err::UnboundVariable::new(symbol.clone())
                         )));
@@ -571,40 +874,29 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                 let mut arg = obj.vm_state().acc.clone();
 
                 let argp_env = obj.vm_state().argp;
-                //引数準備中フレームからappを取得
-                let app = refer_local_var(argp_env, 0, 0);
-                //PUSH_APPの時点で型チェックされているので無条件でAPPに変換する
-                let app = unsafe { app.cast_unchecked::<app::App>() };
-
                 let index = unsafe { (*argp_env).size - 1 };
-                let parameter = app.as_ref().parameter();
-                let params = parameter.params();
-                let param = if params.is_empty() {
-                        None
-                    } else if index < params.len() {
-                        Some(&params[index])
-                    } else if params[params.len() - 1].kind == app::ParamKind::Rest {
-                        Some(&params[params.len() - 1])
-                    } else {
-                        None
-                    };
-                if let Some(param) = param {
-                    // reply check
-                    if param.force && arg.has_replytype() {
-                        let result = check_reply(&mut arg, obj);
-
-                        //まだ返信がない場合は、
-                        if result? == false {
-                            //もう一度PUSH_ARGが実行できるように、現在位置-1をresume後のPCとする
-                            obj.vm_state().pc = program.position() - 1;
-
-                            //引数の値にReply待ちを含んでいるため、返信を待つ
-                            return Err(ExecException::Exception(err::Exception::WaitReply));
-                        }
+
+                // reply check
+                if arg.has_replytype() && param_of(argp_env, index).is_some_and(|param| param.force) {
+                    let result = check_reply(&mut arg, obj);
+
+                    //まだ返信がない場合は、
+                    if result? == false {
+                        //もう一度PUSH_ARGが実行できるように、現在位置-1をresume後のPCとする
+                        obj.vm_state().pc = program.position() - 1;
+
+                        //引数の値にReply待ちを含んでいるため、返信を待つ
+                        return Err(ExecException::Exception(err::Exception::WaitReply));
                     }
+                }
 
-                    // type check
+                // type check
+                //check_reply内でGCが走りappが移動している可能性があるため、パラメータはここで再取得する
+                if let Some(param) = param_of(argp_env, index) {
                     if arg.is_type(param.typeinfo) == false {
+                        let app = refer_local_var(argp_env, 0, 0);
+                        let app = unsafe { app.cast_unchecked::<app::App>() };
+                        save_pc!();
                         return Err(ExecException::Exception(err::Exception::ArgTypeMismatch(
                             err::ArgTypeMismatch::new(
                                 String::from(app.as_ref().name()), index + 1,
@@ -624,6 +916,7 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                 if app.is::<app::App>() {
                     // OK!!  do nothing
                 } else {
+                    save_pc!();
                     return Err(ExecException::Exception(err::Exception::TypeMismatch(
                         err::TypeMismatch::new(app, app::App::typeinfo())
                     )));
@@ -750,6 +1043,10 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                 let buf = &program.get_ref()[cur .. cur + body_size];
                 closure_body.write(buf).unwrap();
 
+                //Closure本体の範囲に含まれるソース位置の対応表を切り出す
+                let source_map = obj.vm_state().code.as_ref().source_map()
+                    .map(|map| map.slice(cur, cur + body_size));
+
                 //読み込んだClosure本体のデータ分、プログラムカウンタを進める
                 program.seek(SeekFrom::Current(body_size as i64)).unwrap();
 
@@ -759,7 +1056,7 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                     ;
                 let parameter = app::Parameter::new(&params);
 
-                obj.vm_state().acc = compiled::Closure::alloc(closure_body, constants, parameter, num_free_vars, obj)?.into_value();
+                obj.vm_state().acc = compiled::Closure::alloc(closure_body, constants, source_map, parameter, num_free_vars, obj)?.into_value();
 
                 reduce!(5);
             }
@@ -777,6 +1074,9 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                 } else {
                     panic!("need closure. but got {}", closure.as_ref())
                 }
+                //作成済みのクロージャへの書き込みなので書き込みバリアを通す
+                let closure = obj.vm_state().acc.clone();
+                obj.write_barrier(closure.as_ref(), v.as_ref());
             }
             tag::CAPTURE_FREE_REF_FREE => {
                 let frame_offset = read_u16(&mut program);
@@ -795,6 +1095,8 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                     } else {
                         panic!("need closure. but got {}", closure.as_ref())
                     }
+                    let closure = obj.vm_state().acc.clone();
+                    obj.write_barrier(closure.as_ref(), v.as_ref());
 
                 } else {
                     //0番目の値がclosure以外の場合、不具合なのでパニックさせる
@@ -876,6 +1178,7 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
 
                 if num_args_remain < parameter.num_require() {
                     //必須の引数が足らないエラー
+                    save_pc!();
                     return Err(ExecException::Exception(err::Exception::Other(format!("Illegal number of argument.\nThe function {}.\n  require:{}, optional:{}, rest:{}\n  but got {} arguments."
                         , app.as_ref().name()
                         , parameter.num_require(),  parameter.num_optional(), parameter.has_rest(), num_args
@@ -894,6 +1197,7 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                 //rest引数がない関数に対して過剰な引数を渡している場合は
                 if num_args_remain != 0 && parameter.has_rest() == false {
                     //エラー
+                    save_pc!();
                     return Err(ExecException::Exception(err::Exception::Other(format!("Illegal number of argument.\nThe function {}.\n  require:{}, optional:{}, rest:{}\n  but got {} arguments."
                         , app.as_ref().name()
                         , parameter.num_require(),  parameter.num_optional(), parameter.has_rest(), num_args
@@ -927,6 +1231,10 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                             reduce_with_check_timelimit!(10);
                         }
                         Err(err) => {
+                            if err.is_catchable() {
+                                //バックトレースの一番内側のフレームにするため、例外が発生した関数を保存
+                                obj.vm_state().failed_func = Some(func.clone());
+                            }
                             return Err(ExecException::Exception(err));
                         }
                     }
@@ -987,6 +1295,37 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
                     program.seek(SeekFrom::Current(offset as i64)).unwrap();
                 }
             }
+            tag::TRY => {
+                let offset = read_u16(&mut program);
+
+                let state = obj.vm_state();
+                let handler = Handler {
+                    prev: state.handler,
+                    cont: state.cont,
+                    env: state.env,
+                    argp: state.argp,
+                    code: state.code.clone(),
+                    pc: program.position() + offset as u64,
+                    depth: state.exec_depth,
+                };
+                //handlerポインタを新しく追加したハンドラに差し替える
+                state.handler = state.stack.push(handler);
+            }
+            tag::TRY_END => {
+                let offset = read_u16(&mut program);
+
+                unsafe {
+                    let state = obj.vm_state();
+                    debug_assert!(state.stack.pos == state.handler.add(1) as *mut u8);
+
+                    //本体が正常に終了したので、ハンドラをスタックから取り除く
+                    state.stack.pos = state.handler as *mut u8;
+                    state.handler = (*state.handler).prev;
+                }
+
+                //ハンドラ部分を読み飛ばす
+                program.seek(SeekFrom::Current(offset as i64)).unwrap();
+            }
             _ => unreachable!()
         }
     }
@@ -999,13 +1338,31 @@ fn execute(obj: &mut Object) -> Result<Ref<Any>, ExecException> {
 }
 
 #[inline]
+fn param_of<'a>(argp_env: *mut Environment, index: usize) -> Option<&'a app::Param> {
+    //引数準備中フレームからappを取得
+    let app = refer_local_var(argp_env, 0, 0);
+    //PUSH_APPの時点で型チェックされているので無条件でAPPに変換する
+    let app = unsafe { app.cast_unchecked::<app::App>() };
+
+    let params = unsafe { &*(app.as_ref().parameter() as *const app::Parameter) }.params();
+    if params.is_empty() {
+        None
+    } else if index < params.len() {
+        Some(&params[index])
+    } else if params[params.len() - 1].kind == app::ParamKind::Rest {
+        Some(&params[params.len() - 1])
+    } else {
+        None
+    }
+}
+
 fn check_reply(v: &mut Ref<Any>, obj: &mut Object) -> Result<bool, OutOfMemory> {
-    //スタック領域内のFPtrをCapとして扱わせる
-    let mut cap = Cap::new(v as *mut Ref<Any>);
+    //check_reply内でGCが走る可能性があるため、値をキャプチャしてから確認する
+    let mut cap = v.clone().capture(obj);
     //返信がないかを確認
     let result = crate::value::check_reply(&mut cap, obj);
-    //そのままDropさせると確保していない内部領域のfreeが走ってしまうのでforgetさせる。
-    std::mem::forget(cap);
+    //GCで移動、もしくは返信の値に置き換わったポインタを書き戻す
+    *v = cap.make();
 
     result
 }